@@ -8,6 +8,22 @@ mod m20230917_000002_create_author;
 mod m20230917_000003_create_music_table;
 mod m20240208_000001_create_progress_table;
 mod m20240207_235046_create_music_chapter;
+mod m20240301_000001_create_bookmark_table;
+mod m20240302_000001_create_preference_table;
+mod m20240303_000001_create_device_progress_table;
+mod m20240304_000001_create_role_table;
+mod m20240305_000001_create_library_table;
+mod m20240306_000001_create_api_token_table;
+mod m20240307_000001_create_totp_table;
+mod m20240308_000001_create_account_identity_table;
+mod m20240309_000001_create_auth_event_table;
+mod m20240310_000001_create_account_session_table;
+mod m20240311_000001_create_invitation_table;
+mod m20240312_000001_add_account_email;
+mod m20240313_000001_create_audit_event_table;
+mod m20240314_000001_create_app_password_table;
+mod m20240315_000001_create_feed_token_table;
+mod m20240316_000001_create_webhook_table;
 
 pub struct Migrator;
 
@@ -20,6 +36,22 @@ impl MigratorTrait for Migrator {
             Box::new(m20230917_000003_create_music_table::Migration),
             Box::new(m20240207_235046_create_music_chapter::Migration),
             Box::new(m20240208_000001_create_progress_table::Migration),
+            Box::new(m20240301_000001_create_bookmark_table::Migration),
+            Box::new(m20240302_000001_create_preference_table::Migration),
+            Box::new(m20240303_000001_create_device_progress_table::Migration),
+            Box::new(m20240304_000001_create_role_table::Migration),
+            Box::new(m20240305_000001_create_library_table::Migration),
+            Box::new(m20240306_000001_create_api_token_table::Migration),
+            Box::new(m20240307_000001_create_totp_table::Migration),
+            Box::new(m20240308_000001_create_account_identity_table::Migration),
+            Box::new(m20240309_000001_create_auth_event_table::Migration),
+            Box::new(m20240310_000001_create_account_session_table::Migration),
+            Box::new(m20240311_000001_create_invitation_table::Migration),
+            Box::new(m20240312_000001_add_account_email::Migration),
+            Box::new(m20240313_000001_create_audit_event_table::Migration),
+            Box::new(m20240314_000001_create_app_password_table::Migration),
+            Box::new(m20240315_000001_create_feed_token_table::Migration),
+            Box::new(m20240316_000001_create_webhook_table::Migration),
         ]
     }
 }
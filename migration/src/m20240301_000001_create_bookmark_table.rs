@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20230917_000001_create_account_table::Account, m20230917_000003_create_music_table::Music,
+    m20240207_235046_create_music_chapter::Chapter,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Bookmark::Table)
+                    .col(
+                        ColumnDef::new(Bookmark::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Bookmark::AccountId).integer().not_null())
+                    .col(ColumnDef::new(Bookmark::MusicId).integer().not_null())
+                    .col(ColumnDef::new(Bookmark::ChapterId).integer().not_null())
+                    .col(ColumnDef::new(Bookmark::Position).double().not_null())
+                    .col(ColumnDef::new(Bookmark::Name).string().not_null())
+                    .col(ColumnDef::new(Bookmark::Note).text())
+                    .col(
+                        ColumnDef::new(Bookmark::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Bookmark-AccountId")
+                            .from(Bookmark::Table, Bookmark::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Bookmark-MusicId")
+                            .from(Bookmark::Table, Bookmark::MusicId)
+                            .to(Music::Table, Music::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Bookmark-ChapterId")
+                            .from(Bookmark::Table, Bookmark::ChapterId)
+                            .to(Chapter::Table, Chapter::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_bookmark_account_id_music_id")
+                    .table(Bookmark::Table)
+                    .col(Bookmark::AccountId)
+                    .col(Bookmark::MusicId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Bookmark::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Bookmark {
+    Table,
+    Id,
+    AccountId,
+    MusicId,
+    ChapterId,
+    Position,
+    Name,
+    Note,
+    Created,
+}
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20230917_000001_create_account_table::Account, m20230917_000003_create_music_table::Music,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the global defaults of each user
+        manager
+            .create_table(
+                Table::create()
+                    .table(Preference::Table)
+                    .col(
+                        ColumnDef::new(Preference::AccountId)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Preference::PlaybackSpeed)
+                            .double()
+                            .not_null()
+                            .default(1.0),
+                    )
+                    .col(
+                        ColumnDef::new(Preference::SkipBackSecs)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(Preference::SleepTimerMins).integer())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Preference-AccountId")
+                            .from(Preference::Table, Preference::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // the per book overrides, null means use the global default
+        manager
+            .create_table(
+                Table::create()
+                    .table(BookPreference::Table)
+                    .col(
+                        ColumnDef::new(BookPreference::AccountId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BookPreference::MusicId).integer().not_null())
+                    .col(ColumnDef::new(BookPreference::PlaybackSpeed).double())
+                    .primary_key(
+                        Index::create()
+                            .col(BookPreference::AccountId)
+                            .col(BookPreference::MusicId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-BookPreference-AccountId")
+                            .from(BookPreference::Table, BookPreference::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-BookPreference-MusicId")
+                            .from(BookPreference::Table, BookPreference::MusicId)
+                            .to(Music::Table, Music::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BookPreference::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Preference::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Preference {
+    Table,
+    AccountId,
+    PlaybackSpeed,
+    SkipBackSecs,
+    SleepTimerMins,
+}
+
+#[derive(DeriveIden)]
+pub enum BookPreference {
+    Table,
+    AccountId,
+    MusicId,
+    PlaybackSpeed,
+}
@@ -0,0 +1,131 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20230917_000001_create_account_table::Account, m20230917_000003_create_music_table::Music,
+    m20240207_235046_create_music_chapter::Chapter,
+    m20240208_000001_create_progress_table::Progress,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the device and the client time of the last accepted update
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Progress::Table)
+                    .add_column(
+                        ColumnDef::new(ProgressDevice::DeviceId)
+                            .string()
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Progress::Table)
+                    .add_column(ColumnDef::new(ProgressDevice::ClientUpdate).big_integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        // the latest position reported by each device
+        manager
+            .create_table(
+                Table::create()
+                    .table(DeviceProgress::Table)
+                    .col(ColumnDef::new(DeviceProgress::AccountId).integer().not_null())
+                    .col(ColumnDef::new(DeviceProgress::MusicId).integer().not_null())
+                    .col(ColumnDef::new(DeviceProgress::DeviceId).string().not_null())
+                    .col(ColumnDef::new(DeviceProgress::DeviceName).string().not_null())
+                    .col(ColumnDef::new(DeviceProgress::ChapterId).integer().not_null())
+                    .col(ColumnDef::new(DeviceProgress::Progress).double().not_null())
+                    .col(
+                        ColumnDef::new(DeviceProgress::ClientUpdate)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(DeviceProgress::Update)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(DeviceProgress::AccountId)
+                            .col(DeviceProgress::MusicId)
+                            .col(DeviceProgress::DeviceId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-DeviceProgress-AccountId")
+                            .from(DeviceProgress::Table, DeviceProgress::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-DeviceProgress-MusicId")
+                            .from(DeviceProgress::Table, DeviceProgress::MusicId)
+                            .to(Music::Table, Music::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-DeviceProgress-ChapterId")
+                            .from(DeviceProgress::Table, DeviceProgress::ChapterId)
+                            .to(Chapter::Table, Chapter::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DeviceProgress::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Progress::Table)
+                    .drop_column(ProgressDevice::ClientUpdate)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Progress::Table)
+                    .drop_column(ProgressDevice::DeviceId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// the new columns of the progress table
+#[derive(DeriveIden)]
+pub enum ProgressDevice {
+    DeviceId,
+    ClientUpdate,
+}
+
+#[derive(DeriveIden)]
+pub enum DeviceProgress {
+    Table,
+    AccountId,
+    MusicId,
+    DeviceId,
+    DeviceName,
+    ChapterId,
+    Progress,
+    ClientUpdate,
+    Update,
+}
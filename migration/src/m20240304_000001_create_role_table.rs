@@ -0,0 +1,100 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// the roles replacing the old role levels, the ids are the `role_level` of the accounts
+const DEFAULT_ROLES: [(i32, &str, &[&str]); 2] = [
+    (
+        0,
+        "admin",
+        &["manage_library", "manage_users", "upload", "download", "delete"],
+    ),
+    (1, "user", &["download"]),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Role::Table)
+                    .col(ColumnDef::new(Role::Id).integer().not_null().primary_key())
+                    .col(ColumnDef::new(Role::Name).string().not_null().unique_key())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(RolePermission::Table)
+                    .col(ColumnDef::new(RolePermission::RoleId).integer().not_null())
+                    .col(
+                        ColumnDef::new(RolePermission::Permission)
+                            .string()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(RolePermission::RoleId)
+                            .col(RolePermission::Permission),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-RolePermission-RoleId")
+                            .from(RolePermission::Table, RolePermission::RoleId)
+                            .to(Role::Table, Role::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        for (id, name, permissions) in DEFAULT_ROLES {
+            manager
+                .exec_stmt(
+                    Query::insert()
+                        .into_table(Role::Table)
+                        .columns([Role::Id, Role::Name])
+                        .values_panic([id.into(), name.into()])
+                        .to_owned(),
+                )
+                .await?;
+            for permission in permissions {
+                manager
+                    .exec_stmt(
+                        Query::insert()
+                            .into_table(RolePermission::Table)
+                            .columns([RolePermission::RoleId, RolePermission::Permission])
+                            .values_panic([id.into(), (*permission).into()])
+                            .to_owned(),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RolePermission::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Role::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Role {
+    Table,
+    Id,
+    Name,
+}
+
+#[derive(DeriveIden)]
+pub enum RolePermission {
+    Table,
+    RoleId,
+    Permission,
+}
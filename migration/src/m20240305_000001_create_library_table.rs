@@ -0,0 +1,161 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20230917_000001_create_account_table::Account, m20230917_000003_create_music_table::Music,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// the library holding the books added before there were libraries
+const DEFAULT_LIBRARY: i32 = 1;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Library::Table)
+                    .col(
+                        ColumnDef::new(Library::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Library::Name).string().not_null().unique_key())
+                    .col(ColumnDef::new(Library::Root).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        // the accounts allowed to see the books of a library
+        manager
+            .create_table(
+                Table::create()
+                    .table(LibraryGrant::Table)
+                    .col(ColumnDef::new(LibraryGrant::LibraryId).integer().not_null())
+                    .col(ColumnDef::new(LibraryGrant::AccountId).integer().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(LibraryGrant::LibraryId)
+                            .col(LibraryGrant::AccountId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-LibraryGrant-LibraryId")
+                            .from(LibraryGrant::Table, LibraryGrant::LibraryId)
+                            .to(Library::Table, Library::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-LibraryGrant-AccountId")
+                            .from(LibraryGrant::Table, LibraryGrant::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // the old `./fetchbook` becomes the default library, everyone keeps seeing it
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(Library::Table)
+                    .columns([Library::Id, Library::Name, Library::Root])
+                    .values_panic([
+                        DEFAULT_LIBRARY.into(),
+                        "default".into(),
+                        "./fetchbook".into(),
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(LibraryGrant::Table)
+                    .columns([LibraryGrant::LibraryId, LibraryGrant::AccountId])
+                    .select_from(
+                        Query::select()
+                            .expr(Expr::val(DEFAULT_LIBRARY))
+                            .column(Account::Id)
+                            .from(Account::Table)
+                            .to_owned(),
+                    )
+                    .map_err(|e| DbErr::Custom(e.to_string()))?
+                    .to_owned(),
+            )
+            .await?;
+
+        // sqlite can not add a foreign key to an existing table, so only the column and an index
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Music::Table)
+                    .add_column(
+                        ColumnDef::new(MusicLibrary::LibraryId)
+                            .integer()
+                            .not_null()
+                            .default(DEFAULT_LIBRARY),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_music_library_id")
+                    .table(Music::Table)
+                    .col(MusicLibrary::LibraryId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_music_library_id")
+                    .table(Music::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Music::Table)
+                    .drop_column(MusicLibrary::LibraryId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(LibraryGrant::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Library::Table).to_owned())
+            .await
+    }
+}
+
+/// the new column of the music table
+#[derive(DeriveIden)]
+pub enum MusicLibrary {
+    LibraryId,
+}
+
+#[derive(DeriveIden)]
+pub enum Library {
+    Table,
+    Id,
+    Name,
+    Root,
+}
+
+#[derive(DeriveIden)]
+pub enum LibraryGrant {
+    Table,
+    LibraryId,
+    AccountId,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230917_000001_create_account_table::Account;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // only the sha256 of a token is stored, the token is shown once when it is created
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiToken::Table)
+                    .col(
+                        ColumnDef::new(ApiToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApiToken::AccountId).integer().not_null())
+                    .col(ColumnDef::new(ApiToken::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(ApiToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ApiToken::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ApiToken::Expires).timestamp())
+                    .col(ColumnDef::new(ApiToken::LastUsed).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-ApiToken-AccountId")
+                            .from(ApiToken::Table, ApiToken::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum ApiToken {
+    Table,
+    Id,
+    AccountId,
+    Name,
+    TokenHash,
+    Created,
+    Expires,
+    LastUsed,
+}
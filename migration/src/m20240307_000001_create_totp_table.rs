@@ -0,0 +1,116 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20230917_000001_create_account_table::Account, m20240304_000001_create_role_table::Role,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the totp secret of an account, not enabled until a code is confirmed
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountTotp::Table)
+                    .col(
+                        ColumnDef::new(AccountTotp::AccountId)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AccountTotp::Secret).string().not_null())
+                    .col(
+                        ColumnDef::new(AccountTotp::Enabled)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    // the time step of the last accepted code, a code can not be used twice
+                    .col(ColumnDef::new(AccountTotp::LastStep).big_integer())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-AccountTotp-AccountId")
+                            .from(AccountTotp::Table, AccountTotp::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // single use codes for a lost authenticator, only the sha256 is stored
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecoveryCode::Table)
+                    .col(ColumnDef::new(RecoveryCode::AccountId).integer().not_null())
+                    .col(ColumnDef::new(RecoveryCode::CodeHash).string().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(RecoveryCode::AccountId)
+                            .col(RecoveryCode::CodeHash),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-RecoveryCode-AccountId")
+                            .from(RecoveryCode::Table, RecoveryCode::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Role::Table)
+                    .add_column(
+                        ColumnDef::new(RoleTotp::RequireTotp)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Role::Table)
+                    .drop_column(RoleTotp::RequireTotp)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(RecoveryCode::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(AccountTotp::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AccountTotp {
+    Table,
+    AccountId,
+    Secret,
+    Enabled,
+    LastStep,
+}
+
+#[derive(DeriveIden)]
+pub enum RecoveryCode {
+    Table,
+    AccountId,
+    CodeHash,
+}
+
+/// the new column of the role table
+#[derive(DeriveIden)]
+pub enum RoleTotp {
+    RequireTotp,
+}
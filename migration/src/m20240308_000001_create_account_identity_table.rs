@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230917_000001_create_account_table::Account;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the account of a user of an openid connect provider, the subject never changes
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountIdentity::Table)
+                    .col(ColumnDef::new(AccountIdentity::Issuer).string().not_null())
+                    .col(ColumnDef::new(AccountIdentity::Subject).string().not_null())
+                    .col(ColumnDef::new(AccountIdentity::AccountId).integer().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(AccountIdentity::Issuer)
+                            .col(AccountIdentity::Subject),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-AccountIdentity-AccountId")
+                            .from(AccountIdentity::Table, AccountIdentity::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AccountIdentity::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AccountIdentity {
+    Table,
+    Issuer,
+    Subject,
+    AccountId,
+}
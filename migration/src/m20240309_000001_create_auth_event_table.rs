@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // logins and failed attempts, the failures also decide the login throttling.
+        // the user name is kept as typed, it may not be an account
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuthEvent::Table)
+                    .col(
+                        ColumnDef::new(AuthEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AuthEvent::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AuthEvent::Kind).string().not_null())
+                    .col(ColumnDef::new(AuthEvent::Username).string().not_null())
+                    .col(ColumnDef::new(AuthEvent::Ip).string().not_null())
+                    .col(ColumnDef::new(AuthEvent::Detail).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_auth_event_username")
+                    .table(AuthEvent::Table)
+                    .col(AuthEvent::Username)
+                    .col(AuthEvent::Created)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_auth_event_ip")
+                    .table(AuthEvent::Table)
+                    .col(AuthEvent::Ip)
+                    .col(AuthEvent::Created)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuthEvent::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AuthEvent {
+    Table,
+    Id,
+    Created,
+    Kind,
+    Username,
+    Ip,
+    Detail,
+}
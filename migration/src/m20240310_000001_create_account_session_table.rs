@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230917_000001_create_account_table::Account;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the logged in sessions of `axum_sessions`, a session without its row here is logged out.
+        // only the sha256 of the session id is stored
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountSession::Table)
+                    .col(
+                        ColumnDef::new(AccountSession::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountSession::SessionHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(AccountSession::AccountId).integer().not_null())
+                    .col(
+                        ColumnDef::new(AccountSession::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountSession::LastSeen)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AccountSession::Ip).string().not_null())
+                    .col(ColumnDef::new(AccountSession::UserAgent).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-AccountSession-AccountId")
+                            .from(AccountSession::Table, AccountSession::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AccountSession::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AccountSession {
+    Table,
+    Id,
+    SessionHash,
+    AccountId,
+    Created,
+    LastSeen,
+    Ip,
+    UserAgent,
+}
@@ -0,0 +1,181 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20230917_000001_create_account_table::Account, m20240304_000001_create_role_table::Role,
+    m20240305_000001_create_library_table::Library,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // an invite link creates an account with the role and library grants of the invitation.
+        // only the sha256 of its token is stored, `max_uses` null is no limit
+        manager
+            .create_table(
+                Table::create()
+                    .table(Invitation::Table)
+                    .col(
+                        ColumnDef::new(Invitation::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Invitation::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Invitation::CreatedBy).integer().not_null())
+                    .col(ColumnDef::new(Invitation::RoleId).integer().not_null())
+                    .col(ColumnDef::new(Invitation::MaxUses).integer())
+                    .col(
+                        ColumnDef::new(Invitation::Uses)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(Invitation::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Invitation::Expires).timestamp().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Invitation-CreatedBy")
+                            .from(Invitation::Table, Invitation::CreatedBy)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-Invitation-RoleId")
+                            .from(Invitation::Table, Invitation::RoleId)
+                            .to(Role::Table, Role::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(InvitationLibrary::Table)
+                    .col(
+                        ColumnDef::new(InvitationLibrary::InvitationId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(InvitationLibrary::LibraryId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(InvitationLibrary::InvitationId)
+                            .col(InvitationLibrary::LibraryId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-InvitationLibrary-InvitationId")
+                            .from(InvitationLibrary::Table, InvitationLibrary::InvitationId)
+                            .to(Invitation::Table, Invitation::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-InvitationLibrary-LibraryId")
+                            .from(InvitationLibrary::Table, InvitationLibrary::LibraryId)
+                            .to(Library::Table, Library::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // settings of the instance changed by the admins, like the self-registration
+        manager
+            .create_table(
+                Table::create()
+                    .table(InstanceSetting::Table)
+                    .col(
+                        ColumnDef::new(InstanceSetting::Name)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(InstanceSetting::Value).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        // a self-registered account can not log in until an admin approves it
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Account::Table)
+                    .add_column(
+                        ColumnDef::new(AccountApproval::Approved)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Account::Table)
+                    .drop_column(AccountApproval::Approved)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(InstanceSetting::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(InvitationLibrary::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Invitation::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Invitation {
+    Table,
+    Id,
+    TokenHash,
+    CreatedBy,
+    RoleId,
+    MaxUses,
+    Uses,
+    Created,
+    Expires,
+}
+
+#[derive(DeriveIden)]
+pub enum InvitationLibrary {
+    Table,
+    InvitationId,
+    LibraryId,
+}
+
+#[derive(DeriveIden)]
+pub enum InstanceSetting {
+    Table,
+    Name,
+    Value,
+}
+
+/// the new column of the account table
+#[derive(DeriveIden)]
+pub enum AccountApproval {
+    Approved,
+}
@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // who changed what, only appended to and pruned by the retention. the actor has no
+        // foreign key, so the events of a deleted account stay
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditEvent::Table)
+                    .col(
+                        ColumnDef::new(AuditEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AuditEvent::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AuditEvent::ActorId).integer())
+                    .col(ColumnDef::new(AuditEvent::ActorName).string().not_null())
+                    .col(ColumnDef::new(AuditEvent::Action).string().not_null())
+                    .col(ColumnDef::new(AuditEvent::Target).string().not_null())
+                    .col(ColumnDef::new(AuditEvent::OldSummary).text().not_null())
+                    .col(ColumnDef::new(AuditEvent::NewSummary).text().not_null())
+                    .col(ColumnDef::new(AuditEvent::Ip).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_event_action")
+                    .table(AuditEvent::Table)
+                    .col(AuditEvent::Action)
+                    .col(AuditEvent::Created)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_event_actor")
+                    .table(AuditEvent::Table)
+                    .col(AuditEvent::ActorName)
+                    .col(AuditEvent::Created)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_event_created")
+                    .table(AuditEvent::Table)
+                    .col(AuditEvent::Created)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditEvent::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AuditEvent {
+    Table,
+    Id,
+    Created,
+    ActorId,
+    ActorName,
+    Action,
+    Target,
+    /// a summary of the target before the change, empty when it was created
+    OldSummary,
+    /// a summary of the target after the change, empty when it was deleted
+    NewSummary,
+    Ip,
+}
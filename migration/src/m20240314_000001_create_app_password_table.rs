@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230917_000001_create_account_table::Account;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the passwords of the subsonic players. they are stored as is because the token login of
+        // subsonic hashes the password with a salt chosen by the player, they only work there
+        manager
+            .create_table(
+                Table::create()
+                    .table(AppPassword::Table)
+                    .col(
+                        ColumnDef::new(AppPassword::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AppPassword::AccountId).integer().not_null())
+                    .col(ColumnDef::new(AppPassword::Name).string().not_null())
+                    .col(ColumnDef::new(AppPassword::Password).string().not_null())
+                    .col(
+                        ColumnDef::new(AppPassword::Created)
+                            .timestamp()
+                            .default(Expr::current_timestamp())
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AppPassword::LastUsed).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-AppPassword-AccountId")
+                            .from(AppPassword::Table, AppPassword::AccountId)
+                            .to(Account::Table, Account::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AppPassword::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AppPassword {
+    Table,
+    Id,
+    AccountId,
+    Name,
+    Password,
+    Created,
+    LastUsed,
+}
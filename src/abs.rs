@@ -0,0 +1,1851 @@
+//! an audiobookshelf compatible api at `/abs`, so its apps can browse, stream and sync.
+//!
+//! the apps are pointed at `https://<server>/abs`. a book is an audiobookshelf library item with one
+//! audio track per chapter, the position of the apps is a time in the whole book and is saved as a
+//! chapter and a position in it. the apps log in with the user name and password and get an api
+//! token that expires after 30 days, accounts with two-factor log in with an existing api token as
+//! the password.
+
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        ConnectInfo, FromRequestParts, Path, Query, Request, State,
+    },
+    http::{request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, LoaderTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::{prelude::*, *},
+    fetchbook::{chapter_file, serve_file},
+    rest::{server_message, visible_book, ApiError},
+    server_api::{
+        auth_event::{
+            ssr::{check_throttle, client_ip, dummy_hash, record, LOGIN_FAILED},
+            AuthEventKind,
+        },
+        authors::AuthorDetail,
+        book::{ssr::book_chapters, BookDetail, ChapterDetail},
+        library::ssr::{can_see_library, visible_library_ids},
+        permission::Permission,
+        progress::{
+            progress_details, save_progress, ProgressResult, ProgressUpdate, FINISHED_MARGIN_SECS,
+        },
+        ssr::{date_from_rfc3339, date_to_rfc3339, fetch_page, load_user_permissions, parse_date},
+        token::ssr::{bearer_token, insert_token, user_from_token, TOKEN_PREFIX},
+        totp::ssr::{enabled_totp, role_requires_totp},
+        User,
+    },
+    state::AppState,
+};
+
+/// the audiobookshelf version the apps are told, they check it for the features they use
+pub const SERVER_VERSION: &str = "2.8.1";
+const SOURCE: &str = "audiobookroom";
+/// the device of the updates that do not name one
+const DEFAULT_DEVICE: &str = "audiobookshelf";
+/// the name of the tokens created by the login of the apps
+const TOKEN_NAME: &str = "audiobookshelf app";
+/// the apps log in with the password again when their token expires
+const TOKEN_DAYS: i64 = 30;
+const MAX_ITEMS_PER_PAGE: u64 = 500;
+const SHELF_SIZE: u64 = 10;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/ping", get(ping))
+        .route("/status", get(status))
+        .route("/login", post(login))
+        .route("/api/authorize", post(authorize))
+        .route("/api/me", get(get_me))
+        .route("/api/me/items-in-progress", get(items_in_progress))
+        .route(
+            "/api/me/progress/:id",
+            get(get_media_progress).patch(patch_media_progress),
+        )
+        .route("/api/libraries", get(list_libraries))
+        .route("/api/libraries/:id", get(get_library))
+        .route("/api/libraries/:id/items", get(list_library_items))
+        .route("/api/libraries/:id/personalized", get(personalized))
+        .route("/api/items/:id", get(get_item))
+        .route("/api/items/:id/play", post(play_item))
+        .route("/api/items/:id/file/:chapter_id", get(item_file))
+        .route("/api/session/local", post(sync_local_session))
+        .route("/api/session/local-all", post(sync_local_sessions))
+        .route("/api/session/:id/sync", post(sync_session))
+        .route("/api/session/:id/close", post(close_session))
+        .fallback(|| async { ApiError::not_found("route") })
+}
+
+/// the user of the api token, from `Authorization: Bearer` or from `?token=` which the players
+/// add to the urls of the audio files
+pub struct AbsAuth {
+    pub user: User,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AbsAuth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let token = match bearer_token(&parts.headers) {
+            Some(token) => token.to_string(),
+            None => Query::<TokenQuery>::try_from_uri(&parts.uri)
+                .ok()
+                .and_then(|q| q.0.token)
+                .ok_or_else(ApiError::unauthorized)?,
+        };
+        let user = user_from_token(&token, &state.db)
+            .await?
+            .ok_or_else(ApiError::unauthorized)?;
+        Ok(Self { user, token })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginResponse {
+    pub user: AbsUser,
+    pub user_default_library_id: Option<String>,
+    pub server_settings: ServerSettings,
+    #[serde(rename = "Source")]
+    pub source: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerSettings {
+    pub id: String,
+    pub version: String,
+    pub language: String,
+    pub sorting_ignore_prefix: bool,
+    pub chromecast_enabled: bool,
+    pub date_format: String,
+    pub time_format: String,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            id: "server-settings".to_string(),
+            version: SERVER_VERSION.to_string(),
+            language: "en-us".to_string(),
+            sorting_ignore_prefix: false,
+            chromecast_enabled: false,
+            date_format: "MM/dd/yyyy".to_string(),
+            time_format: "HH:mm".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusResponse {
+    pub is_init: bool,
+    pub language: String,
+    pub auth_methods: Vec<String>,
+    pub server_version: String,
+    #[serde(rename = "Source")]
+    pub source: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsUser {
+    pub id: String,
+    pub username: String,
+    /// `admin` or `user`
+    #[serde(rename = "type")]
+    pub user_type: String,
+    pub token: String,
+    pub media_progress: Vec<MediaProgress>,
+    pub series_hide_from_continue_listening: Vec<String>,
+    pub bookmarks: Vec<serde_json::Value>,
+    pub is_active: bool,
+    pub is_locked: bool,
+    pub last_seen: Option<i64>,
+    pub created_at: i64,
+    pub permissions: AbsPermissions,
+    pub libraries_accessible: Vec<String>,
+    pub item_tags_selected: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsPermissions {
+    pub download: bool,
+    pub update: bool,
+    pub delete: bool,
+    pub upload: bool,
+    pub access_all_libraries: bool,
+    pub access_all_tags: bool,
+    pub access_explicit_content: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsLibrary {
+    pub id: String,
+    pub name: String,
+    pub folders: Vec<AbsFolder>,
+    pub display_order: usize,
+    pub icon: String,
+    pub media_type: String,
+    pub provider: String,
+    pub settings: LibrarySettings,
+    pub created_at: i64,
+    pub last_update: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsFolder {
+    pub id: String,
+    /// empty for users who can not manage the library
+    pub full_path: String,
+    pub library_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrarySettings {
+    pub cover_aspect_ratio: i32,
+    pub disable_watcher: bool,
+    pub skip_matching_media_with_asin: bool,
+    pub skip_matching_media_with_isbn: bool,
+    pub auto_scan_cron_expression: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibrariesResponse {
+    pub libraries: Vec<AbsLibrary>,
+}
+
+/// `GET /api/libraries/:id?include=filterdata`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryWithFilterData {
+    pub library: AbsLibrary,
+    pub filterdata: FilterData,
+    pub issues: u64,
+    pub num_user_playlists: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterData {
+    pub authors: Vec<AbsAuthor>,
+    pub genres: Vec<String>,
+    pub tags: Vec<String>,
+    pub series: Vec<serde_json::Value>,
+    pub narrators: Vec<String>,
+    pub languages: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryItemsResponse {
+    pub results: Vec<LibraryItem>,
+    pub total: u64,
+    /// 0 is every item
+    pub limit: u64,
+    pub page: u64,
+    pub media_type: String,
+    pub minified: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemsInProgressResponse {
+    pub library_items: Vec<LibraryItem>,
+}
+
+/// a row of the home screen of the apps
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Shelf {
+    pub id: String,
+    pub label: String,
+    pub label_string_key: String,
+    #[serde(rename = "type")]
+    pub shelf_type: String,
+    pub entities: Vec<LibraryItem>,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryItem {
+    pub id: String,
+    pub ino: String,
+    pub library_id: String,
+    pub folder_id: String,
+    pub path: String,
+    pub rel_path: String,
+    pub is_file: bool,
+    pub mtime_ms: i64,
+    pub ctime_ms: i64,
+    pub birthtime_ms: i64,
+    pub added_at: i64,
+    pub updated_at: i64,
+    pub is_missing: bool,
+    pub is_invalid: bool,
+    pub media_type: String,
+    pub media: BookMedia,
+    pub num_files: i32,
+    pub size: u64,
+    /// only with `include=progress`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_media_progress: Option<MediaProgress>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMedia {
+    pub library_item_id: String,
+    pub metadata: BookMetadata,
+    pub cover_path: Option<String>,
+    pub tags: Vec<String>,
+    pub duration: f64,
+    pub size: u64,
+    pub num_tracks: i32,
+    pub num_audio_files: i32,
+    pub num_chapters: i32,
+    /// not in the minified items of the lists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chapters: Option<Vec<AbsChapter>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracks: Option<Vec<AudioTrack>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMetadata {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<AbsAuthor>,
+    pub narrators: Vec<String>,
+    pub series: Vec<serde_json::Value>,
+    pub genres: Vec<String>,
+    pub published_year: Option<String>,
+    pub publisher: Option<String>,
+    pub description: Option<String>,
+    pub isbn: Option<String>,
+    pub asin: Option<String>,
+    pub language: Option<String>,
+    pub explicit: bool,
+    pub author_name: String,
+    pub narrator_name: String,
+    pub series_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsAuthor {
+    pub id: String,
+    pub name: String,
+}
+
+/// a chapter marker in the whole book
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsChapter {
+    pub id: usize,
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioTrack {
+    /// from 1
+    pub index: usize,
+    pub start_offset: f64,
+    pub duration: f64,
+    pub title: String,
+    /// relative to `/abs`, the players add `?token=`
+    pub content_url: String,
+    pub mime_type: String,
+    pub metadata: FileMetadata,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMetadata {
+    pub filename: String,
+    pub ext: String,
+    pub path: String,
+    pub rel_path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaProgress {
+    pub id: String,
+    pub library_item_id: String,
+    pub episode_id: Option<String>,
+    pub duration: f64,
+    /// from 0 to 1
+    pub progress: f64,
+    pub current_time: f64,
+    pub is_finished: bool,
+    pub hide_from_continue_listening: bool,
+    pub last_update: i64,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DeviceInfo {
+    pub device_id: Option<String>,
+    pub client_name: Option<String>,
+    pub client_version: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub sdk_version: Option<i32>,
+}
+
+impl DeviceInfo {
+    fn id(&self) -> String {
+        self.device_id
+            .clone()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| DEFAULT_DEVICE.to_string())
+    }
+
+    fn name(&self) -> String {
+        match (&self.manufacturer, &self.model) {
+            (Some(manufacturer), Some(model)) => format!("{} {}", manufacturer, model),
+            _ => self
+                .client_name
+                .clone()
+                .unwrap_or_else(|| "Audiobookshelf".to_string()),
+        }
+    }
+}
+
+/// the body of `POST /api/items/:id/play`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PlayRequest {
+    pub device_info: DeviceInfo,
+    pub media_player: Option<String>,
+    pub force_direct_play: bool,
+    pub force_transcode: bool,
+    pub supported_mime_types: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackSession {
+    pub id: String,
+    pub user_id: String,
+    pub library_id: String,
+    pub library_item_id: String,
+    pub episode_id: Option<String>,
+    pub media_type: String,
+    pub media_metadata: BookMetadata,
+    pub chapters: Vec<AbsChapter>,
+    pub display_title: String,
+    pub display_author: String,
+    pub cover_path: Option<String>,
+    pub duration: f64,
+    /// 0 is direct play, the files are never transcoded
+    pub play_method: i32,
+    pub media_player: String,
+    pub device_info: DeviceInfo,
+    pub server_version: String,
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub day_of_week: String,
+    pub time_listening: f64,
+    pub start_time: f64,
+    pub current_time: f64,
+    pub started_at: i64,
+    pub updated_at: i64,
+    pub audio_tracks: Vec<AudioTrack>,
+    pub library_item: LibraryItem,
+}
+
+/// the body of `POST /api/session/:id/sync` and `close`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRequest {
+    pub current_time: f64,
+    pub time_listened: Option<f64>,
+    pub duration: Option<f64>,
+}
+
+/// a session played without the server, sent when the app is online again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalSession {
+    pub id: String,
+    pub library_item_id: String,
+    pub current_time: f64,
+    /// milliseconds since the unix epoch
+    pub updated_at: i64,
+    #[serde(default)]
+    pub device_info: DeviceInfo,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalSessions {
+    pub sessions: Vec<LocalSession>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalSessionResult {
+    pub id: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalSessionsResponse {
+    pub results: Vec<LocalSessionResult>,
+}
+
+/// the body of `PATCH /api/me/progress/:id`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProgressPatch {
+    pub current_time: Option<f64>,
+    pub duration: Option<f64>,
+    /// from 0 to 1, used when `current_time` is not set
+    pub progress: Option<f64>,
+    pub is_finished: Option<bool>,
+    /// milliseconds since the unix epoch
+    pub last_update: Option<i64>,
+}
+
+impl ProgressPatch {
+    /// the time in the whole book of the patch
+    pub fn current_time(&self, duration: f64) -> Option<f64> {
+        if self.is_finished == Some(true) {
+            return Some(duration);
+        }
+        self.current_time
+            .or_else(|| self.progress.map(|p| p * duration))
+            .filter(|t| t.is_finite())
+    }
+}
+
+/// where each chapter starts in the whole book, in seconds
+pub fn chapter_starts(chapters: &[ChapterDetail]) -> Vec<f64> {
+    chapters
+        .iter()
+        .scan(0.0, |start, c| {
+            let chapter_start = *start;
+            *start += c.chapter_length.unwrap_or(0.0);
+            Some(chapter_start)
+        })
+        .collect()
+}
+
+/// the length of the chapters, the length of the book when they have none
+pub fn book_duration(book: &BookDetail, chapters: &[ChapterDetail]) -> f64 {
+    let duration: f64 = chapters.iter().filter_map(|c| c.chapter_length).sum();
+    if duration > 0.0 {
+        duration
+    } else {
+        book.total_time.unwrap_or(0.0)
+    }
+}
+
+/// the time in the whole book of a position in a chapter
+pub fn book_time(chapters: &[ChapterDetail], chapter_id: i32, progress: f64) -> f64 {
+    chapters
+        .iter()
+        .zip(chapter_starts(chapters))
+        .find(|(c, _)| c.id == chapter_id)
+        .map_or(0.0, |(_, start)| start + progress)
+}
+
+/// the chapter and the position in it of a time in the whole book, a time after the end is the
+/// end of the last chapter
+pub fn chapter_position(chapters: &[ChapterDetail], current_time: f64) -> Option<(i32, f64)> {
+    let current_time = current_time.max(0.0);
+    chapters
+        .iter()
+        .zip(chapter_starts(chapters))
+        .rev()
+        .find(|(_, start)| *start <= current_time)
+        .map(|(c, start)| {
+            let offset = current_time - start;
+            (c.id, c.chapter_length.map_or(offset, |l| offset.min(l)))
+        })
+}
+
+pub fn mime_type(path: &str) -> &'static str {
+    let ext = path.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+    match ext.as_deref() {
+        Some("mp3") => "audio/mpeg",
+        Some("m4a" | "m4b" | "mp4") => "audio/mp4",
+        Some("ogg" | "oga" | "opus") => "audio/ogg",
+        Some("flac") => "audio/flac",
+        Some("wav") => "audio/wav",
+        Some("aac") => "audio/aac",
+        Some("webm") => "audio/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// the play sessions are not stored, their id is the book and the device
+pub fn session_id(music_id: i32, device_id: &str, device_name: &str) -> String {
+    let device = format!("{}\n{}", device_id, device_name);
+    format!("{}-{}", music_id, URL_SAFE_NO_PAD.encode(device))
+}
+
+/// the book, the device id and the device name of a session id
+pub fn parse_session_id(id: &str) -> Option<(i32, String, String)> {
+    let (music_id, device) = id.split_once('-')?;
+    let device = String::from_utf8(URL_SAFE_NO_PAD.decode(device).ok()?).ok()?;
+    let (device_id, device_name) = device.split_once('\n')?;
+    Some((
+        music_id.parse().ok()?,
+        device_id.to_string(),
+        device_name.to_string(),
+    ))
+}
+
+fn abs_author(author: &AuthorDetail) -> AbsAuthor {
+    AbsAuthor {
+        id: author.id.to_string(),
+        name: author.name.clone(),
+    }
+}
+
+pub fn book_metadata(book: &BookDetail, author: &AuthorDetail) -> BookMetadata {
+    BookMetadata {
+        title: book.name.clone(),
+        subtitle: None,
+        authors: vec![abs_author(author)],
+        narrators: vec![],
+        series: vec![],
+        genres: vec![],
+        published_year: None,
+        publisher: None,
+        description: None,
+        isbn: None,
+        asin: None,
+        language: None,
+        explicit: false,
+        author_name: author.name.clone(),
+        narrator_name: String::new(),
+        series_name: String::new(),
+    }
+}
+
+pub fn chapter_markers(chapters: &[ChapterDetail]) -> Vec<AbsChapter> {
+    chapters
+        .iter()
+        .zip(chapter_starts(chapters))
+        .enumerate()
+        .map(|(i, (c, start))| AbsChapter {
+            id: i,
+            start,
+            end: start + c.chapter_length.unwrap_or(0.0),
+            title: c.chapter_name.clone(),
+        })
+        .collect()
+}
+
+pub fn audio_tracks(book: &BookDetail, chapters: &[ChapterDetail]) -> Vec<AudioTrack> {
+    chapters
+        .iter()
+        .zip(chapter_starts(chapters))
+        .enumerate()
+        .map(|(i, (c, start))| {
+            let filename = c.chapter_url.rsplit('/').next().unwrap_or_default();
+            AudioTrack {
+                index: i + 1,
+                start_offset: start,
+                duration: c.chapter_length.unwrap_or(0.0),
+                title: c.chapter_name.clone(),
+                content_url: format!("/api/items/{}/file/{}", book.id, c.id),
+                mime_type: mime_type(&c.chapter_url).to_string(),
+                metadata: FileMetadata {
+                    filename: filename.to_string(),
+                    ext: filename
+                        .rsplit_once('.')
+                        .map(|(_, e)| format!(".{}", e))
+                        .unwrap_or_default(),
+                    path: c.chapter_url.clone(),
+                    rel_path: c.chapter_url.clone(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// a book as a library item, minified without the chapters
+pub fn library_item(
+    book: &BookDetail,
+    author: &AuthorDetail,
+    chapters: Option<&[ChapterDetail]>,
+) -> LibraryItem {
+    let id = book.id.to_string();
+    let duration = match chapters {
+        Some(chapters) => book_duration(book, chapters),
+        None => book.total_time.unwrap_or(0.0),
+    };
+    LibraryItem {
+        id: id.clone(),
+        ino: id.clone(),
+        library_id: book.library_id.to_string(),
+        folder_id: book.library_id.to_string(),
+        path: book.file_folder.clone(),
+        rel_path: book.file_folder.clone(),
+        is_file: false,
+        mtime_ms: 0,
+        ctime_ms: 0,
+        birthtime_ms: 0,
+        added_at: 0,
+        updated_at: 0,
+        is_missing: false,
+        is_invalid: false,
+        media_type: "book".to_string(),
+        media: BookMedia {
+            library_item_id: id,
+            metadata: book_metadata(book, author),
+            cover_path: None,
+            tags: vec![],
+            duration,
+            size: 0,
+            num_tracks: book.chapters,
+            num_audio_files: book.chapters,
+            num_chapters: book.chapters,
+            chapters: chapters.map(chapter_markers),
+            tracks: chapters.map(|chapters| audio_tracks(book, chapters)),
+        },
+        num_files: book.chapters,
+        size: 0,
+        user_media_progress: None,
+    }
+}
+
+pub fn abs_library(library: library::Model, display_order: usize, manage: bool) -> AbsLibrary {
+    let id = library.id.to_string();
+    AbsLibrary {
+        folders: vec![AbsFolder {
+            id: id.clone(),
+            full_path: if manage { library.root } else { String::new() },
+            library_id: id.clone(),
+        }],
+        id,
+        name: library.name,
+        display_order: display_order + 1,
+        icon: "audiobookshelf".to_string(),
+        media_type: "book".to_string(),
+        provider: "audible".to_string(),
+        settings: LibrarySettings {
+            cover_aspect_ratio: 1,
+            disable_watcher: true,
+            skip_matching_media_with_asin: false,
+            skip_matching_media_with_isbn: false,
+            auto_scan_cron_expression: None,
+        },
+        created_at: 0,
+        last_update: 0,
+    }
+}
+
+/// the progress of a book in the whole book
+pub fn media_progress(
+    book: &BookDetail,
+    chapters: &[ChapterDetail],
+    progress: &ProgressResult,
+) -> MediaProgress {
+    let duration = book_duration(book, chapters);
+    let current_time = book_time(chapters, progress.chapter_id, progress.progress);
+    let last_update = progress
+        .client_update
+        .or_else(|| parse_date(&date_to_rfc3339(&progress.update)).map(|d| d.timestamp_millis()))
+        .unwrap_or(0);
+    let is_finished = duration > 0.0 && current_time >= duration - FINISHED_MARGIN_SECS;
+    MediaProgress {
+        id: format!("{}-{}", progress.account_id, book.id),
+        library_item_id: book.id.to_string(),
+        episode_id: None,
+        duration,
+        progress: if duration > 0.0 {
+            (current_time / duration).min(1.0)
+        } else {
+            0.0
+        },
+        current_time,
+        is_finished,
+        hide_from_continue_listening: false,
+        last_update,
+        started_at: last_update,
+        finished_at: is_finished.then_some(last_update),
+    }
+}
+
+pub fn abs_user(
+    user: User,
+    token: String,
+    libraries: &[i32],
+    media_progress: Vec<MediaProgress>,
+) -> AbsUser {
+    let admin = user.can(Permission::ManageUsers);
+    AbsUser {
+        id: user.id.to_string(),
+        user_type: if admin { "admin" } else { "user" }.to_string(),
+        token,
+        media_progress,
+        series_hide_from_continue_listening: vec![],
+        bookmarks: vec![],
+        is_active: true,
+        is_locked: false,
+        last_seen: None,
+        created_at: 0,
+        permissions: AbsPermissions {
+            download: user.can(Permission::Download),
+            update: user.can(Permission::ManageLibrary),
+            delete: user.can(Permission::Delete),
+            upload: user.can(Permission::Upload),
+            access_all_libraries: user.can(Permission::ManageLibrary),
+            access_all_tags: true,
+            access_explicit_content: true,
+        },
+        libraries_accessible: libraries.iter().map(|id| id.to_string()).collect(),
+        item_tags_selected: vec![],
+        username: user.username,
+    }
+}
+
+/// a session starting at `current_time` of a book
+pub fn playback_session(
+    id: String,
+    user_id: i32,
+    item: LibraryItem,
+    current_time: f64,
+    request: PlayRequest,
+) -> PlaybackSession {
+    let now = chrono::Utc::now();
+    PlaybackSession {
+        id,
+        user_id: user_id.to_string(),
+        library_id: item.library_id.clone(),
+        library_item_id: item.id.clone(),
+        episode_id: None,
+        media_type: "book".to_string(),
+        media_metadata: item.media.metadata.clone(),
+        chapters: item.media.chapters.clone().unwrap_or_default(),
+        display_title: item.media.metadata.title.clone(),
+        display_author: item.media.metadata.author_name.clone(),
+        cover_path: None,
+        duration: item.media.duration,
+        play_method: 0,
+        media_player: request
+            .media_player
+            .unwrap_or_else(|| "unknown".to_string()),
+        device_info: request.device_info,
+        server_version: SERVER_VERSION.to_string(),
+        date: now.format("%Y-%m-%d").to_string(),
+        day_of_week: now.format("%A").to_string(),
+        time_listening: 0.0,
+        start_time: current_time,
+        current_time,
+        started_at: now.timestamp_millis(),
+        updated_at: now.timestamp_millis(),
+        audio_tracks: item.media.tracks.clone().unwrap_or_default(),
+        library_item: item,
+    }
+}
+
+fn path_id(path: Result<Path<String>, PathRejection>, what: &str) -> Result<i32, ApiError> {
+    path?.0.parse().map_err(|_| ApiError::not_found(what))
+}
+
+async fn chapters_of(music_id: i32, db: &DatabaseConnection) -> Result<Vec<ChapterDetail>, DbErr> {
+    Ok(book_chapters(music_id)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+async fn author_of(book: &music::Model, db: &DatabaseConnection) -> Result<AuthorDetail, ApiError> {
+    Author::find_by_id(book.author_id)
+        .one(db)
+        .await?
+        .map(Into::into)
+        .ok_or_else(|| ApiError::not_found("author"))
+}
+
+/// the minified items of books with their authors
+async fn minified_items(
+    books: Vec<music::Model>,
+    db: &DatabaseConnection,
+) -> Result<Vec<LibraryItem>, DbErr> {
+    let authors = books.load_one(Author, db).await?;
+    Ok(books
+        .into_iter()
+        .zip(authors)
+        .filter_map(|(book, author)| Some(library_item(&book.into(), &author?.into(), None)))
+        .collect())
+}
+
+/// the progress of the user in every book they can see
+async fn user_media_progress(
+    user: &User,
+    db: &DatabaseConnection,
+) -> Result<Vec<MediaProgress>, DbErr> {
+    let details = progress_details(user, user.id, db).await?;
+    let chapters = Chapter::find()
+        .filter(chapter::Column::MusicId.is_in(details.iter().map(|(_, b, _)| b.id)))
+        .order_by_asc(chapter::Column::ChapterNum)
+        .all(db)
+        .await?;
+    Ok(details
+        .into_iter()
+        .map(|(progress, book, _)| {
+            let chapters: Vec<ChapterDetail> = chapters
+                .iter()
+                .filter(|c| c.music_id == book.id)
+                .cloned()
+                .map(Into::into)
+                .collect();
+            media_progress(&book, &chapters, &progress)
+        })
+        .collect())
+}
+
+async fn user_response(
+    user: User,
+    token: String,
+    db: &DatabaseConnection,
+) -> Result<AbsUser, DbErr> {
+    let libraries = visible_library_ids(&user, db).await?;
+    let progress = user_media_progress(&user, db).await?;
+    Ok(abs_user(user, token, &libraries, progress))
+}
+
+async fn login_response(
+    user: User,
+    token: String,
+    db: &DatabaseConnection,
+) -> Result<LoginResponse, DbErr> {
+    let libraries = visible_library_ids(&user, db).await?;
+    Ok(LoginResponse {
+        user_default_library_id: libraries.iter().min().map(|id| id.to_string()),
+        user: user_response(user, token, db).await?,
+        server_settings: ServerSettings::default(),
+        source: SOURCE.to_string(),
+    })
+}
+
+/// save a time in the whole book as the progress of a device
+async fn save_book_time(
+    user: &User,
+    book_id: i32,
+    current_time: f64,
+    device: &DeviceInfo,
+    client_update: i64,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    if !current_time.is_finite() {
+        return Err(ApiError::bad_request("currentTime must be a number"));
+    }
+    let book = visible_book(user, book_id, db).await?;
+    let chapters = chapters_of(book.id, db).await?;
+    let (chapter_id, progress) =
+        chapter_position(&chapters, current_time).ok_or_else(|| ApiError::not_found("chapter"))?;
+    let update = ProgressUpdate {
+        music_id: book.id,
+        chapter_id,
+        progress,
+        device_id: device.id(),
+        device_name: device.name(),
+        client_update,
+    };
+    save_progress(db, user.id, update)
+        .await
+        .map_err(ApiError::internal)
+}
+
+async fn ping() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "success": true }))
+}
+
+async fn status() -> Json<StatusResponse> {
+    Json(StatusResponse {
+        is_init: true,
+        language: "en-us".to_string(),
+        auth_methods: vec!["local".to_string()],
+        server_version: SERVER_VERSION.to_string(),
+        source: SOURCE.to_string(),
+    })
+}
+
+/// a new token for the password of an account, or the api token given as the password
+async fn login_token(
+    account: &account::Model,
+    password: &str,
+    db: &DatabaseConnection,
+) -> Result<Option<String>, ApiError> {
+    if password.starts_with(TOKEN_PREFIX) {
+        let user = user_from_token(password, db).await?;
+        return Ok(user
+            .filter(|u| u.id == account.id)
+            .map(|_| password.to_string()));
+    }
+    if !bcrypt::verify(password, &account.password).map_err(ApiError::internal)? {
+        return Ok(None);
+    }
+    if !account.approved {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Your account is waiting for the approval of an admin.",
+        ));
+    }
+    if enabled_totp(account.id, db).await?.is_some()
+        || role_requires_totp(account.role_level, db).await?
+    {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Accounts with two-factor log in with an api token as the password.",
+        ));
+    }
+    Ok(Some(app_token(account.id, db).await?))
+}
+
+/// a token for a login of an app, the expired tokens of the earlier logins are deleted
+async fn app_token(account_id: i32, db: &DatabaseConnection) -> Result<String, DbErr> {
+    let now = chrono::Utc::now();
+    let expired: Vec<i32> = ApiToken::find()
+        .filter(api_token::Column::AccountId.eq(account_id))
+        .filter(api_token::Column::Name.eq(TOKEN_NAME))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter(|t| {
+            !t.expires
+                .as_ref()
+                .and_then(|e| parse_date(&date_to_rfc3339(e)))
+                .is_some_and(|e| e > now)
+        })
+        .map(|t| t.id)
+        .collect();
+    ApiToken::delete_many()
+        .filter(api_token::Column::Id.is_in(expired))
+        .exec(db)
+        .await?;
+    let expires = date_from_rfc3339(&(now + chrono::Duration::days(TOKEN_DAYS)).to_rfc3339());
+    insert_token(account_id, TOKEN_NAME.to_string(), expires, db).await
+}
+
+/// `POST /login`
+async fn login(
+    State(db): State<DatabaseConnection>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Result<Json<LoginRequest>, JsonRejection>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let Json(body) = body?;
+    let ip = client_ip(peer.ip(), &headers).to_string();
+    check_throttle(&body.username, &ip, &db)
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "throttled",
+                server_message(e),
+            )
+        })?;
+    let account = Account::find()
+        .filter(account::Column::Name.eq(body.username.as_str()))
+        .one(&db)
+        .await?;
+    // an unknown user takes as long and fails the same way as a wrong password
+    let token = match &account {
+        Some(account) => login_token(account, &body.password, &db).await?,
+        None => {
+            let _ = bcrypt::verify(&body.password, dummy_hash());
+            None
+        }
+    };
+    let (Some(account), Some(token)) = (account, token) else {
+        record(
+            AuthEventKind::LoginFailure,
+            &body.username,
+            &ip,
+            SOURCE,
+            &db,
+        )
+        .await?;
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            LOGIN_FAILED,
+        ));
+    };
+    record(
+        AuthEventKind::LoginSuccess,
+        &body.username,
+        &ip,
+        SOURCE,
+        &db,
+    )
+    .await?;
+    let user = load_user_permissions(account, &db).await?;
+    Ok(Json(login_response(user, token, &db).await?))
+}
+
+/// `POST /api/authorize`, the login of an app which already has a token
+async fn authorize(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    Ok(Json(login_response(auth.user, auth.token, &db).await?))
+}
+
+/// `GET /api/me`
+async fn get_me(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+) -> Result<Json<AbsUser>, ApiError> {
+    Ok(Json(user_response(auth.user, auth.token, &db).await?))
+}
+
+/// `GET /api/me/items-in-progress`, newest first
+async fn items_in_progress(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+) -> Result<Json<ItemsInProgressResponse>, ApiError> {
+    let mut progress = user_media_progress(&auth.user, &db).await?;
+    progress.retain(|p| !p.is_finished);
+    progress.sort_by_key(|p| std::cmp::Reverse(p.last_update));
+    let ids: Vec<i32> = progress
+        .iter()
+        .filter_map(|p| p.library_item_id.parse().ok())
+        .collect();
+    let books = Music::find()
+        .filter(music::Column::Id.is_in(ids.clone()))
+        .all(&db)
+        .await?;
+    let mut items = minified_items(books, &db).await?;
+    items.sort_by_key(|item| ids.iter().position(|id| id.to_string() == item.id));
+    for item in &mut items {
+        item.user_media_progress = progress
+            .iter()
+            .find(|p| p.library_item_id == item.id)
+            .cloned();
+    }
+    Ok(Json(ItemsInProgressResponse {
+        library_items: items,
+    }))
+}
+
+/// `GET /api/me/progress/:id`
+async fn get_media_progress(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+) -> Result<Json<MediaProgress>, ApiError> {
+    let book = visible_book(&auth.user, path_id(path, "book")?, &db).await?;
+    let progress = Progress::find_by_id((auth.user.id, book.id))
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("progress"))?;
+    let chapters = chapters_of(book.id, &db).await?;
+    Ok(Json(media_progress(
+        &book.into(),
+        &chapters,
+        &progress.into(),
+    )))
+}
+
+/// `PATCH /api/me/progress/:id`, the progress of the apps without a play session
+async fn patch_media_progress(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    body: Result<Json<ProgressPatch>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let book = visible_book(&auth.user, path_id(path, "book")?, &db).await?;
+    let Json(body) = body?;
+    let chapters = chapters_of(book.id, &db).await?;
+    let duration = book_duration(&book.clone().into(), &chapters);
+    let Some(current_time) = body.current_time(duration) else {
+        return Ok(StatusCode::OK);
+    };
+    let client_update = body
+        .last_update
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    save_book_time(
+        &auth.user,
+        book.id,
+        current_time,
+        &DeviceInfo::default(),
+        client_update,
+        &db,
+    )
+    .await?;
+    Ok(StatusCode::OK)
+}
+
+/// `GET /api/libraries`
+async fn list_libraries(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+) -> Result<Json<LibrariesResponse>, ApiError> {
+    let ids = visible_library_ids(&auth.user, &db).await?;
+    let manage = auth.user.can(Permission::ManageLibrary);
+    let libraries = Library::find()
+        .filter(library::Column::Id.is_in(ids))
+        .order_by_asc(library::Column::Id)
+        .all(&db)
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(i, l)| abs_library(l, i, manage))
+        .collect();
+    Ok(Json(LibrariesResponse { libraries }))
+}
+
+async fn visible_library(
+    user: &User,
+    path: Result<Path<String>, PathRejection>,
+    db: &DatabaseConnection,
+) -> Result<library::Model, ApiError> {
+    let library_id = path_id(path, "library")?;
+    if !can_see_library(user, library_id, db).await? {
+        return Err(ApiError::not_found("library"));
+    }
+    Library::find_by_id(library_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("library"))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct IncludeQuery {
+    include: Option<String>,
+}
+
+impl IncludeQuery {
+    fn has(&self, name: &str) -> bool {
+        self.include
+            .as_deref()
+            .is_some_and(|i| i.split(',').any(|i| i.trim() == name))
+    }
+}
+
+/// `GET /api/libraries/:id`, with `include=filterdata` the filters of the library screen
+async fn get_library(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    Query(query): Query<IncludeQuery>,
+) -> Result<Response, ApiError> {
+    let library = visible_library(&auth.user, path, &db).await?;
+    let library_id = library.id;
+    let library = abs_library(library, 0, auth.user.can(Permission::ManageLibrary));
+    if !query.has("filterdata") {
+        return Ok(Json(library).into_response());
+    }
+    let books = Music::find()
+        .filter(music::Column::LibraryId.eq(library_id))
+        .all(&db)
+        .await?;
+    let mut authors: Vec<AbsAuthor> = books
+        .load_one(Author, &db)
+        .await?
+        .into_iter()
+        .flatten()
+        .map(|a| abs_author(&a.into()))
+        .collect();
+    authors.sort_by(|a, b| a.name.cmp(&b.name));
+    authors.dedup();
+    Ok(Json(LibraryWithFilterData {
+        library,
+        filterdata: FilterData {
+            authors,
+            ..Default::default()
+        },
+        issues: 0,
+        num_user_playlists: 0,
+    })
+    .into_response())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ItemsQuery {
+    limit: Option<u64>,
+    page: Option<u64>,
+}
+
+/// `GET /api/libraries/:id/items`, by title. the filters and sorting of the apps are ignored
+async fn list_library_items(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    Query(query): Query<ItemsQuery>,
+) -> Result<Json<LibraryItemsResponse>, ApiError> {
+    let library = visible_library(&auth.user, path, &db).await?;
+    let books = Music::find()
+        .filter(music::Column::LibraryId.eq(library.id))
+        .order_by_asc(music::Column::Name);
+    let limit = query.limit.unwrap_or(0).min(MAX_ITEMS_PER_PAGE);
+    let page = query.page.unwrap_or(0);
+    let (books, total) = if limit == 0 {
+        let books = books.all(&db).await?;
+        let total = books.len() as u64;
+        (books, total)
+    } else {
+        let result = fetch_page(books, page, limit, &db).await?;
+        (result.items, result.number_of_items)
+    };
+    Ok(Json(LibraryItemsResponse {
+        results: minified_items(books, &db).await?,
+        total,
+        limit,
+        page,
+        media_type: "book".to_string(),
+        minified: true,
+    }))
+}
+
+/// `GET /api/libraries/:id/personalized`, the shelves of the home screen
+async fn personalized(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+) -> Result<Json<Vec<Shelf>>, ApiError> {
+    let library = visible_library(&auth.user, path, &db).await?;
+    let mut progress = user_media_progress(&auth.user, &db).await?;
+    progress.retain(|p| !p.is_finished);
+    progress.sort_by_key(|p| std::cmp::Reverse(p.last_update));
+    let ids: Vec<i32> = progress
+        .iter()
+        .filter_map(|p| p.library_item_id.parse().ok())
+        .collect();
+    let in_progress = Music::find()
+        .filter(music::Column::LibraryId.eq(library.id))
+        .filter(music::Column::Id.is_in(ids.clone()))
+        .all(&db)
+        .await?;
+    let mut continue_listening = minified_items(in_progress, &db).await?;
+    continue_listening.sort_by_key(|item| ids.iter().position(|id| id.to_string() == item.id));
+    let recent = Music::find()
+        .filter(music::Column::LibraryId.eq(library.id))
+        .order_by_desc(music::Column::Id)
+        .limit(SHELF_SIZE)
+        .all(&db)
+        .await?;
+    let recently_added = minified_items(recent, &db).await?;
+    let shelf = |id: &str, label: &str, key: &str, entities: Vec<LibraryItem>| Shelf {
+        id: id.to_string(),
+        label: label.to_string(),
+        label_string_key: key.to_string(),
+        shelf_type: "book".to_string(),
+        total: entities.len(),
+        entities,
+    };
+    Ok(Json(vec![
+        shelf(
+            "continue-listening",
+            "Continue Listening",
+            "LabelContinueListening",
+            continue_listening,
+        ),
+        shelf(
+            "recently-added",
+            "Recently Added",
+            "LabelRecentlyAdded",
+            recently_added,
+        ),
+    ]))
+}
+
+/// `GET /api/items/:id`, with the chapters and tracks, and the progress with `include=progress`
+async fn get_item(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    Query(query): Query<IncludeQuery>,
+) -> Result<Json<LibraryItem>, ApiError> {
+    let book = visible_book(&auth.user, path_id(path, "book")?, &db).await?;
+    let author = author_of(&book, &db).await?;
+    let chapters = chapters_of(book.id, &db).await?;
+    let progress = if query.has("progress") {
+        Progress::find_by_id((auth.user.id, book.id))
+            .one(&db)
+            .await?
+    } else {
+        None
+    };
+    let book = book.into();
+    let mut item = library_item(&book, &author, Some(&chapters));
+    item.user_media_progress = progress.map(|p| media_progress(&book, &chapters, &p.into()));
+    Ok(Json(item))
+}
+
+/// `POST /api/items/:id/play`, starts at the saved progress
+async fn play_item(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    body: Option<Json<PlayRequest>>,
+) -> Result<Json<PlaybackSession>, ApiError> {
+    let book = visible_book(&auth.user, path_id(path, "book")?, &db).await?;
+    let request = body.map(|b| b.0).unwrap_or_default();
+    let author = author_of(&book, &db).await?;
+    let chapters = chapters_of(book.id, &db).await?;
+    let current_time = Progress::find_by_id((auth.user.id, book.id))
+        .one(&db)
+        .await?
+        .map_or(0.0, |p| book_time(&chapters, p.chapter_id, p.progress));
+    let id = session_id(
+        book.id,
+        &request.device_info.id(),
+        &request.device_info.name(),
+    );
+    let item = library_item(&book.into(), &author, Some(&chapters));
+    Ok(Json(playback_session(
+        id,
+        auth.user.id,
+        item,
+        current_time,
+        request,
+    )))
+}
+
+/// `GET /api/items/:id/file/:chapter_id`, the audio file of a track
+async fn item_file(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    Path((book_id, chapter_id)): Path<(String, i32)>,
+    request: Request<Body>,
+) -> Result<Response, ApiError> {
+    if !auth.user.can(Permission::Download) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "You do not have the permission: download",
+        ));
+    }
+    let book_id = book_id.parse().map_err(|_| ApiError::not_found("book"))?;
+    let book = visible_book(&auth.user, book_id, &db).await?;
+    let chapter = Chapter::find_by_id(chapter_id)
+        .filter(chapter::Column::MusicId.eq(book.id))
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("chapter"))?;
+    let file = chapter_file(&db, &chapter, &book)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::from(e), "not_found", "file not found"))?;
+    Ok(serve_file(file, request).await)
+}
+
+/// the device and the book of a play session
+fn session_device(
+    path: Result<Path<String>, PathRejection>,
+) -> Result<(i32, DeviceInfo), ApiError> {
+    let (music_id, device_id, device_name) =
+        parse_session_id(&path?.0).ok_or_else(|| ApiError::not_found("session"))?;
+    Ok((
+        music_id,
+        DeviceInfo {
+            device_id: Some(device_id),
+            client_name: Some(device_name),
+            ..Default::default()
+        },
+    ))
+}
+
+/// `POST /api/session/:id/sync`
+async fn sync_session(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    body: Result<Json<SyncRequest>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let (music_id, device) = session_device(path)?;
+    let Json(body) = body?;
+    let now = chrono::Utc::now().timestamp_millis();
+    save_book_time(&auth.user, music_id, body.current_time, &device, now, &db).await?;
+    Ok(StatusCode::OK)
+}
+
+/// `POST /api/session/:id/close`, the body with the last position is optional
+async fn close_session(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<String>, PathRejection>,
+    body: Option<Json<SyncRequest>>,
+) -> Result<StatusCode, ApiError> {
+    let (music_id, device) = session_device(path)?;
+    if let Some(Json(body)) = body {
+        let now = chrono::Utc::now().timestamp_millis();
+        save_book_time(&auth.user, music_id, body.current_time, &device, now, &db).await?;
+    }
+    Ok(StatusCode::OK)
+}
+
+async fn save_local_session(
+    user: &User,
+    session: &LocalSession,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    let book_id = session
+        .library_item_id
+        .parse()
+        .map_err(|_| ApiError::not_found("book"))?;
+    save_book_time(
+        user,
+        book_id,
+        session.current_time,
+        &session.device_info,
+        session.updated_at,
+        db,
+    )
+    .await
+}
+
+/// `POST /api/session/local`, an older position than the saved one is ignored
+async fn sync_local_session(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    body: Result<Json<LocalSession>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Json(session) = body?;
+    save_local_session(&auth.user, &session, &db).await?;
+    Ok(StatusCode::OK)
+}
+
+/// `POST /api/session/local-all`
+async fn sync_local_sessions(
+    auth: AbsAuth,
+    State(db): State<DatabaseConnection>,
+    body: Result<Json<LocalSessions>, JsonRejection>,
+) -> Result<Json<LocalSessionsResponse>, ApiError> {
+    let Json(body) = body?;
+    let mut results = vec![];
+    for session in body.sessions {
+        let result = save_local_session(&auth.user, &session, &db).await;
+        results.push(LocalSessionResult {
+            id: session.id,
+            success: result.is_ok(),
+            error: result.err().map(|e| e.message),
+        });
+    }
+    Ok(Json(LocalSessionsResponse { results }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// check that `ours` has every field of a response of audiobookshelf with the same type. `null`
+    /// matches anything, audiobookshelf leaves the unknown metadata and cover `null` too
+    fn assert_shape(fixture: &Value, ours: &Value, path: &str) {
+        match (fixture, ours) {
+            (Value::Null, _) | (_, Value::Null) => {}
+            (Value::Object(fixture), Value::Object(ours)) => {
+                for (key, value) in fixture {
+                    let field = ours
+                        .get(key)
+                        .unwrap_or_else(|| panic!("missing {}.{}", path, key));
+                    assert_shape(value, field, &format!("{}.{}", path, key));
+                }
+            }
+            (Value::Array(fixture), Value::Array(ours)) => {
+                if let (Some(fixture), Some(ours)) = (fixture.first(), ours.first()) {
+                    assert_shape(fixture, ours, &format!("{}[0]", path));
+                }
+            }
+            (Value::Number(_), Value::Number(_))
+            | (Value::String(_), Value::String(_))
+            | (Value::Bool(_), Value::Bool(_)) => {}
+            (fixture, ours) => panic!("{}: expected {} got {}", path, fixture, ours),
+        }
+    }
+
+    fn fixture(name: &str) -> Value {
+        let path = format!(
+            "{}/tests/fixtures/abs/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+    }
+
+    fn book() -> BookDetail {
+        BookDetail {
+            id: 7,
+            author_id: 3,
+            name: "The Hobbit".to_string(),
+            chapters: 3,
+            total_time: Some(300.0),
+            file_folder: "tolkien/the hobbit".to_string(),
+            music_type: 0,
+            library_id: 1,
+        }
+    }
+
+    fn author() -> AuthorDetail {
+        AuthorDetail {
+            id: 3,
+            avatar: String::new(),
+            name: "J. R. R. Tolkien".to_string(),
+            description: String::new(),
+        }
+    }
+
+    fn chapters() -> Vec<ChapterDetail> {
+        [(11, 100.0), (12, 150.0), (13, 50.0)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (id, length))| ChapterDetail {
+                id,
+                music_id: 7,
+                chapter_num: i as i32 + 1,
+                chapter_name: format!("Chapter {}", i + 1),
+                chapter_url: format!("tolkien/the hobbit/{:04}.mp3", i + 1),
+                chapter_length: Some(length),
+            })
+            .collect()
+    }
+
+    fn progress(chapter_id: i32, progress: f64) -> ProgressResult {
+        ProgressResult {
+            account_id: 2,
+            music_id: 7,
+            chapter_id,
+            progress,
+            device_id: "pixel".to_string(),
+            client_update: Some(1_700_000_000_000),
+            update: crate::server_api::ssr::now(),
+        }
+    }
+
+    fn user() -> User {
+        User {
+            id: 2,
+            username: "alice".to_string(),
+            role: 2,
+            permissions: vec![Permission::Download],
+        }
+    }
+
+    fn to_value<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn converts_between_book_and_chapter_time() {
+        let chapters = chapters();
+        assert_eq!(chapter_starts(&chapters), vec![0.0, 100.0, 250.0]);
+        assert_eq!(book_time(&chapters, 12, 20.0), 120.0);
+        assert_eq!(chapter_position(&chapters, 120.0), Some((12, 20.0)));
+        assert_eq!(chapter_position(&chapters, 0.0), Some((11, 0.0)));
+        assert_eq!(chapter_position(&chapters, 100.0), Some((12, 0.0)));
+        assert_eq!(chapter_position(&chapters, -3.0), Some((11, 0.0)));
+        assert_eq!(chapter_position(&chapters, 400.0), Some((13, 50.0)));
+        assert_eq!(chapter_position(&[], 10.0), None);
+    }
+
+    #[test]
+    fn session_id_round_trips() {
+        let id = session_id(7, "a1b2-c3", "Google Pixel 7");
+        assert_eq!(
+            parse_session_id(&id),
+            Some((7, "a1b2-c3".to_string(), "Google Pixel 7".to_string()))
+        );
+        assert_eq!(parse_session_id("7"), None);
+        assert_eq!(parse_session_id("x-abc"), None);
+    }
+
+    #[test]
+    fn progress_patch_time() {
+        let patch = |json: &str| serde_json::from_str::<ProgressPatch>(json).unwrap();
+        assert_eq!(
+            patch(r#"{"currentTime": 42.5}"#).current_time(300.0),
+            Some(42.5)
+        );
+        assert_eq!(
+            patch(r#"{"progress": 0.5}"#).current_time(300.0),
+            Some(150.0)
+        );
+        assert_eq!(
+            patch(r#"{"isFinished": true}"#).current_time(300.0),
+            Some(300.0)
+        );
+        assert_eq!(
+            patch(r#"{"hideFromContinueListening": true}"#).current_time(300.0),
+            None
+        );
+    }
+
+    #[test]
+    fn login_matches_the_recorded_exchange() {
+        let request: LoginRequest = serde_json::from_value(fixture("login_request")).unwrap();
+        assert_eq!(request.username, "alice");
+        let progress = vec![media_progress(&book(), &chapters(), &progress(12, 20.0))];
+        let response = LoginResponse {
+            user: abs_user(user(), "abr_token".to_string(), &[1], progress),
+            user_default_library_id: Some("1".to_string()),
+            server_settings: ServerSettings::default(),
+            source: SOURCE.to_string(),
+        };
+        assert_shape(&fixture("login_response"), &to_value(&response), "login");
+    }
+
+    #[test]
+    fn library_matches_the_recorded_response() {
+        let library = library::Model {
+            id: 1,
+            name: "Audiobooks".to_string(),
+            root: "/audiobooks".to_string(),
+        };
+        let response = LibrariesResponse {
+            libraries: vec![abs_library(library, 0, true)],
+        };
+        assert_shape(&fixture("libraries"), &to_value(&response), "libraries");
+    }
+
+    #[test]
+    fn items_match_the_recorded_responses() {
+        let minified = LibraryItemsResponse {
+            results: vec![library_item(&book(), &author(), None)],
+            total: 1,
+            limit: 10,
+            page: 0,
+            media_type: "book".to_string(),
+            minified: true,
+        };
+        assert_shape(&fixture("library_items"), &to_value(&minified), "items");
+
+        let item = library_item(&book(), &author(), Some(&chapters()));
+        let value = to_value(&item);
+        assert_shape(&fixture("library_item"), &value, "item");
+        assert_eq!(value["media"]["tracks"][1]["startOffset"], 100.0);
+        assert_eq!(
+            value["media"]["tracks"][1]["contentUrl"],
+            "/api/items/7/file/12"
+        );
+        assert_eq!(value["media"]["chapters"][2]["end"], 300.0);
+    }
+
+    #[test]
+    fn play_session_matches_the_recorded_exchange() {
+        let request: PlayRequest = serde_json::from_value(fixture("play_request")).unwrap();
+        assert_eq!(request.device_info.id(), "4b8c1e0a5d3f2a91");
+        assert_eq!(request.device_info.name(), "Google Pixel 7");
+        let item = library_item(&book(), &author(), Some(&chapters()));
+        let session = playback_session("7-abc".to_string(), 2, item, 120.0, request);
+        assert_shape(&fixture("playback_session"), &to_value(&session), "session");
+
+        let sync: SyncRequest = serde_json::from_value(fixture("sync_request")).unwrap();
+        assert_eq!(sync.current_time, 1234.56);
+        let local: LocalSessions = serde_json::from_value(fixture("local_sessions")).unwrap();
+        assert_eq!(local.sessions[0].library_item_id, "7");
+        assert_eq!(local.sessions[0].updated_at, 1_700_000_360_000);
+    }
+
+    #[test]
+    fn progress_matches_the_recorded_exchange() {
+        let patch: ProgressPatch = serde_json::from_value(fixture("progress_patch")).unwrap();
+        assert_eq!(patch.current_time(300.0), Some(120.0));
+        let media = media_progress(&book(), &chapters(), &progress(12, 20.0));
+        assert_eq!(media.current_time, 120.0);
+        assert_eq!(media.progress, 0.4);
+        assert!(!media.is_finished);
+        assert_shape(&fixture("media_progress"), &to_value(&media), "progress");
+        let finished = media_progress(&book(), &chapters(), &progress(13, 48.0));
+        assert!(finished.is_finished);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn logins_replace_the_expired_app_tokens() {
+        use crate::server_api::test_db::{account, memory_db, table};
+        use sea_orm::{ActiveModelTrait, Set};
+
+        let db = memory_db(vec![
+            table(Role),
+            table(RolePermission),
+            table(Account),
+            table(ApiToken),
+        ])
+        .await;
+        let alice = account("alice", 1, &db).await;
+        // a token of an earlier login, and one made before the app tokens expired
+        let old = app_token(alice.id, &db).await.unwrap();
+        let expired =
+            date_from_rfc3339(&(chrono::Utc::now() - chrono::Duration::days(1)).to_rfc3339());
+        let mut tokens = ApiToken::find().all(&db).await.unwrap();
+        let mut first: api_token::ActiveModel = tokens.remove(0).into();
+        first.expires = Set(expired);
+        first.update(&db).await.unwrap();
+        insert_token(alice.id, TOKEN_NAME.to_string(), None, &db)
+            .await
+            .unwrap();
+        insert_token(alice.id, "script".to_string(), None, &db)
+            .await
+            .unwrap();
+
+        let new = app_token(alice.id, &db).await.unwrap();
+        assert_eq!(user_from_token(&old, &db).await.unwrap(), None);
+        assert_eq!(
+            user_from_token(&new, &db).await.unwrap().map(|u| u.id),
+            Some(alice.id)
+        );
+        let names: Vec<_> = ApiToken::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.name, t.expires.is_some()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("script".to_string(), false),
+                (TOKEN_NAME.to_string(), true)
+            ]
+        );
+    }
+}
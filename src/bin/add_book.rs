@@ -10,7 +10,7 @@ mod ssr {
         init_logger_info();
         let Cli {
             db,
-            book_dir,
+            library,
             new_book_name,
             author_name,
             source_dir,
@@ -20,7 +20,7 @@ mod ssr {
         audiobookroom::tools::create_new_book(
             author_name,
             new_book_name,
-            Path::new(&book_dir),
+            library,
             Path::new(&source_dir),
             &db,
         )
@@ -37,9 +37,9 @@ mod ssr {
         )]
         db: String,
 
-        /// the path store all books
-        #[clap(short, long)]
-        book_dir: String,
+        /// the id of the library to add the book to, its folder stores the files
+        #[clap(short, long, default_value_t = 1)]
+        library: i32,
 
         /// the name of the book to be created
         #[clap(short, long)]
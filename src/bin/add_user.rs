@@ -26,6 +26,7 @@ mod ssr {
 
         name: String,
         password: String,
+        /// the id of the role, 0 is admin and 1 is user by default
         role: i32,
     }
 }
@@ -12,10 +12,11 @@ mod ssr {
             name,
             password,
             role,
+            reset_totp,
         } = Cli::parse();
 
         let db = Database::connect(&db).await.unwrap();
-        audiobookroom::tools::alter_user(name, password, role, &db).await;
+        audiobookroom::tools::alter_user(name, password, role, reset_totp, &db).await;
     }
 
     #[derive(Debug, Parser)]
@@ -27,8 +28,12 @@ mod ssr {
         name: String,
         #[clap(short, long)]
         password: Option<String>,
+        /// the id of the role, 0 is admin and 1 is user by default
         #[clap(short, long)]
         role: Option<i32>,
+        /// remove the two-factor authentication and recovery codes, the user can enrol again
+        #[clap(long)]
+        reset_totp: bool,
     }
 }
 
@@ -0,0 +1,85 @@
+#[cfg(feature = "ssr")]
+mod ssr {
+
+    use audiobookroom::{
+        entities::{account, prelude::*},
+        server_api::ssr::init_logger_info,
+    };
+    use clap::{Parser, Subcommand};
+    use sea_orm::{ColumnTrait, Database, EntityTrait, QueryFilter};
+    pub async fn main() {
+        dotenv::dotenv().ok();
+        init_logger_info();
+        let Cli { db, name, command } = Cli::parse();
+
+        let db = Database::connect(&db).await.unwrap();
+        let user = Account::find()
+            .filter(account::Column::Name.eq(&name))
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("user not found:{}", name));
+        match command {
+            Command::Export { output } => {
+                let data = audiobookroom::tools::export_user_data(user.id, &db)
+                    .await
+                    .unwrap();
+                let data = serde_json::to_string_pretty(&data).unwrap();
+                match output {
+                    Some(output) => std::fs::write(output, data).unwrap(),
+                    None => println!("{}", data),
+                }
+            }
+            Command::Import { input, dry_run } => {
+                let data = std::fs::read_to_string(input).unwrap();
+                let data = serde_json::from_str(&data).unwrap();
+                let report = audiobookroom::tools::import_user_data(user.id, data, dry_run, &db)
+                    .await
+                    .unwrap();
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+        }
+    }
+
+    #[derive(Debug, Parser)]
+    pub struct Cli {
+        /// the database url,start at "mysql://"
+        #[clap(short, long)]
+        db: String,
+        /// the name of the user
+        #[clap(short, long)]
+        name: String,
+        #[clap(subcommand)]
+        command: Command,
+    }
+
+    #[derive(Debug, Subcommand)]
+    pub enum Command {
+        /// export the progress, history and bookmarks of the user as json
+        Export {
+            /// the file to write, print to stdout if not set
+            #[clap(short, long)]
+            output: Option<String>,
+        },
+        /// import a json export into the user, the books are matched by title and author
+        Import {
+            /// the exported json file
+            #[clap(short, long)]
+            input: String,
+            /// only report what would be imported
+            #[clap(long)]
+            dry_run: bool,
+        },
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    ssr::main().await;
+}
+
+#[cfg(not(feature = "ssr"))]
+fn main() {
+    println!("this should run in server, enable \"ssr\" feature to run this code.");
+}
@@ -0,0 +1,377 @@
+//! serve the audio files of the libraries.
+//!
+//! a file is only served when it is a chapter of a book the user can see, the path is checked
+//! before touching the file system so `..` and symlinks can not reach files outside the library.
+
+use std::path::{Component, Path, PathBuf};
+
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, Query, State},
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use crate::{
+    entities::{prelude::*, *},
+    server_api::{
+        feed_token::ssr::user_from_feed_token,
+        library::ssr::{can_see_library, find_visible_book},
+        permission::Permission,
+        ssr::AuthSession,
+        token::ssr::with_bearer_user,
+        User,
+    },
+    state::AppState,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FetchError {
+    /// the path leaves the library
+    Forbidden,
+    NotFound,
+    Database,
+}
+
+impl From<FetchError> for StatusCode {
+    fn from(e: FetchError) -> Self {
+        match e {
+            FetchError::Forbidden => StatusCode::FORBIDDEN,
+            FetchError::NotFound => StatusCode::NOT_FOUND,
+            FetchError::Database => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// the percent encoded form of a path like a `chapter_url`, for the urls of the files
+pub fn percent_encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// find the file of a request inside the root of a library.
+///
+/// `request_path` is the percent encoded path after the library id. returns the path relative to
+/// the root, as stored in `chapter_url`, and the real path of the file
+pub fn resolve_library_path(
+    root: &Path,
+    request_path: &str,
+) -> Result<(String, PathBuf), FetchError> {
+    let decoded = percent_decode(request_path).ok_or(FetchError::NotFound)?;
+    resolve_relative_path(root, &decoded)
+}
+
+/// like [`resolve_library_path`] for a path that is not percent encoded, like a `chapter_url`
+pub fn resolve_relative_path(root: &Path, path: &str) -> Result<(String, PathBuf), FetchError> {
+    if path.contains('\0') {
+        return Err(FetchError::NotFound);
+    }
+    let mut parts = vec![];
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str().ok_or(FetchError::NotFound)?),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(FetchError::Forbidden)
+            }
+        }
+    }
+    if parts.is_empty() {
+        return Err(FetchError::NotFound);
+    }
+    let relative = parts.join("/");
+
+    let root = root.canonicalize().map_err(|_| FetchError::NotFound)?;
+    let file = root
+        .join(&relative)
+        .canonicalize()
+        .map_err(|_| FetchError::NotFound)?;
+    // a symlink inside the library pointing outside of it
+    if !file.starts_with(&root) {
+        return Err(FetchError::Forbidden);
+    }
+    if !file.is_file() {
+        return Err(FetchError::NotFound);
+    }
+    Ok((relative, file))
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// `/fetchbook/:library_id/*path`, the podcast apps send the feed token as `?token=` instead of the
+/// session cookie
+pub async fn fetch_book(
+    State(app_state): State<AppState>,
+    auth_session: AuthSession,
+    UrlPath((library_id, _)): UrlPath<(i32, String)>,
+    request: Request<Body>,
+) -> Response {
+    let feed_token = Query::<TokenQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|q| q.0.token);
+    let user = match feed_token {
+        Some(token) => user_from_feed_token(&token, &app_state.db)
+            .await
+            .ok()
+            .flatten(),
+        None => {
+            with_bearer_user(auth_session, request.headers(), &app_state.db)
+                .await
+                .current_user
+        }
+    };
+    let Some(user) = user else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !user.can(Permission::Download) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match find_file(&app_state.db, &user, library_id, request.uri().path()).await {
+        Ok(file) => serve_file(file, request).await,
+        Err(e) => StatusCode::from(e).into_response(),
+    }
+}
+
+/// serve a file with range requests, for seeking in the players
+pub async fn serve_file(file: PathBuf, request: Request<Body>) -> Response {
+    match ServeFile::new(file).oneshot(request).await {
+        Ok(response) => response.map(Body::new).into_response(),
+        Err(e) => match e {},
+    }
+}
+
+/// the file of a chapter of a book, the user must be allowed to see the book
+pub async fn chapter_file(
+    db: &DatabaseConnection,
+    chapter: &chapter::Model,
+    book: &music::Model,
+) -> Result<PathBuf, FetchError> {
+    let library = book_library(db, book).await?;
+    let (_, file) = resolve_relative_path(Path::new(&library.root), &chapter.chapter_url)?;
+    Ok(file)
+}
+
+/// the names of the cover images looked for in the folder of a book
+pub const COVER_NAMES: [&str; 6] = [
+    "cover.jpg",
+    "cover.jpeg",
+    "cover.png",
+    "cover.webp",
+    "folder.jpg",
+    "folder.png",
+];
+
+/// the cover image put in the folder of a book, the user must be allowed to see the book
+pub async fn cover_file(
+    db: &DatabaseConnection,
+    book: &music::Model,
+) -> Result<PathBuf, FetchError> {
+    let library = book_library(db, book).await?;
+    let root = Path::new(&library.root);
+    for name in COVER_NAMES {
+        match resolve_relative_path(root, &format!("{}/{}", book.file_folder, name)) {
+            Ok((_, file)) => return Ok(file),
+            Err(FetchError::NotFound) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(FetchError::NotFound)
+}
+
+async fn book_library(
+    db: &DatabaseConnection,
+    book: &music::Model,
+) -> Result<library::Model, FetchError> {
+    Library::find_by_id(book.library_id)
+        .one(db)
+        .await
+        .map_err(|_| FetchError::Database)?
+        .ok_or(FetchError::NotFound)
+}
+
+/// the file of a chapter the user can see, hidden books are reported as not found
+async fn find_file(
+    db: &DatabaseConnection,
+    user: &User,
+    library_id: i32,
+    uri_path: &str,
+) -> Result<PathBuf, FetchError> {
+    let library = Library::find_by_id(library_id)
+        .one(db)
+        .await
+        .map_err(|_| FetchError::Database)?
+        .ok_or(FetchError::NotFound)?;
+    if !can_see_library(user, library.id, db)
+        .await
+        .map_err(|_| FetchError::Database)?
+    {
+        return Err(FetchError::NotFound);
+    }
+    let prefix = format!("/fetchbook/{}/", library_id);
+    let request_path = uri_path.strip_prefix(&prefix).ok_or(FetchError::NotFound)?;
+    let (relative, file) = resolve_library_path(Path::new(&library.root), request_path)?;
+
+    // the file must be a chapter of a book in this library
+    let chapter = Chapter::find()
+        .filter(chapter::Column::ChapterUrl.eq(&relative))
+        .find_also_related(Music)
+        .all(db)
+        .await
+        .map_err(|_| FetchError::Database)?
+        .into_iter()
+        .find_map(|(c, book)| book.filter(|b| b.library_id == library.id).map(|_| c))
+        .ok_or(FetchError::NotFound)?;
+    find_visible_book(user, chapter.music_id, db)
+        .await
+        .map_err(|_| FetchError::NotFound)?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a library folder with `author/book/0001.mp3` and a secret file next to it
+    struct TestLibrary {
+        dir: PathBuf,
+    }
+
+    impl TestLibrary {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "audiobookroom-fetchbook-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(dir.join("library/author/my book")).unwrap();
+            std::fs::write(dir.join("library/author/my book/0001.mp3"), b"audio").unwrap();
+            std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+            Self { dir }
+        }
+
+        fn root(&self) -> PathBuf {
+            self.dir.join("library")
+        }
+    }
+
+    impl Drop for TestLibrary {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn serves_files_inside_the_library() {
+        let library = TestLibrary::new("inside");
+        let (relative, file) =
+            resolve_library_path(&library.root(), "author/my%20book/0001.mp3").unwrap();
+        assert_eq!(relative, "author/my book/0001.mp3");
+        assert_eq!(std::fs::read(file).unwrap(), b"audio");
+        let (relative, _) =
+            resolve_library_path(&library.root(), "/author/./my%20book/0001.mp3").unwrap();
+        assert_eq!(relative, "author/my book/0001.mp3");
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        let library = TestLibrary::new("traversal");
+        let root = library.root();
+        for path in [
+            "../secret.txt",
+            "%2e%2e/secret.txt",
+            "%2E%2E%2Fsecret.txt",
+            "author/../../secret.txt",
+            "author/my%20book/..%2f..%2f..%2fsecret.txt",
+        ] {
+            assert_eq!(
+                resolve_library_path(&root, path),
+                Err(FetchError::Forbidden),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_and_bad_paths() {
+        let library = TestLibrary::new("missing");
+        let root = library.root();
+        for path in ["", "/", "author", "author/missing.mp3", "%zz", "author%00.mp3"] {
+            assert_eq!(
+                resolve_library_path(&root, path),
+                Err(FetchError::NotFound),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlink_escape() {
+        let library = TestLibrary::new("symlink");
+        let root = library.root();
+        std::os::unix::fs::symlink(library.dir.join("secret.txt"), root.join("escape.mp3"))
+            .unwrap();
+        std::os::unix::fs::symlink(&library.dir, root.join("outside")).unwrap();
+        assert_eq!(
+            resolve_library_path(&root, "escape.mp3"),
+            Err(FetchError::Forbidden)
+        );
+        assert_eq!(
+            resolve_library_path(&root, "outside/secret.txt"),
+            Err(FetchError::Forbidden)
+        );
+
+        // a symlink staying inside the library is fine
+        std::os::unix::fs::symlink(root.join("author"), root.join("alias")).unwrap();
+        let (relative, _) = resolve_library_path(&root, "alias/my%20book/0001.mp3").unwrap();
+        assert_eq!(relative, "alias/my book/0001.mp3");
+    }
+
+    #[test]
+    fn encoded_paths_are_served() {
+        let library = TestLibrary::new("encoded");
+        let encoded = percent_encode_path("author/my book/0001.mp3");
+        assert_eq!(encoded, "author/my%20book/0001.mp3");
+        let (relative, _) = resolve_library_path(&library.root(), &encoded).unwrap();
+        assert_eq!(relative, "author/my book/0001.mp3");
+        assert_eq!(
+            percent_decode(&percent_encode_path("é & ?#%")).as_deref(),
+            Some("é & ?#%")
+        );
+    }
+}
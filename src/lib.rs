@@ -14,13 +14,33 @@ pub type ProgressDateType = String;
 #[cfg(feature = "mysql")]
 pub type ProgressDateType = chrono::DateTime<chrono::Utc>;
 #[cfg(feature = "ssr")]
+pub mod abs;
+#[cfg(feature = "ssr")]
 pub mod entities;
 #[cfg(feature = "ssr")]
 pub mod fallback;
 #[cfg(feature = "ssr")]
+pub mod fetchbook;
+#[cfg(feature = "ssr")]
+pub mod mail;
+#[cfg(feature = "ssr")]
 pub mod middleware;
 #[cfg(feature = "ssr")]
+pub mod oidc;
+#[cfg(feature = "ssr")]
+pub mod opds;
+#[cfg(feature = "ssr")]
+pub mod podcast;
+#[cfg(feature = "ssr")]
+pub mod proxy_auth;
+#[cfg(feature = "ssr")]
+pub mod rest;
+#[cfg(feature = "ssr")]
 pub mod state;
+#[cfg(feature = "ssr")]
+pub mod subsonic;
+#[cfg(feature = "ssr")]
+pub mod xml;
 
 #[cfg(feature = "ssr")]
 pub mod tools;
@@ -0,0 +1,344 @@
+//! the account mails, sent over smtp.
+//!
+//! configured by environment variables, see [`MailConfig::from_env`]. the templates are in
+//! `templates/mail`, a file with the same name in `MAIL_TEMPLATE_DIR` replaces one. the first
+//! line of a `.txt` template is the subject, an optional `.html` template is the html part.
+
+use std::path::PathBuf;
+
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "password_reset.txt",
+        include_str!("../templates/mail/password_reset.txt"),
+    ),
+    (
+        "password_reset.html",
+        include_str!("../templates/mail/password_reset.html"),
+    ),
+    (
+        "password_changed.txt",
+        include_str!("../templates/mail/password_changed.txt"),
+    ),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// plain text, for a local sink like MailHog
+    None,
+    StartTls,
+    /// tls from the start, usually port 465
+    Tls,
+}
+
+#[derive(Clone, Debug)]
+pub struct MailConfig {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+    pub tls: SmtpTls,
+    pub from: Mailbox,
+    /// the address of the server in the links, e.g. `https://books.example.com`
+    pub public_url: String,
+    pub template_dir: Option<PathBuf>,
+}
+
+/// a rendered mail
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+impl MailConfig {
+    /// `None` when `SMTP_HOST` is not set.
+    ///
+    /// - `SMTP_HOST`, `SMTP_PORT`: the port defaults to 587, 465 with `SMTP_TLS=tls`
+    /// - `SMTP_USERNAME`, `SMTP_PASSWORD`: optional
+    /// - `SMTP_TLS`: `starttls` (default), `tls` or `none`
+    /// - `SMTP_FROM`: e.g. `Audiobookroom <books@example.com>`
+    /// - `PUBLIC_URL`: the address of the server in the links
+    /// - `MAIL_TEMPLATE_DIR`: optional, replaces the built in templates
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let host = var("SMTP_HOST")?;
+        let tls = match var("SMTP_TLS").as_deref().map(str::trim) {
+            None | Some("starttls") => SmtpTls::StartTls,
+            Some("tls") => SmtpTls::Tls,
+            Some("none") => SmtpTls::None,
+            Some(other) => {
+                tracing::warn!("SMTP_TLS: unknown value {}, mails are not sent", other);
+                return None;
+            }
+        };
+        let default_port = if tls == SmtpTls::Tls { 465 } else { 587 };
+        let from = var("SMTP_FROM").unwrap_or_else(|| format!("Audiobookroom <noreply@{}>", host));
+        let Ok(from) = from.trim().parse() else {
+            tracing::warn!("SMTP_FROM: invalid address {}, mails are not sent", from);
+            return None;
+        };
+        let Some(public_url) = var("PUBLIC_URL") else {
+            tracing::warn!("SMTP_HOST is set without PUBLIC_URL, mails are not sent");
+            return None;
+        };
+        Some(Self {
+            host: host.trim().to_string(),
+            port: var("SMTP_PORT")
+                .and_then(|p| p.trim().parse().ok())
+                .unwrap_or(default_port),
+            credentials: var("SMTP_USERNAME").zip(var("SMTP_PASSWORD")),
+            tls,
+            from,
+            public_url: public_url.trim().trim_end_matches('/').to_string(),
+            template_dir: var("MAIL_TEMPLATE_DIR").map(PathBuf::from),
+        })
+    }
+
+    fn template(&self, name: &str) -> Option<String> {
+        let custom = self
+            .template_dir
+            .as_ref()
+            .and_then(|dir| std::fs::read_to_string(dir.join(name)).ok());
+        custom.or_else(|| {
+            TEMPLATES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, t)| t.to_string())
+        })
+    }
+
+    /// fill the `{{name}}` placeholders of a template, `name` without the extension
+    pub fn render(&self, name: &str, vars: &[(&str, &str)]) -> Result<Mail, String> {
+        let text = self
+            .template(&format!("{}.txt", name))
+            .ok_or_else(|| format!("no mail template {}", name))?;
+        let (subject, text) = match text.split_once('\n') {
+            Some((first, rest)) if first.starts_with("Subject:") => (
+                first["Subject:".len()..].trim().to_string(),
+                rest.trim_start(),
+            ),
+            _ => return Err(format!("the mail template {} has no subject line", name)),
+        };
+        Ok(Mail {
+            subject: fill(&subject, vars, false),
+            text: fill(text, vars, false),
+            html: self
+                .template(&format!("{}.html", name))
+                .map(|html| fill(&html, vars, true)),
+        })
+    }
+
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, String> {
+        let builder = match self.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
+                .map_err(|e| e.to_string())?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)
+                .map_err(|e| e.to_string())?,
+        };
+        let builder = builder.port(self.port);
+        let builder = match &self.credentials {
+            Some((username, password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            None => builder,
+        };
+        Ok(builder.build())
+    }
+
+    pub async fn send(&self, to: &str, mail: Mail) -> Result<(), String> {
+        let to: Mailbox = to
+            .parse()
+            .map_err(|e| format!("invalid address {}: {}", to, e))?;
+        let builder = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(mail.subject);
+        let message = match mail.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(mail.text, html)),
+            None => builder.body(mail.text),
+        }
+        .map_err(|e| e.to_string())?;
+        self.transport()?
+            .send(message)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// send in the background, a failure is logged
+    pub fn spawn_send(self, to: String, mail: Mail) {
+        tokio::spawn(async move {
+            if let Err(e) = self.send(&to, mail).await {
+                tracing::error!("failed to send the mail to {}: {}", to, e);
+            }
+        });
+    }
+}
+
+fn fill(template: &str, vars: &[(&str, &str)], html: bool) -> String {
+    vars.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            let value = if html {
+                escape_html(value)
+            } else {
+                value.to_string()
+            };
+            text.replace(&format!("{{{{{}}}}}", name), &value)
+        })
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn config(port: u16, template_dir: Option<PathBuf>) -> MailConfig {
+        MailConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            credentials: None,
+            tls: SmtpTls::None,
+            from: "Audiobookroom <books@example.com>".parse().unwrap(),
+            public_url: "http://localhost:3000".to_string(),
+            template_dir,
+        }
+    }
+
+    #[test]
+    fn renders_the_built_in_templates() {
+        let mail = config(25, None)
+            .render(
+                "password_reset",
+                &[
+                    ("username", "<b>tom & jerry</b>"),
+                    ("link", "http://localhost:3000/reset-password?token=1.2.ab"),
+                    ("minutes", "30"),
+                ],
+            )
+            .unwrap();
+        assert_eq!(mail.subject, "Reset your Audiobookroom password");
+        assert!(mail.text.starts_with("Hello <b>tom & jerry</b>,"));
+        assert!(mail
+            .text
+            .contains("http://localhost:3000/reset-password?token=1.2.ab"));
+        assert!(mail.text.contains("expires in 30 minutes"));
+        assert!(!mail.text.contains("{{"));
+        let html = mail.html.unwrap();
+        assert!(html.contains("Hello &lt;b&gt;tom &amp; jerry&lt;/b&gt;,"));
+        assert!(!html.contains("{{"));
+        let changed = config(25, None)
+            .render("password_changed", &[("username", "tom")])
+            .unwrap();
+        assert_eq!(changed.html, None);
+    }
+
+    #[test]
+    fn a_template_dir_replaces_a_template() {
+        let dir = std::env::temp_dir().join(format!("audiobookroom-mail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("password_reset.txt"),
+            "Subject: Hi {{username}}\n\nGo to {{link}}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("password_changed.txt"), "no subject here\n").unwrap();
+        let config = config(25, Some(dir.clone()));
+        let mail = config
+            .render("password_reset", &[("username", "tom"), ("link", "here")])
+            .unwrap();
+        assert_eq!(mail.subject, "Hi tom");
+        assert_eq!(mail.text, "Go to here\n");
+        // the built in html part is still used
+        assert!(mail.html.unwrap().contains("<a href=\"here\">"));
+        assert!(config.render("password_changed", &[]).is_err());
+        assert!(config.render("welcome", &[]).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// a smtp sink on localhost that accepts one mail and returns its data
+    async fn sink() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            let mut data = String::new();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let command = line.to_ascii_uppercase();
+                if command.starts_with("DATA") {
+                    write.write_all(b"354 go ahead\r\n").await.unwrap();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    write.write_all(b"250 queued\r\n").await.unwrap();
+                } else if command.starts_with("QUIT") {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    write.write_all(b"250 ok\r\n").await.unwrap();
+                }
+            }
+            data
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn sends_to_a_local_sink() {
+        let (port, data) = sink().await;
+        let config = config(port, None);
+        let mail = config
+            .render(
+                "password_reset",
+                &[
+                    ("username", "tom"),
+                    ("link", "http://localhost:3000/reset-password?token=1.2.ab"),
+                    ("minutes", "30"),
+                ],
+            )
+            .unwrap();
+        config.send("tom@example.com", mail).await.unwrap();
+        let data = data.await.unwrap();
+        assert!(data.contains("From: Audiobookroom <books@example.com>"));
+        assert!(data.contains("To: tom@example.com"));
+        assert!(data.contains("Subject: Reset your Audiobookroom password"));
+        assert!(data.contains("multipart/alternative"));
+        assert!(data.contains("text/html"));
+    }
+
+    #[tokio::test]
+    async fn an_invalid_address_is_not_sent() {
+        let mail = Mail {
+            subject: "hi".to_string(),
+            text: "hi".to_string(),
+            html: None,
+        };
+        assert!(config(25, None).send("not an address", mail).await.is_err());
+    }
+}
@@ -2,15 +2,31 @@
 mod ssr {
 
     use audiobookroom::{
+        abs,
         fallback::file_and_error_handler,
-        server_api::{ssr::*, User},
+        fetchbook::fetch_book,
+        oidc::{oidc_callback, oidc_login},
+        opds,
+        podcast::book_feed,
+        proxy_auth::{proxy_auth, ProxyAuth, ProxyAuthConfig},
+        rest,
+        server_api::{
+            audit::ssr::spawn_pruning,
+            auth_event::ssr::{client_ip, ClientIp},
+            session::ssr::track_sessions,
+            ssr::*,
+            token::ssr::{bearer_token, with_bearer_user, BearerAuth},
+            webhook::ssr::spawn_delivery,
+            User,
+        },
         state::AppState,
+        subsonic,
         ui::app::App,
     };
     use axum::{
         body::Body as AxumBody,
-        extract::{Request, State},
-        middleware::Next,
+        extract::{ConnectInfo, Request, State},
+        middleware::from_fn_with_state,
         response::{IntoResponse, Response},
         routing::get,
         Router,
@@ -23,19 +39,25 @@ mod ssr {
     use sqlx::mysql::MySqlPoolOptions as PoolOptions;
     #[cfg(feature = "sqlite")]
     use sqlx::sqlite::SqlitePoolOptions as PoolOptions;
-
-    use tower::ServiceBuilder;
-    use tower_http::services::ServeDir;
+    use std::{net::SocketAddr, sync::Arc};
 
     async fn server_fn_handler(
         State(app_state): State<AppState>,
         auth_session: AuthSession,
+        ConnectInfo(peer): ConnectInfo<SocketAddr>,
         request: Request<AxumBody>,
     ) -> impl IntoResponse {
+        let bearer = bearer_token(request.headers()).is_some();
+        let auth_session = with_bearer_user(auth_session, request.headers(), &app_state.db).await;
+        let client_ip = ClientIp(client_ip(peer.ip(), request.headers()));
         handle_server_fns_with_context(
             move || {
                 provide_context(auth_session.clone());
                 provide_context(app_state.db.clone());
+                provide_context(client_ip);
+                if bearer {
+                    provide_context(BearerAuth);
+                }
             },
             request,
         )
@@ -45,14 +67,17 @@ mod ssr {
     async fn leptos_routes_handler(
         auth_session: AuthSession,
         State(app_state): State<AppState>,
+        ConnectInfo(peer): ConnectInfo<SocketAddr>,
         req: Request<AxumBody>,
     ) -> Response {
+        let client_ip = ClientIp(client_ip(peer.ip(), req.headers()));
         let handler = leptos_axum::render_route_with_context(
             app_state.leptos_options.clone(),
             app_state.routes.clone(),
             move || {
                 provide_context(auth_session.clone());
                 provide_context(app_state.db.clone());
+                provide_context(client_ip);
             },
             App,
         );
@@ -76,6 +101,8 @@ mod ssr {
         let db = SqlxConnector::from_sqlx_sqlite_pool(pool.clone());
         #[cfg(feature = "mysql")]
         let db = SqlxConnector::from_sqlx_mysql_pool(pool.clone());
+        spawn_pruning(db.clone());
+        spawn_delivery(db.clone());
 
         // 2. Auth section
         let session_config = SessionConfig::default().with_table_name("axum_sessions");
@@ -97,37 +124,41 @@ mod ssr {
             db: db.clone(),
         };
 
-        async fn check_login(
-            request: axum::extract::Request,
-            next: Next,
-        ) -> axum::response::Response {
-            let auth = request.extensions().get::<AuthSession>().unwrap();
-            match auth.current_user {
-                Some(_) => next.run(request).await,
-                None => {
-                    // return a 401
-                    axum::response::Response::builder()
-                        .status(401)
-                        .body("Unauthorized".into())
-                        .unwrap()
-                }
-            }
-        }
-
         // 4. build our application with a route, will provide the context for server functions(for api call) and leptos routes (for ssr)
-        //  the fetchbook service is a static service but need behad check login layer
-        let fetch_book_service = ServiceBuilder::new()
-            .layer(axum::middleware::from_fn(check_login))
-            .service(ServeDir::new("fetchbook"));
-
         let app = Router::new()
             .route(
                 "/api/*fn_name",
                 get(server_fn_handler).post(server_fn_handler),
             )
+            .nest("/api/v1", rest::router())
+            .nest("/abs", abs::router())
+            .nest("/rest", subsonic::router())
+            .nest(opds::PREFIX, opds::router())
             .leptos_routes_with_handler(routes, get(leptos_routes_handler))
-            .nest_service("/fetchbook", fetch_book_service)
+            .route("/fetchbook/:library_id/*path", get(fetch_book))
+            .route("/feed/:book_id", get(book_feed))
+            .route("/auth/oidc/login", get(oidc_login))
+            .route("/auth/oidc/callback", get(oidc_callback))
             .fallback(file_and_error_handler)
+            // logs out the revoked sessions, so it runs after the proxy login
+            .layer(from_fn_with_state(db.clone(), track_sessions));
+        // the proxy login needs the session, it is added before the auth layers
+        let app = match ProxyAuthConfig::from_env() {
+            Some(config) => {
+                tracing::info!(
+                    "proxy login with {} from {:?}",
+                    config.header,
+                    config.trusted
+                );
+                let state = ProxyAuth {
+                    config: Arc::new(config),
+                    db: db.clone(),
+                };
+                app.layer(from_fn_with_state(state, proxy_auth))
+            }
+            None => app,
+        };
+        let app = app
             .layer(
                 AuthSessionLayer::<User, i32, SessionPool, SqlxPool>::new(Some(pool.clone()))
                     .with_config(auth_config),
@@ -139,9 +170,12 @@ mod ssr {
         // `axum::Server` is a re-export of `hyper::Server`
         log!("listening on http://{}", &addr);
         let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-        axum::serve(listener, app.into_make_service())
-            .await
-            .unwrap();
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
     }
 }
 #[cfg(feature = "ssr")]
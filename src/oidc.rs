@@ -0,0 +1,740 @@
+//! log in with an openid connect provider, authorization code flow with pkce.
+//!
+//! configured by environment variables, see [`OidcConfig::from_env`]. the accounts are linked by
+//! the issuer and subject and created on the first login. the password login stays available.
+
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    entities::{prelude::*, *},
+    server_api::{
+        auth::add_account,
+        auth_event::{ssr as event, AuthEventKind},
+        library::ssr::grant_default_library,
+        ssr::AuthSession,
+    },
+    state::AppState,
+};
+
+/// the session key of a login started at the provider
+const OIDC_FLOW: &str = "oidc_flow";
+/// seconds to come back from the provider
+const FLOW_TIMEOUT: i64 = 600;
+
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    /// the issuer url, the discovery document is at `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// not needed by public clients
+    pub client_secret: Option<String>,
+    /// `https://<host>/auth/oidc/callback`, registered at the provider
+    pub redirect_url: String,
+    pub scopes: String,
+    /// the shown name of the login button
+    pub display_name: String,
+    pub username_claim: String,
+    pub groups_claim: String,
+    /// group and role id, the first group the user is in decides the role
+    pub role_map: Vec<(String, i32)>,
+    /// the role of users in none of the groups, they can not log in when not set
+    pub default_role: Option<i32>,
+}
+
+impl OidcConfig {
+    /// `None` when `OIDC_ISSUER` is not set.
+    ///
+    /// - `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_REDIRECT_URL`: required
+    /// - `OIDC_CLIENT_SECRET`
+    /// - `OIDC_SCOPES`: default `openid profile email groups`
+    /// - `OIDC_DISPLAY_NAME`: default `SSO`
+    /// - `OIDC_USERNAME_CLAIM`: default `preferred_username`
+    /// - `OIDC_GROUPS_CLAIM`: default `groups`
+    /// - `OIDC_ROLE_MAP`: `group=role_id` separated by commas, e.g. `admins=0,family=1`
+    /// - `OIDC_DEFAULT_ROLE`: a role id
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let issuer = var("OIDC_ISSUER")?;
+        let (Some(client_id), Some(redirect_url)) =
+            (var("OIDC_CLIENT_ID"), var("OIDC_REDIRECT_URL"))
+        else {
+            tracing::warn!("OIDC_ISSUER is set without OIDC_CLIENT_ID and OIDC_REDIRECT_URL");
+            return None;
+        };
+        Some(Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret: var("OIDC_CLIENT_SECRET"),
+            redirect_url,
+            scopes: var("OIDC_SCOPES").unwrap_or("openid profile email groups".to_string()),
+            display_name: var("OIDC_DISPLAY_NAME").unwrap_or("SSO".to_string()),
+            username_claim: var("OIDC_USERNAME_CLAIM").unwrap_or("preferred_username".to_string()),
+            groups_claim: var("OIDC_GROUPS_CLAIM").unwrap_or("groups".to_string()),
+            role_map: var("OIDC_ROLE_MAP")
+                .map(|map| parse_role_map(&map))
+                .unwrap_or_default(),
+            default_role: var("OIDC_DEFAULT_ROLE").and_then(|r| r.trim().parse().ok()),
+        })
+    }
+}
+
+/// parse `group=role_id,group=role_id`, wrong entries are skipped
+pub fn parse_role_map(map: &str) -> Vec<(String, i32)> {
+    map.split(',')
+        .filter_map(|entry| {
+            let (group, role) = entry.split_once('=')?;
+            Some((group.trim().to_string(), role.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// the role of a user in `groups`, `None` when the user may not log in
+pub fn map_role(
+    groups: &[String],
+    role_map: &[(String, i32)],
+    default: Option<i32>,
+) -> Option<i32> {
+    role_map
+        .iter()
+        .find(|(group, _)| groups.contains(group))
+        .map(|(_, role)| *role)
+        .or(default)
+}
+
+/// the groups of a claim that is a list or a single string
+pub fn claim_groups(claims: &Value, claim: &str) -> Vec<String> {
+    match claims.get(claim) {
+        Some(Value::Array(groups)) => groups
+            .iter()
+            .filter_map(|g| g.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(group)) => vec![group.clone()],
+        _ => vec![],
+    }
+}
+
+fn random_string() -> String {
+    use rand::Rng;
+    URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// the s256 code challenge of a pkce verifier
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+    access_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OidcFlow {
+    state: String,
+    nonce: String,
+    verifier: String,
+    created: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+async fn discover(config: &OidcConfig) -> Result<Discovery, String> {
+    let url = format!("{}/.well-known/openid-configuration", config.issuer);
+    reqwest::get(&url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("can not fetch {}: {}", url, e))?
+        .json()
+        .await
+        .map_err(|e| format!("invalid discovery document: {}", e))
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    let message = message.into();
+    tracing::warn!("oidc login failed: {}", message);
+    (status, message).into_response()
+}
+
+/// where the user logs in at the provider
+fn authorization_url(
+    config: &OidcConfig,
+    endpoint: &str,
+    flow: &OidcFlow,
+) -> Result<reqwest::Url, String> {
+    reqwest::Url::parse_with_params(
+        endpoint,
+        [
+            ("response_type", "code"),
+            ("client_id", config.client_id.as_str()),
+            ("redirect_uri", config.redirect_url.as_str()),
+            ("scope", config.scopes.as_str()),
+            ("state", flow.state.as_str()),
+            ("nonce", flow.nonce.as_str()),
+            ("code_challenge", code_challenge(&flow.verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+    .map_err(|e| format!("invalid endpoint: {}", e))
+}
+
+/// `/auth/oidc/login`, redirect to the provider
+pub async fn oidc_login(auth_session: AuthSession) -> Response {
+    let Some(config) = OidcConfig::from_env() else {
+        return error(StatusCode::NOT_FOUND, "single sign-on is not configured");
+    };
+    let discovery = match discover(&config).await {
+        Ok(discovery) => discovery,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, e),
+    };
+    let flow = OidcFlow {
+        state: random_string(),
+        nonce: random_string(),
+        verifier: random_string(),
+        created: chrono::Utc::now().timestamp(),
+    };
+    let url = match authorization_url(&config, &discovery.authorization_endpoint, &flow) {
+        Ok(url) => url,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, e),
+    };
+    auth_session.session.set(OIDC_FLOW, flow);
+    Redirect::to(url.as_str()).into_response()
+}
+
+/// `/auth/oidc/callback`, log in the user coming back from the provider
+pub async fn oidc_callback(
+    State(app_state): State<AppState>,
+    auth_session: AuthSession,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> Response {
+    let Some(config) = OidcConfig::from_env() else {
+        return error(StatusCode::NOT_FOUND, "single sign-on is not configured");
+    };
+    let flow = auth_session.session.get::<OidcFlow>(OIDC_FLOW);
+    auth_session.session.remove(OIDC_FLOW);
+    let flow = match flow {
+        Some(flow) if chrono::Utc::now().timestamp() - flow.created <= FLOW_TIMEOUT => flow,
+        _ => return error(StatusCode::BAD_REQUEST, "the login has expired, try again"),
+    };
+    if let Some(e) = query.error {
+        let description = query.error_description.unwrap_or_default();
+        return error(StatusCode::UNAUTHORIZED, format!("{} {}", e, description));
+    }
+    if query.state.as_deref() != Some(flow.state.as_str()) {
+        return error(StatusCode::BAD_REQUEST, "the state does not match");
+    }
+    let Some(code) = query.code else {
+        return error(StatusCode::BAD_REQUEST, "no authorization code");
+    };
+    let claims = match exchange_code(&config, &flow, &code).await {
+        Ok(claims) => claims,
+        Err(e) => return error(StatusCode::UNAUTHORIZED, e),
+    };
+    match find_or_create_account(&config, &claims, &app_state.db).await {
+        Ok(account_id) => {
+            let username = Account::find_by_id(account_id)
+                .one(&app_state.db)
+                .await
+                .ok()
+                .flatten()
+                .map(|a| a.name)
+                .unwrap_or_default();
+            let ip = event::client_ip(peer.ip(), &headers).to_string();
+            let recorded = event::record(
+                AuthEventKind::SsoLogin,
+                &username,
+                &ip,
+                &config.issuer,
+                &app_state.db,
+            )
+            .await;
+            if let Err(e) = recorded {
+                tracing::error!("failed to record the login of {}: {}", username, e);
+            }
+            auth_session.login_user(account_id);
+            Redirect::to("/").into_response()
+        }
+        Err(e) => error(StatusCode::FORBIDDEN, e),
+    }
+}
+
+/// exchange the code and validate the id token, returns its claims with the userinfo claims
+async fn exchange_code(config: &OidcConfig, flow: &OidcFlow, code: &str) -> Result<Value, String> {
+    let discovery = discover(config).await?;
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", config.redirect_url.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", flow.verifier.as_str()),
+    ];
+    if let Some(secret) = &config.client_secret {
+        form.push(("client_secret", secret.as_str()));
+    }
+    let client = reqwest::Client::new();
+    let tokens: TokenResponse = client
+        .post(&discovery.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("token request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("invalid token response: {}", e))?;
+
+    // the id token comes straight from the token endpoint over the backchannel, so the claims are
+    // checked without verifying the signature (openid connect core 3.1.3.7)
+    let mut claims = id_token_claims(&tokens.id_token)?;
+    validate_claims(&claims, &discovery.issuer, &config.client_id, &flow.nonce)?;
+
+    if let (Some(endpoint), Some(access_token)) =
+        (&discovery.userinfo_endpoint, &tokens.access_token)
+    {
+        let userinfo = client
+            .get(endpoint)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Ok(userinfo) = userinfo {
+            if let (Ok(Value::Object(userinfo)), Value::Object(claims)) =
+                (userinfo.json::<Value>().await, &mut claims)
+            {
+                // the id token wins, the userinfo adds claims like the groups
+                for (key, value) in userinfo {
+                    claims.entry(key).or_insert(value);
+                }
+            }
+        }
+    }
+    Ok(claims)
+}
+
+/// the payload of a jwt
+pub fn id_token_claims(id_token: &str) -> Result<Value, String> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or("the id token is not a jwt")?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| format!("invalid id token: {}", e))?;
+    serde_json::from_slice(&payload).map_err(|e| format!("invalid id token: {}", e))
+}
+
+pub fn validate_claims(
+    claims: &Value,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+) -> Result<(), String> {
+    if claims["iss"].as_str() != Some(issuer) {
+        return Err("the id token is from another issuer".to_string());
+    }
+    let audience = match &claims["aud"] {
+        Value::String(aud) => aud == client_id,
+        Value::Array(aud) => aud.iter().any(|a| a.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !audience {
+        return Err("the id token is for another client".to_string());
+    }
+    if !claims["exp"]
+        .as_i64()
+        .is_some_and(|exp| exp > chrono::Utc::now().timestamp())
+    {
+        return Err("the id token has expired".to_string());
+    }
+    if claims["nonce"].as_str() != Some(nonce) {
+        return Err("the nonce does not match".to_string());
+    }
+    Ok(())
+}
+
+/// the linked account with its role updated from the groups, a new account on the first login
+async fn find_or_create_account(
+    config: &OidcConfig,
+    claims: &Value,
+    db: &DatabaseConnection,
+) -> Result<i32, String> {
+    let issuer = claims["iss"].as_str().unwrap_or_default().to_string();
+    let subject = claims["sub"]
+        .as_str()
+        .ok_or("the id token has no subject")?
+        .to_string();
+    let groups = claim_groups(claims, &config.groups_claim);
+    let role = map_role(&groups, &config.role_map, config.default_role)
+        .ok_or("you are not in a group allowed to use audiobookroom")?;
+
+    let db_error = |e: sea_orm::DbErr| e.to_string();
+    let identity = AccountIdentity::find_by_id((issuer.clone(), subject.clone()))
+        .one(db)
+        .await
+        .map_err(db_error)?;
+    if let Some(identity) = identity {
+        account::ActiveModel {
+            id: Set(identity.account_id),
+            role_level: Set(role),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map_err(db_error)?;
+        return Ok(identity.account_id);
+    }
+
+    let username = [
+        config.username_claim.as_str(),
+        "preferred_username",
+        "email",
+    ]
+    .into_iter()
+    .find_map(|claim| claims[claim].as_str())
+    .unwrap_or(&subject)
+    .to_string();
+    // an existing local account is never taken over by a name from the provider
+    let taken = Account::find()
+        .filter(account::Column::Name.eq(&username))
+        .one(db)
+        .await
+        .map_err(db_error)?;
+    if taken.is_some() {
+        return Err(format!(
+            "a local account named {} already exists, ask an admin to rename it",
+            username
+        ));
+    }
+    // the account has no usable password, it logs in through the provider only
+    let account_id = add_account(username, random_string(), role, db)
+        .await
+        .map_err(db_error)?
+        .id;
+    grant_default_library(account_id, db)
+        .await
+        .map_err(db_error)?;
+    AccountIdentity::insert(account_identity::ActiveModel {
+        issuer: Set(issuer),
+        subject: Set(subject),
+        account_id: Set(account_id),
+    })
+    .exec(db)
+    .await
+    .map_err(db_error)?;
+    tracing::info!("account {} created by single sign-on", account_id);
+    Ok(account_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        http::header::AUTHORIZATION,
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const CLIENT_ID: &str = "audiobookroom";
+    const ACCESS_TOKEN: &str = "access-token";
+
+    fn config(issuer: &str) -> OidcConfig {
+        OidcConfig {
+            issuer: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost:3000/auth/oidc/callback".to_string(),
+            scopes: "openid profile groups".to_string(),
+            display_name: "SSO".to_string(),
+            username_claim: "preferred_username".to_string(),
+            groups_claim: "groups".to_string(),
+            role_map: parse_role_map("admins=0,family=1"),
+            default_role: None,
+        }
+    }
+
+    fn new_flow() -> OidcFlow {
+        OidcFlow {
+            state: random_string(),
+            nonce: random_string(),
+            verifier: random_string(),
+            created: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// an unsigned jwt, the signature is not checked
+    fn jwt(claims: &Value) -> String {
+        let part = |value: &Value| URL_SAFE_NO_PAD.encode(value.to_string());
+        format!(
+            "{}.{}.signature",
+            part(&json!({ "alg": "RS256" })),
+            part(claims)
+        )
+    }
+
+    #[test]
+    fn pkce_challenge_is_s256() {
+        // the example of rfc 7636, appendix b
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn validates_the_claims() {
+        let now = chrono::Utc::now().timestamp();
+        let claims = json!({
+            "iss": "https://id.example.com",
+            "aud": ["other", CLIENT_ID],
+            "exp": now + 60,
+            "nonce": "nonce-1",
+        });
+        let validate = |claims: &Value| {
+            validate_claims(claims, "https://id.example.com", CLIENT_ID, "nonce-1")
+        };
+        let with = |key: &str, value: Value| {
+            let mut claims = claims.clone();
+            claims[key] = value;
+            claims
+        };
+        assert_eq!(validate(&claims), Ok(()));
+        assert_eq!(validate(&with("aud", json!(CLIENT_ID))), Ok(()));
+        assert!(validate(&with("iss", json!("https://other.example.com"))).is_err());
+        assert!(validate(&with("aud", json!("other"))).is_err());
+        assert!(validate(&with("exp", json!(now - 1))).is_err());
+        assert!(validate(&with("exp", Value::Null)).is_err());
+        assert!(validate(&with("nonce", json!("nonce-2"))).is_err());
+        assert!(validate(&with("nonce", Value::Null)).is_err());
+    }
+
+    #[test]
+    fn reads_the_claims_of_an_id_token() {
+        let claims = json!({ "sub": "subject-1" });
+        assert_eq!(id_token_claims(&jwt(&claims)), Ok(claims));
+        assert!(id_token_claims("not a jwt").is_err());
+        assert!(id_token_claims("a.!!!.c").is_err());
+    }
+
+    #[test]
+    fn maps_the_groups_to_roles() {
+        let map = parse_role_map("admins=0, family = 1,broken,kids=x");
+        assert_eq!(map, [("admins".to_string(), 0), ("family".to_string(), 1)]);
+        let groups = |claims: Value| claim_groups(&claims, "groups");
+        assert_eq!(groups(json!({ "groups": "family" })), ["family"]);
+        assert!(groups(json!({ "groups": 1 })).is_empty());
+        // the order of the map decides, not the order of the groups
+        let both = groups(json!({ "groups": ["family", "admins"] }));
+        assert_eq!(map_role(&both, &map, None), Some(0));
+        assert_eq!(map_role(&["guests".to_string()], &map, Some(2)), Some(2));
+        assert_eq!(map_role(&[], &map, None), None);
+    }
+
+    /// the code challenge and nonce of each authorization request, by its code
+    #[derive(Clone)]
+    struct MockIssuer {
+        url: String,
+        codes: Arc<Mutex<HashMap<String, (String, String)>>>,
+    }
+
+    /// an issuer on localhost with the discovery, authorization, token and userinfo endpoints
+    async fn mock_issuer() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let issuer = MockIssuer {
+            url: url.clone(),
+            codes: Default::default(),
+        };
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(mock_discovery))
+            .route("/authorize", get(mock_authorize))
+            .route("/token", post(mock_token))
+            .route("/userinfo", get(mock_userinfo))
+            .with_state(issuer);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    async fn mock_discovery(State(issuer): State<MockIssuer>) -> Json<Value> {
+        Json(json!({
+            "issuer": issuer.url,
+            "authorization_endpoint": format!("{}/authorize", issuer.url),
+            "token_endpoint": format!("{}/token", issuer.url),
+            "userinfo_endpoint": format!("{}/userinfo", issuer.url),
+        }))
+    }
+
+    async fn mock_authorize(
+        State(issuer): State<MockIssuer>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Response {
+        let param = |name: &str| params.get(name).cloned().unwrap_or_default();
+        if param("response_type") != "code"
+            || param("client_id") != CLIENT_ID
+            || param("code_challenge_method") != "S256"
+        {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        let mut codes = issuer.codes.lock().unwrap();
+        let code = format!("code-{}", codes.len());
+        codes.insert(code.clone(), (param("code_challenge"), param("nonce")));
+        // the user logged in, back to the callback
+        let location = reqwest::Url::parse_with_params(
+            &param("redirect_uri"),
+            [("code", code), ("state", param("state"))],
+        )
+        .unwrap();
+        Redirect::to(location.as_str()).into_response()
+    }
+
+    async fn mock_token(
+        State(issuer): State<MockIssuer>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        let field = |name: &str| form.get(name).cloned().unwrap_or_default();
+        // a code is used once
+        let Some((challenge, nonce)) = issuer.codes.lock().unwrap().remove(&field("code")) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let verified = URL_SAFE_NO_PAD.encode(Sha256::digest(field("code_verifier"))) == challenge;
+        if !verified
+            || field("grant_type") != "authorization_code"
+            || field("client_id") != CLIENT_ID
+        {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        let claims = json!({
+            "iss": issuer.url,
+            "aud": CLIENT_ID,
+            "sub": "subject-1",
+            "exp": chrono::Utc::now().timestamp() + 60,
+            "nonce": nonce,
+            "preferred_username": "alice",
+        });
+        Json(json!({
+            "id_token": jwt(&claims),
+            "access_token": ACCESS_TOKEN,
+            "token_type": "Bearer",
+        }))
+        .into_response()
+    }
+
+    async fn mock_userinfo(headers: HeaderMap) -> Response {
+        let bearer = format!("Bearer {}", ACCESS_TOKEN);
+        if headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) != Some(bearer.as_str()) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Json(json!({
+            "sub": "subject-1",
+            "preferred_username": "not-alice",
+            "groups": ["family"],
+        }))
+        .into_response()
+    }
+
+    /// log in at the mock issuer like the browser does, returns the query of the callback
+    async fn authorize(config: &OidcConfig, flow: &OidcFlow) -> HashMap<String, String> {
+        let discovery = discover(config).await.unwrap();
+        assert_eq!(discovery.issuer, config.issuer);
+        let url = authorization_url(config, &discovery.authorization_endpoint, flow).unwrap();
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let response = client.get(url).send().await.unwrap();
+        assert!(response.status().is_redirection());
+        let location = response.headers()[reqwest::header::LOCATION]
+            .to_str()
+            .unwrap();
+        assert!(location.starts_with(&config.redirect_url));
+        reqwest::Url::parse(location)
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn logs_in_at_a_local_mock_issuer() {
+        let config = config(&mock_issuer().await);
+        let flow = new_flow();
+        let callback = authorize(&config, &flow).await;
+        assert_eq!(callback["state"], flow.state);
+
+        let claims = exchange_code(&config, &flow, &callback["code"])
+            .await
+            .unwrap();
+        assert_eq!(claims["sub"], "subject-1");
+        // the id token wins, the userinfo adds the groups
+        assert_eq!(claims["preferred_username"], "alice");
+        let groups = claim_groups(&claims, &config.groups_claim);
+        assert_eq!(
+            map_role(&groups, &config.role_map, config.default_role),
+            Some(1)
+        );
+        // the code was used up
+        assert!(exchange_code(&config, &flow, &callback["code"])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_verifier_or_nonce() {
+        let config = config(&mock_issuer().await);
+        let flow = new_flow();
+
+        let callback = authorize(&config, &flow).await;
+        let other_verifier = OidcFlow {
+            verifier: random_string(),
+            ..new_flow()
+        };
+        let error = exchange_code(&config, &other_verifier, &callback["code"])
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("token request failed"), "{}", error);
+
+        let callback = authorize(&config, &flow).await;
+        let other_nonce = OidcFlow {
+            verifier: flow.verifier.clone(),
+            ..new_flow()
+        };
+        let error = exchange_code(&config, &other_nonce, &callback["code"])
+            .await
+            .unwrap_err();
+        assert_eq!(error, "the nonce does not match");
+    }
+}
@@ -0,0 +1,1170 @@
+//! an opds catalog at `/opds` (opds 1.2, atom) and `/opds/v2` (opds 2.0, json), for the reading
+//! apps that browse catalogs.
+//!
+//! the navigation feeds list the authors, the series, the recently added books and all the books,
+//! paged like the server functions. the library has no series, they are the books of an author
+//! named like `Title 2` or `Title, Book 2`, see [`series_of`]. every book is an acquisition feed
+//! with one entry per chapter, the files are served at `/opds/files/:chapter_id`. the apps log in
+//! with http basic (the user name and password, or an api token as the password for accounts with
+//! two-factor), an api token as `Authorization: Bearer` or `?token=`, or the session of the
+//! browser.
+
+use std::{collections::HashMap, net::SocketAddr};
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        ConnectInfo, FromRequestParts, Path, Query, Request, State,
+    },
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+        request::Parts,
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    abs::mime_type,
+    entities::{prelude::*, *},
+    fetchbook::{chapter_file, percent_encode_path, serve_file},
+    rest::{server_message, visible_book, ApiError},
+    server_api::{
+        auth_event::{
+            ssr::{check_throttle, client_ip, dummy_hash, record},
+            AuthEventKind,
+        },
+        authors::ssr::visible_authors,
+        book::ssr::{book_chapters, visible_books},
+        library::ssr::{find_visible_chapter, visible_library_ids},
+        permission::Permission,
+        ssr::{fetch_page, load_user_permissions, AuthSession},
+        token::ssr::{bearer_token, user_from_token, TOKEN_PREFIX},
+        totp::ssr::{enabled_totp, role_requires_totp},
+        PageItems, User,
+    },
+    state::AppState,
+    xml::escape_xml,
+};
+
+/// where the router is nested
+pub const PREFIX: &str = "/opds";
+pub const PAGE_SIZE: u64 = 50;
+const SOURCE: &str = "opds";
+const REALM: &str = "audiobookroom";
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+const OPDS_NS: &str = "http://opds-spec.org/2010/catalog";
+const OPENSEARCH_NS: &str = "http://a9.com/-/spec/opensearch/1.1/";
+const ACQUISITION: &str = "http://opds-spec.org/acquisition";
+const OPDS_JSON: &str = "application/opds+json";
+
+pub fn router() -> Router<AppState> {
+    catalog_routes()
+        .layer(Extension(Format::Atom))
+        .route("/opensearch.xml", get(opensearch))
+        .route("/files/:chapter_id", get(chapter_download))
+        .nest("/v2", catalog_routes().layer(Extension(Format::Json)))
+}
+
+fn catalog_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(root))
+        .route("/authors", get(authors))
+        .route("/authors/:id", get(author_books))
+        .route("/series", get(series))
+        .route("/series/:id", get(series_books))
+        .route("/recent", get(recent))
+        .route("/books", get(books))
+        .route("/books/:id", get(book))
+        .route("/search", get(search))
+}
+
+/// opds 1.2 or opds 2.0, by the path of the feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Atom,
+    Json,
+}
+
+impl Format {
+    pub fn prefix(self) -> String {
+        match self {
+            Format::Atom => PREFIX.to_string(),
+            Format::Json => format!("{}/v2", PREFIX),
+        }
+    }
+
+    /// the name of the search parameter
+    fn search_param(self) -> &'static str {
+        match self {
+            Format::Atom => "q",
+            Format::Json => "query",
+        }
+    }
+}
+
+/// what the links of a feed lead to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Navigation,
+    Acquisition,
+}
+
+impl Kind {
+    fn atom_type(self) -> &'static str {
+        match self {
+            Kind::Navigation => "application/atom+xml;profile=opds-catalog;kind=navigation",
+            Kind::Acquisition => "application/atom+xml;profile=opds-catalog;kind=acquisition",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Paging {
+    /// from 0
+    pub page: u64,
+    pub per_page: u64,
+    pub number_of_items: u64,
+    pub number_of_pages: u64,
+}
+
+impl<T> From<&PageItems<T>> for Paging {
+    fn from(page: &PageItems<T>) -> Self {
+        Self {
+            page: page.page,
+            per_page: page.max_item,
+            number_of_items: page.number_of_items,
+            number_of_pages: page.number_of_pages,
+        }
+    }
+}
+
+/// an entry that leads to another feed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Navigation {
+    pub id: String,
+    pub title: String,
+    /// after the prefix of the format
+    pub path: String,
+    pub content: String,
+    pub kind: Kind,
+}
+
+/// a chapter that can be downloaded
+#[derive(Clone, Debug, PartialEq)]
+pub struct Publication {
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    /// from the root of the server
+    pub href: String,
+    pub mime_type: String,
+    /// seconds
+    pub duration: Option<f64>,
+}
+
+/// a feed, rendered as atom or json
+#[derive(Clone, Debug, PartialEq)]
+pub struct Catalog {
+    pub id: String,
+    pub title: String,
+    /// after the prefix of the format, empty for the root
+    pub path: String,
+    pub kind: Kind,
+    /// the terms of a search feed
+    pub search: Option<String>,
+    /// rfc3339, there are no dates in the library so it is the time of the request
+    pub updated: String,
+    pub paging: Option<Paging>,
+    pub navigation: Vec<Navigation>,
+    pub publications: Vec<Publication>,
+}
+
+impl Catalog {
+    fn new(id: &str, title: &str, path: &str, kind: Kind) -> Self {
+        Self {
+            id: format!("urn:audiobookroom:{}", id),
+            title: title.to_string(),
+            path: path.to_string(),
+            kind,
+            search: None,
+            updated: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            paging: None,
+            navigation: vec![],
+            publications: vec![],
+        }
+    }
+
+    /// the url of a page of the feed
+    pub fn href(&self, format: Format, page: Option<u64>) -> String {
+        let mut params = vec![];
+        if let Some(search) = &self.search {
+            params.push(format!(
+                "{}={}",
+                format.search_param(),
+                percent_encode_path(search)
+            ));
+        }
+        if let Some(page) = page {
+            params.push(format!("page={}", page));
+        }
+        let url = format!("{}{}", format.prefix(), self.path);
+        match params.is_empty() {
+            true => url,
+            false => format!("{}?{}", url, params.join("&")),
+        }
+    }
+
+    /// the rel and the url of the links to the feed itself and the other pages
+    pub fn page_links(&self, format: Format) -> Vec<(&'static str, String)> {
+        let Some(paging) = self.paging else {
+            return vec![("self", self.href(format, None))];
+        };
+        let mut links = vec![("self", self.href(format, Some(paging.page)))];
+        let last = paging.number_of_pages.saturating_sub(1);
+        if paging.page > 0 {
+            links.push(("first", self.href(format, Some(0))));
+            links.push(("previous", self.href(format, Some(paging.page - 1))));
+        }
+        if paging.page < last {
+            links.push(("next", self.href(format, Some(paging.page + 1))));
+            links.push(("last", self.href(format, Some(last))));
+        }
+        links
+    }
+}
+
+fn atom_link(rel: &str, href: &str, kind: &str) -> String {
+    format!(
+        "<link rel=\"{}\" href=\"{}\" type=\"{}\"/>\n",
+        escape_xml(rel),
+        escape_xml(href),
+        escape_xml(kind)
+    )
+}
+
+/// the opds 1.2 document of a catalog
+pub fn render_atom(catalog: &Catalog) -> String {
+    let updated = escape_xml(&catalog.updated);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<feed xmlns=\"{}\" xmlns:opds=\"{}\" xmlns:opensearch=\"{}\">\n",
+        ATOM_NS, OPDS_NS, OPENSEARCH_NS
+    ));
+    out.push_str(&format!("<id>{}</id>\n", escape_xml(&catalog.id)));
+    out.push_str(&format!("<title>{}</title>\n", escape_xml(&catalog.title)));
+    out.push_str(&format!("<updated>{}</updated>\n", updated));
+    out.push_str(&format!("<author><name>{}</name></author>\n", REALM));
+    for (rel, href) in catalog.page_links(Format::Atom) {
+        out.push_str(&atom_link(rel, &href, catalog.kind.atom_type()));
+    }
+    out.push_str(&atom_link("start", PREFIX, Kind::Navigation.atom_type()));
+    out.push_str(&atom_link(
+        "search",
+        &format!("{}/opensearch.xml", PREFIX),
+        "application/opensearchdescription+xml",
+    ));
+    if let Some(paging) = catalog.paging {
+        out.push_str(&format!(
+            "<opensearch:totalResults>{}</opensearch:totalResults>\n",
+            paging.number_of_items
+        ));
+        out.push_str(&format!(
+            "<opensearch:itemsPerPage>{}</opensearch:itemsPerPage>\n",
+            paging.per_page
+        ));
+        out.push_str(&format!(
+            "<opensearch:startIndex>{}</opensearch:startIndex>\n",
+            paging.page * paging.per_page + 1
+        ));
+    }
+    for entry in &catalog.navigation {
+        out.push_str("<entry>\n");
+        out.push_str(&format!("<id>{}</id>\n", escape_xml(&entry.id)));
+        out.push_str(&format!("<title>{}</title>\n", escape_xml(&entry.title)));
+        out.push_str(&format!("<updated>{}</updated>\n", updated));
+        out.push_str(&format!(
+            "<content type=\"text\">{}</content>\n",
+            escape_xml(&entry.content)
+        ));
+        out.push_str(&atom_link(
+            "subsection",
+            &format!("{}{}", PREFIX, entry.path),
+            entry.kind.atom_type(),
+        ));
+        out.push_str("</entry>\n");
+    }
+    for entry in &catalog.publications {
+        out.push_str("<entry>\n");
+        out.push_str(&format!("<id>{}</id>\n", escape_xml(&entry.id)));
+        out.push_str(&format!("<title>{}</title>\n", escape_xml(&entry.title)));
+        out.push_str(&format!("<updated>{}</updated>\n", updated));
+        out.push_str(&format!(
+            "<author><name>{}</name></author>\n",
+            escape_xml(&entry.author)
+        ));
+        out.push_str(&atom_link(ACQUISITION, &entry.href, &entry.mime_type));
+        out.push_str("</entry>\n");
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+fn json_link(rel: &str, href: &str, kind: &str) -> Value {
+    json!({ "rel": rel, "href": href, "type": kind })
+}
+
+/// the opds 2.0 document of a catalog
+pub fn render_json(catalog: &Catalog) -> Value {
+    let mut metadata = json!({ "title": catalog.title });
+    if let Some(paging) = catalog.paging {
+        metadata["numberOfItems"] = json!(paging.number_of_items);
+        metadata["itemsPerPage"] = json!(paging.per_page);
+        metadata["currentPage"] = json!(paging.page + 1);
+    }
+    let mut links: Vec<Value> = catalog
+        .page_links(Format::Json)
+        .into_iter()
+        .map(|(rel, href)| json_link(rel, &href, OPDS_JSON))
+        .collect();
+    links.push(json_link("start", &Format::Json.prefix(), OPDS_JSON));
+    links.push(json!({
+        "rel": "search",
+        "href": format!("{}/search{{?query}}", Format::Json.prefix()),
+        "type": OPDS_JSON,
+        "templated": true,
+    }));
+    let mut feed = json!({ "metadata": metadata, "links": links });
+    // the arrays of opds 2.0 can not be empty
+    if !catalog.navigation.is_empty() {
+        feed["navigation"] = catalog
+            .navigation
+            .iter()
+            .map(|entry| {
+                json!({
+                    "href": format!("{}{}", Format::Json.prefix(), entry.path),
+                    "title": entry.title,
+                    "type": OPDS_JSON,
+                    "rel": "subsection",
+                })
+            })
+            .collect();
+    }
+    if !catalog.publications.is_empty() {
+        feed["publications"] = catalog
+            .publications
+            .iter()
+            .map(|entry| {
+                let mut metadata = json!({
+                    "@type": "http://schema.org/Audiobook",
+                    "identifier": entry.id,
+                    "title": entry.title,
+                    "author": [{ "name": entry.author }],
+                });
+                if let Some(duration) = entry.duration {
+                    metadata["duration"] = json!(duration);
+                }
+                json!({
+                    "metadata": metadata,
+                    "links": [json_link(ACQUISITION, &entry.href, &entry.mime_type)],
+                })
+            })
+            .collect();
+    }
+    feed
+}
+
+fn respond(format: Format, catalog: &Catalog) -> Response {
+    match format {
+        Format::Atom => (
+            [(CONTENT_TYPE, catalog.kind.atom_type())],
+            render_atom(catalog),
+        )
+            .into_response(),
+        Format::Json => ([(CONTENT_TYPE, OPDS_JSON)], Json(render_json(catalog))).into_response(),
+    }
+}
+
+/// the user name and password of an `Authorization: Basic` header
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let credentials = String::from_utf8(STANDARD.decode(credentials.trim()).ok()?).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// the user of a request, see the module documentation for the ways to log in
+pub struct OpdsUser(pub User);
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// asks the apps for the user name and password
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, format!("Basic realm=\"{}\"", REALM))],
+    )
+        .into_response()
+}
+
+/// the user of http basic credentials, the password is an api token for accounts with two-factor.
+///
+/// a reading app sends them again for every feed and every file, so a wrong password is throttled
+/// and recorded as a failed login but a right one is not recorded
+async fn basic_user(
+    username: &str,
+    password: &str,
+    ip: &str,
+    db: &DatabaseConnection,
+) -> Result<Option<User>, ApiError> {
+    check_throttle(username, ip, db).await.map_err(|e| {
+        ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            "throttled",
+            server_message(e),
+        )
+    })?;
+    let account = Account::find()
+        .filter(account::Column::Name.eq(username))
+        .one(db)
+        .await?;
+    let user = match account {
+        Some(account) if password.starts_with(TOKEN_PREFIX) => user_from_token(password, db)
+            .await?
+            .filter(|u| u.id == account.id),
+        Some(account) => {
+            let valid = bcrypt::verify(password, &account.password).map_err(ApiError::internal)?;
+            // accounts with two-factor log in with an api token
+            let totp = enabled_totp(account.id, db).await?.is_some()
+                || role_requires_totp(account.role_level, db).await?;
+            match valid && account.approved && !totp {
+                true => Some(load_user_permissions(account, db).await?),
+                false => None,
+            }
+        }
+        // an unknown user takes as long as a wrong password
+        None => {
+            let _ = bcrypt::verify(password, dummy_hash());
+            None
+        }
+    };
+    if user.is_none() {
+        record(AuthEventKind::LoginFailure, username, ip, SOURCE, db).await?;
+    }
+    Ok(user)
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for OpdsUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Response> {
+        let db = &state.db;
+        if let Some((username, password)) = basic_credentials(&parts.headers) {
+            let ConnectInfo(peer) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            let ip = client_ip(peer.ip(), &parts.headers).to_string();
+            return match basic_user(&username, &password, &ip, db).await {
+                Ok(Some(user)) => Ok(Self(user)),
+                Ok(None) => Err(unauthorized()),
+                Err(e) => Err(e.into_response()),
+            };
+        }
+        let token = bearer_token(&parts.headers)
+            .map(str::to_string)
+            .or_else(|| {
+                Query::<TokenQuery>::try_from_uri(&parts.uri)
+                    .ok()
+                    .and_then(|q| q.0.token)
+            });
+        let user = match token {
+            Some(token) => user_from_token(&token, db)
+                .await
+                .map_err(|e| ApiError::from(e).into_response())?,
+            None => AuthSession::from_request_parts(parts, state)
+                .await
+                .ok()
+                .and_then(|s| s.current_user),
+        };
+        user.map(Self).ok_or_else(unauthorized)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    page: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchQuery {
+    /// opds 1.2
+    q: Option<String>,
+    /// opds 2.0
+    query: Option<String>,
+    page: Option<u64>,
+}
+
+fn navigation(id: &str, title: &str, path: &str, content: String, kind: Kind) -> Navigation {
+    Navigation {
+        id: format!("urn:audiobookroom:{}", id),
+        title: title.to_string(),
+        path: path.to_string(),
+        content,
+        kind,
+    }
+}
+
+fn count(n: impl std::fmt::Display, what: &str) -> String {
+    format!("{} {}", n, what)
+}
+
+/// the title and the volume of a book named like `Title 2`, `Title - 02 - Subtitle`,
+/// `Title, Book 2` or `Title Vol. 2`, the number is the first one after the title
+pub fn series_of(name: &str) -> Option<(String, u32)> {
+    const MARKERS: [&str; 6] = ["book", "vol", "volume", "part", "no", "nr"];
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let (i, volume) = words.iter().enumerate().skip(1).find_map(|(i, word)| {
+        let number = word
+            .trim_start_matches('#')
+            .trim_end_matches([',', '.', ':', ')']);
+        let number = number.trim_start_matches('(');
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some((i, number.parse().ok()?))
+    })?;
+    let mut title = &words[..i];
+    while let Some((last, rest)) = title.split_last() {
+        let marker = last.trim_end_matches(['.', ',']).to_lowercase();
+        if MARKERS.contains(&marker.as_str()) || last.chars().all(|c| "-–:,#(".contains(c)) {
+            title = rest;
+        } else {
+            break;
+        }
+    }
+    let title = title
+        .join(" ")
+        .trim_end_matches([',', ':', '-', '–'])
+        .trim()
+        .to_string();
+    (!title.is_empty()).then_some((title, volume))
+}
+
+/// the books of an author with the same series title, in order of their volume
+#[derive(Clone, Debug)]
+pub struct Series {
+    /// the id of the first book added, stays the same while it is in the library
+    pub id: i32,
+    pub title: String,
+    pub author_id: i32,
+    pub books: Vec<music::Model>,
+}
+
+/// the series of at least two books, by title
+pub fn group_series(books: Vec<music::Model>) -> Vec<Series> {
+    let mut groups: HashMap<(i32, String), Series> = HashMap::new();
+    for book in books {
+        let Some((title, _)) = series_of(&book.name) else {
+            continue;
+        };
+        let series = groups
+            .entry((book.author_id, title.to_lowercase()))
+            .or_insert_with(|| Series {
+                id: book.id,
+                title,
+                author_id: book.author_id,
+                books: vec![],
+            });
+        series.id = series.id.min(book.id);
+        series.books.push(book);
+    }
+    let mut series: Vec<Series> = groups
+        .into_values()
+        .filter(|series| series.books.len() > 1)
+        .collect();
+    for series in &mut series {
+        series
+            .books
+            .sort_by_key(|book| (series_of(&book.name).map(|(_, volume)| volume), book.id));
+    }
+    series.sort_by_key(|series| (series.title.to_lowercase(), series.id));
+    series
+}
+
+/// the names of the authors by id
+async fn author_names(
+    ids: impl IntoIterator<Item = i32>,
+    db: &DatabaseConnection,
+) -> Result<HashMap<i32, String>, ApiError> {
+    Ok(Author::find()
+        .filter(author::Column::Id.is_in(ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|a| (a.id, a.name))
+        .collect())
+}
+
+/// books as entries leading to their acquisition feeds
+async fn book_navigation(
+    books: Vec<music::Model>,
+    db: &DatabaseConnection,
+) -> Result<Vec<Navigation>, ApiError> {
+    let authors = author_names(books.iter().map(|b| b.author_id), db).await?;
+    Ok(books
+        .into_iter()
+        .map(|book| {
+            let author = authors.get(&book.author_id).cloned().unwrap_or_default();
+            navigation(
+                &format!("book:{}", book.id),
+                &book.name,
+                &format!("/books/{}", book.id),
+                format!("{}, {}", author, count(book.chapters, "chapters")),
+                Kind::Acquisition,
+            )
+        })
+        .collect())
+}
+
+/// the books of a page as entries leading to their acquisition feeds
+async fn book_entries(
+    query: sea_orm::Select<music::Entity>,
+    page: Option<u64>,
+    catalog: &mut Catalog,
+    db: &DatabaseConnection,
+) -> Result<(), ApiError> {
+    let page = fetch_page(query, page.unwrap_or(0), PAGE_SIZE, db).await?;
+    catalog.paging = Some((&page).into());
+    catalog.navigation = book_navigation(page.items, db).await?;
+    Ok(())
+}
+
+/// `GET /opds`, the start of the catalog
+async fn root(OpdsUser(user): OpdsUser, Extension(format): Extension<Format>) -> Response {
+    let mut catalog = Catalog::new("root", REALM, "", Kind::Navigation);
+    catalog.navigation = vec![
+        navigation(
+            "authors",
+            "Authors",
+            "/authors",
+            "The books by author".to_string(),
+            Kind::Navigation,
+        ),
+        navigation(
+            "series",
+            "Series",
+            "/series",
+            "The books of a series in order".to_string(),
+            Kind::Navigation,
+        ),
+        navigation(
+            "recent",
+            "Recently Added",
+            "/recent",
+            "The newest books first".to_string(),
+            Kind::Navigation,
+        ),
+        navigation(
+            "books",
+            "All Books",
+            "/books",
+            format!("The books {} can listen to", user.username),
+            Kind::Navigation,
+        ),
+    ];
+    respond(format, &catalog)
+}
+
+/// `GET /opds/authors`
+async fn authors(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let mut catalog = Catalog::new("authors", "Authors", "/authors", Kind::Navigation);
+    let authors = visible_authors(&user, &db).await?;
+    let page = fetch_page(authors, query.page.unwrap_or(0), PAGE_SIZE, &db).await?;
+    catalog.paging = Some((&page).into());
+    let libraries = visible_library_ids(&user, &db).await?;
+    for author in page.items {
+        let books = Music::find()
+            .filter(music::Column::AuthorId.eq(author.id))
+            .filter(music::Column::LibraryId.is_in(libraries.clone()))
+            .count(&db)
+            .await?;
+        catalog.navigation.push(navigation(
+            &format!("author:{}", author.id),
+            &author.name,
+            &format!("/authors/{}", author.id),
+            count(books, "books"),
+            Kind::Navigation,
+        ));
+    }
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/authors/:id`, the books of an author
+async fn author_books(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Path(author_id) = path?;
+    let Query(query) = query?;
+    let author = visible_authors(&user, &db)
+        .await?
+        .filter(author::Column::Id.eq(author_id))
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("author"))?;
+    let mut catalog = Catalog::new(
+        &format!("author:{}", author.id),
+        &author.name,
+        &format!("/authors/{}", author.id),
+        Kind::Navigation,
+    );
+    let books = visible_books(&user, &db)
+        .await?
+        .filter(music::Column::AuthorId.eq(author.id));
+    book_entries(books, query.page, &mut catalog, &db).await?;
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/series`
+async fn series(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let mut catalog = Catalog::new("series", "Series", "/series", Kind::Navigation);
+    let series = group_series(visible_books(&user, &db).await?.all(&db).await?);
+    let page = query.page.unwrap_or(0);
+    let number_of_items = series.len() as u64;
+    catalog.paging = Some(Paging {
+        page,
+        per_page: PAGE_SIZE,
+        number_of_items,
+        number_of_pages: number_of_items.div_ceil(PAGE_SIZE),
+    });
+    let series: Vec<Series> = series
+        .into_iter()
+        .skip((page * PAGE_SIZE) as usize)
+        .take(PAGE_SIZE as usize)
+        .collect();
+    let authors = author_names(series.iter().map(|s| s.author_id), &db).await?;
+    catalog.navigation = series
+        .into_iter()
+        .map(|series| {
+            let author = authors.get(&series.author_id).cloned().unwrap_or_default();
+            navigation(
+                &format!("series:{}", series.id),
+                &series.title,
+                &format!("/series/{}", series.id),
+                format!("{}, {}", author, count(series.books.len(), "books")),
+                Kind::Navigation,
+            )
+        })
+        .collect();
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/series/:id`, the books of a series in order, the id is the one of its oldest book
+async fn series_books(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Response, ApiError> {
+    let Path(series_id) = path?;
+    let book = visible_book(&user, series_id, &db)
+        .await
+        .map_err(|_| ApiError::not_found("series"))?;
+    let books = visible_books(&user, &db)
+        .await?
+        .filter(music::Column::AuthorId.eq(book.author_id))
+        .all(&db)
+        .await?;
+    let series = group_series(books)
+        .into_iter()
+        .find(|s| s.id == series_id)
+        .ok_or_else(|| ApiError::not_found("series"))?;
+    let mut catalog = Catalog::new(
+        &format!("series:{}", series.id),
+        &series.title,
+        &format!("/series/{}", series.id),
+        Kind::Navigation,
+    );
+    catalog.navigation = book_navigation(series.books, &db).await?;
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/recent`, the ids grow with the books added
+async fn recent(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let mut catalog = Catalog::new("recent", "Recently Added", "/recent", Kind::Navigation);
+    let libraries = visible_library_ids(&user, &db).await?;
+    let books = Music::find()
+        .filter(music::Column::LibraryId.is_in(libraries))
+        .order_by_desc(music::Column::Id);
+    book_entries(books, query.page, &mut catalog, &db).await?;
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/books`
+async fn books(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let mut catalog = Catalog::new("books", "All Books", "/books", Kind::Navigation);
+    let books = visible_books(&user, &db).await?;
+    book_entries(books, query.page, &mut catalog, &db).await?;
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/search?q=` and `/opds/v2/search?query=`, the books by title or author
+async fn search(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    query: Result<Query<SearchQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let terms = query
+        .q
+        .or(query.query)
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if terms.is_empty() {
+        return Err(ApiError::bad_request("the search is empty"));
+    }
+    let mut catalog = Catalog::new(
+        "search",
+        &format!("Search: {}", terms),
+        "/search",
+        Kind::Navigation,
+    );
+    let authors: Vec<i32> = Author::find()
+        .filter(author::Column::Name.contains(&terms))
+        .select_only()
+        .column(author::Column::Id)
+        .into_tuple()
+        .all(&db)
+        .await?;
+    let books = visible_books(&user, &db).await?.filter(
+        Condition::any()
+            .add(music::Column::Name.contains(&terms))
+            .add(music::Column::AuthorId.is_in(authors)),
+    );
+    catalog.search = Some(terms);
+    book_entries(books, query.page, &mut catalog, &db).await?;
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/books/:id`, the acquisition feed of a book with an entry per chapter
+async fn book(
+    OpdsUser(user): OpdsUser,
+    Extension(format): Extension<Format>,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Response, ApiError> {
+    let Path(book_id) = path?;
+    let book = visible_book(&user, book_id, &db).await?;
+    let author = Author::find_by_id(book.author_id)
+        .one(&db)
+        .await?
+        .map(|a| a.name)
+        .unwrap_or_default();
+    let mut catalog = Catalog::new(
+        &format!("book:{}", book.id),
+        &book.name,
+        &format!("/books/{}", book.id),
+        Kind::Acquisition,
+    );
+    catalog.publications = book_chapters(book.id)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|chapter| Publication {
+            id: format!("urn:audiobookroom:chapter:{}", chapter.id),
+            title: chapter.chapter_name,
+            author: author.clone(),
+            href: format!("{}/files/{}", PREFIX, chapter.id),
+            mime_type: mime_type(&chapter.chapter_url).to_string(),
+            duration: chapter.chapter_length,
+        })
+        .collect();
+    Ok(respond(format, &catalog))
+}
+
+/// `GET /opds/opensearch.xml`, how the opds 1.2 apps search
+async fn opensearch(_: OpdsUser) -> Response {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <OpenSearchDescription xmlns=\"{}\">\n\
+         <ShortName>{}</ShortName>\n\
+         <Description>Search the books by title or author</Description>\n\
+         <InputEncoding>UTF-8</InputEncoding>\n\
+         <OutputEncoding>UTF-8</OutputEncoding>\n\
+         <Url type=\"{}\" template=\"{}/search?q={{searchTerms}}\"/>\n\
+         </OpenSearchDescription>\n",
+        OPENSEARCH_NS,
+        REALM,
+        Kind::Navigation.atom_type(),
+        PREFIX,
+    );
+    (
+        [(CONTENT_TYPE, "application/opensearchdescription+xml")],
+        body,
+    )
+        .into_response()
+}
+
+/// `GET /opds/files/:chapter_id`, the file of a chapter
+async fn chapter_download(
+    OpdsUser(user): OpdsUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+    request: Request<Body>,
+) -> Result<Response, ApiError> {
+    if !user.can(Permission::Download) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "You do not have the permission: download",
+        ));
+    }
+    let Path(chapter_id) = path?;
+    let (chapter, book) = find_visible_chapter(&user, chapter_id, &db)
+        .await
+        .map_err(|_| ApiError::not_found("chapter"))?;
+    let file = chapter_file(&db, &chapter, &book)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::from(e), "not_found", "file not found"))?;
+    Ok(serve_file(file, request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paged(page: u64, number_of_pages: u64) -> Catalog {
+        let mut catalog =
+            Catalog::new("search", "Search: tom & jerry", "/search", Kind::Navigation);
+        catalog.updated = "2024-03-15T10:00:00Z".to_string();
+        catalog.search = Some("tom & jerry".to_string());
+        catalog.paging = Some(Paging {
+            page,
+            per_page: 2,
+            number_of_items: number_of_pages * 2,
+            number_of_pages,
+        });
+        catalog
+    }
+
+    fn book_feed() -> Catalog {
+        let mut catalog = Catalog::new("book:7", "The Hobbit", "/books/7", Kind::Acquisition);
+        catalog.updated = "2024-03-15T10:00:00Z".to_string();
+        catalog.publications = vec![Publication {
+            id: "urn:audiobookroom:chapter:11".to_string(),
+            title: "Chapter <1>".to_string(),
+            author: "J. R. R. Tolkien".to_string(),
+            href: "/opds/files/11".to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            duration: Some(100.0),
+        }];
+        catalog
+    }
+
+    fn book(id: i32, author_id: i32, name: &str) -> music::Model {
+        music::Model {
+            id,
+            author_id,
+            name: name.to_string(),
+            chapters: 1,
+            total_time: None,
+            file_folder: format!("{}/{}", author_id, name),
+            music_type: 0,
+            library_id: 1,
+        }
+    }
+
+    #[test]
+    fn reads_the_series_of_a_name() {
+        let series = |title: &str, volume| Some((title.to_string(), volume));
+        assert_eq!(series_of("Discworld 1"), series("Discworld", 1));
+        assert_eq!(
+            series_of("The Expanse - 02 - Caliban's War"),
+            series("The Expanse", 2)
+        );
+        assert_eq!(
+            series_of("Wheel of Time, Book 3: The Dragon Reborn"),
+            series("Wheel of Time", 3)
+        );
+        assert_eq!(series_of("Dune Vol. 4"), series("Dune", 4));
+        assert_eq!(series_of("Sandman #5"), series("Sandman", 5));
+        assert_eq!(series_of("Mistborn (1)"), series("Mistborn", 1));
+        assert_eq!(series_of("The Hobbit"), None);
+        assert_eq!(series_of("1984"), None);
+        assert_eq!(series_of("Book 2"), None);
+        assert_eq!(series_of("Catch-22"), None);
+    }
+
+    #[test]
+    fn groups_the_series_of_an_author() {
+        let series = group_series(vec![
+            book(3, 1, "Discworld 2"),
+            book(1, 1, "The Hobbit"),
+            book(5, 1, "discworld 1"),
+            book(4, 2, "Discworld 3"),
+            book(6, 2, "Dune 1"),
+            book(7, 2, "Dune, Book 2"),
+            book(8, 3, "Sandman 1"),
+        ]);
+        let found: Vec<(i32, &str, Vec<i32>)> = series
+            .iter()
+            .map(|s| {
+                (
+                    s.id,
+                    s.title.as_str(),
+                    s.books.iter().map(|b| b.id).collect(),
+                )
+            })
+            .collect();
+        // one book is not a series, another author is another series
+        assert_eq!(
+            found,
+            [(3, "Discworld", vec![5, 3]), (6, "Dune", vec![6, 7])]
+        );
+    }
+
+    #[test]
+    fn reads_basic_credentials() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(basic_credentials(&headers), None);
+        // `Aladdin:open sesame` from rfc 7617
+        headers.insert(
+            AUTHORIZATION,
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==".parse().unwrap(),
+        );
+        assert_eq!(
+            basic_credentials(&headers),
+            Some(("Aladdin".to_string(), "open sesame".to_string()))
+        );
+        headers.insert(AUTHORIZATION, "Bearer abr_x".parse().unwrap());
+        assert_eq!(basic_credentials(&headers), None);
+        headers.insert(AUTHORIZATION, "Basic !!!".parse().unwrap());
+        assert_eq!(basic_credentials(&headers), None);
+    }
+
+    #[test]
+    fn links_the_other_pages() {
+        assert_eq!(
+            paged(0, 1).page_links(Format::Atom),
+            [(
+                "self",
+                "/opds/search?q=tom%20%26%20jerry&page=0".to_string()
+            )]
+        );
+        let links = paged(1, 3).page_links(Format::Json);
+        let rels: Vec<&str> = links.iter().map(|(rel, _)| *rel).collect();
+        assert_eq!(rels, ["self", "first", "previous", "next", "last"]);
+        assert_eq!(links[4].1, "/opds/v2/search?query=tom%20%26%20jerry&page=2");
+        let rels: Vec<&str> = paged(2, 3)
+            .page_links(Format::Atom)
+            .iter()
+            .map(|l| l.0)
+            .collect();
+        assert_eq!(rels, ["self", "first", "previous"]);
+    }
+
+    #[test]
+    fn renders_navigation_as_atom() {
+        let mut catalog = paged(0, 2);
+        catalog.navigation = vec![navigation(
+            "book:7",
+            "Tom & Jerry",
+            "/books/7",
+            "3 chapters".to_string(),
+            Kind::Acquisition,
+        )];
+        let atom = render_atom(&catalog);
+        assert!(atom.contains(&format!("<feed xmlns=\"{}\"", ATOM_NS)));
+        assert!(atom.contains("<title>Search: tom &amp; jerry</title>"));
+        assert!(atom.contains(
+            "<link rel=\"next\" href=\"/opds/search?q=tom%20%26%20jerry&amp;page=1\" type=\"application/atom+xml;profile=opds-catalog;kind=navigation\"/>"
+        ));
+        assert!(atom.contains("<opensearch:totalResults>4</opensearch:totalResults>"));
+        assert!(atom.contains(
+            "<link rel=\"subsection\" href=\"/opds/books/7\" type=\"application/atom+xml;profile=opds-catalog;kind=acquisition\"/>"
+        ));
+        assert!(atom.ends_with("</entry>\n</feed>\n"));
+    }
+
+    #[test]
+    fn renders_acquisitions_as_atom() {
+        let atom = render_atom(&book_feed());
+        assert!(atom.contains("<title>Chapter &lt;1&gt;</title>"));
+        assert!(atom.contains(
+            "<link rel=\"http://opds-spec.org/acquisition\" href=\"/opds/files/11\" type=\"audio/mpeg\"/>"
+        ));
+        assert!(!atom.contains("opensearch:totalResults"));
+    }
+
+    #[test]
+    fn renders_json() {
+        let feed = render_json(&paged(1, 3));
+        assert_eq!(feed["metadata"]["currentPage"], 2);
+        assert_eq!(feed["metadata"]["numberOfItems"], 6);
+        assert!(feed.get("navigation").is_none());
+        assert!(feed["links"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|l| l["rel"] == "search" && l["templated"] == true));
+
+        let feed = render_json(&book_feed());
+        let publication = &feed["publications"][0];
+        assert_eq!(publication["metadata"]["title"], "Chapter <1>");
+        assert_eq!(
+            publication["metadata"]["author"][0]["name"],
+            "J. R. R. Tolkien"
+        );
+        assert_eq!(publication["links"][0]["rel"], ACQUISITION);
+        assert_eq!(publication["links"][0]["href"], "/opds/files/11");
+        assert_eq!(feed["links"][0]["href"], "/opds/v2/books/7");
+    }
+}
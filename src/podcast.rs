@@ -0,0 +1,313 @@
+//! a private podcast feed of every book at `/feed/:book_id?token=`, to listen in a podcast app.
+//!
+//! every chapter is an episode whose enclosure is the file in `/fetchbook` with the same token.
+//! the token is the feed token of the user, resetting or revoking it in the settings stops all the
+//! feeds of the user.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::Deserialize;
+
+use crate::{
+    abs::mime_type,
+    entities::prelude::*,
+    fetchbook::{chapter_file, percent_encode_path},
+    server_api::{
+        auth_event::ssr::is_trusted_proxy, book::ssr::book_chapters,
+        feed_token::ssr::user_from_feed_token, library::ssr::find_visible_book,
+        permission::Permission,
+    },
+    xml::escape_xml,
+};
+
+const ITUNES_NS: &str = "http://www.itunes.com/dtds/podcast-1.0.dtd";
+
+/// a book as a podcast
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feed {
+    pub title: String,
+    pub author: String,
+    /// the page of the book
+    pub link: String,
+    pub episodes: Vec<Episode>,
+}
+
+/// a chapter as an episode
+#[derive(Clone, Debug, PartialEq)]
+pub struct Episode {
+    pub guid: String,
+    pub title: String,
+    pub number: i32,
+    pub url: String,
+    /// bytes, 0 when the file can not be read
+    pub length: u64,
+    pub mime_type: String,
+    /// seconds
+    pub duration: Option<f64>,
+    pub published: chrono::DateTime<chrono::Utc>,
+}
+
+/// the `hh:mm:ss` of `itunes:duration`
+pub fn itunes_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// the chapters have no date, they are one minute apart in their order so the apps that sort the
+/// episodes by date keep it
+pub fn episode_date(number: i32) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(number.max(0) as i64 * 60, 0).unwrap_or_default()
+}
+
+/// the rss 2.0 document of a feed, with the itunes tags the podcast apps read
+pub fn render_feed(feed: &Feed) -> String {
+    let title = escape_xml(&feed.title);
+    let author = escape_xml(&feed.author);
+    let link = escape_xml(&feed.link);
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<rss version=\"2.0\" xmlns:itunes=\"{}\">\n<channel>\n",
+        ITUNES_NS
+    ));
+    out.push_str(&format!("<title>{}</title>\n", title));
+    out.push_str(&format!("<link>{}</link>\n", link));
+    out.push_str(&format!(
+        "<description>{} by {}</description>\n",
+        title, author
+    ));
+    out.push_str(&format!("<itunes:author>{}</itunes:author>\n", author));
+    out.push_str("<itunes:type>serial</itunes:type>\n");
+    out.push_str("<itunes:explicit>false</itunes:explicit>\n");
+    out.push_str("<itunes:block>Yes</itunes:block>\n");
+    out.push_str(
+        "<itunes:category text=\"Arts\"><itunes:category text=\"Books\"/></itunes:category>\n",
+    );
+    for episode in &feed.episodes {
+        out.push_str("<item>\n");
+        out.push_str(&format!(
+            "<guid isPermaLink=\"false\">{}</guid>\n",
+            escape_xml(&episode.guid)
+        ));
+        out.push_str(&format!("<title>{}</title>\n", escape_xml(&episode.title)));
+        out.push_str(&format!(
+            "<itunes:title>{}</itunes:title>\n",
+            escape_xml(&episode.title)
+        ));
+        out.push_str(&format!(
+            "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            escape_xml(&episode.url),
+            episode.length,
+            escape_xml(&episode.mime_type)
+        ));
+        out.push_str(&format!(
+            "<pubDate>{}</pubDate>\n",
+            episode.published.to_rfc2822()
+        ));
+        if let Some(duration) = episode.duration {
+            out.push_str(&format!(
+                "<itunes:duration>{}</itunes:duration>\n",
+                itunes_duration(duration)
+            ));
+        }
+        out.push_str(&format!(
+            "<itunes:episode>{}</itunes:episode>\n",
+            episode.number
+        ));
+        out.push_str("<itunes:episodeType>full</itunes:episodeType>\n");
+        out.push_str("</item>\n");
+    }
+    out.push_str("</channel>\n</rss>\n");
+    out
+}
+
+/// the address of the server in the feeds, `PUBLIC_URL` or the host of the request
+pub fn public_url(peer: IpAddr, headers: &HeaderMap) -> String {
+    if let Some(url) = std::env::var("PUBLIC_URL")
+        .ok()
+        .filter(|u| !u.trim().is_empty())
+    {
+        return url.trim().trim_end_matches('/').to_string();
+    }
+    request_url(headers, is_trusted_proxy(peer))
+}
+
+/// the address the request was sent to, the `X-Forwarded-Host` and `X-Forwarded-Proto` headers
+/// are only read from a trusted proxy, anyone else could point the feed to their own server
+fn request_url(headers: &HeaderMap, trusted: bool) -> String {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let forwarded = |name: &str| header(name).filter(|_| trusted);
+    let proto = forwarded("x-forwarded-proto").unwrap_or("http");
+    let host = forwarded("x-forwarded-host")
+        .or_else(|| header("host"))
+        .unwrap_or("localhost");
+    format!("{}://{}", proto, host)
+}
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    token: Option<String>,
+}
+
+/// `GET /feed/:book_id?token=`
+pub async fn book_feed(
+    State(db): State<DatabaseConnection>,
+    Path(book_id): Path<i32>,
+    Query(query): Query<FeedQuery>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(token) = query.token else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    match feed(&db, book_id, &token, &public_url(peer.ip(), &headers)).await {
+        Ok(feed) => (
+            [(CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+            render_feed(&feed),
+        )
+            .into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+async fn feed(
+    db: &DatabaseConnection,
+    book_id: i32,
+    token: &str,
+    base: &str,
+) -> Result<Feed, StatusCode> {
+    let internal = |e: sea_orm::DbErr| {
+        tracing::error!("feed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let user = user_from_feed_token(token, db)
+        .await
+        .map_err(internal)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    // the episodes can not be played without the files
+    if !user.can(Permission::Download) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let book = find_visible_book(&user, book_id, db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let author = Author::find_by_id(book.author_id)
+        .one(db)
+        .await
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let chapters = book_chapters(book.id).all(db).await.map_err(internal)?;
+    let mut episodes = Vec::with_capacity(chapters.len());
+    for chapter in chapters {
+        let length = match chapter_file(db, &chapter, &book).await {
+            Ok(file) => std::fs::metadata(file).map_or(0, |m| m.len()),
+            Err(_) => 0,
+        };
+        episodes.push(Episode {
+            guid: format!("audiobookroom-chapter-{}", chapter.id),
+            title: chapter.chapter_name.clone(),
+            number: chapter.chapter_num,
+            url: format!(
+                "{}/fetchbook/{}/{}?token={}",
+                base,
+                book.library_id,
+                percent_encode_path(&chapter.chapter_url),
+                token
+            ),
+            length,
+            mime_type: mime_type(&chapter.chapter_url).to_string(),
+            duration: chapter.chapter_length,
+            published: episode_date(chapter.chapter_num),
+        });
+    }
+    Ok(Feed {
+        title: book.name.clone(),
+        author: author.name,
+        link: format!("{}/books/book/{}", base, book.id),
+        episodes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> Feed {
+        Feed {
+            title: "Tom & Jerry".to_string(),
+            author: "<anonymous>".to_string(),
+            link: "https://books.example.com/books/book/7".to_string(),
+            episodes: (1..=2)
+                .map(|n| Episode {
+                    guid: format!("audiobookroom-chapter-{}", 10 + n),
+                    title: format!("Chapter {}", n),
+                    number: n,
+                    url: format!(
+                        "https://books.example.com/fetchbook/1/a/b%20c/{:04}.mp3?token=abrf_x&y",
+                        n
+                    ),
+                    length: 1234,
+                    mime_type: "audio/mpeg".to_string(),
+                    duration: Some(3723.4),
+                    published: episode_date(n),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(itunes_duration(3723.4), "01:02:03");
+        assert_eq!(itunes_duration(59.6), "00:01:00");
+        assert_eq!(itunes_duration(-1.0), "00:00:00");
+    }
+
+    #[test]
+    fn episodes_are_dated_in_order() {
+        assert!(episode_date(1) < episode_date(2));
+        assert_eq!(
+            episode_date(1).to_rfc2822(),
+            "Thu, 1 Jan 1970 00:01:00 +0000"
+        );
+    }
+
+    #[test]
+    fn renders_an_item_per_chapter() {
+        let rss = render_feed(&feed());
+        assert!(rss.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\""));
+        assert!(rss.contains(&format!("xmlns:itunes=\"{}\"", ITUNES_NS)));
+        assert_eq!(rss.matches("<item>").count(), 2);
+        assert!(rss.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(rss.contains("<itunes:author>&lt;anonymous&gt;</itunes:author>"));
+        assert!(rss.contains("<title>Chapter 2</title>"));
+        assert!(rss.contains(
+            "<enclosure url=\"https://books.example.com/fetchbook/1/a/b%20c/0001.mp3?token=abrf_x&amp;y\" length=\"1234\" type=\"audio/mpeg\"/>"
+        ));
+        assert!(rss.contains("<itunes:duration>01:02:03</itunes:duration>"));
+        assert!(rss.contains("<itunes:episode>2</itunes:episode>"));
+        assert!(rss.ends_with("</channel>\n</rss>\n"));
+    }
+
+    #[test]
+    fn only_trusted_proxies_forward_the_host() {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "10.0.0.2:3000".parse().unwrap());
+        headers.insert("x-forwarded-host", "evil.example.com".parse().unwrap());
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        assert_eq!(request_url(&headers, false), "http://10.0.0.2:3000");
+        assert_eq!(request_url(&headers, true), "https://evil.example.com");
+        headers.remove("x-forwarded-host");
+        assert_eq!(request_url(&headers, true), "https://10.0.0.2:3000");
+        assert_eq!(request_url(&HeaderMap::new(), false), "http://localhost");
+    }
+}
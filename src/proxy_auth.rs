@@ -0,0 +1,319 @@
+//! log in the user named by a header of a trusted reverse proxy, like authelia or oauth2-proxy.
+//!
+//! the header is only read from the configured proxy addresses, anyone else could send it. a
+//! request from a proxy without the header keeps its session, so the password login still works.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+use crate::{
+    entities::{prelude::*, *},
+    server_api::{
+        auth::add_account,
+        auth_event::{ssr as event, AuthEventKind},
+        library::ssr::grant_default_library,
+        ssr::{load_user_permissions, AuthSession},
+    },
+};
+
+/// an address range like `10.0.0.0/8` or `fd00::/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// parse a range, a single address is a range of one address
+    pub fn parse(cidr: &str) -> Option<Self> {
+        let (addr, prefix) = match cidr.trim().split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+            None => (cidr.trim().parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // an ipv4 client of a dual stack listener shows up as `::ffff:a.b.c.d`
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+        let (net, ip, bits) = match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                (u32::from(net) as u128, u32::from(ip) as u128, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => (u128::from(net), u128::from(ip), 128),
+            _ => return false,
+        };
+        let shift = bits - self.prefix as u32;
+        shift >= bits || (net >> shift) == (ip >> shift)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProxyAuthConfig {
+    /// the header with the user name, e.g. `Remote-User`
+    pub header: String,
+    pub trusted: Vec<Cidr>,
+    /// create unknown users with this role, unknown users are rejected when not set
+    pub auto_create_role: Option<i32>,
+}
+
+impl ProxyAuthConfig {
+    /// `None` when `PROXY_AUTH_HEADER` is not set.
+    ///
+    /// - `PROXY_AUTH_HEADER`: e.g. `Remote-User`
+    /// - `PROXY_AUTH_TRUSTED`: the addresses of the proxies separated by commas, e.g.
+    ///   `127.0.0.1,172.16.0.0/12`
+    /// - `PROXY_AUTH_AUTO_CREATE`: the role id of the accounts created for unknown users
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let header = var("PROXY_AUTH_HEADER")?;
+        let trusted: Vec<Cidr> = var("PROXY_AUTH_TRUSTED")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|c| !c.trim().is_empty())
+            .filter_map(|c| {
+                let cidr = Cidr::parse(c);
+                if cidr.is_none() {
+                    tracing::warn!("PROXY_AUTH_TRUSTED: invalid address {}", c);
+                }
+                cidr
+            })
+            .collect();
+        if trusted.is_empty() {
+            tracing::warn!("PROXY_AUTH_HEADER is set without PROXY_AUTH_TRUSTED, it is ignored");
+            return None;
+        }
+        Some(Self {
+            header: header.trim().to_string(),
+            trusted,
+            auto_create_role: var("PROXY_AUTH_AUTO_CREATE").and_then(|r| r.trim().parse().ok()),
+        })
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// the user name of the header, `None` without one or when `peer` is not a trusted proxy
+    pub fn username(&self, peer: IpAddr, headers: &HeaderMap) -> Option<String> {
+        let username = headers.get(&self.header)?.to_str().ok()?.trim();
+        if username.is_empty() {
+            return None;
+        }
+        if !self.is_trusted(peer) {
+            tracing::warn!("ignored {} from untrusted {}", self.header, peer);
+            return None;
+        }
+        Some(username.to_string())
+    }
+}
+
+#[derive(Clone)]
+pub struct ProxyAuth {
+    pub config: Arc<ProxyAuthConfig>,
+    pub db: DatabaseConnection,
+}
+
+/// the middleware, it must run inside the `AuthSessionLayer`
+pub async fn proxy_auth(
+    State(state): State<ProxyAuth>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let username =
+        peer.and_then(|peer| Some((peer, state.config.username(peer, request.headers())?)));
+    if let Some((peer, username)) = username {
+        if let Some(auth_session) = request.extensions().get::<AuthSession>().cloned() {
+            let auth_session = login(auth_session, &username, peer, &state).await;
+            request.extensions_mut().insert(auth_session);
+        }
+    }
+    next.run(request).await
+}
+
+/// log in the user of the header when the session has another user
+async fn login(
+    mut auth_session: AuthSession,
+    username: &str,
+    peer: IpAddr,
+    state: &ProxyAuth,
+) -> AuthSession {
+    if auth_session
+        .current_user
+        .as_ref()
+        .is_some_and(|u| u.username == username)
+    {
+        return auth_session;
+    }
+    match find_or_create_account(username, state).await {
+        Ok(Some(account)) => {
+            let account_id = account.id;
+            match load_user_permissions(account, &state.db).await {
+                Ok(user) => {
+                    let ip = peer.to_string();
+                    let recorded =
+                        event::record(AuthEventKind::ProxyLogin, username, &ip, "", &state.db)
+                            .await;
+                    if let Err(e) = recorded {
+                        tracing::error!("failed to record the login of {}: {}", username, e);
+                    }
+                    auth_session.login_user(account_id);
+                    // the user of this request, the session has it from the next request on
+                    auth_session.current_user = Some(user);
+                }
+                Err(e) => tracing::error!("proxy login of {} failed: {}", username, e),
+            }
+        }
+        Ok(None) => {
+            tracing::warn!("proxy login of unknown user {}", username);
+            auth_session.logout_user();
+            auth_session.current_user = None;
+        }
+        Err(e) => tracing::error!("proxy login of {} failed: {}", username, e),
+    }
+    auth_session
+}
+
+async fn find_or_create_account(
+    username: &str,
+    state: &ProxyAuth,
+) -> Result<Option<account::Model>, sea_orm::DbErr> {
+    let account = Account::find()
+        .filter(account::Column::Name.eq(username))
+        .one(&state.db)
+        .await?;
+    if account.is_some() {
+        return Ok(account);
+    }
+    let Some(role) = state.config.auto_create_role else {
+        return Ok(None);
+    };
+    // the account has no usable password, the proxy logs it in
+    use rand::{distributions::Alphanumeric, Rng};
+    let password: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let account = add_account(username.to_string(), password, role, &state.db).await?;
+    grant_default_library(account.id, &state.db).await?;
+    tracing::info!("account {} created by the proxy", username);
+    Ok(Some(account))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(cidr: &str) -> Cidr {
+        Cidr::parse(cidr).unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ranges_and_addresses() {
+        assert_eq!(cidr("10.0.0.0/8"), cidr(" 10.0.0.0/8 "));
+        assert_eq!(cidr("127.0.0.1"), cidr("127.0.0.1/32"));
+        assert_eq!(cidr("::1"), cidr("::1/128"));
+        assert!(Cidr::parse("0.0.0.0/0").is_some());
+        assert!(Cidr::parse("::/0").is_some());
+        for bad in [
+            "",
+            "10.0.0.0/",
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0.0/-1",
+            "10.0.0.0/8/8",
+            "10.0.0/8",
+            "256.0.0.1",
+            "localhost",
+            "fd00::/x",
+        ] {
+            assert_eq!(Cidr::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn ipv4_ranges() {
+        let net = cidr("172.16.0.0/12");
+        assert!(net.contains(ip("172.16.0.1")));
+        assert!(net.contains(ip("172.31.255.255")));
+        assert!(!net.contains(ip("172.32.0.0")));
+        assert!(!net.contains(ip("10.0.0.1")));
+        assert!(cidr("127.0.0.1/32").contains(ip("127.0.0.1")));
+        assert!(!cidr("127.0.0.1/32").contains(ip("127.0.0.2")));
+        assert!(cidr("0.0.0.0/0").contains(ip("203.0.113.7")));
+        assert!(!cidr("0.0.0.0/0").contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn ipv6_ranges() {
+        let net = cidr("fd00::/8");
+        assert!(net.contains(ip("fd12:3456::1")));
+        assert!(!net.contains(ip("fe80::1")));
+        assert!(!net.contains(ip("10.0.0.1")));
+        assert!(cidr("::1/128").contains(ip("::1")));
+        assert!(!cidr("::1/128").contains(ip("::2")));
+        assert!(cidr("::/0").contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn mapped_ipv4_addresses_match_ipv4_ranges() {
+        assert!(cidr("10.0.0.0/8").contains(ip("::ffff:10.1.2.3")));
+        assert!(!cidr("10.0.0.0/8").contains(ip("::ffff:11.1.2.3")));
+        assert!(cidr("127.0.0.1").contains(ip("::ffff:127.0.0.1")));
+    }
+
+    #[test]
+    fn only_trusted_proxies_name_the_user() {
+        let config = ProxyAuthConfig {
+            header: "Remote-User".to_string(),
+            trusted: vec![cidr("127.0.0.1"), cidr("fd00::/8")],
+            auto_create_role: None,
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("remote-user", " alice ".parse().unwrap());
+        assert_eq!(
+            config.username(ip("127.0.0.1"), &headers),
+            Some("alice".to_string())
+        );
+        assert_eq!(
+            config.username(ip("::ffff:127.0.0.1"), &headers),
+            Some("alice".to_string())
+        );
+        assert_eq!(
+            config.username(ip("fd00::2"), &headers),
+            Some("alice".to_string())
+        );
+        assert!(config.is_trusted(ip("127.0.0.1")));
+        assert!(!config.is_trusted(ip("192.168.1.2")));
+        assert_eq!(config.username(ip("192.168.1.2"), &headers), None);
+        assert_eq!(config.username(ip("::1"), &headers), None);
+
+        assert_eq!(config.username(ip("127.0.0.1"), &HeaderMap::new()), None);
+        headers.insert("remote-user", "  ".parse().unwrap());
+        assert_eq!(config.username(ip("127.0.0.1"), &headers), None);
+    }
+}
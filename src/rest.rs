@@ -0,0 +1,939 @@
+//! the versioned json api at `/api/v1`.
+//!
+//! unlike the server functions its paths and bodies stay stable. it uses the same queries as the
+//! server functions, the user is the one of the session or of an api token
+//! (`Authorization: Bearer`). errors are `{"error": {"code": "...", "message": "..."}}` with a
+//! matching status code. the lists take `page` (from 0) and `per_page`, or `cursor` with the
+//! `next_cursor` of the previous page.
+//!
+//! the openapi document is at `/api/v1/openapi.json` and can be read at `/api/v1/docs`.
+
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequestParts, Path, Query, State,
+    },
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use leptos::ServerFnError;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, LoaderTrait, ModelTrait, QueryFilter,
+    QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+use utoipa_redoc::{Redoc, Servable};
+
+use crate::{
+    entities::{prelude::*, *},
+    server_api::{
+        authors::{ssr::visible_authors, AuthorDetail},
+        book::{
+            ssr::{book_chapters, visible_books},
+            BookDetail, ChapterDetail,
+        },
+        library::ssr::can_see_library,
+        permission::{check, Access, Permission},
+        progress::{save_progress, visible_progress, with_details, ProgressResult, ProgressUpdate},
+        ssr::{
+            fetch_page, load_user_permissions, role_permissions, user_from_account, AuthSession,
+        },
+        token::ssr::with_bearer_user,
+        AuthorPage, BookPage, User,
+    },
+    state::AppState,
+};
+
+pub const DEFAULT_PER_PAGE: u64 = 20;
+pub const MAX_PER_PAGE: u64 = 100;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/books", get(list_books))
+        .route("/books/:id", get(get_book))
+        .route("/books/:id/chapters", get(list_chapters))
+        .route("/books/:id/progress", get(get_progress).put(put_progress))
+        .route("/chapters/:id", get(get_chapter))
+        .route("/authors", get(list_authors))
+        .route("/authors/:id", get(get_author))
+        .route("/authors/:id/books", get(list_author_books))
+        .route("/progress", get(list_progress))
+        .route("/users", get(list_users))
+        .route("/users/me", get(get_me))
+        .route("/users/:id", get(get_user))
+        .route("/users/:id/progress", get(list_user_progress))
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .merge(Redoc::with_url("/docs", ApiDoc::openapi()))
+        .fallback(|| async { ApiError::not_found("route") })
+}
+
+/// the openapi 3 document of the api, for generating clients. the version is the one of the
+/// package
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "audiobookroom", description = "the json api of audiobookroom"),
+    paths(
+        list_books,
+        get_book,
+        list_chapters,
+        get_progress,
+        put_progress,
+        get_chapter,
+        list_authors,
+        get_author,
+        list_author_books,
+        list_progress,
+        list_users,
+        get_me,
+        get_user,
+        list_user_progress,
+    ),
+    components(schemas(
+        BookDetail,
+        ChapterDetail,
+        ProgressResult,
+        AuthorDetail,
+        User,
+        Permission,
+        BookPage,
+        AuthorPage,
+        BookItem,
+        ProgressItem,
+        ProgressBody,
+        BookList,
+        BookDetailList,
+        ChapterList,
+        AuthorList,
+        ProgressList,
+        UserList,
+        ErrorBody,
+        ErrorDetail,
+    )),
+    modifiers(&ApiToken),
+    security(("api_token" = []))
+)]
+pub struct ApiDoc;
+
+/// the `Authorization: Bearer` api tokens, the session cookie works too
+struct ApiToken;
+
+impl Modify for ApiToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "api_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ErrorDetail {
+    /// e.g. `not_found`, stable unlike the message
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", "Not logged in")
+    }
+
+    /// the details are only logged
+    pub fn internal(e: impl std::fmt::Display) -> Self {
+        tracing::error!("api: {}", e);
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            "Internal server error",
+        )
+    }
+
+    /// hidden books are not found either, so their ids can not be probed
+    pub fn not_found(what: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("{} not found", what),
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: ErrorDetail {
+                code: self.code.to_string(),
+                message: self.message,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// the message of a server function error without the `error running server function:` prefix
+pub fn server_message(e: ServerFnError) -> String {
+    match e {
+        ServerFnError::ServerError(message) => message,
+        e => e.to_string(),
+    }
+}
+
+impl From<DbErr> for ApiError {
+    fn from(e: DbErr) -> Self {
+        Self::internal(e)
+    }
+}
+
+macro_rules! rejection {
+    ($rejection:ty) => {
+        impl From<$rejection> for ApiError {
+            fn from(rejection: $rejection) -> Self {
+                Self::new(rejection.status(), "bad_request", rejection.body_text())
+            }
+        }
+    };
+}
+rejection!(PathRejection);
+rejection!(QueryRejection);
+rejection!(JsonRejection);
+
+/// the user of the session or the api token
+pub struct ApiUser(pub User);
+
+impl ApiUser {
+    fn require(&self, access: Access) -> Result<(), ApiError> {
+        check(Some(&self.0), access)
+            .map_err(|e| ApiError::new(StatusCode::FORBIDDEN, "forbidden", server_message(e)))
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ApiUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::unauthorized())?;
+        let auth_session = with_bearer_user(auth_session, &parts.headers, &state.db).await;
+        auth_session
+            .current_user
+            .map(ApiUser)
+            .ok_or_else(ApiError::unauthorized)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+    /// the first page is 0
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    /// the `next_cursor` of the previous page, instead of `page`
+    pub cursor: Option<i32>,
+}
+
+/// how a list is fetched, checked from [`ListParams`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paging {
+    Page { page: u64, per_page: u64 },
+    Cursor { after: i32, per_page: u64 },
+}
+
+impl ListParams {
+    pub fn paging(&self) -> Result<Paging, ApiError> {
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if !(1..=MAX_PER_PAGE).contains(&per_page) {
+            return Err(ApiError::bad_request(format!(
+                "per_page must be between 1 and {}",
+                MAX_PER_PAGE
+            )));
+        }
+        match (self.page, self.cursor) {
+            (Some(_), Some(_)) => Err(ApiError::bad_request(
+                "page and cursor can not be used together",
+            )),
+            (_, Some(after)) => Ok(Paging::Cursor { after, per_page }),
+            (page, None) => Ok(Paging::Page {
+                page: page.unwrap_or(0),
+                per_page,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+#[aliases(
+    BookList = ListResponse<BookItem>,
+    BookDetailList = ListResponse<BookDetail>,
+    ChapterList = ListResponse<ChapterDetail>,
+    AuthorList = ListResponse<AuthorDetail>,
+    ProgressList = ListResponse<ProgressItem>,
+    UserList = ListResponse<User>
+)]
+pub struct ListResponse<T> {
+    pub items: Vec<T>,
+    pub per_page: u64,
+    /// only when listed by `page`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_items: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<u64>,
+    /// the `cursor` of the next page, `None` on the last page
+    pub next_cursor: Option<i32>,
+}
+
+impl<T> ListResponse<T> {
+    fn map<U>(self, f: impl FnMut(T) -> U) -> ListResponse<U> {
+        ListResponse {
+            items: self.items.into_iter().map(f).collect(),
+            per_page: self.per_page,
+            page: self.page,
+            total_items: self.total_items,
+            total_pages: self.total_pages,
+            next_cursor: self.next_cursor,
+        }
+    }
+
+    /// like `map`, dropping the items `f` returns `None` for
+    fn filter_map<U>(self, f: impl FnMut(T) -> Option<U>) -> ListResponse<U> {
+        ListResponse {
+            items: self.items.into_iter().filter_map(f).collect(),
+            per_page: self.per_page,
+            page: self.page,
+            total_items: self.total_items,
+            total_pages: self.total_pages,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// a page of a query ordered by `column`, the cursor is the value of `column` of an item
+async fn list<E>(
+    query: sea_orm::Select<E>,
+    column: E::Column,
+    cursor_of: impl Fn(&E::Model) -> i32,
+    params: Result<Query<ListParams>, QueryRejection>,
+    db: &DatabaseConnection,
+) -> Result<ListResponse<E::Model>, ApiError>
+where
+    E: EntityTrait,
+    E::Model: Sync,
+{
+    let Query(params) = params?;
+    match params.paging()? {
+        Paging::Page { page, per_page } => {
+            let result = fetch_page(query, page, per_page, db).await?;
+            let has_next = page + 1 < result.number_of_pages;
+            Ok(ListResponse {
+                next_cursor: result.items.last().filter(|_| has_next).map(&cursor_of),
+                items: result.items,
+                per_page,
+                page: Some(page),
+                total_items: Some(result.number_of_items),
+                total_pages: Some(result.number_of_pages),
+            })
+        }
+        Paging::Cursor { after, per_page } => {
+            let mut items = query
+                .filter(column.gt(after))
+                .limit(per_page + 1)
+                .all(db)
+                .await?;
+            let has_next = items.len() as u64 > per_page;
+            items.truncate(per_page as usize);
+            Ok(ListResponse {
+                next_cursor: items.last().filter(|_| has_next).map(&cursor_of),
+                items,
+                per_page,
+                page: None,
+                total_items: None,
+                total_pages: None,
+            })
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BookItem {
+    #[serde(flatten)]
+    pub book: BookDetail,
+    pub author: AuthorDetail,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProgressItem {
+    pub progress: ProgressResult,
+    pub book: BookDetail,
+    pub chapter: ChapterDetail,
+}
+
+/// the body of `PUT /books/:id/progress`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ProgressBody {
+    pub chapter_id: i32,
+    /// seconds into the chapter
+    pub progress: f64,
+    /// `api` when not set
+    pub device_id: Option<String>,
+    pub device_name: Option<String>,
+    /// milliseconds since the unix epoch, the time of the server when not set. an update older
+    /// than the saved progress is ignored
+    pub client_update: Option<i64>,
+}
+
+fn id(path: Result<Path<i32>, PathRejection>) -> Result<i32, ApiError> {
+    Ok(path?.0)
+}
+
+/// the book when the user can see it
+pub(crate) async fn visible_book(
+    user: &User,
+    book_id: i32,
+    db: &DatabaseConnection,
+) -> Result<music::Model, ApiError> {
+    let book = Music::find_by_id(book_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("book"))?;
+    if !can_see_library(user, book.library_id, db).await? {
+        return Err(ApiError::not_found("book"));
+    }
+    Ok(book)
+}
+
+/// the books of a page with their authors
+async fn with_authors(
+    books: ListResponse<music::Model>,
+    db: &DatabaseConnection,
+) -> Result<ListResponse<BookItem>, ApiError> {
+    let authors = books.items.load_one(Author, db).await?;
+    let mut authors = authors.into_iter();
+    Ok(books.filter_map(|book| {
+        Some(BookItem {
+            book: book.into(),
+            author: authors.next().flatten()?.into(),
+        })
+    }))
+}
+
+/// the books the user can see, with their authors
+#[utoipa::path(
+    get,
+    path = "/api/v1/books",
+    params(ListParams),
+    responses(
+        (status = 200, body = BookList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_books(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<BookItem>>, ApiError> {
+    let query = visible_books(&user, &db).await?;
+    let page = list(query, music::Column::Id, |b| b.id, params, &db).await?;
+    Ok(Json(with_authors(page, &db).await?))
+}
+
+/// a book with its author
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{id}",
+    params(("id" = i32, Path, description = "the id of the book")),
+    responses(
+        (status = 200, body = BookItem),
+        (status = 404, description = "No such book or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn get_book(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Json<BookItem>, ApiError> {
+    let book = visible_book(&user, id(path)?, &db).await?;
+    let author = book
+        .find_related(Author)
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("author"))?;
+    Ok(Json(BookItem {
+        book: book.into(),
+        author: author.into(),
+    }))
+}
+
+/// the chapters of a book in order, the cursor is the chapter number
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{id}/chapters",
+    params(("id" = i32, Path, description = "the id of the book"), ListParams),
+    responses(
+        (status = 200, body = ChapterList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 404, description = "No such book or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_chapters(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<ChapterDetail>>, ApiError> {
+    let book = visible_book(&user, id(path)?, &db).await?;
+    let query = book_chapters(book.id);
+    let page = list(
+        query,
+        chapter::Column::ChapterNum,
+        |c| c.chapter_num,
+        params,
+        &db,
+    )
+    .await?;
+    Ok(Json(page.map(Into::into)))
+}
+
+/// the progress of the current user in a book
+#[utoipa::path(
+    get,
+    path = "/api/v1/books/{id}/progress",
+    params(("id" = i32, Path, description = "the id of the book")),
+    responses(
+        (status = 200, body = ProgressResult),
+        (status = 404, description = "No such book or progress or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn get_progress(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Json<ProgressResult>, ApiError> {
+    let book = visible_book(&user, id(path)?, &db).await?;
+    let progress = Progress::find_by_id((user.id, book.id))
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("progress"))?;
+    Ok(Json(progress.into()))
+}
+
+/// save the progress of the current user in a book
+#[utoipa::path(
+    put,
+    path = "/api/v1/books/{id}/progress",
+    params(("id" = i32, Path, description = "the id of the book")),
+    request_body = ProgressBody,
+    responses(
+        (status = 204, description = "Saved, or ignored when older than the saved progress"),
+        (status = 400, description = "Bad body", body = ErrorBody),
+        (status = 404, description = "No such book or chapter or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn put_progress(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+    body: Result<Json<ProgressBody>, JsonRejection>,
+) -> Result<StatusCode, ApiError> {
+    let book = visible_book(&user, id(path)?, &db).await?;
+    let Json(body) = body?;
+    if !body.progress.is_finite() || body.progress < 0.0 {
+        return Err(ApiError::bad_request("progress must be a positive number"));
+    }
+    Chapter::find_by_id(body.chapter_id)
+        .filter(chapter::Column::MusicId.eq(book.id))
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("chapter"))?;
+    let update = ProgressUpdate {
+        music_id: book.id,
+        chapter_id: body.chapter_id,
+        progress: body.progress,
+        device_id: body.device_id.unwrap_or_else(|| "api".to_string()),
+        device_name: body.device_name.unwrap_or_else(|| "api".to_string()),
+        client_update: body
+            .client_update
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis()),
+    };
+    save_progress(&db, user.id, update)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// a chapter
+#[utoipa::path(
+    get,
+    path = "/api/v1/chapters/{id}",
+    params(("id" = i32, Path, description = "the id of the chapter")),
+    responses(
+        (status = 200, body = ChapterDetail),
+        (status = 404, description = "No such chapter or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn get_chapter(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Json<ChapterDetail>, ApiError> {
+    let chapter = Chapter::find_by_id(id(path)?)
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("chapter"))?;
+    visible_book(&user, chapter.music_id, &db)
+        .await
+        .map_err(|_| ApiError::not_found("chapter"))?;
+    Ok(Json(chapter.into()))
+}
+
+/// the authors with a book the user can see
+#[utoipa::path(
+    get,
+    path = "/api/v1/authors",
+    params(ListParams),
+    responses(
+        (status = 200, body = AuthorList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_authors(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<AuthorDetail>>, ApiError> {
+    let query = visible_authors(&user, &db).await?;
+    let page = list(query, author::Column::Id, |a| a.id, params, &db).await?;
+    Ok(Json(page.map(Into::into)))
+}
+
+/// an author
+#[utoipa::path(
+    get,
+    path = "/api/v1/authors/{id}",
+    params(("id" = i32, Path, description = "the id of the author")),
+    responses(
+        (status = 200, body = AuthorDetail),
+        (status = 404, description = "No such author or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn get_author(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Json<AuthorDetail>, ApiError> {
+    let author = visible_authors(&user, &db)
+        .await?
+        .filter(author::Column::Id.eq(id(path)?))
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("author"))?;
+    Ok(Json(author.into()))
+}
+
+/// the books of an author the user can see
+#[utoipa::path(
+    get,
+    path = "/api/v1/authors/{id}/books",
+    params(("id" = i32, Path, description = "the id of the author"), ListParams),
+    responses(
+        (status = 200, body = BookDetailList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_author_books(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<BookDetail>>, ApiError> {
+    let query = visible_books(&user, &db)
+        .await?
+        .filter(music::Column::AuthorId.eq(id(path)?));
+    let page = list(query, music::Column::Id, |b| b.id, params, &db).await?;
+    Ok(Json(page.map(Into::into)))
+}
+
+/// the progress of a page with its books and chapters
+async fn progress_items(
+    progress: ListResponse<progress::Model>,
+    db: &DatabaseConnection,
+) -> Result<ListResponse<ProgressItem>, ApiError> {
+    let ListResponse {
+        items,
+        per_page,
+        page,
+        total_items,
+        total_pages,
+        next_cursor,
+    } = progress;
+    Ok(ListResponse {
+        items: with_details(items, db)
+            .await?
+            .into_iter()
+            .map(|(progress, book, chapter)| ProgressItem {
+                progress,
+                book,
+                chapter,
+            })
+            .collect(),
+        per_page,
+        page,
+        total_items,
+        total_pages,
+        next_cursor,
+    })
+}
+
+/// the progress of a user in the books the current user can see, the cursor is the book id
+async fn list_account_progress(
+    user: &User,
+    account_id: i32,
+    params: Result<Query<ListParams>, QueryRejection>,
+    db: &DatabaseConnection,
+) -> Result<ListResponse<ProgressItem>, ApiError> {
+    let query = visible_progress(user, account_id, db).await?;
+    let page = list(query, progress::Column::MusicId, |p| p.music_id, params, db).await?;
+    progress_items(page, db).await
+}
+
+/// the progress of the current user in every book, the cursor is the book id
+#[utoipa::path(
+    get,
+    path = "/api/v1/progress",
+    params(ListParams),
+    responses(
+        (status = 200, body = ProgressList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_progress(
+    ApiUser(user): ApiUser,
+    State(db): State<DatabaseConnection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<ProgressItem>>, ApiError> {
+    Ok(Json(
+        list_account_progress(&user, user.id, params, &db).await?,
+    ))
+}
+
+/// the progress of a user, other users need manage_users. the cursor is the book id
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/progress",
+    params(("id" = i32, Path, description = "the id of the user"), ListParams),
+    responses(
+        (status = 200, body = ProgressList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 403, description = "Missing the permission", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_user_progress(
+    user: ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<ProgressItem>>, ApiError> {
+    let account_id = id(path)?;
+    user.require(Access::SelfOr(account_id, Permission::ManageUsers))?;
+    Ok(Json(
+        list_account_progress(&user.0, account_id, params, &db).await?,
+    ))
+}
+
+/// every user, needs manage_users
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    params(ListParams),
+    responses(
+        (status = 200, body = UserList),
+        (status = 400, description = "Bad paging parameters", body = ErrorBody),
+        (status = 403, description = "Missing the permission", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn list_users(
+    user: ApiUser,
+    State(db): State<DatabaseConnection>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> Result<Json<ListResponse<User>>, ApiError> {
+    user.require(Access::Permission(Permission::ManageUsers))?;
+    let query = Account::find().order_by_asc(account::Column::Id);
+    let page = list(query, account::Column::Id, |a| a.id, params, &db).await?;
+    let permissions = role_permissions(&db).await?;
+    Ok(Json(page.map(|account| {
+        let permissions = permissions
+            .get(&account.role_level)
+            .cloned()
+            .unwrap_or_default();
+        user_from_account(account, permissions)
+    })))
+}
+
+/// the current user
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me",
+    responses(
+        (status = 200, body = User),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn get_me(ApiUser(user): ApiUser) -> Json<User> {
+    Json(user)
+}
+
+/// a user, other users need manage_users
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}",
+    params(("id" = i32, Path, description = "the id of the user")),
+    responses(
+        (status = 200, body = User),
+        (status = 403, description = "Missing the permission", body = ErrorBody),
+        (status = 404, description = "No such user or it is hidden", body = ErrorBody),
+        (status = 401, description = "Not logged in", body = ErrorBody)
+    )
+)]
+async fn get_user(
+    user: ApiUser,
+    State(db): State<DatabaseConnection>,
+    path: Result<Path<i32>, PathRejection>,
+) -> Result<Json<User>, ApiError> {
+    let account_id = id(path)?;
+    user.require(Access::SelfOr(account_id, Permission::ManageUsers))?;
+    let account = Account::find_by_id(account_id)
+        .one(&db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("user"))?;
+    Ok(Json(load_user_permissions(account, &db).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(page: Option<u64>, per_page: Option<u64>, cursor: Option<i32>) -> ListParams {
+        ListParams {
+            page,
+            per_page,
+            cursor,
+        }
+    }
+
+    #[test]
+    fn openapi_has_the_paths_and_schemas() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert_eq!(doc["info"]["title"], "audiobookroom");
+        assert_eq!(doc["info"]["version"], env!("CARGO_PKG_VERSION"));
+        for path in [
+            "/api/v1/books",
+            "/api/v1/books/{id}/progress",
+            "/api/v1/progress",
+            "/api/v1/users",
+            "/api/v1/users/me",
+        ] {
+            assert!(doc["paths"].get(path).is_some(), "missing {}", path);
+        }
+        for schema in [
+            "BookDetail",
+            "ChapterDetail",
+            "ProgressResult",
+            "BookPage",
+            "User",
+            "ProgressList",
+            "UserList",
+        ] {
+            assert!(
+                doc["components"]["schemas"].get(schema).is_some(),
+                "missing {}",
+                schema
+            );
+        }
+    }
+
+    #[test]
+    fn paging_defaults() {
+        assert_eq!(
+            ListParams::default().paging(),
+            Ok(Paging::Page {
+                page: 0,
+                per_page: DEFAULT_PER_PAGE
+            })
+        );
+        assert_eq!(
+            params(Some(3), Some(5), None).paging(),
+            Ok(Paging::Page {
+                page: 3,
+                per_page: 5
+            })
+        );
+        assert_eq!(
+            params(None, Some(50), Some(42)).paging(),
+            Ok(Paging::Cursor {
+                after: 42,
+                per_page: 50
+            })
+        );
+    }
+
+    #[test]
+    fn paging_rejects_bad_params() {
+        for bad in [
+            params(None, Some(0), None),
+            params(None, Some(MAX_PER_PAGE + 1), None),
+            params(Some(1), None, Some(1)),
+        ] {
+            let error = bad.paging().unwrap_err();
+            assert_eq!(error.status, StatusCode::BAD_REQUEST, "{:?}", bad);
+            assert_eq!(error.code, "bad_request");
+        }
+    }
+}
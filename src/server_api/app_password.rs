@@ -0,0 +1,143 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppPasswordDetail {
+    pub id: i32,
+    pub name: String,
+    /// rfc3339
+    pub created: String,
+    pub last_used: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            ssr::{load_user_permissions, now},
+            User,
+        },
+    };
+
+    /// a random password, grouped to be typed on a tv or a phone
+    pub fn generate_app_password() -> String {
+        use rand::{distributions::Alphanumeric, Rng};
+        let random: Vec<char> = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(24)
+            .map(|c| char::from(c).to_ascii_lowercase())
+            .collect();
+        random
+            .chunks(6)
+            .map(|c| c.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// the approved user named `username` that has an app password accepted by `matches`,
+    /// the last used time of the password is updated
+    pub async fn user_from_app_password(
+        username: &str,
+        matches: impl Fn(&str) -> bool,
+        db: &DatabaseConnection,
+    ) -> Result<Option<User>, DbErr> {
+        let Some(account) = Account::find()
+            .filter(account::Column::Name.eq(username))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if !account.approved {
+            return Ok(None);
+        }
+        let passwords = AppPassword::find()
+            .filter(app_password::Column::AccountId.eq(account.id))
+            .all(db)
+            .await?;
+        let Some(password) = passwords.into_iter().find(|p| matches(&p.password)) else {
+            return Ok(None);
+        };
+        AppPassword::update(app_password::ActiveModel {
+            id: sea_orm::Set(password.id),
+            last_used: sea_orm::Set(Some(now())),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(Some(load_user_permissions(account, db).await?))
+    }
+}
+
+#[server]
+/// the app passwords of the current user
+pub async fn list_app_passwords() -> Result<Vec<AppPasswordDetail>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let passwords = AppPassword::find()
+        .filter(app_password::Column::AccountId.eq(user.id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|p| AppPasswordDetail {
+            id: p.id,
+            name: p.name,
+            created: date_to_rfc3339(&p.created),
+            last_used: p.last_used.as_ref().map(date_to_rfc3339),
+        })
+        .collect();
+    Ok(passwords)
+}
+
+#[server]
+/// create an app password for the current user.
+///
+/// returns the password, it can not be shown again
+pub async fn create_app_password(name: String) -> Result<String, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("The password name is empty."));
+    }
+    let db = db()?;
+    let password = self::ssr::generate_app_password();
+    AppPassword::insert(app_password::ActiveModel {
+        account_id: sea_orm::Set(user.id),
+        name: sea_orm::Set(name),
+        password: sea_orm::Set(password.clone()),
+        created: sea_orm::Set(now()),
+        last_used: sea_orm::Set(None),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    Ok(password)
+}
+
+#[server]
+/// revoke an app password of the current user
+pub async fn revoke_app_password(password_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let result = AppPassword::delete_many()
+        .filter(app_password::Column::Id.eq(password_id))
+        .filter(app_password::Column::AccountId.eq(user.id))
+        .exec(&db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(ServerFnError::new("App password not found"));
+    }
+    Ok(())
+}
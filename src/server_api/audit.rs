@@ -0,0 +1,303 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::PageItems;
+
+/// stored by [`AuditAction::as_str`] in `audit_event.action`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AuditAction {
+    BookAdded,
+    BookDeleted,
+    UserAdded,
+    UserDeleted,
+    PasswordChanged,
+    EmailChanged,
+    UserRoleChanged,
+    RoleSaved,
+    RoleDeleted,
+    LibrarySaved,
+    LibraryDeleted,
+    LibraryGrantChanged,
+    InvitationCreated,
+    InvitationRevoked,
+    AccountApproved,
+    RegistrationChanged,
+    SessionsRevoked,
+    RetentionChanged,
+    WebhookSaved,
+    WebhookDeleted,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 20] = [
+        AuditAction::BookAdded,
+        AuditAction::BookDeleted,
+        AuditAction::UserAdded,
+        AuditAction::UserDeleted,
+        AuditAction::PasswordChanged,
+        AuditAction::EmailChanged,
+        AuditAction::UserRoleChanged,
+        AuditAction::RoleSaved,
+        AuditAction::RoleDeleted,
+        AuditAction::LibrarySaved,
+        AuditAction::LibraryDeleted,
+        AuditAction::LibraryGrantChanged,
+        AuditAction::InvitationCreated,
+        AuditAction::InvitationRevoked,
+        AuditAction::AccountApproved,
+        AuditAction::RegistrationChanged,
+        AuditAction::SessionsRevoked,
+        AuditAction::RetentionChanged,
+        AuditAction::WebhookSaved,
+        AuditAction::WebhookDeleted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::BookAdded => "book_added",
+            AuditAction::BookDeleted => "book_deleted",
+            AuditAction::UserAdded => "user_added",
+            AuditAction::UserDeleted => "user_deleted",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::EmailChanged => "email_changed",
+            AuditAction::UserRoleChanged => "user_role_changed",
+            AuditAction::RoleSaved => "role_saved",
+            AuditAction::RoleDeleted => "role_deleted",
+            AuditAction::LibrarySaved => "library_saved",
+            AuditAction::LibraryDeleted => "library_deleted",
+            AuditAction::LibraryGrantChanged => "library_grant_changed",
+            AuditAction::InvitationCreated => "invitation_created",
+            AuditAction::InvitationRevoked => "invitation_revoked",
+            AuditAction::AccountApproved => "account_approved",
+            AuditAction::RegistrationChanged => "registration_changed",
+            AuditAction::SessionsRevoked => "sessions_revoked",
+            AuditAction::RetentionChanged => "retention_changed",
+            AuditAction::WebhookSaved => "webhook_saved",
+            AuditAction::WebhookDeleted => "webhook_deleted",
+        }
+    }
+
+    pub fn parse(action: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == action)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEventDetail {
+    pub id: i32,
+    /// rfc3339
+    pub created: String,
+    /// `None` when the account was deleted or nobody was logged in
+    pub actor_id: Option<i32>,
+    pub actor_name: String,
+    pub action: Option<AuditAction>,
+    /// e.g. `book 12`
+    pub target: String,
+    /// empty when the target was created
+    pub before: String,
+    /// empty when the target was deleted
+    pub after: String,
+    pub ip: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use std::time::Duration;
+
+    use leptos::ServerFnError;
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+    use super::AuditAction;
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            auth_event::ssr::current_ip,
+            registration::ssr::{instance_setting, set_instance_setting},
+            ssr::{auth, date_from_rfc3339, now},
+        },
+    };
+
+    const AUDIT_RETENTION_DAYS: &str = "audit_retention_days";
+    /// days the events are kept when the admins never set it
+    pub const DEFAULT_RETENTION_DAYS: u32 = 365;
+    /// a shorter retention would let an admin erase their own recent changes
+    pub const MIN_RETENTION_DAYS: u32 = 30;
+
+    /// record a change by the user of the current server function
+    pub async fn audit(
+        action: AuditAction,
+        target: &str,
+        before: &str,
+        after: &str,
+        db: &DatabaseConnection,
+    ) -> Result<(), ServerFnError> {
+        let actor = auth()?.current_user;
+        AuditEvent::insert(audit_event::ActiveModel {
+            created: Set(now()),
+            actor_id: Set(actor.as_ref().map(|u| u.id)),
+            actor_name: Set(actor.map(|u| u.username).unwrap_or_default()),
+            action: Set(action.as_str().to_string()),
+            target: Set(target.to_string()),
+            old_summary: Set(before.to_string()),
+            new_summary: Set(after.to_string()),
+            ip: Set(current_ip()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(())
+    }
+
+    /// 0 keeps the events forever
+    pub async fn retention_days(db: &DatabaseConnection) -> Result<u32, DbErr> {
+        Ok(instance_setting(AUDIT_RETENTION_DAYS, db)
+            .await?
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS))
+    }
+
+    /// 0 or at least [`MIN_RETENTION_DAYS`]
+    pub fn check_retention_days(days: u32) -> Result<(), ServerFnError> {
+        if days != 0 && days < MIN_RETENTION_DAYS {
+            return Err(ServerFnError::new(format!(
+                "The audit events are kept at least {} days, 0 keeps them forever",
+                MIN_RETENTION_DAYS
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn set_retention_days(days: u32, db: &DatabaseConnection) -> Result<(), DbErr> {
+        set_instance_setting(AUDIT_RETENTION_DAYS, days.to_string(), db).await
+    }
+
+    /// delete the events older than the retention, returns how many
+    pub async fn prune_audit_events(db: &DatabaseConnection) -> Result<u64, DbErr> {
+        let days = retention_days(db).await?;
+        if days == 0 {
+            return Ok(0);
+        }
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(days as i64);
+        let cutoff = date_from_rfc3339(&cutoff.to_rfc3339()).expect("rfc3339 of a date");
+        let result = AuditEvent::delete_many()
+            .filter(audit_event::Column::Created.lt(cutoff))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// prune the events at the start and then once a day
+    pub fn spawn_pruning(db: DatabaseConnection) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+            loop {
+                interval.tick().await;
+                match prune_audit_events(&db).await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::info!("pruned {} audit events", n),
+                    Err(e) => tracing::error!("failed to prune the audit events: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[server]
+/// the audit events, newest first, optionally of one action, actor or target
+pub async fn list_audit_events(
+    page_num: u64,
+    max_item: u64,
+    action: Option<AuditAction>,
+    actor: Option<String>,
+    target: Option<String>,
+) -> Result<PageItems<AuditEventDetail>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::{ItemsAndPagesNumber, PaginatorTrait, QueryOrder};
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let mut query = AuditEvent::find().order_by_desc(audit_event::Column::Id);
+    if let Some(action) = action {
+        query = query.filter(audit_event::Column::Action.eq(action.as_str()));
+    }
+    if let Some(actor) = actor.filter(|a| !a.trim().is_empty()) {
+        query = query.filter(audit_event::Column::ActorName.eq(actor.trim()));
+    }
+    if let Some(target) = target.filter(|t| !t.trim().is_empty()) {
+        query = query.filter(audit_event::Column::Target.contains(target.trim()));
+    }
+    let page = query.paginate(&db, max_item);
+    let ItemsAndPagesNumber {
+        number_of_items,
+        number_of_pages,
+    } = page.num_items_and_pages().await?;
+    let items = page
+        .fetch_page(page_num)
+        .await?
+        .into_iter()
+        .map(|e| AuditEventDetail {
+            id: e.id,
+            created: date_to_rfc3339(&e.created),
+            actor_id: e.actor_id,
+            actor_name: e.actor_name,
+            action: AuditAction::parse(&e.action),
+            target: e.target,
+            before: e.old_summary,
+            after: e.new_summary,
+            ip: e.ip,
+        })
+        .collect();
+    Ok(PageItems {
+        page: page_num,
+        max_item,
+        number_of_items,
+        number_of_pages,
+        items,
+    })
+}
+
+#[server]
+/// days the audit events are kept, 0 keeps them forever
+pub async fn get_audit_retention() -> Result<u32, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    Ok(self::ssr::retention_days(&db()?).await?)
+}
+
+#[server]
+/// 0 or at least 30 days, the older events are deleted by the daily pruning
+pub async fn set_audit_retention(days: u32) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    check_retention_days(days)?;
+    let db = db()?;
+    let before = retention_days(&db).await?;
+    set_retention_days(days, &db).await?;
+    audit(
+        AuditAction::RetentionChanged,
+        "audit_retention_days",
+        &before.to_string(),
+        &days.to_string(),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::ssr::*;
+
+    #[test]
+    fn the_retention_is_at_least_30_days() {
+        assert!(check_retention_days(0).is_ok());
+        assert!(check_retention_days(1).is_err());
+        assert!(check_retention_days(MIN_RETENTION_DAYS - 1).is_err());
+        assert!(check_retention_days(MIN_RETENTION_DAYS).is_ok());
+        assert!(check_retention_days(DEFAULT_RETENTION_DAYS).is_ok());
+    }
+}
@@ -1,21 +1,30 @@
+use super::permission::Permission;
 use super::User;
 use leptos::*;
+use serde::{Deserialize, Serialize};
+
+/// what the login page shows after the password is accepted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoginStep {
+    /// logged in
+    Done,
+    /// enter a code of the authenticator or a recovery code
+    Totp,
+    /// the role requires two-factor authentication, enrol before logging in
+    EnrolTotp,
+}
 #[server]
 pub async fn get_user_by_id(id: i32) -> Result<User, ServerFnError> {
+    use super::permission::{authorize, Access};
     use super::ssr::*;
-    let user = get_user().await?;
-    if !user.is_some_and(|user| user.role == 0 || user.id == id) {
-        return Err(ServerFnError::ServerError(
-            "You are not authorized to get user.".to_string(),
-        ));
-    }
+    authorize(Access::SelfOr(id, Permission::ManageUsers)).await?;
 
     let db = db()?;
     let user = entities::account::Entity::find_by_id(id)
         .one(&db)
         .await?
         .ok_or(ServerFnError::new("User does not exist."))?;
-    Ok(user.into())
+    Ok(load_user_permissions(user, &db).await?)
 }
 
 #[server]
@@ -27,16 +36,35 @@ pub async fn get_user() -> Result<Option<User>, ServerFnError> {
 }
 #[server]
 pub async fn get_all_users() -> Result<Vec<User>, ServerFnError> {
+    use super::permission::{authorize, Access};
     use super::ssr::*;
-    let db = db()?;
-    let users = crate::entities::account::Entity::find().all(&db).await?;
-    let users = users.into_iter().map(Into::into).collect();
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    Ok(all_users(&db()?).await?)
+}
+
+/// every account with the permissions of its role
+#[cfg(feature = "ssr")]
+pub async fn all_users(db: &sea_orm::DatabaseConnection) -> Result<Vec<User>, sea_orm::DbErr> {
+    use super::ssr::{role_permissions, user_from_account, EntityTrait};
+    let permissions = role_permissions(db).await?;
+    let users = crate::entities::account::Entity::find().all(db).await?;
+    let users = users
+        .into_iter()
+        .map(|account| {
+            let permissions = permissions
+                .get(&account.role_level)
+                .cloned()
+                .unwrap_or_default();
+            user_from_account(account, permissions)
+        })
+        .collect();
     Ok(users)
 }
 #[server]
+/// whether the current user can manage the users
 pub async fn is_admin() -> Result<bool, ServerFnError> {
     let user = get_user().await?;
-    let is_admin = user.is_some_and(|user| user.role == 0);
+    let is_admin = user.is_some_and(|user| user.can(Permission::ManageUsers));
     Ok(is_admin)
 }
 
@@ -54,28 +82,63 @@ pub async fn login(
     username: String,
     password: String,
     remember: Option<String>,
-) -> Result<(), ServerFnError> {
+) -> Result<LoginStep, ServerFnError> {
+    use super::auth_event::{ssr as event, AuthEventKind};
     use super::ssr::*;
+    use super::totp::ssr::{enabled_totp, role_requires_totp, set_pending_login};
     let db = db()?;
     let auth = auth()?;
+    let ip = event::current_ip();
+    event::check_throttle(&username, &ip, &db).await?;
 
     let user = entities::account::Entity::find()
-        .filter(entities::account::Column::Name.eq(username))
+        .filter(entities::account::Column::Name.eq(username.as_str()))
         .one(&db)
-        .await?
-        .ok_or(ServerFnError::new("Login failed: User does not exist."))?;
-
-    match verify(password, &user.password)? {
-        true => {
+        .await?;
+    // an unknown user takes as long and fails the same way as a wrong password
+    let valid = match &user {
+        Some(user) => verify(&password, &user.password)?,
+        None => verify(&password, event::dummy_hash()).map(|_| false)?,
+    };
+    let Some(user) = user.filter(|_| valid) else {
+        event::record(AuthEventKind::LoginFailure, &username, &ip, "", &db).await?;
+        return Err(ServerFnError::new(event::LOGIN_FAILED));
+    };
+    if !user.approved {
+        return Err(ServerFnError::new(
+            "Your account is waiting for the approval of an admin.",
+        ));
+    }
+    let step = if enabled_totp(user.id, &db).await?.is_some() {
+        LoginStep::Totp
+    } else if role_requires_totp(user.role_level, &db).await? {
+        LoginStep::EnrolTotp
+    } else {
+        LoginStep::Done
+    };
+    match step {
+        LoginStep::Done => {
+            event::record(AuthEventKind::LoginSuccess, &username, &ip, "", &db).await?;
             auth.login_user(user.id);
             auth.remember_user(remember.is_some());
             leptos_axum::redirect("/");
-            Ok(())
         }
-        false => Err(ServerFnError::ServerError(
-            "Password does not match.".to_string(),
-        )),
+        LoginStep::Totp | LoginStep::EnrolTotp => {
+            set_pending_login(
+                &auth,
+                user.id,
+                remember.is_some(),
+                step == LoginStep::EnrolTotp,
+            );
+        }
     }
+    Ok(step)
+}
+
+#[server]
+/// the name of the single sign-on provider, `None` when it is not configured
+pub async fn sso_provider() -> Result<Option<String>, ServerFnError> {
+    Ok(crate::oidc::OidcConfig::from_env().map(|config| config.display_name))
 }
 
 #[server(Signup, "/api")]
@@ -100,48 +163,137 @@ pub async fn signup(
 }
 #[server]
 pub async fn add_user(username: String, password: String, role: i32) -> Result<(), ServerFnError> {
-    let is_admin = is_admin().await?;
-    if !is_admin {
-        return Err(ServerFnError::ServerError(
-            "You are not authorized to add user.".to_string(),
-        ));
-    }
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    Role::find_by_id(role)
+        .one(&db()?)
+        .await?
+        .ok_or(ServerFnError::new("Role does not exist."))?;
 
-    add_user_util(username, password, role).await?;
+    let user = add_user_util(username, password, role).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::UserAdded,
+        &format!("user {}", user.id),
+        "",
+        &format!("{}, role {}", user.username, user.role),
+        &db()?,
+    )
+    .await?;
 
     Ok(())
 }
 #[server]
-pub async fn change_user_passwd(user_id: i32, new_password: String) -> Result<(), ServerFnError> {
+/// with `logout_other_sessions` the other sessions of the user are logged out
+pub async fn change_user_passwd(
+    user_id: i32,
+    new_password: String,
+    logout_other_sessions: bool,
+) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::session::ssr::{revoke_sessions, session_hash};
     use super::ssr::*;
     let db = db()?;
-    let user = get_user().await?;
-    let is_current_user_or_admin = user.is_some_and(|user| user.role == 0 || user.id == user_id);
-    if !is_current_user_or_admin {
-        return Err(ServerFnError::ServerError(
-            "You are not authorized to change user password.".to_string(),
-        ));
-    }
+    let current = authorize(Access::SelfOr(user_id, Permission::ManageUsers)).await?;
+    super::token::ssr::require_session()?;
     let password_hashed = hash(new_password, DEFAULT_COST).unwrap();
     let user = entities::account::ActiveModel {
         id: sea_orm::ActiveValue::Set(user_id),
         password: sea_orm::ActiveValue::Set(password_hashed),
         ..Default::default()
     };
-    user.update(&db).await?;
+    let user = user.update(&db).await?;
+    let mut after = "password changed".to_string();
+    if logout_other_sessions {
+        // the session changing the password stays logged in
+        let auth = auth()?;
+        let except = (current.id == user_id).then(|| session_hash(&auth));
+        let revoked = revoke_sessions(user_id, except.as_deref(), &db).await?;
+        after = format!("{}, {} sessions logged out", after, revoked);
+    }
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::PasswordChanged,
+        &format!("user {}", user_id),
+        &user.name,
+        &after,
+        &db,
+    )
+    .await?;
     Ok(())
 }
 
 #[server]
-pub async fn delete_user(user_id: i32) -> Result<(), ServerFnError> {
+/// the email address of the password reset mails
+pub async fn get_user_email(user_id: i32) -> Result<Option<String>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    authorize(Access::SelfOr(user_id, Permission::ManageUsers)).await?;
+    let account = Account::find_by_id(user_id)
+        .one(&db()?)
+        .await?
+        .ok_or(ServerFnError::new("User does not exist."))?;
+    Ok(account.email)
+}
+
+#[server]
+/// an empty address removes it
+pub async fn set_user_email(user_id: i32, email: String) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
     use super::ssr::*;
+    authorize(Access::SelfOr(user_id, Permission::ManageUsers)).await?;
     let db = db()?;
-    let is_admin = is_admin().await?;
-    if !is_admin {
-        return Err(ServerFnError::ServerError(
-            "You are not authorized to delete user.".to_string(),
-        ));
+    let email = email.trim();
+    let email = if email.is_empty() {
+        None
+    } else {
+        email
+            .parse::<lettre::Address>()
+            .map_err(|_| ServerFnError::new("The email address is not valid."))?;
+        let taken = Account::find()
+            .filter(entities::account::Column::Email.eq(email))
+            .filter(entities::account::Column::Id.ne(user_id))
+            .one(&db)
+            .await?
+            .is_some();
+        if taken {
+            return Err(ServerFnError::new(
+                "The email address is used by another account.",
+            ));
+        }
+        Some(email.to_string())
+    };
+    let before = Account::find_by_id(user_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("User does not exist."))?
+        .email;
+    entities::account::ActiveModel {
+        id: sea_orm::ActiveValue::Set(user_id),
+        email: sea_orm::ActiveValue::Set(email.clone()),
+        ..Default::default()
     }
+    .update(&db)
+    .await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::EmailChanged,
+        &format!("user {}", user_id),
+        &before.unwrap_or_default(),
+        &email.unwrap_or_default(),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server]
+pub async fn delete_user(user_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    let db = db()?;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
     // before delete the user, should delete all the progress of the user
     let account = Account::find_by_id(user_id).one(&db).await?;
 
@@ -150,7 +302,60 @@ pub async fn delete_user(user_id: i32) -> Result<(), ServerFnError> {
     for progress in progresses {
         progress.delete(&db).await?;
     }
+    let bookmarks = account.find_related(Bookmark).all(&db).await?;
+    for bookmark in bookmarks {
+        bookmark.delete(&db).await?;
+    }
+    let book_preferences = account.find_related(BookPreference).all(&db).await?;
+    for book_preference in book_preferences {
+        book_preference.delete(&db).await?;
+    }
+    let device_progresses = account.find_related(DeviceProgress).all(&db).await?;
+    for device_progress in device_progresses {
+        device_progress.delete(&db).await?;
+    }
+    ApiToken::delete_many()
+        .filter(entities::api_token::Column::AccountId.eq(account.id))
+        .exec(&db)
+        .await?;
+    AppPassword::delete_many()
+        .filter(entities::app_password::Column::AccountId.eq(account.id))
+        .exec(&db)
+        .await?;
+    FeedToken::delete_many()
+        .filter(entities::feed_token::Column::AccountId.eq(account.id))
+        .exec(&db)
+        .await?;
+    super::totp::ssr::reset_totp(account.id, &db).await?;
+    AccountIdentity::delete_many()
+        .filter(entities::account_identity::Column::AccountId.eq(account.id))
+        .exec(&db)
+        .await?;
+    LibraryGrant::delete_many()
+        .filter(entities::library_grant::Column::AccountId.eq(account.id))
+        .exec(&db)
+        .await?;
+    super::session::ssr::revoke_sessions(account.id, None, &db).await?;
+    let invitations = Invitation::find()
+        .filter(entities::invitation::Column::CreatedBy.eq(account.id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|i| i.id)
+        .collect();
+    super::invitation::ssr::delete_invitations(invitations, &db).await?;
+    Preference::delete_by_id(account.id).exec(&db).await?;
+    let before = format!("{}, role {}", account.name, account.role_level);
     account.delete(&db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::UserDeleted,
+        &format!("user {}", user_id),
+        &before,
+        "",
+        &db,
+    )
+    .await?;
 
     Ok(())
 }
@@ -162,25 +367,87 @@ pub async fn add_user_util(
 ) -> Result<User, ServerFnError> {
     use super::ssr::*;
     let db = db()?;
+    let user = add_account(username, password, role, &db).await?;
+    super::library::ssr::grant_default_library(user.id, &db).await?;
+    Ok(load_user_permissions(user, &db).await?)
+}
 
+/// check the name and password of an account created by a new user, returns the trimmed name
+#[cfg(feature = "ssr")]
+pub async fn check_new_account(
+    username: &str,
+    password: &str,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<String, ServerFnError> {
+    use super::ssr::{entities, Account, ColumnTrait, EntityTrait, QueryFilter};
+    let username = username.trim();
+    if username.is_empty() || password.is_empty() {
+        return Err(ServerFnError::new("Enter a user name and a password."));
+    }
+    let taken = Account::find()
+        .filter(entities::account::Column::Name.eq(username))
+        .one(db)
+        .await?
+        .is_some();
+    if taken {
+        return Err(ServerFnError::new("The user name is taken."));
+    }
+    Ok(username.to_string())
+}
+
+/// insert an account with a bcrypt hash of the password, the callers grant the libraries
+#[cfg(feature = "ssr")]
+pub async fn add_account(
+    username: String,
+    password: String,
+    role: i32,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<crate::entities::account::Model, sea_orm::DbErr> {
+    let user = insert_account(username, password, role, db).await?;
+    account_added(&user, db).await?;
+    Ok(user)
+}
+
+/// `add_account` without telling the webhooks, for accounts created in a transaction. only the
+/// self-registration makes accounts waiting for an approval, it inserts them itself
+#[cfg(feature = "ssr")]
+pub async fn insert_account<C: sea_orm::ConnectionTrait>(
+    username: String,
+    password: String,
+    role: i32,
+    db: &C,
+) -> Result<crate::entities::account::Model, sea_orm::DbErr> {
+    use super::ssr::{entities, hash, ActiveModelTrait, DEFAULT_COST};
     let password_hashed = hash(password, DEFAULT_COST).unwrap();
 
     let user = entities::account::ActiveModel {
         name: sea_orm::ActiveValue::Set(username),
         password: sea_orm::ActiveValue::Set(password_hashed),
         role_level: sea_orm::ActiveValue::Set(role),
+        approved: sea_orm::ActiveValue::Set(true),
         ..Default::default()
     };
 
-    let user = user.insert(&db).await?;
+    user.insert(db).await
+}
 
-    Ok(user.into())
+/// tell the webhooks about a new account
+#[cfg(feature = "ssr")]
+pub async fn account_added(
+    user: &crate::entities::account::Model,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<(), sea_orm::DbErr> {
+    use super::webhook::{ssr as webhook, WebhookEvent};
+    let data = serde_json::json!({ "user": webhook::user_data(user) });
+    webhook::trigger(WebhookEvent::UserCreated, data, db).await
 }
 #[server(Logout, "/api")]
 pub async fn logout() -> Result<(), ServerFnError> {
     use super::ssr::*;
     let auth = auth()?;
+    let db = db()?;
 
+    super::session::ssr::forget_session(&auth, &db).await?;
     auth.logout_user();
     leptos_axum::redirect("/");
 
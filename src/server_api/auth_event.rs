@@ -0,0 +1,399 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::PageItems;
+
+/// stored by [`AuthEventKind::as_str`] in `auth_event.kind`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AuthEventKind {
+    LoginSuccess,
+    /// unknown user or wrong password
+    LoginFailure,
+    /// a wrong code of the authenticator or recovery code
+    TotpFailure,
+    /// rejected before checking the password because of earlier failures
+    Throttled,
+    SsoLogin,
+    ProxyLogin,
+    PasswordResetRequested,
+    PasswordReset,
+}
+
+impl AuthEventKind {
+    pub const ALL: [AuthEventKind; 8] = [
+        AuthEventKind::LoginSuccess,
+        AuthEventKind::LoginFailure,
+        AuthEventKind::TotpFailure,
+        AuthEventKind::Throttled,
+        AuthEventKind::SsoLogin,
+        AuthEventKind::ProxyLogin,
+        AuthEventKind::PasswordResetRequested,
+        AuthEventKind::PasswordReset,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventKind::LoginSuccess => "login_success",
+            AuthEventKind::LoginFailure => "login_failure",
+            AuthEventKind::TotpFailure => "totp_failure",
+            AuthEventKind::Throttled => "throttled",
+            AuthEventKind::SsoLogin => "sso_login",
+            AuthEventKind::ProxyLogin => "proxy_login",
+            AuthEventKind::PasswordResetRequested => "password_reset_requested",
+            AuthEventKind::PasswordReset => "password_reset",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+
+    /// counted by the throttling
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            AuthEventKind::LoginFailure | AuthEventKind::TotpFailure
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthEventDetail {
+    pub id: i32,
+    /// rfc3339
+    pub created: String,
+    pub kind: Option<AuthEventKind>,
+    pub username: String,
+    pub ip: String,
+    pub detail: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use std::{net::IpAddr, sync::OnceLock};
+
+    use http::HeaderMap;
+    use leptos::{use_context, ServerFnError};
+    use sea_orm::{
+        ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+        Set,
+    };
+
+    use super::AuthEventKind;
+    use crate::{
+        entities::{prelude::*, *},
+        proxy_auth::Cidr,
+        server_api::ssr::{date_to_rfc3339, now, parse_date},
+    };
+
+    /// the same message for an unknown user and a wrong password
+    pub const LOGIN_FAILED: &str = "Login failed: wrong user name or password.";
+
+    /// the address of the client of a server function, provided by the server function handler
+    #[derive(Clone, Copy, Debug)]
+    pub struct ClientIp(pub IpAddr);
+
+    /// whether an address is one of the reverse proxies in `TRUSTED_PROXIES`, only they can set
+    /// the forwarded headers
+    pub fn is_trusted_proxy(ip: IpAddr) -> bool {
+        static TRUSTED: OnceLock<Vec<Cidr>> = OnceLock::new();
+        TRUSTED
+            .get_or_init(|| {
+                std::env::var("TRUSTED_PROXIES")
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter_map(Cidr::parse)
+                    .collect()
+            })
+            .iter()
+            .any(|cidr| cidr.contains(ip))
+    }
+
+    /// the address of the client, from `X-Forwarded-For` when the peer is in `TRUSTED_PROXIES`
+    pub fn client_ip(peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !is_trusted_proxy(peer) {
+            return peer;
+        }
+        // the last address not added by one of the proxies
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|ip| ip.trim().parse().ok())
+            .collect();
+        forwarded
+            .into_iter()
+            .rev()
+            .find(|ip| !is_trusted_proxy(*ip))
+            .unwrap_or(peer)
+    }
+
+    /// the address of the client of the current server function
+    pub fn current_ip() -> String {
+        use_context::<ClientIp>()
+            .map(|ip| ip.0.to_string())
+            .unwrap_or_default()
+    }
+
+    pub async fn record(
+        kind: AuthEventKind,
+        username: &str,
+        ip: &str,
+        detail: &str,
+        db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        AuthEvent::insert(auth_event::ActiveModel {
+            created: Set(now()),
+            kind: Set(kind.as_str().to_string()),
+            username: Set(username.to_string()),
+            ip: Set(ip.to_string()),
+            detail: Set(detail.to_string()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(())
+    }
+
+    /// the time of the last event of a kind for a user name
+    pub async fn last_event(
+        kind: AuthEventKind,
+        username: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, DbErr> {
+        Ok(AuthEvent::find()
+            .filter(auth_event::Column::Username.eq(username))
+            .filter(auth_event::Column::Kind.eq(kind.as_str()))
+            .order_by_desc(auth_event::Column::Id)
+            .one(db)
+            .await?
+            .and_then(|e| parse_date(&date_to_rfc3339(&e.created))))
+    }
+
+    /// how failed logins slow down the next attempts
+    #[derive(Clone, Copy, Debug)]
+    pub struct Throttle {
+        /// seconds after the first failure, doubled by each failure
+        pub base_delay: i64,
+        pub max_delay: i64,
+        /// failures within `lockout` seconds that lock out the login
+        pub lockout_after: usize,
+        pub lockout: i64,
+    }
+
+    impl Throttle {
+        /// per user name, `LOGIN_LOCKOUT_FAILURES` and `LOGIN_LOCKOUT_MINUTES` change the lockout
+        pub fn account() -> Self {
+            let var = |name: &str| -> Option<i64> {
+                std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+            };
+            Self {
+                base_delay: 1,
+                max_delay: 60,
+                lockout_after: var("LOGIN_LOCKOUT_FAILURES").unwrap_or(10) as usize,
+                lockout: var("LOGIN_LOCKOUT_MINUTES").unwrap_or(15) * 60,
+            }
+        }
+
+        /// per address, higher so a household behind one address is not locked out by one user
+        pub fn ip() -> Self {
+            let account = Self::account();
+            Self {
+                lockout_after: account.lockout_after * 3,
+                ..account
+            }
+        }
+    }
+
+    /// seconds until the next attempt is allowed after failures at the given unix times
+    pub fn retry_after(failures: &[i64], now: i64, throttle: &Throttle) -> i64 {
+        let recent: Vec<i64> = failures
+            .iter()
+            .copied()
+            .filter(|t| now - t < throttle.lockout)
+            .collect();
+        let Some(&last) = recent.iter().max() else {
+            return 0;
+        };
+        let wait = if recent.len() >= throttle.lockout_after {
+            throttle.lockout
+        } else {
+            let doubled = throttle.base_delay << (recent.len() - 1).min(30);
+            doubled.min(throttle.max_delay)
+        };
+        (last + wait - now).max(0)
+    }
+
+    /// the unix times of the newest failures, at most `limit`, for a user name only those after
+    /// its last login
+    async fn failures(
+        column: auth_event::Column,
+        value: &str,
+        stop_at_success: bool,
+        limit: usize,
+        db: &DatabaseConnection,
+    ) -> Result<Vec<i64>, DbErr> {
+        // the throttled attempts are left out before the limit, or they would push the failures
+        // out and lift the lockout
+        let kinds = AuthEventKind::ALL
+            .into_iter()
+            .filter(|k| k.is_failure() || (stop_at_success && *k == AuthEventKind::LoginSuccess))
+            .map(|k| k.as_str());
+        let events = AuthEvent::find()
+            .filter(column.eq(value))
+            .filter(auth_event::Column::Kind.is_in(kinds))
+            .order_by_desc(auth_event::Column::Id)
+            .limit(limit.max(1) as u64)
+            .all(db)
+            .await?;
+        Ok(events
+            .into_iter()
+            .filter_map(|e| Some((AuthEventKind::parse(&e.kind)?, e.created)))
+            .take_while(|(kind, _)| *kind != AuthEventKind::LoginSuccess)
+            .filter_map(|(_, created)| parse_date(&date_to_rfc3339(&created)))
+            .map(|created| created.timestamp())
+            .collect())
+    }
+
+    /// reject the attempt while the user name or the address has to wait
+    pub async fn check_throttle(
+        username: &str,
+        ip: &str,
+        db: &DatabaseConnection,
+    ) -> Result<(), ServerFnError> {
+        let now = chrono::Utc::now().timestamp();
+        let (account_throttle, ip_throttle) = (Throttle::account(), Throttle::ip());
+        // more failures than lock out do not change the wait
+        let account = failures(
+            auth_event::Column::Username,
+            username,
+            true,
+            account_throttle.lockout_after,
+            db,
+        )
+        .await?;
+        let address = failures(
+            auth_event::Column::Ip,
+            ip,
+            false,
+            ip_throttle.lockout_after,
+            db,
+        )
+        .await?;
+        let account_wait = retry_after(&account, now, &account_throttle);
+        let wait = account_wait.max(retry_after(&address, now, &ip_throttle));
+        if wait > 0 {
+            record(AuthEventKind::Throttled, username, ip, "", db).await?;
+            return Err(ServerFnError::new(format!(
+                "Too many failed logins, try again in {} seconds.",
+                wait
+            )));
+        }
+        Ok(())
+    }
+
+    /// a bcrypt hash to check the password of an unknown user against, so it takes as long as a
+    /// known user
+    pub fn dummy_hash() -> &'static str {
+        static HASH: OnceLock<String> = OnceLock::new();
+        HASH.get_or_init(|| bcrypt::hash("audiobookroom", bcrypt::DEFAULT_COST).unwrap())
+    }
+}
+
+#[server]
+/// the auth events, newest first, optionally of one kind or user name
+pub async fn list_auth_events(
+    page_num: u64,
+    max_item: u64,
+    kind: Option<AuthEventKind>,
+    username: Option<String>,
+) -> Result<PageItems<AuthEventDetail>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::{ItemsAndPagesNumber, PaginatorTrait, QueryOrder};
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let mut query = AuthEvent::find().order_by_desc(auth_event::Column::Id);
+    if let Some(kind) = kind {
+        query = query.filter(auth_event::Column::Kind.eq(kind.as_str()));
+    }
+    if let Some(username) = username.filter(|u| !u.trim().is_empty()) {
+        query = query.filter(auth_event::Column::Username.eq(username.trim()));
+    }
+    let page = query.paginate(&db, max_item);
+    let ItemsAndPagesNumber {
+        number_of_items,
+        number_of_pages,
+    } = page.num_items_and_pages().await?;
+    let items = page
+        .fetch_page(page_num)
+        .await?
+        .into_iter()
+        .map(|e| AuthEventDetail {
+            id: e.id,
+            created: date_to_rfc3339(&e.created),
+            kind: AuthEventKind::parse(&e.kind),
+            username: e.username,
+            ip: e.ip,
+            detail: e.detail,
+        })
+        .collect();
+    Ok(PageItems {
+        page: page_num,
+        max_item,
+        number_of_items,
+        number_of_pages,
+        items,
+    })
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::{ssr::*, AuthEventKind};
+    use crate::{
+        entities::prelude::*,
+        server_api::test_db::{memory_db, table},
+    };
+
+    #[tokio::test]
+    async fn throttled_attempts_do_not_lift_the_lockout() {
+        let db = memory_db(vec![table(AuthEvent)]).await;
+        for _ in 0..Throttle::account().lockout_after {
+            record(AuthEventKind::LoginFailure, "alice", "10.0.0.1", "", &db)
+                .await
+                .unwrap();
+        }
+        // every rejected attempt is recorded, far more of them than the failures
+        for _ in 0..150 {
+            assert!(check_throttle("alice", "10.0.0.2", &db).await.is_err());
+        }
+        // the address is locked out by the failures of many user names
+        for i in 0..Throttle::ip().lockout_after {
+            let username = format!("user{}", i);
+            record(AuthEventKind::TotpFailure, &username, "10.0.0.3", "", &db)
+                .await
+                .unwrap();
+        }
+        for i in 0..150 {
+            let username = format!("guess{}", i);
+            assert!(check_throttle(&username, "10.0.0.3", &db).await.is_err());
+        }
+        assert!(check_throttle("bob", "10.0.0.4", &db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn a_login_clears_the_failures_of_the_user() {
+        let db = memory_db(vec![table(AuthEvent)]).await;
+        for _ in 0..Throttle::account().lockout_after {
+            record(AuthEventKind::LoginFailure, "alice", "10.0.0.1", "", &db)
+                .await
+                .unwrap();
+        }
+        record(AuthEventKind::LoginSuccess, "alice", "10.0.0.2", "", &db)
+            .await
+            .unwrap();
+        assert!(check_throttle("alice", "10.0.0.2", &db).await.is_ok());
+    }
+}
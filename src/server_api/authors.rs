@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use super::PageItems;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct AuthorDetail {
     pub id: i32,
     pub avatar: String,
@@ -22,15 +23,49 @@ impl From<crate::entities::author::Model> for AuthorDetail {
     }
 }
 
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{library::ssr::visible_library_ids, User},
+    };
+
+    /// the authors with a book the user can see, by id
+    pub async fn visible_authors(
+        user: &User,
+        db: &DatabaseConnection,
+    ) -> Result<sea_orm::Select<author::Entity>, DbErr> {
+        let libraries = visible_library_ids(user, db).await?;
+        Ok(Author::find()
+            .filter(
+                author::Column::Id.in_subquery(
+                    sea_orm::sea_query::Query::select()
+                        .column(music::Column::AuthorId)
+                        .from(music::Entity)
+                        .and_where(music::Column::LibraryId.is_in(libraries))
+                        .to_owned(),
+                ),
+            )
+            .order_by_asc(author::Column::Id))
+    }
+}
+
 #[server]
 pub async fn get_author_by_id(id: i32) -> Result<Option<AuthorDetail>, ServerFnError> {
-    use crate::entities::prelude::*;
+    use crate::entities::author;
     use super::ssr::*;
     let db = db()?;
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
-    let author = Author::find_by_id(id).one(&db).await?;
+    // only the authors with a book the user can see
+    let author = self::ssr::visible_authors(&user, &db)
+        .await?
+        .filter(author::Column::Id.eq(id))
+        .one(&db)
+        .await?;
     let author = author.map(Into::into);
     Ok(author)
 }
@@ -40,31 +75,13 @@ pub async fn list_all_authors(
     page_num: u64,
     max_item: u64,
 ) -> Result<PageItems<AuthorDetail>, ServerFnError> {
-    use crate::entities::author;
-    use crate::entities::prelude::*;
     use super::ssr::*;
 
-    use sea_orm::prelude::*;
-    use sea_orm::*;
     let db = db()?;
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
-    let page = Author::find()
-        .order_by_asc(author::Column::Id)
-        .paginate(&db, max_item);
-    let ItemsAndPagesNumber {
-        number_of_items,
-        number_of_pages,
-    } = page.num_items_and_pages().await?;
-    let item = page.fetch_page(page_num).await?;
-    let authors = item.into_iter().map(Into::into).collect();
-    let authors = PageItems {
-        page: page_num,
-        max_item,
-        number_of_items,
-        number_of_pages,
-        items: authors,
-    };
-    Ok(authors)
+    let query = self::ssr::visible_authors(&user, &db).await?;
+    let authors = fetch_page(query, page_num, max_item, &db).await?;
+    Ok(authors.map(Into::into))
 }
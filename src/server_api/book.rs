@@ -2,6 +2,7 @@ use leptos::*;
 use serde::{Deserialize, Serialize};
 
 use super::authors::AuthorDetail;
+use super::PageItems;
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BookChapter {
@@ -12,15 +13,7 @@ pub struct BookChapter {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct PageItems<T> {
-    pub page: u64,
-    pub max_item: u64,
-    pub number_of_items: u64,
-    pub number_of_pages: u64,
-    pub items: Vec<T>,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct BookDetail {
     pub id: i32,
     pub author_id: i32,
@@ -29,6 +22,7 @@ pub struct BookDetail {
     pub total_time: Option<f64>,
     pub file_folder: String,
     pub music_type: i32,
+    pub library_id: i32,
 }
 
 #[cfg(feature = "ssr")]
@@ -42,54 +36,58 @@ impl From<crate::entities::music::Model> for BookDetail {
             total_time: m.total_time,
             file_folder: m.file_folder,
             music_type: m.music_type,
+            library_id: m.library_id,
         }
     }
 }
 
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{library::ssr::visible_library_ids, User},
+    };
+
+    /// the books of the libraries a user can see, by id
+    pub async fn visible_books(
+        user: &User,
+        db: &DatabaseConnection,
+    ) -> Result<sea_orm::Select<music::Entity>, DbErr> {
+        let libraries = visible_library_ids(user, db).await?;
+        Ok(Music::find()
+            .filter(music::Column::LibraryId.is_in(libraries))
+            .order_by_asc(music::Column::Id))
+    }
+
+    /// the chapters of a book in order
+    pub fn book_chapters(music_id: i32) -> sea_orm::Select<chapter::Entity> {
+        Chapter::find()
+            .filter(chapter::Column::MusicId.eq(music_id))
+            .order_by_asc(chapter::Column::ChapterNum)
+    }
+}
+
 #[server]
 pub async fn get_books_by_author(
     author_id: i32,
     page_num: u64,
     max_item: u64,
 ) -> Result<PageItems<BookDetail>, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
 
-    use crate::entities::{prelude::*, *};
+    use crate::entities::*;
 
     use super::ssr::*;
-    use sea_orm::prelude::*;
-    use sea_orm::{ItemsAndPagesNumber, QueryOrder};
     let db = db()?;
-    let page = Music::find()
-        .filter(music::Column::AuthorId.eq(author_id))
-        .order_by_asc(music::Column::Id)
-        .paginate(&db, max_item);
-    let ItemsAndPagesNumber {
-        number_of_items,
-        number_of_pages,
-    } = page.num_items_and_pages().await?;
-    let item = page.fetch_page(page_num).await?;
-    let books = item
-        .into_iter()
-        .map(|it| BookDetail {
-            id: it.id,
-            author_id: it.author_id,
-            name: it.name,
-            chapters: it.chapters,
-            total_time: it.total_time,
-            file_folder: it.file_folder,
-            music_type: it.music_type,
-        })
-        .collect();
-    Ok(PageItems {
-        number_of_items,
-        number_of_pages,
-        page: page_num,
-        max_item: max_item,
-        items: books,
-    })
+    let query = self::ssr::visible_books(&user, &db)
+        .await?
+        .filter(music::Column::AuthorId.eq(author_id));
+    let page = fetch_page(query, page_num, max_item, &db).await?;
+    Ok(page.map(Into::into))
 }
 
 #[server(GetBookAllDetail,"/api","GetJson")]
@@ -112,6 +110,7 @@ pub async fn get_book_all_detail(
     let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
+    super::library::ssr::find_visible_book(&user, book_id, &db).await?;
     let (book, author) = Music::find_by_id(book_id)
         .find_also_related(author::Entity)
         .one(&db)
@@ -142,16 +141,13 @@ pub async fn get_book_all_detail(
 }
 #[server]
 pub async fn get_book_detail(book_id: i32) -> Result<BookDetail, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
 
     use super::ssr::*;
     let db = db()?;
-    let book = Music::find_by_id(book_id)
-        .one(&db)
-        .await?
-        .ok_or(ServerFnError::new("Book not found"))?;
+    let book = super::library::ssr::find_visible_book(&user, book_id, &db).await?;
     Ok(book.into())
 }
 
@@ -160,39 +156,18 @@ pub async fn get_books_details(
     page_num: u64,
     max_item: u64,
 ) -> Result<PageItems<(BookDetail, AuthorDetail)>, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
 
-    use crate::entities::{prelude::*, *};
-
     use super::ssr::*;
     use sea_orm::prelude::*;
-    use sea_orm::{ItemsAndPagesNumber, QueryOrder};
     let db = db()?;
-    let page = Music::find()
-        .order_by_asc(music::Column::Id)
-        .paginate(&db, max_item);
-    let ItemsAndPagesNumber {
-        number_of_items,
-        number_of_pages,
-    } = page.num_items_and_pages().await?;
-    let item = page.fetch_page(page_num).await?;
-    let authors = item.load_one(Author, &db).await?;
-
-    let items = item
-        .into_iter()
-        .zip(authors)
-        .map(|(it, au)| (it.into(), au.unwrap().into()))
-        .collect();
-
-    Ok(PageItems {
-        number_of_items,
-        number_of_pages,
-        page: page_num,
-        max_item: max_item,
-        items,
-    })
+    let query = self::ssr::visible_books(&user, &db).await?;
+    let page = fetch_page(query, page_num, max_item, &db).await?;
+    let authors = page.items.load_one(Author, &db).await?;
+    let mut authors = authors.into_iter();
+    Ok(page.filter_map(|book| Some((book.into(), authors.next().flatten()?.into()))))
 }
 
 #[server]
@@ -200,43 +175,15 @@ pub async fn get_books(
     page_num: u64,
     max_item: u64,
 ) -> Result<PageItems<BookDetail>, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
 
-    use crate::entities::{prelude::*, *};
-
     use super::ssr::*;
-    use sea_orm::prelude::*;
-    use sea_orm::{ItemsAndPagesNumber, QueryOrder};
     let db = db()?;
-    let page = Music::find()
-        .order_by_asc(music::Column::Id)
-        .paginate(&db, max_item);
-    let ItemsAndPagesNumber {
-        number_of_items,
-        number_of_pages,
-    } = page.num_items_and_pages().await?;
-    let item = page.fetch_page(page_num).await?;
-    let books = item
-        .into_iter()
-        .map(|it| BookDetail {
-            id: it.id,
-            author_id: it.author_id,
-            name: it.name,
-            chapters: it.chapters,
-            total_time: it.total_time,
-            file_folder: it.file_folder,
-            music_type: it.music_type,
-        })
-        .collect();
-    Ok(PageItems {
-        number_of_items,
-        number_of_pages,
-        page: page_num,
-        max_item: max_item,
-        items: books,
-    })
+    let query = self::ssr::visible_books(&user, &db).await?;
+    let page = fetch_page(query, page_num, max_item, &db).await?;
+    Ok(page.map(Into::into))
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -249,26 +196,40 @@ pub async fn add_book(
     author_name: String,
     name: String,
     source: String,
+    library_id: i32,
 ) -> Result<AddBookResult, ServerFnError> {
-    crate::server_api::auth::get_user()
-        .await?
-        .ok_or(ServerFnError::new("Not logged in"))?;
+    use super::permission::{authorize, Access, Permission};
+    let user = authorize(Access::Permission(Permission::Upload)).await?;
 
     use super::ssr::*;
     let db = db()?;
+    if !super::library::ssr::can_see_library(&user, library_id, &db).await? {
+        return Err(ServerFnError::new("Library does not exist."));
+    }
     // first create the author
 
+    let summary = format!("{}/{} in library {}", author_name, name, library_id);
     let create_result = crate::tools::create_new_book(
         author_name,
         name,
-        std::path::Path::new("./fetchbook"),
+        library_id,
         std::path::Path::new(&source),
         &db,
     )
     .await;
-    if let Err(e) = create_result {
-        return Err(ServerFnError::new(e.to_string()));
-    }
+    let book_id = match create_result {
+        Ok(book_id) => book_id,
+        Err(e) => return Err(ServerFnError::new(e.to_string())),
+    };
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::BookAdded,
+        &format!("book {}", book_id),
+        "",
+        &summary,
+        &db,
+    )
+    .await?;
 
     Ok(AddBookResult {
         msg: "Book added".to_string(),
@@ -285,6 +246,7 @@ pub struct ChapterPage {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct ChapterDetail {
     pub id: i32,
     pub music_id: i32,
@@ -314,42 +276,22 @@ pub async fn get_chapters(
     page_num: u64,
     max_item: u64,
 ) -> Result<ChapterPage, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
 
     use super::ssr::*;
-    use crate::entities::*;
 
-    use sea_orm::prelude::*;
-    use sea_orm::{ItemsAndPagesNumber, QueryOrder};
     let db = db()?;
-    let page = Chapter::find()
-        .filter(chapter::Column::MusicId.eq(music_id))
-        .order_by_asc(chapter::Column::ChapterNum)
-        .paginate(&db, max_item);
-    let ItemsAndPagesNumber {
-        number_of_items,
-        number_of_pages,
-    } = page.num_items_and_pages().await?;
-    let item = page.fetch_page(page_num).await?;
-    let chapters = item
-        .into_iter()
-        .map(|it| ChapterDetail {
-            id: it.id,
-            music_id: it.music_id,
-            chapter_num: it.chapter_num,
-            chapter_name: it.chapter_name,
-            chapter_url: it.chapter_url,
-            chapter_length: it.chapter_length,
-        })
-        .collect();
+    super::library::ssr::find_visible_book(&user, music_id, &db).await?;
+    let query = self::ssr::book_chapters(music_id);
+    let page = fetch_page(query, page_num, max_item, &db).await?;
     Ok(ChapterPage {
-        number_of_items,
-        number_of_pages,
+        number_of_items: page.number_of_items,
+        number_of_pages: page.number_of_pages,
         page: page_num,
         max_item: max_item,
-        items: chapters,
+        items: page.items.into_iter().map(Into::into).collect(),
     })
 }
 
@@ -358,12 +300,13 @@ pub async fn search_chapter_by_chapter_num(
     book_id: i32,
     chapter_num: i32,
 ) -> Result<ChapterDetail, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
     use super::ssr::*;
 
     let db = db()?;
+    super::library::ssr::find_visible_book(&user, book_id, &db).await?;
     use crate::entities::chapter;
     let chapter = Chapter::find()
         .filter(chapter::Column::MusicId.eq(book_id))
@@ -386,33 +329,28 @@ pub async fn get_chapter_details(
     chapter_id: i32,
 ) -> Result<(BookDetail, AuthorDetail, ChapterDetail), ServerFnError> {
     use super::ssr::*;
-    use crate::entities::*;
 
     let db = db()?;
-    let _user = crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
-    let (chapter, book) = Chapter::find_by_id(chapter_id)
-        .find_also_related(music::Entity)
+    let (chapter, book) = super::library::ssr::find_visible_chapter(&user, chapter_id, &db).await?;
+    let author = book
+        .find_related(Author)
         .one(&db)
         .await?
-        .unwrap();
-    let book = book.unwrap();
-    let author = book.find_related(Author).one(&db).await?.unwrap();
+        .ok_or(ServerFnError::new("Author not found"))?;
     Ok((book.into(), author.into(), chapter.into()))
 }
 #[server]
 pub async fn get_chatper_detail(chapter_id: i32) -> Result<ChapterDetail, ServerFnError> {
-    crate::server_api::auth::get_user()
+    let user = crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
     use super::ssr::*;
 
     let db = db()?;
-    let chapter = Chapter::find_by_id(chapter_id)
-        .one(&db)
-        .await?
-        .ok_or(ServerFnError::new("Chapter not found"))?;
+    let (chapter, _) = super::library::ssr::find_visible_chapter(&user, chapter_id, &db).await?;
     Ok(ChapterDetail {
         id: chapter.id,
         music_id: chapter.music_id,
@@ -425,9 +363,8 @@ pub async fn get_chatper_detail(chapter_id: i32) -> Result<ChapterDetail, Server
 
 #[server]
 pub async fn delete_book(book_id: i32) -> Result<(), ServerFnError> {
-    crate::server_api::auth::get_user()
-        .await?
-        .ok_or(ServerFnError::new("Not logged in"))?;
+    use super::permission::{authorize, Access, Permission};
+    let user = authorize(Access::Permission(Permission::Delete)).await?;
     use super::ssr::*;
 
     use sea_orm::prelude::*;
@@ -435,18 +372,51 @@ pub async fn delete_book(book_id: i32) -> Result<(), ServerFnError> {
     use crate::entities::*;
     let book = Music::find_by_id(book_id).one(&db).await?;
     if let Some(book) = book {
+        if !super::library::ssr::can_see_library(&user, book.library_id, &db).await? {
+            return Err(ServerFnError::new("Book not found"));
+        }
         // delete the progress
         let all_progress = book.find_related(Progress).all(&db).await?;
         for p in all_progress {
             p.delete(&db).await?;
         }
+        let all_device_progress = book.find_related(DeviceProgress).all(&db).await?;
+        for p in all_device_progress {
+            p.delete(&db).await?;
+        }
+        // delete the bookmarks
+        let all_bookmarks = book.find_related(Bookmark).all(&db).await?;
+        for b in all_bookmarks {
+            b.delete(&db).await?;
+        }
+        // delete the book preferences
+        let all_book_preferences = book.find_related(BookPreference).all(&db).await?;
+        for p in all_book_preferences {
+            p.delete(&db).await?;
+        }
         // delete the chapters
         let all_chapters = book.find_related(Chapter).all(&db).await?;
+        let all_chapters_count = all_chapters.len();
         for c in all_chapters {
             c.delete(&db).await?;
         }
         // delete the book
         Music::delete_by_id(book.id).exec(&db).await?;
+        use super::audit::{ssr::audit, AuditAction};
+        audit(
+            AuditAction::BookDeleted,
+            &format!("book {}", book.id),
+            &format!(
+                "{} in library {}, {} chapters",
+                book.file_folder, book.library_id, all_chapters_count
+            ),
+            "",
+            &db,
+        )
+        .await?;
+        use super::webhook::{ssr as webhook, WebhookEvent};
+        let data = serde_json::json!({ "book": webhook::book_data(&book, &db).await? });
+        webhook::trigger(WebhookEvent::BookDeleted, data, &db).await?;
 
         let book_count = Music::find()
             .filter(music::Column::AuthorId.eq(book.author_id))
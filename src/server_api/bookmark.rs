@@ -0,0 +1,206 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ProgressDateType;
+
+use super::book::ChapterDetail;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BookmarkResult {
+    pub id: i32,
+    pub account_id: i32,
+    pub music_id: i32,
+    pub chapter_id: i32,
+    pub position: f64,
+    pub name: String,
+    pub note: Option<String>,
+
+    pub created: ProgressDateType,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::entities::bookmark::Model> for BookmarkResult {
+    fn from(b: crate::entities::bookmark::Model) -> Self {
+        Self {
+            id: b.id,
+            account_id: b.account_id,
+            music_id: b.music_id,
+            chapter_id: b.chapter_id,
+            position: b.position,
+            name: b.name,
+            note: b.note,
+            created: b.created,
+        }
+    }
+}
+
+/// format a position in seconds as `HH:MM:SS`
+pub fn format_position(position: f64) -> String {
+    let position = position as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        position / 3600,
+        position / 60 % 60,
+        position % 60
+    )
+}
+
+#[server]
+/// get all bookmarks of the current user in a book, ordered by chapter and position
+pub async fn get_bookmarks_by_book(
+    music_id: i32,
+) -> Result<Vec<(BookmarkResult, ChapterDetail)>, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    use crate::entities::*;
+    let db = db()?;
+
+    let mut b = Bookmark::find()
+        .filter(bookmark::Column::AccountId.eq(user.id))
+        .filter(bookmark::Column::MusicId.eq(music_id))
+        .find_also_related(Chapter)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|(b, c)| (BookmarkResult::from(b), ChapterDetail::from(c.unwrap())))
+        .collect::<Vec<_>>();
+    b.sort_by(|(a, ac), (b, bc)| {
+        ac.chapter_num
+            .cmp(&bc.chapter_num)
+            .then(a.position.total_cmp(&b.position))
+    });
+    Ok(b)
+}
+
+#[server]
+pub async fn add_bookmark(
+    music_id: i32,
+    chapter_id: i32,
+    position: f64,
+    name: String,
+    note: Option<String>,
+) -> Result<BookmarkResult, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    use crate::entities::*;
+    let db = db()?;
+
+    let (chapter, _) = super::library::ssr::find_visible_chapter(&user, chapter_id, &db).await?;
+    if chapter.music_id != music_id {
+        return Err(ServerFnError::new("Chapter does not belong to the book"));
+    }
+    let name = match name.trim() {
+        "" => format_position(position),
+        name => name.to_string(),
+    };
+    let note = note.filter(|note| !note.trim().is_empty());
+    let b = bookmark::ActiveModel {
+        account_id: sea_orm::ActiveValue::set(user.id),
+        music_id: sea_orm::ActiveValue::set(music_id),
+        chapter_id: sea_orm::ActiveValue::set(chapter_id),
+        position: sea_orm::ActiveValue::set(position),
+        name: sea_orm::ActiveValue::set(name),
+        note: sea_orm::ActiveValue::set(note),
+        created: sea_orm::ActiveValue::set(now()),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await?;
+    Ok(b.into())
+}
+
+#[server]
+pub async fn update_bookmark(
+    bookmark_id: i32,
+    name: String,
+    note: Option<String>,
+) -> Result<(), ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    use sea_orm::IntoActiveModel;
+    let db = db()?;
+
+    let b = Bookmark::find_by_id(bookmark_id)
+        .one(&db)
+        .await?
+        .filter(|b| b.account_id == user.id)
+        .ok_or(ServerFnError::new("Bookmark not found"))?;
+    let mut b = b.into_active_model();
+    if !name.trim().is_empty() {
+        b.name = sea_orm::ActiveValue::set(name.trim().to_string());
+    }
+    b.note = sea_orm::ActiveValue::set(note.filter(|note| !note.trim().is_empty()));
+    b.save(&db).await?;
+    Ok(())
+}
+
+#[server]
+pub async fn delete_bookmark(bookmark_id: i32) -> Result<(), ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    let db = db()?;
+
+    let b = Bookmark::find_by_id(bookmark_id)
+        .one(&db)
+        .await?
+        .filter(|b| b.account_id == user.id)
+        .ok_or(ServerFnError::new("Bookmark not found"))?;
+    b.delete(&db).await?;
+    Ok(())
+}
+
+#[server]
+/// export all bookmarks of the current user in a book as a markdown document
+pub async fn export_bookmarks_markdown(music_id: i32) -> Result<String, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    let db = db()?;
+
+    let book = super::library::ssr::find_visible_book(&user, music_id, &db).await?;
+    let bookmarks = get_bookmarks_by_book(music_id).await?;
+    let author = book.find_related(Author).one(&db).await?;
+
+    let mut md = format!("# {}\n\n", book.name);
+    if let Some(author) = author {
+        md.push_str(&format!("*{}*\n\n", author.name));
+    }
+    if bookmarks.is_empty() {
+        md.push_str("No bookmarks.\n");
+    }
+    let mut last_chapter = None;
+    for (b, c) in bookmarks {
+        if last_chapter != Some(c.id) {
+            if last_chapter.is_some() {
+                md.push('\n');
+            }
+            md.push_str(&format!("## {}\n\n", c.chapter_name));
+            last_chapter = Some(c.id);
+        }
+        md.push_str(&format!(
+            "- **{}** `{}`\n",
+            b.name,
+            format_position(b.position)
+        ));
+        if let Some(note) = b.note {
+            for line in note.lines() {
+                md.push_str(&format!("  > {}\n", line));
+            }
+        }
+    }
+    Ok(md)
+}
@@ -0,0 +1,148 @@
+use leptos::*;
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            ssr::{load_user_permissions, now},
+            token::ssr::hash_token,
+            User,
+        },
+    };
+
+    /// the prefix of the feed tokens, they are not api tokens
+    pub const FEED_TOKEN_PREFIX: &str = "abrf_";
+
+    pub fn generate_feed_token() -> String {
+        use rand::{distributions::Alphanumeric, Rng};
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+        format!("{}{}", FEED_TOKEN_PREFIX, random)
+    }
+
+    /// replace the feed token of an account, returns the token, only its hash is stored
+    pub async fn insert_feed_token(
+        account_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<String, DbErr> {
+        FeedToken::delete_many()
+            .filter(feed_token::Column::AccountId.eq(account_id))
+            .exec(db)
+            .await?;
+        let token = generate_feed_token();
+        FeedToken::insert(feed_token::ActiveModel {
+            account_id: sea_orm::Set(account_id),
+            token_hash: sea_orm::Set(hash_token(&token)),
+            created: sea_orm::Set(now()),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(token)
+    }
+
+    /// the approved user of a feed token
+    pub async fn user_from_feed_token(
+        token: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<User>, DbErr> {
+        let Some(feed_token) = FeedToken::find()
+            .filter(feed_token::Column::TokenHash.eq(hash_token(token)))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let Some(account) = Account::find_by_id(feed_token.account_id).one(db).await? else {
+            return Ok(None);
+        };
+        if !account.approved {
+            return Ok(None);
+        }
+        Ok(Some(load_user_permissions(account, db).await?))
+    }
+}
+
+/// the feed url of a book, relative to the server
+pub fn feed_path(music_id: i32, token: &str) -> String {
+    format!("/feed/{}?token={}", music_id, token)
+}
+
+#[server]
+/// whether the current user has a feed token
+pub async fn has_feed_token() -> Result<bool, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let token = FeedToken::find()
+        .filter(feed_token::Column::AccountId.eq(user.id))
+        .one(&db)
+        .await?;
+    Ok(token.is_some())
+}
+
+#[server]
+/// create the feed token of the current user, an existing one is replaced and its feeds stop
+/// working. the token is returned only here, only its hash is stored
+pub async fn reset_feed_token() -> Result<String, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::db;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    Ok(self::ssr::insert_feed_token(user.id, &db).await?)
+}
+
+#[server]
+/// revoke the feed token of the current user, all their feeds stop working
+pub async fn revoke_feed_token() -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    FeedToken::delete_many()
+        .filter(feed_token::Column::AccountId.eq(user.id))
+        .exec(&db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use sea_orm::EntityTrait;
+
+    use super::ssr::*;
+    use crate::{
+        entities::prelude::*,
+        server_api::test_db::{account, memory_db, table},
+    };
+
+    #[tokio::test]
+    async fn a_new_token_replaces_the_old_one() {
+        let db = memory_db(vec![
+            table(Role),
+            table(RolePermission),
+            table(Account),
+            table(FeedToken),
+        ])
+        .await;
+        let alice = account("alice", 1, &db).await;
+        let old = insert_feed_token(alice.id, &db).await.unwrap();
+        let new = insert_feed_token(alice.id, &db).await.unwrap();
+        assert!(new.starts_with(FEED_TOKEN_PREFIX));
+        assert_eq!(user_from_feed_token(&old, &db).await.unwrap(), None);
+        let user = user_from_feed_token(&new, &db).await.unwrap().unwrap();
+        assert_eq!(user.id, alice.id);
+        let stored = FeedToken::find().all(&db).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_ne!(stored[0].token_hash, new);
+    }
+}
@@ -0,0 +1,312 @@
+//! import the positions of other audiobook players.
+//!
+//! the entries are first matched against the library and shown to the user by
+//! [`preview_progress_import`], only the matches the user selected are written by
+//! [`apply_progress_import`].
+
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportFormat {
+    /// the json of the listening sessions (`/api/me/listening-sessions`) of Audiobookshelf
+    Audiobookshelf,
+    /// the json backup of Smart AudioBook Player
+    SmartAudioBookPlayer,
+    /// `title,author,chapter index,seconds`, the chapter index starts at 1
+    Csv,
+}
+
+impl ImportFormat {
+    pub const ALL: [ImportFormat; 3] = [
+        ImportFormat::Audiobookshelf,
+        ImportFormat::SmartAudioBookPlayer,
+        ImportFormat::Csv,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImportFormat::Audiobookshelf => "Audiobookshelf",
+            ImportFormat::SmartAudioBookPlayer => "Smart AudioBook Player",
+            ImportFormat::Csv => "CSV",
+        }
+    }
+}
+
+/// a position read from another player
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImportEntry {
+    pub title: String,
+    pub author: String,
+    /// starts at 0, like `chapter_num`
+    pub chapter_index: Option<i32>,
+    pub chapter_name: Option<String>,
+    /// the position inside the chapter
+    pub seconds: f64,
+    /// rfc3339
+    pub updated: Option<String>,
+}
+
+/// the book and chapter an entry was matched to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImportTarget {
+    pub music_id: i32,
+    pub title: String,
+    pub author: String,
+    pub chapter_id: i32,
+    pub chapter_num: i32,
+    pub chapter_name: String,
+    /// the similarity of the book, between 0 and 1
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImportMatch {
+    pub entry: ImportEntry,
+    pub target: Option<ImportTarget>,
+}
+
+/// a match the user accepted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImportSelection {
+    pub music_id: i32,
+    pub chapter_id: i32,
+    pub progress: f64,
+    /// rfc3339
+    pub updated: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod parse {
+    use super::ImportEntry;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct AbsChapter {
+        start: f64,
+        title: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct AbsSession {
+        library_item_id: Option<String>,
+        display_title: String,
+        #[serde(default)]
+        display_author: String,
+        /// the position in the whole book
+        current_time: f64,
+        #[serde(default)]
+        chapters: Vec<AbsChapter>,
+        /// milliseconds
+        updated_at: Option<i64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum AbsExport {
+        Sessions { sessions: Vec<AbsSession> },
+        List(Vec<AbsSession>),
+    }
+
+    fn from_millis(millis: i64) -> Option<String> {
+        chrono::DateTime::from_timestamp(
+            millis.div_euclid(1000),
+            millis.rem_euclid(1000) as u32 * 1_000_000,
+        )
+        .map(|t| t.to_rfc3339())
+    }
+
+    /// only the newest session of each book is kept
+    pub fn audiobookshelf(data: &str) -> eyre::Result<Vec<ImportEntry>> {
+        let sessions = match serde_json::from_str(data)? {
+            AbsExport::Sessions { sessions } => sessions,
+            AbsExport::List(sessions) => sessions,
+        };
+        let mut newest = std::collections::HashMap::new();
+        for session in sessions {
+            let key = session
+                .library_item_id
+                .clone()
+                .unwrap_or_else(|| format!("{}/{}", session.display_author, session.display_title));
+            let is_older = newest
+                .get(&key)
+                .is_some_and(|s: &AbsSession| s.updated_at >= session.updated_at);
+            if !is_older {
+                newest.insert(key, session);
+            }
+        }
+        let mut entries = newest
+            .into_values()
+            .map(|s| {
+                // the chapter containing the position, the first one when there are no chapters
+                let chapter = s
+                    .chapters
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find(|(_, c)| c.start <= s.current_time);
+                let (chapter_index, chapter_name, seconds) = match chapter {
+                    Some((i, c)) => (i as i32, Some(c.title.clone()), s.current_time - c.start),
+                    None => (0, None, s.current_time),
+                };
+                ImportEntry {
+                    title: s.display_title,
+                    author: s.display_author,
+                    chapter_index: Some(chapter_index),
+                    chapter_name,
+                    seconds,
+                    updated: s.updated_at.and_then(from_millis),
+                }
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.updated.cmp(&a.updated));
+        Ok(entries)
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SabpBook {
+        /// the folder of the book, the player uses it as the title
+        #[serde(alias = "name", alias = "folderName")]
+        title: String,
+        #[serde(default)]
+        author: String,
+        /// the file being played, without the folder
+        #[serde(alias = "currentFile", alias = "fileName")]
+        file: Option<String>,
+        /// milliseconds in the current file
+        #[serde(alias = "positionMs")]
+        position: i64,
+        /// milliseconds
+        #[serde(alias = "lastPlayed")]
+        last_update: Option<i64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SabpBackup {
+        Books { books: Vec<SabpBook> },
+        List(Vec<SabpBook>),
+    }
+
+    pub fn smart_audiobook_player(data: &str) -> eyre::Result<Vec<ImportEntry>> {
+        let books = match serde_json::from_str(data)? {
+            SabpBackup::Books { books } => books,
+            SabpBackup::List(books) => books,
+        };
+        Ok(books
+            .into_iter()
+            .map(|b| ImportEntry {
+                title: b.title,
+                author: b.author,
+                chapter_index: None,
+                // the chapter names are the file names without the extension
+                chapter_name: b.file.map(|f| {
+                    std::path::Path::new(&f)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or(f)
+                }),
+                seconds: b.position as f64 / 1000.,
+                updated: b.last_update.and_then(from_millis),
+            })
+            .collect())
+    }
+
+    /// split a csv line, fields can be quoted with `"`, and `""` is a quote inside them
+    fn split_csv_line(line: &str) -> Vec<String> {
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(std::mem::take(&mut field)),
+                c => field.push(c),
+            }
+        }
+        fields.push(field);
+        fields.into_iter().map(|f| f.trim().to_string()).collect()
+    }
+
+    /// `title,author,chapter index,seconds`, a header line is skipped
+    pub fn csv(data: &str) -> eyre::Result<Vec<ImportEntry>> {
+        let mut entries = vec![];
+        for (line_num, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = split_csv_line(line);
+            let [title, author, chapter, seconds] = fields.as_slice() else {
+                eyre::bail!(
+                    "line {}: expected 4 fields, got {}",
+                    line_num + 1,
+                    fields.len()
+                );
+            };
+            let (Ok(chapter), Ok(seconds)) = (chapter.parse::<i32>(), seconds.parse::<f64>())
+            else {
+                if line_num == 0 {
+                    // the header
+                    continue;
+                }
+                eyre::bail!("line {}: bad chapter index or seconds", line_num + 1);
+            };
+            if chapter < 1 {
+                eyre::bail!("line {}: the chapter index starts at 1", line_num + 1);
+            }
+            entries.push(ImportEntry {
+                title: title.clone(),
+                author: author.clone(),
+                chapter_index: Some(chapter - 1),
+                chapter_name: None,
+                seconds,
+                updated: None,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+#[server]
+/// read the positions of another player and find them in the library, nothing is written
+pub async fn preview_progress_import(
+    format: ImportFormat,
+    data: String,
+) -> Result<Vec<ImportMatch>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let entries = match format {
+        ImportFormat::Audiobookshelf => parse::audiobookshelf(&data),
+        ImportFormat::SmartAudioBookPlayer => parse::smart_audiobook_player(&data),
+        ImportFormat::Csv => parse::csv(&data),
+    }
+    .map_err(|e| ServerFnError::new(format!("Can not read the {}: {}", format.name(), e)))?;
+    crate::tools::match_import_entries(entries, &db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
+
+#[server]
+/// write the selected matches as the progress of a user, returns the number of books written
+pub async fn apply_progress_import(
+    account_id: i32,
+    selections: Vec<ImportSelection>,
+) -> Result<usize, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    crate::tools::apply_progress_import(account_id, selections, &db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
@@ -0,0 +1,458 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvitationDetail {
+    pub id: i32,
+    pub role: i32,
+    pub libraries: Vec<i32>,
+    /// `None` for no limit
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    /// rfc3339
+    pub created: String,
+    pub expires: String,
+}
+
+/// what the invite page shows before the account is created
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvitationInfo {
+    pub role_name: String,
+    pub expires: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use leptos::ServerFnError;
+    use sea_orm::{
+        sea_query::Expr, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+        TransactionTrait,
+    };
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            auth::{account_added, check_new_account, insert_account},
+            library::ssr::grant_default_library,
+            ssr::{date_to_rfc3339, parse_date},
+            token::ssr::hash_token,
+        },
+    };
+
+    /// the prefix of the invite tokens
+    pub const INVITE_PREFIX: &str = "inv_";
+
+    /// the invitation of a token when it is not expired and has uses left
+    pub async fn usable_invitation(
+        token: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<invitation::Model>, DbErr> {
+        let invitation = Invitation::find()
+            .filter(invitation::Column::TokenHash.eq(hash_token(token.trim())))
+            .one(db)
+            .await?;
+        Ok(invitation.filter(|i| {
+            let expired =
+                parse_date(&date_to_rfc3339(&i.expires)).is_none_or(|e| e <= chrono::Utc::now());
+            !expired && i.max_uses.is_none_or(|max| i.uses < max)
+        }))
+    }
+
+    pub async fn invitation_libraries(
+        invitation_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<Vec<i32>, DbErr> {
+        Ok(InvitationLibrary::find()
+            .filter(invitation_library::Column::InvitationId.eq(invitation_id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|l| l.library_id)
+            .collect())
+    }
+
+    /// create an account with an invitation. the use is counted in the transaction that creates
+    /// the account, so a failed account does not use up the invitation
+    pub async fn join_with_invitation(
+        token: &str,
+        username: &str,
+        password: String,
+        db: &DatabaseConnection,
+    ) -> Result<(invitation::Model, account::Model), ServerFnError> {
+        let used_up = || ServerFnError::new("The invitation has expired or was used up.");
+        let invitation = usable_invitation(token, db).await?.ok_or_else(used_up)?;
+        let username = check_new_account(username, &password, db).await?;
+        let libraries = invitation_libraries(invitation.id, db).await?;
+
+        let txn = db.begin().await?;
+        // two accounts can not take the last use
+        let mut claim = Invitation::update_many()
+            .col_expr(
+                invitation::Column::Uses,
+                Expr::col(invitation::Column::Uses).add(1),
+            )
+            .filter(invitation::Column::Id.eq(invitation.id));
+        if let Some(max_uses) = invitation.max_uses {
+            claim = claim.filter(invitation::Column::Uses.lt(max_uses));
+        }
+        if claim.exec(&txn).await?.rows_affected == 0 {
+            return Err(used_up());
+        }
+        let account = insert_account(username, password, invitation.role_id, &txn).await?;
+        if libraries.is_empty() {
+            grant_default_library(account.id, &txn).await?;
+        }
+        for library_id in libraries {
+            LibraryGrant::insert(library_grant::ActiveModel {
+                library_id: Set(library_id),
+                account_id: Set(account.id),
+            })
+            .exec(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        account_added(&account, db).await?;
+        Ok((invitation, account))
+    }
+
+    /// delete invitations with their library grants
+    pub async fn delete_invitations(ids: Vec<i32>, db: &DatabaseConnection) -> Result<(), DbErr> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        InvitationLibrary::delete_many()
+            .filter(invitation_library::Column::InvitationId.is_in(ids.clone()))
+            .exec(db)
+            .await?;
+        Invitation::delete_many()
+            .filter(invitation::Column::Id.is_in(ids))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
+
+#[server]
+/// the invitations, newest first
+pub async fn list_invitations() -> Result<Vec<InvitationDetail>, ServerFnError> {
+    use self::ssr::invitation_libraries;
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::QueryOrder;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let invitations = Invitation::find()
+        .order_by_desc(invitation::Column::Id)
+        .all(&db)
+        .await?;
+    let mut details = Vec::with_capacity(invitations.len());
+    for i in invitations {
+        details.push(InvitationDetail {
+            id: i.id,
+            role: i.role_id,
+            libraries: invitation_libraries(i.id, &db).await?,
+            max_uses: i.max_uses,
+            uses: i.uses,
+            created: date_to_rfc3339(&i.created),
+            expires: date_to_rfc3339(&i.expires),
+        });
+    }
+    Ok(details)
+}
+
+#[server]
+/// create an invitation, returns the path of the invite link, it is shown once
+pub async fn create_invitation(
+    role: i32,
+    libraries: Vec<i32>,
+    max_uses: Option<i32>,
+    expires_in_days: u32,
+) -> Result<String, ServerFnError> {
+    use self::ssr::INVITE_PREFIX;
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use super::token::ssr::hash_token;
+    use crate::entities::*;
+    use rand::{distributions::Alphanumeric, Rng};
+    use sea_orm::Set;
+    let user = authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    Role::find_by_id(role)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Role does not exist."))?;
+    if max_uses.is_some_and(|max| max < 1) {
+        return Err(ServerFnError::new(
+            "An invitation must allow at least one use.",
+        ));
+    }
+    if !(1..=365).contains(&expires_in_days) {
+        return Err(ServerFnError::new(
+            "An invitation expires in 1 to 365 days.",
+        ));
+    }
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let token = format!("{}{}", INVITE_PREFIX, random);
+    let expires = chrono::Utc::now() + chrono::Duration::days(expires_in_days as i64);
+    let expires = date_from_rfc3339(&expires.to_rfc3339()).unwrap();
+    let invitation_id = Invitation::insert(invitation::ActiveModel {
+        token_hash: Set(hash_token(&token)),
+        created_by: Set(user.id),
+        role_id: Set(role),
+        max_uses: Set(max_uses),
+        uses: Set(0),
+        created: Set(now()),
+        expires: Set(expires),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?
+    .last_insert_id;
+    let libraries_summary = libraries.clone();
+    for library_id in libraries {
+        Library::find_by_id(library_id)
+            .one(&db)
+            .await?
+            .ok_or(ServerFnError::new("Library does not exist."))?;
+        InvitationLibrary::insert(invitation_library::ActiveModel {
+            invitation_id: Set(invitation_id),
+            library_id: Set(library_id),
+        })
+        .exec(&db)
+        .await?;
+    }
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::InvitationCreated,
+        &format!("invitation {}", invitation_id),
+        "",
+        &format!(
+            "role {}, libraries {:?}, uses {}, expires in {} days",
+            role,
+            libraries_summary,
+            max_uses.map_or("unlimited".to_string(), |m| m.to_string()),
+            expires_in_days
+        ),
+        &db,
+    )
+    .await?;
+    Ok(format!("/invite/{}", token))
+}
+
+#[server]
+pub async fn revoke_invitation(invitation_id: i32) -> Result<(), ServerFnError> {
+    use self::ssr::delete_invitations;
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    delete_invitations(vec![invitation_id], &db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::InvitationRevoked,
+        &format!("invitation {}", invitation_id),
+        "",
+        "",
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server(GetInvitationInfo)]
+/// the invitation of a link, `None` when it is unknown, expired or used up
+pub async fn invitation_info(token: String) -> Result<Option<InvitationInfo>, ServerFnError> {
+    use self::ssr::usable_invitation;
+    use super::ssr::*;
+    let db = db()?;
+    let Some(invitation) = usable_invitation(&token, &db).await? else {
+        return Ok(None);
+    };
+    let role_name = Role::find_by_id(invitation.role_id)
+        .one(&db)
+        .await?
+        .map(|r| r.name)
+        .unwrap_or_default();
+    Ok(Some(InvitationInfo {
+        role_name,
+        expires: date_to_rfc3339(&invitation.expires),
+    }))
+}
+
+#[server(AcceptInvitation, "/api")]
+/// create an account with an invitation and log it in
+pub async fn accept_invitation(
+    token: String,
+    username: String,
+    password: String,
+) -> Result<(), ServerFnError> {
+    use super::ssr::*;
+    let db = db()?;
+    let (invitation, account) =
+        self::ssr::join_with_invitation(&token, &username, password, &db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::UserAdded,
+        &format!("user {}", account.id),
+        "",
+        &format!(
+            "{}, role {}, with invitation {}",
+            account.name, account.role_level, invitation.id
+        ),
+        &db,
+    )
+    .await?;
+    let auth = auth()?;
+    auth.login_user(account.id);
+    leptos_axum::redirect("/");
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set};
+
+    use super::ssr::*;
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            library::ssr::DEFAULT_LIBRARY_ID,
+            ssr::{date_from_rfc3339, now},
+            test_db::{account, memory_db, table},
+            token::ssr::hash_token,
+        },
+    };
+
+    async fn setup() -> DatabaseConnection {
+        let db = memory_db(vec![
+            table(Role),
+            table(Account),
+            table(Library),
+            table(LibraryGrant),
+            table(Invitation),
+            table(InvitationLibrary),
+            table(Webhook),
+        ])
+        .await;
+        role::ActiveModel {
+            id: Set(1),
+            name: Set("user".to_string()),
+            require_totp: Set(false),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        for name in ["books", "kids"] {
+            library::ActiveModel {
+                name: Set(name.to_string()),
+                root: Set(format!("./{}", name)),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        account("admin", 1, &db).await;
+        db
+    }
+
+    /// an invitation of the admin, returns its token
+    async fn invite(
+        max_uses: Option<i32>,
+        days: i64,
+        libraries: &[i32],
+        db: &DatabaseConnection,
+    ) -> String {
+        let token = format!("{}{}", INVITE_PREFIX, max_uses.unwrap_or(0) + days as i32);
+        let expires = chrono::Utc::now() + chrono::Duration::days(days);
+        let invitation = invitation::ActiveModel {
+            token_hash: Set(hash_token(&token)),
+            created_by: Set(1),
+            role_id: Set(1),
+            max_uses: Set(max_uses),
+            uses: Set(0),
+            created: Set(now()),
+            expires: Set(date_from_rfc3339(&expires.to_rfc3339()).unwrap()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        for &library_id in libraries {
+            invitation_library::ActiveModel {
+                invitation_id: Set(invitation.id),
+                library_id: Set(library_id),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        token
+    }
+
+    async fn grants(account_id: i32, db: &DatabaseConnection) -> Vec<i32> {
+        LibraryGrant::find()
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|g| g.account_id == account_id)
+            .map(|g| g.library_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn an_expired_invitation_is_refused() {
+        let db = setup().await;
+        let token = invite(None, -1, &[], &db).await;
+        assert_eq!(usable_invitation(&token, &db).await.unwrap(), None);
+        assert!(join_with_invitation(&token, "bob", "pw".to_string(), &db)
+            .await
+            .is_err());
+        assert_eq!(usable_invitation("inv_unknown", &db).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn the_uses_are_limited() {
+        let db = setup().await;
+        let token = invite(Some(1), 1, &[2], &db).await;
+        let (invitation, bob) = join_with_invitation(&token, "bob", "pw".to_string(), &db)
+            .await
+            .unwrap();
+        assert_eq!(grants(bob.id, &db).await, vec![2]);
+        let invitation = Invitation::find_by_id(invitation.id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(invitation.uses, 1);
+        assert!(join_with_invitation(&token, "carol", "pw".to_string(), &db)
+            .await
+            .is_err());
+        assert_eq!(Account::find().all(&db).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_taken_name_does_not_use_the_invitation() {
+        let db = setup().await;
+        let token = invite(Some(1), 1, &[], &db).await;
+        assert!(
+            join_with_invitation(&token, " admin ", "pw".to_string(), &db)
+                .await
+                .is_err()
+        );
+        assert!(join_with_invitation(&token, "bob", "".to_string(), &db)
+            .await
+            .is_err());
+        // the use is still there, and an invitation without libraries grants the default one
+        let (_, bob) = join_with_invitation(&token, "bob", "pw".to_string(), &db)
+            .await
+            .unwrap();
+        assert_eq!(grants(bob.id, &db).await, vec![DEFAULT_LIBRARY_ID]);
+    }
+}
@@ -0,0 +1,374 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::permission::Permission;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryDetail {
+    pub id: i32,
+    pub name: String,
+    /// the folder of the books on the server, empty for users who can not manage the library
+    pub root: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use leptos::ServerFnError;
+    use sea_orm::{
+        ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+    };
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{permission::Permission, User},
+    };
+
+    /// the library the migration made of the old `./fetchbook`
+    pub const DEFAULT_LIBRARY_ID: i32 = 1;
+
+    /// let a new account see the default library, unless it was deleted
+    pub async fn grant_default_library<C: ConnectionTrait>(
+        account_id: i32,
+        db: &C,
+    ) -> Result<(), DbErr> {
+        if Library::find_by_id(DEFAULT_LIBRARY_ID)
+            .one(db)
+            .await?
+            .is_none()
+        {
+            return Ok(());
+        }
+        LibraryGrant::insert(library_grant::ActiveModel {
+            library_id: Set(DEFAULT_LIBRARY_ID),
+            account_id: Set(account_id),
+        })
+        .exec(db)
+        .await?;
+        Ok(())
+    }
+
+    /// the libraries a user can see, users managing the library see all of them
+    pub async fn visible_library_ids(
+        user: &User,
+        db: &DatabaseConnection,
+    ) -> Result<Vec<i32>, DbErr> {
+        if user.can(Permission::ManageLibrary) {
+            return Ok(Library::find()
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|l| l.id)
+                .collect());
+        }
+        Ok(LibraryGrant::find()
+            .filter(library_grant::Column::AccountId.eq(user.id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|g| g.library_id)
+            .collect())
+    }
+
+    pub async fn can_see_library(
+        user: &User,
+        library_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<bool, DbErr> {
+        Ok(visible_library_ids(user, db).await?.contains(&library_id))
+    }
+
+    /// the book when the user can see it, a hidden book is reported as not found
+    pub async fn find_visible_book(
+        user: &User,
+        music_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<music::Model, ServerFnError> {
+        let book = Music::find_by_id(music_id)
+            .one(db)
+            .await?
+            .ok_or(ServerFnError::new("Book not found"))?;
+        if !can_see_library(user, book.library_id, db).await? {
+            return Err(ServerFnError::new("Book not found"));
+        }
+        Ok(book)
+    }
+
+    /// the chapter and its book when the user can see them
+    pub async fn find_visible_chapter(
+        user: &User,
+        chapter_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<(chapter::Model, music::Model), ServerFnError> {
+        let chapter = Chapter::find_by_id(chapter_id)
+            .one(db)
+            .await?
+            .ok_or(ServerFnError::new("Chapter not found"))?;
+        let book = find_visible_book(user, chapter.music_id, db)
+            .await
+            .map_err(|_| ServerFnError::new("Chapter not found"))?;
+        Ok((chapter, book))
+    }
+}
+
+#[server]
+/// the libraries the current user can see
+pub async fn list_libraries() -> Result<Vec<LibraryDetail>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let ids = self::ssr::visible_library_ids(&user, &db).await?;
+    let manage = user.can(Permission::ManageLibrary);
+    let libraries = Library::find()
+        .filter(library::Column::Id.is_in(ids))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|l| LibraryDetail {
+            id: l.id,
+            name: l.name,
+            root: if manage { l.root } else { String::new() },
+        })
+        .collect();
+    Ok(libraries)
+}
+
+#[server]
+/// create a library when `library_id` is not set, otherwise rename it or move its root.
+///
+/// the root is created when it does not exist, the files are not moved
+pub async fn save_library(
+    library_id: Option<i32>,
+    name: String,
+    root: String,
+) -> Result<i32, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::Set;
+    authorize(Access::Permission(Permission::ManageLibrary)).await?;
+    let (name, root) = (name.trim().to_string(), root.trim().to_string());
+    if name.is_empty() || root.is_empty() {
+        return Err(ServerFnError::new("The name and the folder are required."));
+    }
+    std::fs::create_dir_all(&root)
+        .map_err(|e| ServerFnError::new(format!("Can not create {}: {}", root, e)))?;
+    let db = db()?;
+    let after = format!("{} at {}", name, root);
+    let model = library::ActiveModel {
+        name: Set(name),
+        root: Set(root),
+        ..Default::default()
+    };
+    let mut before = String::new();
+    let library_id = match library_id {
+        Some(library_id) => {
+            let library = Library::find_by_id(library_id)
+                .one(&db)
+                .await?
+                .ok_or(ServerFnError::new("Library does not exist."))?;
+            before = format!("{} at {}", library.name, library.root);
+            let mut model = model;
+            model.id = Set(library_id);
+            model.update(&db).await?;
+            library_id
+        }
+        None => Library::insert(model).exec(&db).await?.last_insert_id,
+    };
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::LibrarySaved,
+        &format!("library {}", library_id),
+        &before,
+        &after,
+        &db,
+    )
+    .await?;
+    Ok(library_id)
+}
+
+#[server]
+/// delete an empty library
+pub async fn delete_library(library_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::PaginatorTrait;
+    authorize(Access::Permission(Permission::ManageLibrary)).await?;
+    let db = db()?;
+    let books = Music::find()
+        .filter(music::Column::LibraryId.eq(library_id))
+        .count(&db)
+        .await?;
+    if books > 0 {
+        return Err(ServerFnError::new(format!(
+            "The library still has {} books.",
+            books
+        )));
+    }
+    let library = Library::find_by_id(library_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Library does not exist."))?;
+    LibraryGrant::delete_many()
+        .filter(library_grant::Column::LibraryId.eq(library_id))
+        .exec(&db)
+        .await?;
+    InvitationLibrary::delete_many()
+        .filter(invitation_library::Column::LibraryId.eq(library_id))
+        .exec(&db)
+        .await?;
+    Library::delete_by_id(library_id).exec(&db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::LibraryDeleted,
+        &format!("library {}", library_id),
+        &format!("{} at {}", library.name, library.root),
+        "",
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server]
+/// the accounts granted to see a library
+pub async fn get_library_grants(library_id: i32) -> Result<Vec<i32>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    authorize(Access::Permission(Permission::ManageLibrary)).await?;
+    let db = db()?;
+    let grants = LibraryGrant::find()
+        .filter(library_grant::Column::LibraryId.eq(library_id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|g| g.account_id)
+        .collect();
+    Ok(grants)
+}
+
+#[server]
+pub async fn set_library_grant(
+    library_id: i32,
+    account_id: i32,
+    granted: bool,
+) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::Set;
+    authorize(Access::Permission(Permission::ManageLibrary)).await?;
+    let db = db()?;
+    let current = LibraryGrant::find_by_id((library_id, account_id))
+        .one(&db)
+        .await?;
+    let grant = |granted: bool| {
+        let state = if granted { "granted" } else { "not granted" };
+        format!("user {} {}", account_id, state)
+    };
+    let before = grant(current.is_some());
+    match (current, granted) {
+        (None, true) => {
+            LibraryGrant::insert(library_grant::ActiveModel {
+                library_id: Set(library_id),
+                account_id: Set(account_id),
+            })
+            .exec(&db)
+            .await?;
+        }
+        (Some(grant), false) => {
+            grant.delete(&db).await?;
+        }
+        _ => return Ok(()),
+    }
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::LibraryGrantChanged,
+        &format!("library {}", library_id),
+        &before,
+        &grant(granted),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+
+    use super::{ssr::*, Permission};
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            test_db::{account, memory_db, table},
+            User,
+        },
+    };
+
+    async fn library(name: &str, db: &DatabaseConnection) -> i32 {
+        library::ActiveModel {
+            name: Set(name.to_string()),
+            root: Set(format!("./{}", name)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap()
+        .id
+    }
+
+    fn user(id: i32, permissions: Vec<Permission>) -> User {
+        User {
+            id,
+            username: format!("user {}", id),
+            role: 1,
+            permissions,
+        }
+    }
+
+    #[tokio::test]
+    async fn users_see_the_granted_libraries() {
+        let db = memory_db(vec![table(Account), table(Library), table(LibraryGrant)]).await;
+        let alice = account("alice", 1, &db).await;
+        let bob = account("bob", 1, &db).await;
+        let books = library("books", &db).await;
+        let kids = library("kids", &db).await;
+        grant_default_library(alice.id, &db).await.unwrap();
+        assert_eq!(books, DEFAULT_LIBRARY_ID);
+        library_grant::ActiveModel {
+            library_id: Set(kids),
+            account_id: Set(bob.id),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let alice = user(alice.id, vec![]);
+        let bob = user(bob.id, vec![]);
+        assert_eq!(visible_library_ids(&alice, &db).await.unwrap(), vec![books]);
+        assert_eq!(visible_library_ids(&bob, &db).await.unwrap(), vec![kids]);
+        assert!(can_see_library(&alice, books, &db).await.unwrap());
+        assert!(!can_see_library(&alice, kids, &db).await.unwrap());
+        assert!(!can_see_library(&bob, books, &db).await.unwrap());
+        assert!(!can_see_library(&alice, kids + 1, &db).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn library_managers_see_every_library() {
+        let db = memory_db(vec![table(Account), table(Library), table(LibraryGrant)]).await;
+        let admin = account("admin", 0, &db).await;
+        let books = library("books", &db).await;
+        let kids = library("kids", &db).await;
+        let admin = user(admin.id, vec![Permission::ManageLibrary]);
+        assert_eq!(
+            visible_library_ids(&admin, &db).await.unwrap(),
+            vec![books, kids]
+        );
+        assert!(can_see_library(&admin, kids, &db).await.unwrap());
+        assert!(!can_see_library(&admin, kids + 1, &db).await.unwrap());
+    }
+}
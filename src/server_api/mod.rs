@@ -1,12 +1,38 @@
 use serde::{Deserialize, Serialize};
 
+pub mod app_password;
+pub mod audit;
 pub mod auth;
+pub mod auth_event;
 pub mod book;
+pub mod bookmark;
+pub mod feed_token;
+pub mod importer;
+pub mod invitation;
+pub mod library;
+pub mod password_reset;
+pub mod permission;
+pub mod preference;
 pub mod progress;
+pub mod registration;
+pub mod role;
+pub mod session;
+pub mod token;
+pub mod totp;
+pub mod transfer;
+pub mod webhook;
 
 pub mod authors;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "ssr",
+    derive(utoipa::ToSchema),
+    aliases(
+        BookPage = PageItems<book::BookDetail>,
+        AuthorPage = PageItems<authors::AuthorDetail>
+    )
+)]
 pub struct PageItems<T> {
     pub page: u64,
     pub max_item: u64,
@@ -15,11 +41,37 @@ pub struct PageItems<T> {
     pub items: Vec<T>,
 }
 
+impl<T> PageItems<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> PageItems<U> {
+        PageItems {
+            page: self.page,
+            max_item: self.max_item,
+            number_of_items: self.number_of_items,
+            number_of_pages: self.number_of_pages,
+            items: self.items.into_iter().map(f).collect(),
+        }
+    }
+
+    /// like `map`, dropping the items `f` returns `None` for
+    pub fn filter_map<U>(self, f: impl FnMut(T) -> Option<U>) -> PageItems<U> {
+        PageItems {
+            page: self.page,
+            max_item: self.max_item,
+            number_of_items: self.number_of_items,
+            number_of_pages: self.number_of_pages,
+            items: self.items.into_iter().filter_map(f).collect(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub role: i32,
+    /// the permissions of the role
+    pub permissions: Vec<permission::Permission>,
 }
 
 #[cfg(feature = "ssr")]
@@ -54,7 +106,7 @@ pub mod ssr {
     pub use async_trait::async_trait;
     pub use bcrypt::{hash, verify, DEFAULT_COST};
 
-    use super::User;
+    use super::{permission::Permission, User};
     use leptos::*;
     use sea_orm::DatabaseConnection;
 
@@ -63,11 +115,75 @@ pub mod ssr {
             .ok_or_else(|| ServerFnError::ServerError("DatabaseConnection missing.".into()))
     }
 
+    /// one page of a query, the first page is 0
+    pub async fn fetch_page<E>(
+        query: sea_orm::Select<E>,
+        page_num: u64,
+        max_item: u64,
+        db: &DatabaseConnection,
+    ) -> Result<super::PageItems<E::Model>, sea_orm::DbErr>
+    where
+        E: EntityTrait,
+        E::Model: Sync,
+    {
+        use sea_orm::{ItemsAndPagesNumber, PaginatorTrait};
+        let page = query.paginate(db, max_item);
+        let ItemsAndPagesNumber {
+            number_of_items,
+            number_of_pages,
+        } = page.num_items_and_pages().await?;
+        Ok(super::PageItems {
+            page: page_num,
+            max_item,
+            number_of_items,
+            number_of_pages,
+            items: page.fetch_page(page_num).await?,
+        })
+    }
+
     pub fn auth() -> Result<AuthSession, ServerFnError> {
         use_context::<AuthSession>()
             .ok_or_else(|| ServerFnError::ServerError("Auth session missing.".into()))
     }
 
+    /// the current time in the representation used by the timestamp columns
+    pub fn now() -> crate::ProgressDateType {
+        let now = chrono::Utc::now();
+        #[cfg(feature = "sqlite")]
+        let now = now.to_rfc3339();
+        now
+    }
+
+    /// parse a timestamp stored in the database or written by an export
+    pub fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::parse_from_rfc3339(date)
+            .map(|date| date.with_timezone(&chrono::Utc))
+            .ok()
+            .or_else(|| {
+                // the format of the `CURRENT_TIMESTAMP` default
+                chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+                    .ok()
+                    .map(|date| date.and_utc())
+            })
+    }
+
+    pub fn date_to_rfc3339(date: &crate::ProgressDateType) -> String {
+        #[cfg(feature = "mysql")]
+        let date = date.to_rfc3339();
+        #[cfg(feature = "sqlite")]
+        let date = parse_date(date)
+            .map(|date| date.to_rfc3339())
+            .unwrap_or_else(|| date.clone());
+        date
+    }
+
+    pub fn date_from_rfc3339(date: &str) -> Option<crate::ProgressDateType> {
+        let date = parse_date(date)?;
+        #[cfg(feature = "sqlite")]
+        let date = date.to_rfc3339();
+        Some(date)
+    }
+
     pub fn init_logger_info() {
         tracing_subscriber::fmt::SubscriberBuilder::default()
             .with_env_filter(
@@ -81,14 +197,45 @@ pub mod ssr {
     pub struct SqlPermissionTokens {
         pub token: String,
     }
-    impl From<entities::account::Model> for User {
-        fn from(account: entities::account::Model) -> Self {
-            User {
-                id: account.id,
-                username: account.name,
-                role: account.role_level,
+
+    /// the permissions of each role
+    pub async fn role_permissions(
+        db: &DatabaseConnection,
+    ) -> Result<std::collections::HashMap<i32, Vec<Permission>>, sea_orm::DbErr> {
+        let mut roles = std::collections::HashMap::<i32, Vec<Permission>>::new();
+        for p in RolePermission::find().all(db).await? {
+            if let Some(permission) = Permission::parse(&p.permission) {
+                roles.entry(p.role_id).or_default().push(permission);
             }
         }
+        Ok(roles)
+    }
+
+    pub fn user_from_account(
+        account: entities::account::Model,
+        permissions: Vec<Permission>,
+    ) -> User {
+        User {
+            id: account.id,
+            username: account.name,
+            role: account.role_level,
+            permissions,
+        }
+    }
+
+    /// the user of an account with the permissions of its role
+    pub async fn load_user_permissions(
+        account: entities::account::Model,
+        db: &DatabaseConnection,
+    ) -> Result<User, sea_orm::DbErr> {
+        let permissions = RolePermission::find()
+            .filter(entities::role_permission::Column::RoleId.eq(account.role_level))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|p| Permission::parse(&p.permission))
+            .collect();
+        Ok(user_from_account(account, permissions))
     }
     #[async_trait]
     impl Authentication<User, i32, SqlxPool> for User {
@@ -101,7 +248,7 @@ pub mod ssr {
 
             use crate::entities::prelude::*;
             let user = Account::find_by_id(userid).one(&db).await?.unwrap();
-            Ok(user.into())
+            Ok(load_user_permissions(user, &db).await?)
         }
 
         fn is_authenticated(&self) -> bool {
@@ -120,11 +267,48 @@ pub mod ssr {
     #[async_trait]
     impl HasPermission<SqlxPool> for User {
         async fn has(&self, perm: &str, _pool: &Option<&SqlxPool>) -> bool {
-            match perm {
-                "admin" => self.role == 0,
-                "user" => self.role <= 1,
-                _ => false,
-            }
+            Permission::parse(perm).is_some_and(|perm| self.can(perm))
+        }
+    }
+}
+
+/// an in-memory database for the tests
+#[cfg(all(test, feature = "sqlite"))]
+pub mod test_db {
+    use sea_orm::{
+        sea_query::TableCreateStatement, ActiveModelTrait, ConnectOptions, ConnectionTrait,
+        Database, DatabaseConnection, DbBackend, EntityTrait, Schema, Set,
+    };
+
+    use crate::entities::*;
+
+    /// the table of an entity
+    pub fn table<E: EntityTrait>(entity: E) -> TableCreateStatement {
+        Schema::new(DbBackend::Sqlite).create_table_from_entity(entity)
+    }
+
+    /// a database with the tables, a table goes after the tables its foreign keys point to
+    pub async fn memory_db(tables: Vec<TableCreateStatement>) -> DatabaseConnection {
+        let mut options = ConnectOptions::new("sqlite::memory:");
+        options.max_connections(1);
+        let db = Database::connect(options).await.unwrap();
+        for table in tables {
+            db.execute(DbBackend::Sqlite.build(&table)).await.unwrap();
+        }
+        db
+    }
+
+    /// an approved account with a password that matches nothing
+    pub async fn account(name: &str, role_level: i32, db: &DatabaseConnection) -> account::Model {
+        account::ActiveModel {
+            name: Set(name.to_string()),
+            password: Set("!".to_string()),
+            role_level: Set(role_level),
+            approved: Set(true),
+            ..Default::default()
         }
+        .insert(db)
+        .await
+        .unwrap()
     }
 }
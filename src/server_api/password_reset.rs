@@ -0,0 +1,269 @@
+use leptos::*;
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use std::sync::OnceLock;
+
+    use hmac::{Hmac, Mac};
+    use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+    use sha2::Sha256;
+
+    use crate::entities::{prelude::*, *};
+
+    /// minutes a reset link works
+    pub const TOKEN_MINUTES: i64 = 30;
+    /// minutes between two reset mails of an account
+    pub const REQUEST_INTERVAL_MINUTES: i64 = 5;
+
+    /// `PASSWORD_RESET_SECRET`, or a random secret that is lost with a restart
+    fn secret() -> &'static [u8] {
+        static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+        SECRET.get_or_init(|| match std::env::var("PASSWORD_RESET_SECRET") {
+            Ok(secret) if !secret.trim().is_empty() => secret.trim().as_bytes().to_vec(),
+            _ => {
+                tracing::warn!("PASSWORD_RESET_SECRET is not set, reset links end with a restart");
+                rand::random::<[u8; 32]>().to_vec()
+            }
+        })
+    }
+
+    /// signs the current password hash too, so the link stops working once it is used
+    fn mac(account_id: i32, expires: i64, password_hash: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret()).expect("hmac accepts keys of any length");
+        mac.update(format!("{}.{}.{}", account_id, expires, password_hash).as_bytes());
+        mac
+    }
+
+    /// `{account id}.{expires}.{signature}`
+    fn sign_token(account_id: i32, password_hash: &str, expires: i64) -> String {
+        let signature: String = mac(account_id, expires, password_hash)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("{}.{}.{}", account_id, expires, signature)
+    }
+
+    pub fn reset_token(account: &account::Model) -> String {
+        let expires = chrono::Utc::now().timestamp() + TOKEN_MINUTES * 60;
+        sign_token(account.id, &account.password, expires)
+    }
+
+    fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+
+    /// the account id, the expiry and the signature of a well formed token
+    fn parse_token(token: &str) -> Option<(i32, i64, Vec<u8>)> {
+        let mut parts = token.trim().splitn(3, '.');
+        let account_id = parts.next()?.parse().ok()?;
+        let expires = parts.next()?.parse().ok()?;
+        let signature = hex_decode(parts.next()?)?;
+        Some((account_id, expires, signature))
+    }
+
+    /// whether a token is not expired and was signed with the current password hash of its
+    /// account
+    fn verify_token(
+        account_id: i32,
+        expires: i64,
+        signature: &[u8],
+        password_hash: &str,
+        now: i64,
+    ) -> bool {
+        expires > now
+            && mac(account_id, expires, password_hash)
+                .verify_slice(signature)
+                .is_ok()
+    }
+
+    /// the account of a token that is not expired or used
+    pub async fn account_of_token(
+        token: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<account::Model>, DbErr> {
+        let now = chrono::Utc::now().timestamp();
+        let Some((account_id, expires, signature)) = parse_token(token) else {
+            return Ok(None);
+        };
+        if expires <= now {
+            return Ok(None);
+        }
+        let Some(account) = Account::find_by_id(account_id).one(db).await? else {
+            return Ok(None);
+        };
+        let valid = verify_token(account.id, expires, &signature, &account.password, now);
+        Ok(valid.then_some(account))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const NOW: i64 = 1_700_000_000;
+
+        fn verify(token: &str, password_hash: &str, now: i64) -> bool {
+            let Some((account_id, expires, signature)) = parse_token(token) else {
+                return false;
+            };
+            verify_token(account_id, expires, &signature, password_hash, now)
+        }
+
+        #[test]
+        fn a_token_roundtrips() {
+            let token = sign_token(7, "$2b$12$hash", NOW + 60);
+            let (account_id, expires, _) = parse_token(&token).unwrap();
+            assert_eq!((account_id, expires), (7, NOW + 60));
+            assert!(verify(&token, "$2b$12$hash", NOW));
+            assert!(verify(&format!(" {}\n", token), "$2b$12$hash", NOW));
+        }
+
+        #[test]
+        fn an_expired_token_is_rejected() {
+            let token = sign_token(7, "$2b$12$hash", NOW + 60);
+            assert!(verify(&token, "$2b$12$hash", NOW + 59));
+            assert!(!verify(&token, "$2b$12$hash", NOW + 60));
+        }
+
+        #[test]
+        fn a_token_stops_working_when_the_password_changes() {
+            let token = sign_token(7, "$2b$12$hash", NOW + 60);
+            assert!(!verify(&token, "$2b$12$changed", NOW));
+        }
+
+        #[test]
+        fn a_changed_token_is_rejected() {
+            let token = sign_token(7, "$2b$12$hash", NOW + 60);
+            let (_, signature) = token.rsplit_once('.').unwrap();
+            // another account, a later expiry
+            assert!(!verify(
+                &format!("8.{}.{}", NOW + 60, signature),
+                "$2b$12$hash",
+                NOW
+            ));
+            assert!(!verify(
+                &format!("7.{}.{}", NOW + 600, signature),
+                "$2b$12$hash",
+                NOW
+            ));
+            for broken in ["", "7", "7.x.00", "7.1.0", "7.1.zz", "x.1.00"] {
+                assert_eq!(parse_token(broken), None, "{}", broken);
+            }
+        }
+    }
+}
+
+#[server]
+/// shows the forgot password link on the login page
+pub async fn password_reset_available() -> Result<bool, ServerFnError> {
+    Ok(crate::mail::MailConfig::from_env().is_some())
+}
+
+#[server]
+/// mail a reset link to the account with this name or email address. it answers the same when
+/// there is no such account, so the names can not be guessed
+pub async fn request_password_reset(login: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use super::auth_event::{ssr as event, AuthEventKind};
+    use super::ssr::*;
+    use crate::mail::MailConfig;
+    use sea_orm::Condition;
+    let config =
+        MailConfig::from_env().ok_or(ServerFnError::new("Password reset is not available."))?;
+    let db = db()?;
+    let login = login.trim();
+    let account = Account::find()
+        .filter(
+            Condition::any()
+                .add(entities::account::Column::Name.eq(login))
+                .add(entities::account::Column::Email.eq(login)),
+        )
+        .one(&db)
+        .await?;
+    let Some((account, email)) = account
+        .filter(|a| a.approved)
+        .and_then(|a| a.email.clone().map(|email| (a, email)))
+    else {
+        return Ok(());
+    };
+    let last = event::last_event(AuthEventKind::PasswordResetRequested, &account.name, &db).await?;
+    if last.is_some_and(|t| {
+        chrono::Utc::now() - t < chrono::Duration::minutes(REQUEST_INTERVAL_MINUTES)
+    }) {
+        return Ok(());
+    }
+    let ip = event::current_ip();
+    event::record(
+        AuthEventKind::PasswordResetRequested,
+        &account.name,
+        &ip,
+        "",
+        &db,
+    )
+    .await?;
+    let link = format!(
+        "{}/reset-password?token={}",
+        config.public_url,
+        reset_token(&account)
+    );
+    let minutes = TOKEN_MINUTES.to_string();
+    let mail = config
+        .render(
+            "password_reset",
+            &[
+                ("username", account.name.as_str()),
+                ("link", link.as_str()),
+                ("minutes", minutes.as_str()),
+            ],
+        )
+        .map_err(ServerFnError::new)?;
+    // sent in the background, so the answer takes as long as for an unknown account
+    config.spawn_send(email, mail);
+    Ok(())
+}
+
+#[server(ResetPassword, "/api")]
+/// set a new password with the token of a reset link, all the sessions are logged out
+pub async fn reset_password(token: String, new_password: String) -> Result<(), ServerFnError> {
+    use self::ssr::account_of_token;
+    use super::auth_event::{ssr as event, AuthEventKind};
+    use super::session::ssr::revoke_sessions;
+    use super::ssr::*;
+    use crate::mail::MailConfig;
+    use sea_orm::Set;
+    if new_password.is_empty() {
+        return Err(ServerFnError::new("Enter a new password."));
+    }
+    let db = db()?;
+    let account = account_of_token(&token, &db)
+        .await?
+        .ok_or(ServerFnError::new(
+            "The reset link has expired or was used already.",
+        ))?;
+    entities::account::ActiveModel {
+        id: Set(account.id),
+        password: Set(hash(new_password, DEFAULT_COST)?),
+        ..Default::default()
+    }
+    .update(&db)
+    .await?;
+    revoke_sessions(account.id, None, &db).await?;
+    let ip = event::current_ip();
+    event::record(AuthEventKind::PasswordReset, &account.name, &ip, "", &db).await?;
+    if let (Some(config), Some(email)) = (MailConfig::from_env(), account.email) {
+        let vars = [("username", account.name.as_str()), ("ip", ip.as_str())];
+        match config.render("password_changed", &vars) {
+            Ok(mail) => config.spawn_send(email, mail),
+            Err(e) => tracing::error!("{}", e),
+        }
+    }
+    Ok(())
+}
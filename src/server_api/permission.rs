@@ -0,0 +1,325 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::User;
+
+/// what a role allows its users to do, stored by [`Permission::as_str`] in `role_permission`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum Permission {
+    /// change the settings of the library
+    ManageLibrary,
+    /// add, delete and change the users and roles, and access their data
+    ManageUsers,
+    /// add books
+    Upload,
+    /// fetch the audio files
+    Download,
+    /// delete books
+    Delete,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::ManageLibrary,
+        Permission::ManageUsers,
+        Permission::Upload,
+        Permission::Download,
+        Permission::Delete,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ManageLibrary => "manage_library",
+            Permission::ManageUsers => "manage_users",
+            Permission::Upload => "upload",
+            Permission::Download => "download",
+            Permission::Delete => "delete",
+        }
+    }
+
+    pub fn parse(permission: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == permission)
+    }
+}
+
+impl User {
+    pub fn can(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+/// what a server function requires from the current user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// any logged in user
+    Login,
+    Permission(Permission),
+    /// the data of the given account, other accounts need the permission
+    SelfOr(i32, Permission),
+}
+
+/// check the access of a user, `None` is not logged in
+pub fn check(user: Option<&User>, access: Access) -> Result<(), ServerFnError> {
+    let user = user.ok_or(ServerFnError::new("Not logged in"))?;
+    let permission = match access {
+        Access::Login => return Ok(()),
+        Access::SelfOr(account_id, _) if account_id == user.id => return Ok(()),
+        Access::Permission(permission) | Access::SelfOr(_, permission) => permission,
+    };
+    if user.can(permission) {
+        Ok(())
+    } else {
+        Err(ServerFnError::ServerError(format!(
+            "You do not have the permission: {}",
+            permission.as_str()
+        )))
+    }
+}
+
+/// get the current user and check its access
+#[cfg(feature = "ssr")]
+pub async fn authorize(access: Access) -> Result<User, ServerFnError> {
+    let user = super::auth::get_user().await?;
+    check(user.as_ref(), access)?;
+    Ok(user.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: i32, permissions: &[Permission]) -> User {
+        User {
+            id,
+            username: format!("user{}", id),
+            role: 0,
+            permissions: permissions.to_vec(),
+        }
+    }
+
+    #[test]
+    fn permission_names() {
+        for p in Permission::ALL {
+            assert_eq!(Permission::parse(p.as_str()), Some(p));
+        }
+        assert_eq!(Permission::parse("admin"), None);
+    }
+
+    #[test]
+    fn permission_matrix() {
+        let admin = user(1, &Permission::ALL);
+        let listener = user(2, &[Permission::Download]);
+        let uploader = user(3, &[Permission::Upload, Permission::Download]);
+        let nobody = user(4, &[]);
+
+        for p in Permission::ALL {
+            assert!(check(Some(&admin), Access::Permission(p)).is_ok());
+            assert!(check(Some(&nobody), Access::Permission(p)).is_err());
+            assert!(check(None, Access::Permission(p)).is_err());
+            assert_eq!(
+                check(Some(&listener), Access::Permission(p)).is_ok(),
+                p == Permission::Download
+            );
+            assert_eq!(
+                check(Some(&uploader), Access::Permission(p)).is_ok(),
+                p == Permission::Upload || p == Permission::Download
+            );
+        }
+    }
+
+    #[test]
+    fn login_only() {
+        assert!(check(None, Access::Login).is_err());
+        assert!(check(Some(&user(4, &[])), Access::Login).is_ok());
+    }
+
+    #[test]
+    fn self_or_permission() {
+        let admin = user(1, &Permission::ALL);
+        let listener = user(2, &[Permission::Download]);
+        let own = Access::SelfOr(2, Permission::ManageUsers);
+        let other = Access::SelfOr(1, Permission::ManageUsers);
+
+        assert!(check(Some(&listener), own).is_ok());
+        assert!(check(Some(&listener), other).is_err());
+        assert!(check(Some(&admin), own).is_ok());
+        assert!(check(Some(&admin), other).is_ok());
+        assert!(check(None, own).is_err());
+    }
+
+    /// the checks of every server function, a new one has to be added here
+    const SERVER_FN_ACCESS: &[(&str, &str)] = &[
+        ("list_app_passwords", "login"),
+        ("create_app_password", "login"),
+        ("revoke_app_password", "login"),
+        ("list_audit_events", "manage_users"),
+        ("get_audit_retention", "manage_users"),
+        ("set_audit_retention", "manage_users"),
+        ("get_user_by_id", "self or manage_users"),
+        ("get_user", "public"),
+        ("get_all_users", "manage_users"),
+        ("is_admin", "public"),
+        ("have_user", "public"),
+        ("login", "public"),
+        ("sso_provider", "public"),
+        ("signup", "public"),
+        ("add_user", "manage_users"),
+        ("change_user_passwd", "self or manage_users"),
+        ("get_user_email", "self or manage_users"),
+        ("set_user_email", "self or manage_users"),
+        ("delete_user", "manage_users"),
+        ("logout", "public"),
+        ("list_auth_events", "manage_users"),
+        ("get_author_by_id", "login"),
+        ("list_all_authors", "login"),
+        ("get_books_by_author", "login"),
+        ("get_book_all_detail", "login"),
+        ("get_book_detail", "login"),
+        ("get_books_details", "login"),
+        ("get_books", "login"),
+        ("add_book", "upload"),
+        ("get_chapters", "login"),
+        ("search_chapter_by_chapter_num", "login"),
+        ("get_chapter_details", "login"),
+        ("get_chatper_detail", "login"),
+        ("delete_book", "delete"),
+        ("get_bookmarks_by_book", "login"),
+        ("add_bookmark", "login"),
+        ("update_bookmark", "login"),
+        ("delete_bookmark", "login"),
+        ("export_bookmarks_markdown", "login"),
+        ("has_feed_token", "login"),
+        ("reset_feed_token", "login"),
+        ("revoke_feed_token", "login"),
+        ("preview_progress_import", "manage_users"),
+        ("apply_progress_import", "manage_users"),
+        ("list_invitations", "manage_users"),
+        ("create_invitation", "manage_users"),
+        ("revoke_invitation", "manage_users"),
+        ("invitation_info", "public"),
+        ("accept_invitation", "public"),
+        ("list_libraries", "login"),
+        ("save_library", "manage_library"),
+        ("delete_library", "manage_library"),
+        ("get_library_grants", "manage_library"),
+        ("set_library_grant", "manage_library"),
+        ("password_reset_available", "public"),
+        ("request_password_reset", "public"),
+        ("reset_password", "public"),
+        ("get_preference", "login"),
+        ("set_preference", "login"),
+        ("get_book_preference", "login"),
+        ("set_book_preference", "login"),
+        ("get_playback_settings", "login"),
+        ("get_progress_detail_by_user", "self or manage_users"),
+        ("get_progress_by_user", "self or manage_users"),
+        ("get_progress", "self or manage_users"),
+        ("set_progress", "login"),
+        ("set_progress_many", "login"),
+        ("get_device_progresses", "login"),
+        ("get_progress_conflicts", "login"),
+        ("registration_open", "public"),
+        ("get_registration_settings", "manage_users"),
+        ("save_registration_settings", "manage_users"),
+        ("register", "public"),
+        ("list_pending_accounts", "manage_users"),
+        ("approve_account", "manage_users"),
+        ("list_roles", "login"),
+        ("save_role", "manage_users"),
+        ("delete_role", "manage_users"),
+        ("set_user_role", "manage_users"),
+        ("list_sessions", "login"),
+        ("revoke_session", "login"),
+        ("revoke_other_sessions", "login"),
+        ("force_logout", "manage_users"),
+        ("list_api_tokens", "login"),
+        ("create_api_token", "login"),
+        ("revoke_api_token", "login"),
+        ("get_totp_status", "login"),
+        ("begin_totp_enrolment", "login"),
+        ("confirm_totp_enrolment", "login"),
+        ("disable_totp", "login"),
+        ("regenerate_recovery_codes", "login"),
+        ("verify_login_totp", "public"),
+        ("export_user_data", "self or manage_users"),
+        ("import_user_data", "self or manage_users"),
+        ("list_webhooks", "manage_users"),
+        ("create_webhook", "manage_users"),
+        ("set_webhook_enabled", "manage_users"),
+        ("delete_webhook", "manage_users"),
+        ("test_webhook", "manage_users"),
+        ("list_webhook_deliveries", "manage_users"),
+        ("retry_webhook_delivery", "manage_users"),
+    ];
+
+    /// the server functions of the source files in `server_api`, with their body
+    fn server_fns() -> Vec<(String, String)> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/server_api");
+        let mut fns = vec![];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            for item in source.split("\n#[server").skip(1) {
+                let start = item.find("pub async fn ").unwrap() + "pub async fn ".len();
+                let item = &item[start..];
+                let name = &item[..item.find('(').unwrap()];
+                let body = &item[..item.find("\n}\n").unwrap_or(item.len())];
+                fns.push((name.to_string(), body.to_string()));
+            }
+        }
+        fns
+    }
+
+    /// what the body of a server function checks, `None` when it checks nothing itself
+    fn checked_access(body: &str) -> Option<String> {
+        for p in Permission::ALL {
+            let permission = format!("Permission::{:?}", p);
+            if body.contains(&format!("Access::Permission({})", permission)) {
+                return Some(p.as_str().to_string());
+            }
+            if body.contains("Access::SelfOr(") && body.contains(&permission) {
+                return Some(format!("self or {}", p.as_str()));
+            }
+        }
+        ["Access::Login", "\"Not logged in\"", "enrolling_account("]
+            .iter()
+            .any(|check| body.contains(check))
+            .then(|| "login".to_string())
+    }
+
+    fn calls(body: &str, name: &str) -> bool {
+        body.match_indices(&format!("{}(", name))
+            .any(|(i, _)| !body[..i].ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+    }
+
+    #[test]
+    fn every_server_fn_checks_its_access() {
+        let fns = server_fns();
+        let direct: Vec<(&str, Option<String>)> = fns
+            .iter()
+            .map(|(name, body)| (name.as_str(), checked_access(body)))
+            .collect();
+        let mut access: Vec<(&str, String)> = vec![];
+        for ((name, body), (_, checked)) in fns.iter().zip(&direct) {
+            // a server function that only calls others has their checks
+            let delegated = || {
+                direct
+                    .iter()
+                    .filter(|(other, _)| other != name && calls(body, other))
+                    .find_map(|(_, checked)| checked.clone())
+            };
+            let checked = checked
+                .clone()
+                .or_else(delegated)
+                .unwrap_or("public".to_string());
+            access.push((name.as_str(), checked));
+        }
+        access.sort();
+        let mut expected: Vec<(&str, String)> = SERVER_FN_ACCESS
+            .iter()
+            .map(|(name, access)| (*name, access.to_string()))
+            .collect();
+        expected.sort();
+        assert_eq!(access, expected);
+    }
+}
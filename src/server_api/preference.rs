@@ -0,0 +1,195 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+pub const MIN_PLAYBACK_SPEED: f64 = 0.5;
+pub const MAX_PLAYBACK_SPEED: f64 = 3.0;
+
+/// the global defaults of a user
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Preference {
+    pub playback_speed: f64,
+    /// how many seconds to rewind when resuming from the saved progress
+    pub skip_back_secs: i32,
+    /// start a sleep timer of this many minutes when the player starts
+    pub sleep_timer_mins: Option<i32>,
+}
+
+impl Default for Preference {
+    fn default() -> Self {
+        Self {
+            playback_speed: 1.0,
+            skip_back_secs: 0,
+            sleep_timer_mins: None,
+        }
+    }
+}
+
+/// the per book overrides of a user, `None` means use the global default
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BookPreference {
+    pub music_id: i32,
+    pub playback_speed: Option<f64>,
+}
+
+/// the settings the player should use for a book, the book overrides merged into the defaults
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlaybackSettings {
+    pub playback_speed: f64,
+    pub skip_back_secs: i32,
+    pub sleep_timer_mins: Option<i32>,
+    /// whether the speed comes from the book overrides
+    pub book_override: bool,
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::entities::preference::Model> for Preference {
+    fn from(p: crate::entities::preference::Model) -> Self {
+        Self {
+            playback_speed: p.playback_speed,
+            skip_back_secs: p.skip_back_secs,
+            sleep_timer_mins: p.sleep_timer_mins,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<crate::entities::book_preference::Model> for BookPreference {
+    fn from(p: crate::entities::book_preference::Model) -> Self {
+        Self {
+            music_id: p.music_id,
+            playback_speed: p.playback_speed,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn check_playback_speed(speed: f64) -> Result<(), ServerFnError> {
+    if !(MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED).contains(&speed) {
+        return Err(ServerFnError::new(format!(
+            "Playback speed must be between {} and {}",
+            MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED
+        )));
+    }
+    Ok(())
+}
+
+#[server]
+/// get the global defaults of the current user
+pub async fn get_preference() -> Result<Preference, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    let db = db()?;
+    let p = entities::preference::Entity::find_by_id(user.id)
+        .one(&db)
+        .await?
+        .map(Into::into)
+        .unwrap_or_default();
+    Ok(p)
+}
+
+#[server]
+pub async fn set_preference(
+    playback_speed: f64,
+    skip_back_secs: i32,
+    sleep_timer_mins: Option<i32>,
+) -> Result<(), ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+    check_playback_speed(playback_speed)?;
+    if skip_back_secs < 0 {
+        return Err(ServerFnError::new("Skip back must not be negative"));
+    }
+    let sleep_timer_mins = sleep_timer_mins.filter(|mins| *mins > 0);
+
+    use super::ssr::*;
+    use crate::entities::*;
+    let db = db()?;
+    let model = preference::ActiveModel {
+        account_id: sea_orm::ActiveValue::set(user.id),
+        playback_speed: sea_orm::ActiveValue::set(playback_speed),
+        skip_back_secs: sea_orm::ActiveValue::set(skip_back_secs),
+        sleep_timer_mins: sea_orm::ActiveValue::set(sleep_timer_mins),
+    };
+    if preference::Entity::find_by_id(user.id)
+        .one(&db)
+        .await?
+        .is_some()
+    {
+        model.update(&db).await?;
+    } else {
+        preference::Entity::insert(model).exec(&db).await?;
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn get_book_preference(music_id: i32) -> Result<Option<BookPreference>, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    use crate::entities::*;
+    let db = db()?;
+    let p = book_preference::Entity::find_by_id((user.id, music_id))
+        .one(&db)
+        .await?
+        .map(Into::into);
+    Ok(p)
+}
+
+#[server]
+/// set the speed of a book, the override is removed when it is `None`
+pub async fn set_book_preference(
+    music_id: i32,
+    playback_speed: Option<f64>,
+) -> Result<(), ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+    if let Some(speed) = playback_speed {
+        check_playback_speed(speed)?;
+    }
+
+    use super::ssr::*;
+    use crate::entities::*;
+    let db = db()?;
+    let current = book_preference::Entity::find_by_id((user.id, music_id))
+        .one(&db)
+        .await?;
+    if playback_speed.is_none() {
+        if let Some(current) = current {
+            current.delete(&db).await?;
+        }
+        return Ok(());
+    }
+    let model = book_preference::ActiveModel {
+        account_id: sea_orm::ActiveValue::set(user.id),
+        music_id: sea_orm::ActiveValue::set(music_id),
+        playback_speed: sea_orm::ActiveValue::set(playback_speed),
+    };
+    if current.is_some() {
+        model.update(&db).await?;
+    } else {
+        book_preference::Entity::insert(model).exec(&db).await?;
+    }
+    Ok(())
+}
+
+#[server]
+/// get the settings the player should use for a book
+pub async fn get_playback_settings(music_id: i32) -> Result<PlaybackSettings, ServerFnError> {
+    let preference = get_preference().await?;
+    let book = get_book_preference(music_id).await?;
+    let speed = book.and_then(|b| b.playback_speed);
+    Ok(PlaybackSettings {
+        playback_speed: speed.unwrap_or(preference.playback_speed),
+        skip_back_secs: preference.skip_back_secs,
+        sleep_timer_mins: preference.sleep_timer_mins,
+        book_override: speed.is_some(),
+    })
+}
@@ -5,13 +5,20 @@ use crate::ProgressDateType;
 
 use super::book::{BookDetail, ChapterDetail};
 
+/// a book is finished when the position is this close to its end
+pub const FINISHED_MARGIN_SECS: f64 = 5.0;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct ProgressResult {
     pub account_id: i32,
     pub music_id: i32,
     pub chapter_id: i32,
     pub progress: f64,
+    pub device_id: String,
+    pub client_update: Option<i64>,
 
+    #[cfg_attr(feature = "ssr", schema(value_type = String, format = DateTime))]
     pub update: ProgressDateType,
 }
 
@@ -23,52 +30,86 @@ impl From<crate::entities::progress::Model> for ProgressResult {
             music_id: p.music_id,
             chapter_id: p.chapter_id,
             progress: p.progress,
+            device_id: p.device_id,
+            client_update: p.client_update,
             update: p.update,
         }
     }
 }
 
-#[server]
-pub async fn get_progress_detail_by_user(
+/// the progress of an account in the books `user` can see, by book
+#[cfg(feature = "ssr")]
+pub async fn visible_progress(
+    user: &super::User,
     account_id: i32,
-) -> Result<Vec<(ProgressResult, BookDetail, ChapterDetail)>, ServerFnError> {
-    crate::server_api::auth::get_user()
-        .await?
-        .ok_or(ServerFnError::new("Not logged in"))?;
+    db: &sea_orm::DatabaseConnection,
+) -> Result<sea_orm::Select<crate::entities::progress::Entity>, sea_orm::DbErr> {
+    use crate::entities::{prelude::*, *};
+    use sea_orm::{prelude::*, QueryOrder};
+    let libraries = super::library::ssr::visible_library_ids(user, db).await?;
+    Ok(Progress::find()
+        .filter(progress::Column::AccountId.eq(account_id))
+        .filter(
+            progress::Column::MusicId.in_subquery(
+                sea_orm::sea_query::Query::select()
+                    .column(music::Column::Id)
+                    .from(music::Entity)
+                    .and_where(music::Column::LibraryId.is_in(libraries))
+                    .to_owned(),
+            ),
+        )
+        .order_by_asc(progress::Column::MusicId))
+}
 
-    use super::ssr::*;
-    use crate::entities::*;
+/// the progress with its book and chapter
+#[cfg(feature = "ssr")]
+pub async fn with_details(
+    p: Vec<crate::entities::progress::Model>,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<Vec<(ProgressResult, BookDetail, ChapterDetail)>, sea_orm::DbErr> {
+    use crate::entities::prelude::*;
     use sea_orm::prelude::*;
-    let db = db()?;
-
-    let p = Progress::find()
-        .filter(progress::Column::AccountId.eq(account_id))
-        .all(&db)
-        .await?;
-    let book = p
-        .load_one(Music, &db)
-        .await?
-        .into_iter()
-        .map(Option::unwrap);
-    let chapter = p
-        .load_one(Chapter, &db)
-        .await?
-        .into_iter()
-        .map(Option::unwrap);
+    let book = p.load_one(Music, db).await?;
+    let chapter = p.load_one(Chapter, db).await?;
     let p = p
         .into_iter()
         .zip(book)
         .zip(chapter)
-        .map(|((p, b), c)| (p.into(), b.into(), c.into()))
+        .filter_map(|((p, b), c)| Some((p.into(), b?.into(), c?.into())))
         .collect::<Vec<_>>();
     Ok(p)
 }
 
+/// the progress of an account in the books `user` can see, with the book and chapter
+#[cfg(feature = "ssr")]
+pub async fn progress_details(
+    user: &super::User,
+    account_id: i32,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<Vec<(ProgressResult, BookDetail, ChapterDetail)>, sea_orm::DbErr> {
+    let p = visible_progress(user, account_id, db)
+        .await?
+        .all(db)
+        .await?;
+    with_details(p, db).await
+}
+
+#[server]
+pub async fn get_progress_detail_by_user(
+    account_id: i32,
+) -> Result<Vec<(ProgressResult, BookDetail, ChapterDetail)>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    let user = authorize(Access::SelfOr(account_id, Permission::ManageUsers)).await?;
+
+    use super::ssr::*;
+    let db = db()?;
+    Ok(progress_details(&user, account_id, &db).await?)
+}
+
 #[server]
 pub async fn get_progress_by_user(account_id: i32) -> Result<Vec<ProgressResult>, ServerFnError> {
-    crate::server_api::auth::get_user()
-        .await?
-        .ok_or(ServerFnError::new("Not logged in"))?;
+    use super::permission::{authorize, Access, Permission};
+    authorize(Access::SelfOr(account_id, Permission::ManageUsers)).await?;
 
     use super::ssr::*;
     use crate::entities::*;
@@ -88,9 +129,8 @@ pub async fn get_progress(
     music_id: i32,
     account_id: i32,
 ) -> Result<Option<ProgressResult>, ServerFnError> {
-    crate::server_api::auth::get_user()
-        .await?
-        .ok_or(ServerFnError::new("Not logged in"))?;
+    use super::permission::{authorize, Access, Permission};
+    authorize(Access::SelfOr(account_id, Permission::ManageUsers)).await?;
 
     use super::ssr::*;
     let db = db()?;
@@ -104,19 +144,171 @@ pub async fn get_progress(
     Ok(p)
 }
 
+/// two device positions further apart than this are a conflict
+pub const CONFLICT_THRESHOLD_SECS: f64 = 60.0;
+
+/// the latest position reported by one device
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceProgressResult {
+    pub device_id: String,
+    pub device_name: String,
+    pub music_id: i32,
+    pub chapter_id: i32,
+    pub chapter_num: i32,
+    pub chapter_name: String,
+    pub progress: f64,
+    /// the client time of the update, in milliseconds since the unix epoch
+    pub client_update: i64,
+}
+
+impl DeviceProgressResult {
+    /// whether two positions are too far apart to silently pick one
+    pub fn conflicts_with(&self, other: &DeviceProgressResult) -> bool {
+        self.chapter_id != other.chapter_id
+            || (self.progress - other.progress).abs() > CONFLICT_THRESHOLD_SECS
+    }
+}
+
+/// one position update sent by a device
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProgressUpdate {
+    pub music_id: i32,
+    pub chapter_id: i32,
+    pub progress: f64,
+    pub device_id: String,
+    pub device_name: String,
+    /// the client time of the update, in milliseconds since the unix epoch
+    pub client_update: i64,
+}
+
+/// save the position of a device.
+///
+/// the position of every device is kept, the progress of the book is only replaced when the update
+/// is newer than the last accepted one, so a delayed request can not clobber a newer position.
+#[cfg(feature = "ssr")]
+pub async fn save_progress(
+    db: &sea_orm::DatabaseConnection,
+    account_id: i32,
+    update: ProgressUpdate,
+) -> Result<(), ServerFnError> {
+    use super::ssr::{now, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter};
+    use crate::entities::{prelude::*, *};
+    let ProgressUpdate {
+        music_id,
+        chapter_id,
+        progress,
+        device_id,
+        device_name,
+        client_update,
+    } = update;
+
+    // record the position of the device
+    let device = device_progress::ActiveModel {
+        account_id: sea_orm::ActiveValue::set(account_id),
+        music_id: sea_orm::ActiveValue::set(music_id),
+        device_id: sea_orm::ActiveValue::set(device_id.clone()),
+        device_name: sea_orm::ActiveValue::set(device_name),
+        chapter_id: sea_orm::ActiveValue::set(chapter_id),
+        progress: sea_orm::ActiveValue::set(progress),
+        client_update: sea_orm::ActiveValue::set(client_update),
+        update: sea_orm::ActiveValue::set(now()),
+    };
+    let current_device = DeviceProgress::find_by_id((account_id, music_id, device_id.clone()))
+        .one(db)
+        .await?;
+    match current_device {
+        Some(current_device) if current_device.client_update > client_update => {}
+        Some(_) => {
+            device.update(db).await?;
+        }
+        None => {
+            DeviceProgress::insert(device).exec(db).await?;
+        }
+    }
+
+    // a position near the end of the last chapter finishes the book
+    let last_chapter = {
+        use sea_orm::QueryOrder;
+        Chapter::find()
+            .filter(chapter::Column::MusicId.eq(music_id))
+            .order_by_desc(chapter::Column::ChapterNum)
+            .one(db)
+            .await?
+    };
+    let at_end = |chapter_id: i32, progress: f64| {
+        last_chapter.as_ref().is_some_and(|last| {
+            last.id == chapter_id
+                && last
+                    .chapter_length
+                    .is_some_and(|l| progress >= l - FINISHED_MARGIN_SECS)
+        })
+    };
+
+    let p = Progress::find_by_id((account_id, music_id)).one(db).await?;
+    let was_finished = p.as_ref().is_some_and(|p| at_end(p.chapter_id, p.progress));
+    if let Some(p) = p {
+        use sea_orm::IntoActiveModel;
+
+        if p.client_update.is_some_and(|last| last > client_update) {
+            tracing::info!(
+                "ignore stale progress from device {}: {} < {:?}",
+                device_id,
+                client_update,
+                p.client_update
+            );
+            return Ok(());
+        }
+        let mut p = p.into_active_model();
+        p.chapter_id = sea_orm::ActiveValue::set(chapter_id);
+        p.progress = sea_orm::ActiveValue::set(progress);
+        p.device_id = sea_orm::ActiveValue::set(device_id);
+        p.client_update = sea_orm::ActiveValue::set(Some(client_update));
+        p.update = sea_orm::ActiveValue::set(now());
+        p.save(db).await?;
+    } else {
+        Progress::insert(progress::ActiveModel {
+            account_id: sea_orm::ActiveValue::set(account_id),
+            music_id: sea_orm::ActiveValue::set(music_id),
+            chapter_id: sea_orm::ActiveValue::set(chapter_id),
+            progress: sea_orm::ActiveValue::set(progress),
+            device_id: sea_orm::ActiveValue::set(device_id),
+            client_update: sea_orm::ActiveValue::set(Some(client_update)),
+            update: sea_orm::ActiveValue::set(now()),
+        })
+        .exec(db)
+        .await?;
+    }
+
+    if !was_finished && at_end(chapter_id, progress) {
+        use super::webhook::{ssr as webhook, WebhookEvent};
+        let account = Account::find_by_id(account_id).one(db).await?;
+        let book = Music::find_by_id(music_id).one(db).await?;
+        if let (Some(account), Some(book)) = (account, book) {
+            let data = serde_json::json!({
+                "user": webhook::user_data(&account),
+                "book": webhook::book_data(&book, db).await?,
+            });
+            webhook::trigger(WebhookEvent::BookFinished, data, db).await?;
+        }
+    }
+    Ok(())
+}
+
 #[server]
 pub async fn set_progress(
     account_id: i32,
     music_id: i32,
     chapter_id: i32,
     progress: f64,
+    device_id: String,
+    device_name: String,
+    client_update: i64,
 ) -> Result<(), ServerFnError> {
     crate::server_api::auth::get_user()
         .await?
         .ok_or(ServerFnError::new("Not logged in"))?;
 
     use super::ssr::*;
-    use crate::entities::*;
     let db = db()?;
     let auth = auth()?;
     let current_user = auth.current_user;
@@ -124,34 +316,277 @@ pub async fn set_progress(
     if current_user.id != account_id {
         return Err(ServerFnError::new("Not authorized"));
     }
-    let p = Progress::find_by_id((account_id, music_id))
-        .one(&db)
-        .await?;
-    if let Some(p) = p {
-        use sea_orm::IntoActiveModel;
+    let update = ProgressUpdate {
+        music_id,
+        chapter_id,
+        progress,
+        device_id,
+        device_name,
+        client_update,
+    };
+    check_visible_update(&current_user, &update, &db).await?;
+    save_progress(&db, account_id, update).await
+}
 
-        let mut p = p.into_active_model();
-        p.chapter_id = sea_orm::ActiveValue::set(chapter_id);
-        p.progress = sea_orm::ActiveValue::set(progress);
-        let now = chrono::Utc::now();
-        #[cfg(feature = "sqlite")]
-        let now = now.to_rfc3339();
-        p.update = sea_orm::ActiveValue::set(now);
-        p.save(&db).await?;
-    } else {
-        let now = chrono::Utc::now();
-        #[cfg(feature = "sqlite")]
-        let now = now.to_rfc3339();
-        Progress::insert(progress::ActiveModel {
-            account_id: sea_orm::ActiveValue::set(account_id),
-            music_id: sea_orm::ActiveValue::set(music_id),
-            chapter_id: sea_orm::ActiveValue::set(chapter_id),
-            progress: sea_orm::ActiveValue::set(progress),
-            update: sea_orm::ActiveValue::set(now),
-        })
-        .exec(&db)
+/// check that the chapter of an update is in its book and the user can see the book
+#[cfg(feature = "ssr")]
+pub async fn check_visible_update(
+    user: &super::User,
+    update: &ProgressUpdate,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<(), ServerFnError> {
+    let (chapter, _) =
+        super::library::ssr::find_visible_chapter(user, update.chapter_id, db).await?;
+    if chapter.music_id != update.music_id {
+        return Err(ServerFnError::new("Chapter not found"));
+    }
+    Ok(())
+}
+
+/// only keep the newest update of each book, in the order of the books first appearance
+pub fn newest_per_book(updates: Vec<ProgressUpdate>) -> Vec<ProgressUpdate> {
+    let mut newest: Vec<ProgressUpdate> = vec![];
+    for update in updates {
+        match newest.iter_mut().find(|u| u.music_id == update.music_id) {
+            Some(current) if current.client_update <= update.client_update => *current = update,
+            Some(_) => {}
+            None => newest.push(update),
+        }
+    }
+    newest
+}
+
+/// save a batch of updates of a user, returns the updates that were rejected with the reason.
+///
+/// only the newest update of each book is applied, an update of a book the user can not see is
+/// rejected without stopping the others.
+#[cfg(feature = "ssr")]
+pub async fn save_progress_many(
+    user: &super::User,
+    updates: Vec<ProgressUpdate>,
+    db: &sea_orm::DatabaseConnection,
+) -> Result<Vec<(ProgressUpdate, String)>, ServerFnError> {
+    let mut rejected = vec![];
+    for update in newest_per_book(updates) {
+        match check_visible_update(user, &update, db).await {
+            Ok(()) => save_progress(db, user.id, update).await?,
+            Err(e) => rejected.push((update, e.to_string())),
+        }
+    }
+    Ok(rejected)
+}
+
+#[server]
+/// save a batch of queued updates of the current user, like the updates recorded while offline.
+///
+/// returns the rejected updates, sending them again would not help.
+pub async fn set_progress_many(
+    updates: Vec<ProgressUpdate>,
+) -> Result<Vec<(ProgressUpdate, String)>, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    let db = db()?;
+    save_progress_many(&user, updates, &db).await
+}
+
+#[server]
+/// get the latest position of every device of the current user in a book, newest first
+pub async fn get_device_progresses(
+    music_id: i32,
+) -> Result<Vec<DeviceProgressResult>, ServerFnError> {
+    let user = crate::server_api::auth::get_user()
+        .await?
+        .ok_or(ServerFnError::new("Not logged in"))?;
+
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::QueryOrder;
+    let db = db()?;
+
+    let p = DeviceProgress::find()
+        .filter(device_progress::Column::AccountId.eq(user.id))
+        .filter(device_progress::Column::MusicId.eq(music_id))
+        .order_by_desc(device_progress::Column::ClientUpdate)
+        .find_also_related(Chapter)
+        .all(&db)
         .await?;
+    let p = p
+        .into_iter()
+        .filter_map(|(p, c)| {
+            let c = c?;
+            Some(DeviceProgressResult {
+                device_id: p.device_id,
+                device_name: p.device_name,
+                music_id: p.music_id,
+                chapter_id: p.chapter_id,
+                chapter_num: c.chapter_num,
+                chapter_name: c.chapter_name,
+                progress: p.progress,
+                client_update: p.client_update,
+            })
+        })
+        .collect();
+    Ok(p)
+}
+
+#[server]
+/// get the positions to choose from when starting a book on a device.
+///
+/// returns an empty list when the devices agree with the newest position, otherwise the newest
+/// position first followed by every device that conflicts with it.
+pub async fn get_progress_conflicts(
+    music_id: i32,
+) -> Result<Vec<DeviceProgressResult>, ServerFnError> {
+    let devices = get_device_progresses(music_id).await?;
+    let Some(newest) = devices.first() else {
+        return Ok(vec![]);
+    };
+    let mut choices = vec![newest.clone()];
+    for device in devices.iter().skip(1) {
+        if device.conflicts_with(newest) && choices.iter().all(|c| c.conflicts_with(device)) {
+            choices.push(device.clone());
+        }
+    }
+    if choices.len() < 2 {
+        return Ok(vec![]);
     }
+    Ok(choices)
+}
 
-    Ok(())
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::*;
+
+    fn update(music_id: i32, chapter_id: i32, client_update: i64) -> ProgressUpdate {
+        ProgressUpdate {
+            music_id,
+            chapter_id,
+            progress: client_update as f64,
+            device_id: "phone".to_string(),
+            device_name: "Firefox on Android".to_string(),
+            client_update,
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest_update_of_each_book() {
+        let updates = vec![
+            update(1, 10, 100),
+            update(2, 20, 50),
+            update(1, 11, 300),
+            update(1, 12, 200),
+            update(2, 21, 50),
+        ];
+        assert_eq!(
+            newest_per_book(updates),
+            vec![update(1, 11, 300), update(2, 21, 50)]
+        );
+        assert_eq!(newest_per_book(vec![]), vec![]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod db {
+        use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, Set};
+
+        use super::*;
+        use crate::{
+            entities::{prelude::*, *},
+            server_api::{
+                library::ssr::grant_default_library,
+                test_db::{account, memory_db, table},
+                User,
+            },
+        };
+
+        /// a book with one chapter in a new library, returns the ids of the book and the chapter
+        async fn book(library: &str, db: &DatabaseConnection) -> (i32, i32) {
+            let library = library::ActiveModel {
+                name: Set(library.to_string()),
+                root: Set(format!("./{}", library)),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            let author = author::ActiveModel {
+                avatar: Set(String::new()),
+                name: Set("author".to_string()),
+                description: Set(String::new()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            let book = music::ActiveModel {
+                author_id: Set(author.id),
+                name: Set("book".to_string()),
+                chapters: Set(1),
+                file_folder: Set("book".to_string()),
+                music_type: Set(0),
+                library_id: Set(library.id),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            let chapter = chapter::ActiveModel {
+                music_id: Set(book.id),
+                chapter_num: Set(1),
+                chapter_name: Set("one".to_string()),
+                chapter_url: Set("book/one.mp3".to_string()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            (book.id, chapter.id)
+        }
+
+        #[tokio::test]
+        async fn the_hidden_books_are_rejected_alone() {
+            let db = memory_db(vec![
+                table(Account),
+                table(Library),
+                table(LibraryGrant),
+                table(Author),
+                table(Music),
+                table(Chapter),
+                table(Progress),
+                table(DeviceProgress),
+            ])
+            .await;
+            let alice = account("alice", 1, &db).await;
+            let (visible, visible_chapter) = book("books", &db).await;
+            let (hidden, hidden_chapter) = book("kids", &db).await;
+            grant_default_library(alice.id, &db).await.unwrap();
+            let alice = User {
+                id: alice.id,
+                username: alice.name,
+                role: 1,
+                permissions: vec![],
+            };
+
+            let updates = vec![
+                update(hidden, hidden_chapter, 100),
+                update(visible, visible_chapter, 100),
+                // a chapter of another book
+                update(visible, hidden_chapter, 50),
+            ];
+            let rejected = save_progress_many(&alice, updates, &db).await.unwrap();
+            assert_eq!(
+                rejected
+                    .iter()
+                    .map(|(u, _)| (u.music_id, u.chapter_id))
+                    .collect::<Vec<_>>(),
+                vec![(hidden, hidden_chapter)]
+            );
+            let saved = Progress::find().all(&db).await.unwrap();
+            assert_eq!(saved.len(), 1);
+            assert_eq!(saved[0].music_id, visible);
+            assert_eq!(saved[0].chapter_id, visible_chapter);
+        }
+    }
 }
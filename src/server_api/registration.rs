@@ -0,0 +1,198 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::User;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegistrationSettings {
+    /// anyone can register, the accounts wait for the approval of an admin
+    pub open: bool,
+    /// the role of the registered accounts
+    pub role: i32,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use sea_orm::{DatabaseConnection, DbErr, EntityTrait, Set};
+
+    use super::RegistrationSettings;
+    use crate::entities::{prelude::*, *};
+
+    const REGISTRATION_OPEN: &str = "registration_open";
+    const REGISTRATION_ROLE: &str = "registration_role";
+
+    pub async fn instance_setting(
+        name: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<String>, DbErr> {
+        Ok(InstanceSetting::find_by_id(name.to_string())
+            .one(db)
+            .await?
+            .map(|s| s.value))
+    }
+
+    pub async fn set_instance_setting(
+        name: &str,
+        value: String,
+        db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        let model = instance_setting::ActiveModel {
+            name: Set(name.to_string()),
+            value: Set(value),
+        };
+        if InstanceSetting::find_by_id(name.to_string())
+            .one(db)
+            .await?
+            .is_some()
+        {
+            InstanceSetting::update(model).exec(db).await?;
+        } else {
+            InstanceSetting::insert(model).exec(db).await?;
+        }
+        Ok(())
+    }
+
+    /// closed with the default role 1 when it was never set
+    pub async fn registration_settings(
+        db: &DatabaseConnection,
+    ) -> Result<RegistrationSettings, DbErr> {
+        let open = instance_setting(REGISTRATION_OPEN, db).await?;
+        let role = instance_setting(REGISTRATION_ROLE, db).await?;
+        Ok(RegistrationSettings {
+            open: open.is_some_and(|o| o == "true"),
+            role: role.and_then(|r| r.parse().ok()).unwrap_or(1),
+        })
+    }
+
+    pub async fn set_registration_settings(
+        settings: &RegistrationSettings,
+        db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        set_instance_setting(REGISTRATION_OPEN, settings.open.to_string(), db).await?;
+        set_instance_setting(REGISTRATION_ROLE, settings.role.to_string(), db).await
+    }
+}
+
+#[server]
+/// shows the register link on the login page
+pub async fn registration_open() -> Result<bool, ServerFnError> {
+    use super::ssr::db;
+    Ok(self::ssr::registration_settings(&db()?).await?.open)
+}
+
+#[server]
+pub async fn get_registration_settings() -> Result<RegistrationSettings, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::db;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    Ok(self::ssr::registration_settings(&db()?).await?)
+}
+
+#[server]
+pub async fn save_registration_settings(open: bool, role: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    Role::find_by_id(role)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Role does not exist."))?;
+    let before = self::ssr::registration_settings(&db).await?;
+    let settings = RegistrationSettings { open, role };
+    self::ssr::set_registration_settings(&settings, &db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    let summary = |s: &RegistrationSettings| format!("open {}, role {}", s.open, s.role);
+    audit(
+        AuditAction::RegistrationChanged,
+        "registration",
+        &summary(&before),
+        &summary(&settings),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server(Register, "/api")]
+/// create an account that can log in once an admin approves it
+pub async fn register(username: String, password: String) -> Result<(), ServerFnError> {
+    use super::auth::check_new_account;
+    use super::ssr::*;
+    use sea_orm::Set;
+    let db = db()?;
+    let settings = self::ssr::registration_settings(&db).await?;
+    if !settings.open {
+        return Err(ServerFnError::new("Registration is closed."));
+    }
+    let username = check_new_account(&username, &password, &db).await?;
+    let password_hashed = hash(password, DEFAULT_COST)?;
+    let account = entities::account::ActiveModel {
+        name: Set(username),
+        password: Set(password_hashed),
+        role_level: Set(settings.role),
+        approved: Set(false),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await?;
+    super::library::ssr::grant_default_library(account.id, &db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::UserAdded,
+        &format!("user {}", account.id),
+        "",
+        &format!("{}, role {}, registered", account.name, account.role_level),
+        &db,
+    )
+    .await?;
+    use super::webhook::{ssr as webhook, WebhookEvent};
+    let data = serde_json::json!({ "user": webhook::user_data(&account) });
+    webhook::trigger(WebhookEvent::UserCreated, data, &db).await?;
+    Ok(())
+}
+
+#[server]
+/// the registered accounts waiting for approval
+pub async fn list_pending_accounts() -> Result<Vec<User>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let accounts = Account::find()
+        .filter(entities::account::Column::Approved.eq(false))
+        .all(&db)
+        .await?;
+    let mut users = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        users.push(load_user_permissions(account, &db).await?);
+    }
+    Ok(users)
+}
+
+#[server]
+/// let a registered account log in, a rejected account is deleted with `delete_user`
+pub async fn approve_account(user_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use sea_orm::Set;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let account = entities::account::ActiveModel {
+        id: Set(user_id),
+        approved: Set(true),
+        ..Default::default()
+    }
+    .update(&db)
+    .await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::AccountApproved,
+        &format!("user {}", user_id),
+        &format!("{}, waiting for approval", account.name),
+        &format!("{}, approved", account.name),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
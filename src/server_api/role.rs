@@ -0,0 +1,225 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::permission::Permission;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleDetail {
+    pub id: i32,
+    pub name: String,
+    pub permissions: Vec<Permission>,
+    /// the users must log in with two-factor authentication
+    pub require_totp: bool,
+}
+
+/// the role in the audit log
+#[cfg(feature = "ssr")]
+fn role_summary(name: &str, permissions: &[Permission], require_totp: bool) -> String {
+    let permissions: Vec<&str> = permissions.iter().map(|p| p.as_str()).collect();
+    format!(
+        "{}: {}{}",
+        name,
+        permissions.join(","),
+        if require_totp { ", two-factor" } else { "" }
+    )
+}
+
+#[server]
+pub async fn list_roles() -> Result<Vec<RoleDetail>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    authorize(Access::Login).await?;
+    let db = db()?;
+    let mut permissions = role_permissions(&db).await?;
+    let roles = Role::find()
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|r| RoleDetail {
+            id: r.id,
+            name: r.name,
+            permissions: permissions.remove(&r.id).unwrap_or_default(),
+            require_totp: r.require_totp,
+        })
+        .collect();
+    Ok(roles)
+}
+
+#[server]
+/// create a role when `role_id` is not set, otherwise replace the name and permissions of the role
+pub async fn save_role(
+    role_id: Option<i32>,
+    name: String,
+    permissions: Vec<Permission>,
+    require_totp: bool,
+) -> Result<i32, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::{QueryOrder, Set};
+    let user = authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("The role name is empty."));
+    }
+    // the users and roles could not be managed any more
+    if role_id == Some(user.role) && !permissions.contains(&Permission::ManageUsers) {
+        return Err(ServerFnError::new(
+            "You can not remove manage_users from your own role.",
+        ));
+    }
+    let db = db()?;
+    let mut before = String::new();
+    let role_id = match role_id {
+        Some(role_id) => {
+            let role = Role::find_by_id(role_id)
+                .one(&db)
+                .await?
+                .ok_or(ServerFnError::new("Role does not exist."))?;
+            let old_permissions = role_permissions(&db).await?.remove(&role_id);
+            before = role_summary(
+                &role.name,
+                &old_permissions.unwrap_or_default(),
+                role.require_totp,
+            );
+            let mut model: role::ActiveModel = role.into();
+            model.name = Set(name.clone());
+            model.require_totp = Set(require_totp);
+            model.update(&db).await?;
+            role_id
+        }
+        None => {
+            let last = Role::find()
+                .order_by_desc(role::Column::Id)
+                .one(&db)
+                .await?;
+            let role_id = last.map_or(0, |r| r.id + 1);
+            Role::insert(role::ActiveModel {
+                id: Set(role_id),
+                name: Set(name.clone()),
+                require_totp: Set(require_totp),
+            })
+            .exec(&db)
+            .await?;
+            role_id
+        }
+    };
+    RolePermission::delete_many()
+        .filter(role_permission::Column::RoleId.eq(role_id))
+        .exec(&db)
+        .await?;
+    let mut permissions = permissions;
+    permissions.sort_by_key(|p| p.as_str());
+    permissions.dedup();
+    let after = role_summary(&name, &permissions, require_totp);
+    if !permissions.is_empty() {
+        RolePermission::insert_many(permissions.into_iter().map(|p| {
+            role_permission::ActiveModel {
+                role_id: Set(role_id),
+                permission: Set(p.as_str().to_string()),
+            }
+        }))
+        .exec(&db)
+        .await?;
+    }
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::RoleSaved,
+        &format!("role {}", role_id),
+        &before,
+        &after,
+        &db,
+    )
+    .await?;
+    Ok(role_id)
+}
+
+#[server]
+/// delete a role that no user has
+pub async fn delete_role(role_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::PaginatorTrait;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let users = Account::find()
+        .filter(account::Column::RoleLevel.eq(role_id))
+        .count(&db)
+        .await?;
+    if users > 0 {
+        return Err(ServerFnError::new(format!(
+            "The role is still used by {} users.",
+            users
+        )));
+    }
+    let invitations = Invitation::find()
+        .filter(invitation::Column::RoleId.eq(role_id))
+        .count(&db)
+        .await?;
+    if invitations > 0 {
+        return Err(ServerFnError::new(format!(
+            "The role is still used by {} invitations.",
+            invitations
+        )));
+    }
+    let role = Role::find_by_id(role_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Role does not exist."))?;
+    let old_permissions = role_permissions(&db).await?.remove(&role_id);
+    RolePermission::delete_many()
+        .filter(role_permission::Column::RoleId.eq(role_id))
+        .exec(&db)
+        .await?;
+    Role::delete_by_id(role_id).exec(&db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::RoleDeleted,
+        &format!("role {}", role_id),
+        &role_summary(
+            &role.name,
+            &old_permissions.unwrap_or_default(),
+            role.require_totp,
+        ),
+        "",
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server]
+pub async fn set_user_role(user_id: i32, role_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    let user = authorize(Access::Permission(Permission::ManageUsers)).await?;
+    if user.id == user_id {
+        return Err(ServerFnError::new("You can not change your own role."));
+    }
+    let db = db()?;
+    Role::find_by_id(role_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Role does not exist."))?;
+    let before = Account::find_by_id(user_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("User does not exist."))?;
+    let account = entities::account::ActiveModel {
+        id: sea_orm::ActiveValue::Set(user_id),
+        role_level: sea_orm::ActiveValue::Set(role_id),
+        ..Default::default()
+    };
+    account.update(&db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::UserRoleChanged,
+        &format!("user {}", user_id),
+        &format!("{}, role {}", before.name, before.role_level),
+        &format!("{}, role {}", before.name, role_id),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
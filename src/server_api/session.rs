@@ -0,0 +1,268 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionDetail {
+    pub id: i32,
+    /// rfc3339
+    pub created: String,
+    pub last_seen: String,
+    pub ip: String,
+    pub user_agent: String,
+    /// the session of this browser
+    pub current: bool,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use std::net::SocketAddr;
+
+    use axum::{
+        extract::{ConnectInfo, Request, State},
+        middleware::Next,
+        response::Response,
+    };
+    use http::header::USER_AGENT;
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            auth_event::ssr::client_ip,
+            ssr::{date_to_rfc3339, now, parse_date, AuthSession},
+            token::ssr::hash_token,
+        },
+    };
+
+    /// the session key of the account and session hash with a row in `account_session`
+    const TRACKED: &str = "tracked_session";
+    /// seconds between the updates of the last activity
+    const LAST_SEEN_INTERVAL: i64 = 60;
+    /// days without activity after which a session is forgotten
+    const MAX_IDLE_DAYS: i64 = 60;
+
+    /// the value of `account_session.session_hash`
+    pub fn session_hash(auth: &AuthSession) -> String {
+        hash_token(&auth.session.get_session_id().to_string())
+    }
+
+    /// the middleware, it must run inside the `AuthSessionLayer`
+    pub async fn track_sessions(
+        State(db): State<DatabaseConnection>,
+        mut request: Request,
+        next: Next,
+    ) -> Response {
+        if let Some(mut auth_session) = request.extensions().get::<AuthSession>().cloned() {
+            let peer = request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| client_ip(addr.ip(), request.headers()).to_string())
+                .unwrap_or_default();
+            let user_agent = request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .chars()
+                .take(255)
+                .collect::<String>();
+            match track(&mut auth_session, &peer, &user_agent, &db).await {
+                Ok(()) => {
+                    request.extensions_mut().insert(auth_session);
+                }
+                Err(e) => tracing::error!("failed to track the session: {}", e),
+            }
+        }
+        next.run(request).await
+    }
+
+    /// keep the row of a logged in session, log out a session whose row was deleted
+    async fn track(
+        auth: &mut AuthSession,
+        ip: &str,
+        user_agent: &str,
+        db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        let Some(account_id) = auth.current_user.as_ref().map(|u| u.id) else {
+            return Ok(());
+        };
+        let hash = session_hash(auth);
+        let row = AccountSession::find()
+            .filter(account_session::Column::SessionHash.eq(&hash))
+            .one(db)
+            .await?;
+        let tracked = auth.session.get::<(i32, String)>(TRACKED);
+        match row {
+            Some(row) if row.account_id == account_id => {
+                let last_seen = parse_date(&date_to_rfc3339(&row.last_seen));
+                let idle = last_seen.map_or(i64::MAX, |t| (chrono::Utc::now() - t).num_seconds());
+                if idle >= LAST_SEEN_INTERVAL || row.ip != ip {
+                    AccountSession::update(account_session::ActiveModel {
+                        id: Set(row.id),
+                        last_seen: Set(now()),
+                        ip: Set(ip.to_string()),
+                        ..Default::default()
+                    })
+                    .exec(db)
+                    .await?;
+                }
+            }
+            None if tracked == Some((account_id, hash.clone())) => {
+                tracing::info!("revoked session of account {} logged out", account_id);
+                auth.session.remove(TRACKED);
+                auth.logout_user();
+                auth.current_user = None;
+            }
+            row => {
+                // a new login, or a login of another account in this browser
+                if let Some(row) = row {
+                    AccountSession::delete_by_id(row.id).exec(db).await?;
+                }
+                forget_idle(account_id, db).await?;
+                AccountSession::insert(account_session::ActiveModel {
+                    session_hash: Set(hash.clone()),
+                    account_id: Set(account_id),
+                    created: Set(now()),
+                    last_seen: Set(now()),
+                    ip: Set(ip.to_string()),
+                    user_agent: Set(user_agent.to_string()),
+                    ..Default::default()
+                })
+                .exec(db)
+                .await?;
+                auth.session.set(TRACKED, (account_id, hash));
+            }
+        }
+        Ok(())
+    }
+
+    /// delete the rows of the sessions that have expired by now
+    async fn forget_idle(account_id: i32, db: &DatabaseConnection) -> Result<(), DbErr> {
+        let sessions = AccountSession::find()
+            .filter(account_session::Column::AccountId.eq(account_id))
+            .all(db)
+            .await?;
+        let idle: Vec<i32> = sessions
+            .into_iter()
+            .filter(|s| {
+                parse_date(&date_to_rfc3339(&s.last_seen))
+                    .is_none_or(|t| (chrono::Utc::now() - t).num_days() >= MAX_IDLE_DAYS)
+            })
+            .map(|s| s.id)
+            .collect();
+        if !idle.is_empty() {
+            AccountSession::delete_many()
+                .filter(account_session::Column::Id.is_in(idle))
+                .exec(db)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// the row of this session is deleted when logging out, not only when it is revoked
+    pub async fn forget_session(auth: &AuthSession, db: &DatabaseConnection) -> Result<(), DbErr> {
+        AccountSession::delete_many()
+            .filter(account_session::Column::SessionHash.eq(session_hash(auth)))
+            .exec(db)
+            .await?;
+        auth.session.remove(TRACKED);
+        Ok(())
+    }
+
+    /// log out the sessions of an account, except the session with the given hash
+    pub async fn revoke_sessions(
+        account_id: i32,
+        except: Option<&str>,
+        db: &DatabaseConnection,
+    ) -> Result<u64, DbErr> {
+        let mut query =
+            AccountSession::delete_many().filter(account_session::Column::AccountId.eq(account_id));
+        if let Some(except) = except {
+            query = query.filter(account_session::Column::SessionHash.ne(except));
+        }
+        Ok(query.exec(db).await?.rows_affected)
+    }
+}
+
+#[server]
+/// the sessions of the current user, the current one first
+pub async fn list_sessions() -> Result<Vec<SessionDetail>, ServerFnError> {
+    use self::ssr::session_hash;
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::QueryOrder;
+    let user = authorize(Access::Login).await?;
+    let auth = auth()?;
+    let current = session_hash(&auth);
+    let db = db()?;
+    let mut sessions: Vec<SessionDetail> = AccountSession::find()
+        .filter(account_session::Column::AccountId.eq(user.id))
+        .order_by_desc(account_session::Column::LastSeen)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|s| SessionDetail {
+            id: s.id,
+            created: date_to_rfc3339(&s.created),
+            last_seen: date_to_rfc3339(&s.last_seen),
+            ip: s.ip,
+            user_agent: s.user_agent,
+            current: s.session_hash == current,
+        })
+        .collect();
+    sessions.sort_by_key(|s| !s.current);
+    Ok(sessions)
+}
+
+#[server]
+/// log out one of the sessions of the current user
+pub async fn revoke_session(session_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let deleted = AccountSession::delete_many()
+        .filter(account_session::Column::Id.eq(session_id))
+        .filter(account_session::Column::AccountId.eq(user.id))
+        .exec(&db)
+        .await?;
+    if deleted.rows_affected == 0 {
+        return Err(ServerFnError::new("Session does not exist."));
+    }
+    Ok(())
+}
+
+#[server]
+/// log out the other sessions of the current user, returns how many
+pub async fn revoke_other_sessions() -> Result<u64, ServerFnError> {
+    use self::ssr::{revoke_sessions, session_hash};
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    let user = authorize(Access::Login).await?;
+    let auth = auth()?;
+    let db = db()?;
+    Ok(revoke_sessions(user.id, Some(&session_hash(&auth)), &db).await?)
+}
+
+#[server]
+/// log out all the sessions of a user, returns how many
+pub async fn force_logout(user_id: i32) -> Result<u64, ServerFnError> {
+    use self::ssr::revoke_sessions;
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let revoked = revoke_sessions(user_id, None, &db).await?;
+    use super::audit::{ssr::audit, AuditAction};
+    audit(
+        AuditAction::SessionsRevoked,
+        &format!("user {}", user_id),
+        "",
+        &format!("{} sessions logged out", revoked),
+        &db,
+    )
+    .await?;
+    Ok(revoked)
+}
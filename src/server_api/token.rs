@@ -0,0 +1,322 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiTokenDetail {
+    pub id: i32,
+    pub name: String,
+    /// rfc3339
+    pub created: String,
+    pub expires: Option<String>,
+    pub last_used: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use http::{header::AUTHORIZATION, HeaderMap};
+    use leptos::{use_context, ServerFnError};
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+    use sha2::{Digest, Sha256};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{
+            ssr::{date_to_rfc3339, load_user_permissions, now, parse_date, AuthSession},
+            User,
+        },
+    };
+
+    /// the prefix of the generated tokens, makes them easy to find in a config file
+    pub const TOKEN_PREFIX: &str = "abr_";
+
+    pub fn generate_token() -> String {
+        use rand::{distributions::Alphanumeric, Rng};
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(40)
+            .map(char::from)
+            .collect();
+        format!("{}{}", TOKEN_PREFIX, random)
+    }
+
+    /// the value stored in `api_token.token_hash`
+    pub fn hash_token(token: &str) -> String {
+        Sha256::digest(token.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// create a token for an account, returns the token, only its hash is stored
+    pub async fn insert_token(
+        account_id: i32,
+        name: String,
+        expires: Option<crate::ProgressDateType>,
+        db: &DatabaseConnection,
+    ) -> Result<String, DbErr> {
+        let token = generate_token();
+        ApiToken::insert(api_token::ActiveModel {
+            account_id: sea_orm::Set(account_id),
+            name: sea_orm::Set(name),
+            token_hash: sea_orm::Set(hash_token(&token)),
+            created: sea_orm::Set(now()),
+            expires: sea_orm::Set(expires),
+            last_used: sea_orm::Set(None),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(token)
+    }
+
+    /// the token of an `Authorization: Bearer` header
+    pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+        let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+        let (scheme, token) = value.split_once(' ')?;
+        scheme
+            .eq_ignore_ascii_case("bearer")
+            .then_some(token.trim())
+            .filter(|t| !t.is_empty())
+    }
+
+    /// the user of a token that is not expired, the last used time of the token is updated
+    pub async fn user_from_token(
+        token: &str,
+        db: &DatabaseConnection,
+    ) -> Result<Option<User>, DbErr> {
+        let Some(api_token) = ApiToken::find()
+            .filter(api_token::Column::TokenHash.eq(hash_token(token)))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if let Some(expires) = &api_token.expires {
+            let expires = date_to_rfc3339(expires);
+            if !parse_date(&expires).is_some_and(|e| e > chrono::Utc::now()) {
+                return Ok(None);
+            }
+        }
+        let Some(account) = Account::find_by_id(api_token.account_id).one(db).await? else {
+            return Ok(None);
+        };
+        ApiToken::update(api_token::ActiveModel {
+            id: sea_orm::Set(api_token.id),
+            last_used: sea_orm::Set(Some(now())),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(Some(load_user_permissions(account, db).await?))
+    }
+
+    /// the user of a request, the one of the bearer token when there is one and the one of the
+    /// session otherwise. a wrong or expired token means no user
+    pub async fn bearer_user(
+        session_user: Option<User>,
+        headers: &HeaderMap,
+        db: &DatabaseConnection,
+    ) -> Option<User> {
+        match bearer_token(headers) {
+            Some(token) => user_from_token(token, db).await.ok().flatten(),
+            None => session_user,
+        }
+    }
+
+    /// replace the user of the session by the user of the bearer token of a request.
+    ///
+    /// the token is not stored in the session, a wrong or expired token means no user
+    pub async fn with_bearer_user(
+        mut auth_session: AuthSession,
+        headers: &HeaderMap,
+        db: &DatabaseConnection,
+    ) -> AuthSession {
+        auth_session.current_user =
+            bearer_user(auth_session.current_user.take(), headers, db).await;
+        auth_session
+    }
+
+    /// provided to the server functions called with a bearer token
+    #[derive(Clone, Copy, Debug)]
+    pub struct BearerAuth;
+
+    /// reject the server functions called with a bearer token, for the changes that need the
+    /// login of the user: a token must not create tokens or change the password
+    pub fn require_session() -> Result<(), ServerFnError> {
+        match use_context::<BearerAuth>() {
+            Some(BearerAuth) => Err(ServerFnError::new(
+                "An api token can not do this, log in with the password.",
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[server]
+/// the api tokens of the current user
+pub async fn list_api_tokens() -> Result<Vec<ApiTokenDetail>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let tokens = ApiToken::find()
+        .filter(api_token::Column::AccountId.eq(user.id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|t| ApiTokenDetail {
+            id: t.id,
+            name: t.name,
+            created: date_to_rfc3339(&t.created),
+            expires: t.expires.as_ref().map(date_to_rfc3339),
+            last_used: t.last_used.as_ref().map(date_to_rfc3339),
+        })
+        .collect();
+    Ok(tokens)
+}
+
+#[server]
+/// create a token for the current user, it never expires when `expires_in_days` is not set.
+///
+/// returns the token, it can not be shown again
+pub async fn create_api_token(
+    name: String,
+    expires_in_days: Option<u32>,
+) -> Result<String, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    let user = authorize(Access::Login).await?;
+    self::ssr::require_session()?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("The token name is empty."));
+    }
+    let expires = expires_in_days
+        .map(|days| {
+            let expires = chrono::Utc::now() + chrono::Duration::days(days as i64);
+            date_from_rfc3339(&expires.to_rfc3339()).ok_or(ServerFnError::new("Invalid expiry."))
+        })
+        .transpose()?;
+    let db = db()?;
+    Ok(self::ssr::insert_token(user.id, name, expires, &db).await?)
+}
+
+#[server]
+/// revoke a token of the current user
+pub async fn revoke_api_token(token_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let result = ApiToken::delete_many()
+        .filter(api_token::Column::Id.eq(token_id))
+        .filter(api_token::Column::AccountId.eq(user.id))
+        .exec(&db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(ServerFnError::new("Token not found"));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use http::{header::AUTHORIZATION, HeaderMap};
+
+    use super::ssr::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, authorization.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn reads_the_bearer_token() {
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+        assert_eq!(bearer_token(&headers("Bearer abr_x")), Some("abr_x"));
+        assert_eq!(bearer_token(&headers("bearer  abr_x ")), Some("abr_x"));
+        assert_eq!(bearer_token(&headers("Basic YTpi")), None);
+        assert_eq!(bearer_token(&headers("Bearer ")), None);
+        assert_eq!(bearer_token(&headers("Bearer")), None);
+    }
+
+    #[test]
+    fn tokens_are_stored_hashed() {
+        let token = generate_token();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 40);
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+        assert_ne!(hash_token(&token), hash_token(&generate_token()));
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod db {
+        use sea_orm::{DatabaseConnection, EntityTrait};
+
+        use super::*;
+        use crate::{
+            entities::prelude::*,
+            server_api::{
+                ssr::{date_from_rfc3339, load_user_permissions},
+                test_db::{account, memory_db, table},
+                User,
+            },
+        };
+
+        async fn alice() -> (DatabaseConnection, User) {
+            let db = memory_db(vec![
+                table(Role),
+                table(RolePermission),
+                table(Account),
+                table(ApiToken),
+            ])
+            .await;
+            let alice = account("alice", 1, &db).await;
+            let alice = load_user_permissions(alice, &db).await.unwrap();
+            (db, alice)
+        }
+
+        fn days_from_now(days: i64) -> Option<crate::ProgressDateType> {
+            let date = chrono::Utc::now() + chrono::Duration::days(days);
+            date_from_rfc3339(&date.to_rfc3339())
+        }
+
+        #[tokio::test]
+        async fn an_expired_token_is_rejected() {
+            let (db, alice) = alice().await;
+            let expired = insert_token(alice.id, "old".to_string(), days_from_now(-1), &db)
+                .await
+                .unwrap();
+            assert_eq!(user_from_token(&expired, &db).await.unwrap(), None);
+            let valid = insert_token(alice.id, "new".to_string(), days_from_now(1), &db)
+                .await
+                .unwrap();
+            assert_eq!(user_from_token(&valid, &db).await.unwrap(), Some(alice));
+            let used = ApiToken::find().all(&db).await.unwrap();
+            assert_eq!(used[0].last_used, None);
+            assert!(used[1].last_used.is_some());
+        }
+
+        #[tokio::test]
+        async fn a_wrong_token_means_no_user() {
+            let (db, alice) = alice().await;
+            let token = insert_token(alice.id, "app".to_string(), None, &db)
+                .await
+                .unwrap();
+            let session = Some(alice.clone());
+            // the session is kept without a token and replaced with one
+            assert_eq!(
+                bearer_user(session.clone(), &HeaderMap::new(), &db).await,
+                session
+            );
+            let wrong = headers(&format!("Bearer {}x", token));
+            assert_eq!(bearer_user(session.clone(), &wrong, &db).await, None);
+            let right = headers(&format!("Bearer {}", token));
+            assert_eq!(bearer_user(None, &right, &db).await, Some(alice));
+        }
+    }
+}
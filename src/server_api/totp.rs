@@ -0,0 +1,564 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TotpStatus {
+    pub enabled: bool,
+    /// the role of the user requires two-factor authentication
+    pub required: bool,
+    pub recovery_codes_left: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TotpEnrolment {
+    /// base32, for authenticators that can not scan the qr code
+    pub secret: String,
+    /// the `otpauth://` provisioning uri
+    pub uri: String,
+    /// the uri as a qr code
+    pub qr_svg: String,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use hmac::{Hmac, Mac};
+    use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::{ssr::AuthSession, token::ssr::hash_token},
+    };
+
+    pub const ISSUER: &str = "Audiobookroom";
+    /// seconds of a time step, rfc 6238 default
+    pub const STEP: u64 = 30;
+    pub const RECOVERY_CODES: usize = 10;
+    /// the session key of a login waiting for its second factor
+    const PENDING_LOGIN: &str = "pending_login";
+    /// seconds to enter the code after the password
+    const PENDING_LOGIN_TIMEOUT: i64 = 300;
+
+    const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    pub fn base32_encode(data: &[u8]) -> String {
+        let mut out = String::new();
+        let (mut buffer, mut bits) = (0u32, 0);
+        for &b in data {
+            buffer = (buffer << 8) | b as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                out.push(BASE32[((buffer >> bits) & 31) as usize] as char);
+            }
+            buffer &= (1 << bits) - 1;
+        }
+        if bits > 0 {
+            out.push(BASE32[((buffer << (5 - bits)) & 31) as usize] as char);
+        }
+        out
+    }
+
+    /// decode base32, ignoring case, spaces and padding
+    pub fn base32_decode(data: &str) -> Option<Vec<u8>> {
+        let mut out = vec![];
+        let (mut buffer, mut bits) = (0u32, 0);
+        for c in data.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+            let value = BASE32
+                .iter()
+                .position(|&b| b as char == c.to_ascii_uppercase())?;
+            buffer = (buffer << 5) | value as u32;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                out.push((buffer >> bits) as u8);
+                buffer &= (1 << bits) - 1;
+            }
+        }
+        Some(out)
+    }
+
+    /// a new random secret of 160 bits, base32 encoded
+    pub fn generate_secret() -> String {
+        use rand::Rng;
+        base32_encode(&rand::thread_rng().gen::<[u8; 20]>())
+    }
+
+    /// the 6 digit code of a time step, rfc 4226 with hmac-sha1
+    pub fn code_at(secret: &[u8], step: u64) -> u32 {
+        let mut mac =
+            Hmac::<sha1::Sha1>::new_from_slice(secret).expect("hmac accepts keys of any length");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        binary % 1_000_000
+    }
+
+    /// the time step of `code` when it is valid at `unix_time`, one step of clock drift is allowed
+    pub fn verify_code(secret: &[u8], code: &str, unix_time: u64) -> Option<u64> {
+        let code: u32 = code.trim().replace(' ', "").parse().ok()?;
+        let current = unix_time / STEP;
+        (current.saturating_sub(1)..=current + 1).find(|&step| code_at(secret, step) == code)
+    }
+
+    pub fn provisioning_uri(username: &str, secret: &str) -> String {
+        use crate::ui::encode_uri_component;
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits=6&period={}",
+            encode_uri_component(ISSUER),
+            encode_uri_component(username),
+            secret,
+            encode_uri_component(ISSUER),
+            STEP
+        )
+    }
+
+    pub fn qr_svg(uri: &str) -> String {
+        use qrcode::{render::svg, QrCode};
+        QrCode::new(uri.as_bytes())
+            .map(|qr| qr.render::<svg::Color>().min_dimensions(200, 200).build())
+            .unwrap_or_default()
+    }
+
+    /// a login with a correct password, waiting for the second factor
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct PendingLogin {
+        pub account_id: i32,
+        pub remember: bool,
+        /// the role requires 2fa and the account has not enrolled yet
+        pub enrol: bool,
+        pub created: i64,
+    }
+
+    pub fn set_pending_login(auth: &AuthSession, account_id: i32, remember: bool, enrol: bool) {
+        let pending = PendingLogin {
+            account_id,
+            remember,
+            enrol,
+            created: chrono::Utc::now().timestamp(),
+        };
+        auth.session.set(PENDING_LOGIN, pending);
+    }
+
+    pub fn pending_login(auth: &AuthSession) -> Option<PendingLogin> {
+        auth.session
+            .get::<PendingLogin>(PENDING_LOGIN)
+            .filter(|p| chrono::Utc::now().timestamp() - p.created <= PENDING_LOGIN_TIMEOUT)
+    }
+
+    /// the user name of the pending login, for the auth events
+    pub async fn pending_username(
+        pending: &PendingLogin,
+        db: &DatabaseConnection,
+    ) -> Result<String, DbErr> {
+        Ok(Account::find_by_id(pending.account_id)
+            .one(db)
+            .await?
+            .map(|a| a.name)
+            .unwrap_or_default())
+    }
+
+    /// log in the account of the pending login
+    pub async fn finish_pending_login(
+        auth: &AuthSession,
+        pending: &PendingLogin,
+        db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        use crate::server_api::auth_event::{ssr as event, AuthEventKind};
+        let username = pending_username(pending, db).await?;
+        event::record(
+            AuthEventKind::LoginSuccess,
+            &username,
+            &event::current_ip(),
+            "two-factor",
+            db,
+        )
+        .await?;
+        auth.session.remove(PENDING_LOGIN);
+        auth.login_user(pending.account_id);
+        auth.remember_user(pending.remember);
+        Ok(())
+    }
+
+    pub async fn role_requires_totp(role_id: i32, db: &DatabaseConnection) -> Result<bool, DbErr> {
+        Ok(Role::find_by_id(role_id)
+            .one(db)
+            .await?
+            .is_some_and(|r| r.require_totp))
+    }
+
+    pub async fn enabled_totp(
+        account_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<Option<account_totp::Model>, DbErr> {
+        Ok(AccountTotp::find_by_id(account_id)
+            .one(db)
+            .await?
+            .filter(|t| t.enabled))
+    }
+
+    /// check a code of the authenticator, a used code is rejected
+    pub async fn check_totp(
+        totp: &account_totp::Model,
+        code: &str,
+        db: &DatabaseConnection,
+    ) -> Result<bool, DbErr> {
+        let Some(secret) = base32_decode(&totp.secret) else {
+            return Ok(false);
+        };
+        let now = chrono::Utc::now().timestamp() as u64;
+        let Some(step) = verify_code(&secret, code, now) else {
+            return Ok(false);
+        };
+        if totp.last_step.is_some_and(|last| step as i64 <= last) {
+            return Ok(false);
+        }
+        AccountTotp::update(account_totp::ActiveModel {
+            account_id: Set(totp.account_id),
+            last_step: Set(Some(step as i64)),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+        Ok(true)
+    }
+
+    /// check a code of the authenticator or a recovery code, the recovery code is used up
+    pub async fn check_second_factor(
+        account_id: i32,
+        code: &str,
+        db: &DatabaseConnection,
+    ) -> Result<bool, DbErr> {
+        let Some(totp) = enabled_totp(account_id, db).await? else {
+            return Ok(false);
+        };
+        if check_totp(&totp, code, db).await? {
+            return Ok(true);
+        }
+        let used = RecoveryCode::delete_many()
+            .filter(recovery_code::Column::AccountId.eq(account_id))
+            .filter(recovery_code::Column::CodeHash.eq(hash_token(&normalize_recovery(code))))
+            .exec(db)
+            .await?;
+        Ok(used.rows_affected > 0)
+    }
+
+    fn normalize_recovery(code: &str) -> String {
+        code.trim().to_lowercase().replace([' ', '-'], "")
+    }
+
+    /// replace the recovery codes of an account, returns the new codes
+    pub async fn new_recovery_codes(
+        account_id: i32,
+        db: &DatabaseConnection,
+    ) -> Result<Vec<String>, DbErr> {
+        use rand::{distributions::Alphanumeric, Rng};
+        RecoveryCode::delete_many()
+            .filter(recovery_code::Column::AccountId.eq(account_id))
+            .exec(db)
+            .await?;
+        let codes: Vec<String> = (0..RECOVERY_CODES)
+            .map(|_| {
+                let code: String = rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(10)
+                    .map(|c| (c as char).to_ascii_lowercase())
+                    .collect();
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect();
+        RecoveryCode::insert_many(codes.iter().map(|code| recovery_code::ActiveModel {
+            account_id: Set(account_id),
+            code_hash: Set(hash_token(&normalize_recovery(code))),
+        }))
+        .exec(db)
+        .await?;
+        Ok(codes)
+    }
+
+    /// remove the second factor of an account
+    pub async fn reset_totp(account_id: i32, db: &DatabaseConnection) -> Result<(), DbErr> {
+        RecoveryCode::delete_many()
+            .filter(recovery_code::Column::AccountId.eq(account_id))
+            .exec(db)
+            .await?;
+        AccountTotp::delete_by_id(account_id).exec(db).await?;
+        Ok(())
+    }
+
+    /// the account enrolling: the current user, or a login whose role requires 2fa
+    pub fn enrolling_account(
+        auth: &AuthSession,
+    ) -> Result<(i32, Option<PendingLogin>), leptos::ServerFnError> {
+        if let Some(user) = &auth.current_user {
+            return Ok((user.id, None));
+        }
+        match pending_login(auth) {
+            Some(pending) if pending.enrol => Ok((pending.account_id, Some(pending))),
+            _ => Err(leptos::ServerFnError::new("Not logged in")),
+        }
+    }
+}
+
+#[server]
+pub async fn get_totp_status() -> Result<TotpStatus, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::PaginatorTrait;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    let enabled = self::ssr::enabled_totp(user.id, &db).await?.is_some();
+    let recovery_codes_left = RecoveryCode::find()
+        .filter(recovery_code::Column::AccountId.eq(user.id))
+        .count(&db)
+        .await?;
+    Ok(TotpStatus {
+        enabled,
+        required: self::ssr::role_requires_totp(user.role, &db).await?,
+        recovery_codes_left,
+    })
+}
+
+#[server]
+/// create a new secret, it is used after a code is confirmed by [`confirm_totp_enrolment`]
+pub async fn begin_totp_enrolment() -> Result<TotpEnrolment, ServerFnError> {
+    use self::ssr::*;
+    use super::ssr::{auth, db};
+    use crate::entities::{prelude::*, *};
+    use sea_orm::{EntityTrait, Set};
+    let auth = auth()?;
+    let (account_id, _) = enrolling_account(&auth)?;
+    let db = db()?;
+    if enabled_totp(account_id, &db).await?.is_some() {
+        return Err(ServerFnError::new(
+            "Two-factor authentication is already enabled.",
+        ));
+    }
+    let account = Account::find_by_id(account_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("User does not exist."))?;
+    let secret = generate_secret();
+    AccountTotp::delete_by_id(account_id).exec(&db).await?;
+    AccountTotp::insert(account_totp::ActiveModel {
+        account_id: Set(account_id),
+        secret: Set(secret.clone()),
+        enabled: Set(false),
+        last_step: Set(None),
+    })
+    .exec(&db)
+    .await?;
+    let uri = provisioning_uri(&account.name, &secret);
+    Ok(TotpEnrolment {
+        qr_svg: qr_svg(&uri),
+        secret,
+        uri,
+    })
+}
+
+#[server]
+/// enable the secret when the code matches, returns the recovery codes.
+///
+/// a login waiting for the enrolment is finished
+pub async fn confirm_totp_enrolment(code: String) -> Result<Vec<String>, ServerFnError> {
+    use self::ssr::*;
+    use super::ssr::{auth, db};
+    use crate::entities::{prelude::*, *};
+    use sea_orm::{EntityTrait, Set};
+    let auth = auth()?;
+    let (account_id, pending) = enrolling_account(&auth)?;
+    let db = db()?;
+    let totp = AccountTotp::find_by_id(account_id)
+        .one(&db)
+        .await?
+        .filter(|t| !t.enabled)
+        .ok_or(ServerFnError::new("Start the enrolment first."))?;
+    if !check_totp(&totp, &code, &db).await? {
+        return Err(ServerFnError::new("The code is not correct."));
+    }
+    AccountTotp::update(account_totp::ActiveModel {
+        account_id: Set(account_id),
+        enabled: Set(true),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    let codes = new_recovery_codes(account_id, &db).await?;
+    if let Some(pending) = pending {
+        finish_pending_login(&auth, &pending, &db).await?;
+    }
+    Ok(codes)
+}
+
+#[server]
+/// turn off two-factor authentication, not allowed when the role requires it
+pub async fn disable_totp(code: String) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    if self::ssr::role_requires_totp(user.role, &db).await? {
+        return Err(ServerFnError::new(
+            "Your role requires two-factor authentication.",
+        ));
+    }
+    if !self::ssr::check_second_factor(user.id, &code, &db).await? {
+        return Err(ServerFnError::new("The code is not correct."));
+    }
+    self::ssr::reset_totp(user.id, &db).await?;
+    Ok(())
+}
+
+#[server]
+/// replace the recovery codes, the old ones stop working
+pub async fn regenerate_recovery_codes(code: String) -> Result<Vec<String>, ServerFnError> {
+    use super::permission::{authorize, Access};
+    use super::ssr::*;
+    let user = authorize(Access::Login).await?;
+    let db = db()?;
+    if !self::ssr::check_second_factor(user.id, &code, &db).await? {
+        return Err(ServerFnError::new("The code is not correct."));
+    }
+    Ok(self::ssr::new_recovery_codes(user.id, &db).await?)
+}
+
+#[server(VerifyLoginTotp, "/api")]
+/// the second step of the login, a code of the authenticator or a recovery code
+pub async fn verify_login_totp(code: String) -> Result<(), ServerFnError> {
+    use self::ssr::*;
+    use super::auth_event::{ssr as event, AuthEventKind};
+    use super::ssr::{auth, db};
+    let auth = auth()?;
+    let pending = pending_login(&auth).ok_or(ServerFnError::new(
+        "The login has expired, enter your password again.",
+    ))?;
+    let db = db()?;
+    let username = pending_username(&pending, &db).await?;
+    let ip = event::current_ip();
+    event::check_throttle(&username, &ip, &db).await?;
+    if !check_second_factor(pending.account_id, &code, &db).await? {
+        event::record(AuthEventKind::TotpFailure, &username, &ip, "", &db).await?;
+        return Err(ServerFnError::new("The code is not correct."));
+    }
+    finish_pending_login(&auth, &pending, &db).await?;
+    leptos_axum::redirect("/");
+    Ok(())
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::ssr::*;
+
+    /// the secret of the test vectors of rfc 4226 and rfc 6238
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_rfc_4226() {
+        let codes = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (step, code) in codes.into_iter().enumerate() {
+            assert_eq!(code_at(RFC_SECRET, step as u64), code, "counter {}", step);
+        }
+    }
+
+    #[test]
+    fn codes_match_rfc_6238() {
+        // the last 6 of the 8 digits of the sha1 vectors
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(verify_code(RFC_SECRET, code, time), Some(time / STEP));
+        }
+    }
+
+    #[test]
+    fn codes_allow_one_step_of_drift() {
+        assert_eq!(verify_code(RFC_SECRET, "287082", 59), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, " 287 082 ", 59), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, "287082", 0), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, "287082", 89), Some(1));
+        assert_eq!(verify_code(RFC_SECRET, "287082", 90), None);
+        assert_eq!(verify_code(RFC_SECRET, "287083", 59), None);
+        assert_eq!(verify_code(RFC_SECRET, "abcdef", 59), None);
+    }
+
+    #[test]
+    fn base32_roundtrips() {
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(
+            base32_encode(RFC_SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+        for len in 0..=20 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+            assert_eq!(base32_decode(&base32_encode(&data)), Some(data));
+        }
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_eq!(base32_decode(&secret).map(|s| s.len()), Some(20));
+    }
+
+    #[test]
+    fn base32_ignores_case_spaces_and_padding() {
+        assert_eq!(
+            base32_decode("mzxw 6ytb oi======"),
+            Some(b"foobar".to_vec())
+        );
+        assert_eq!(base32_decode("MZXW1YTBOI"), None);
+        assert_eq!(base32_decode("MZXW8YTBOI"), None);
+        assert_eq!(base32_decode("MZXW-YTBOI"), None);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn a_used_code_is_rejected() {
+        use sea_orm::EntityTrait;
+
+        use crate::{
+            entities::{prelude::*, *},
+            server_api::test_db::{account, memory_db, table},
+        };
+
+        let db = memory_db(vec![table(Account), table(AccountTotp)]).await;
+        let alice = account("alice", 1, &db).await;
+        let secret = generate_secret();
+        let totp = account_totp::Model {
+            account_id: alice.id,
+            secret: secret.clone(),
+            enabled: true,
+            last_step: None,
+        };
+        AccountTotp::insert(account_totp::ActiveModel::from(totp))
+            .exec(&db)
+            .await
+            .unwrap();
+        let step = chrono::Utc::now().timestamp() as u64 / STEP;
+        let key = base32_decode(&secret).unwrap();
+        let code = format!("{:06}", code_at(&key, step));
+        let earlier = format!("{:06}", code_at(&key, step - 1));
+
+        let totp = enabled_totp(alice.id, &db).await.unwrap().unwrap();
+        assert!(check_totp(&totp, &code, &db).await.unwrap());
+        // the same code again, and an older code of the drift window
+        let totp = enabled_totp(alice.id, &db).await.unwrap().unwrap();
+        assert_eq!(totp.last_step, Some(step as i64));
+        assert!(!check_totp(&totp, &code, &db).await.unwrap());
+        assert!(!check_totp(&totp, &earlier, &db).await.unwrap());
+    }
+}
@@ -0,0 +1,105 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+pub const EXPORT_VERSION: u32 = 1;
+
+/// a book, identified by its title and author instead of the database id
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BookRef {
+    pub title: String,
+    pub author: String,
+}
+
+/// a chapter, identified by its name, the number is used when the name is not found
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChapterRef {
+    pub chapter_num: i32,
+    pub chapter_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProgressExport {
+    pub book: BookRef,
+    pub chapter: ChapterRef,
+    pub progress: f64,
+    /// rfc3339
+    pub updated: String,
+}
+
+/// the latest position of one device, this is the listening history across devices
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceProgressExport {
+    pub book: BookRef,
+    pub chapter: ChapterRef,
+    pub device_id: String,
+    pub device_name: String,
+    pub progress: f64,
+    pub client_update: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BookmarkExport {
+    pub book: BookRef,
+    pub chapter: ChapterRef,
+    pub position: f64,
+    pub name: String,
+    pub note: Option<String>,
+    /// rfc3339
+    pub created: String,
+}
+
+/// everything a user did in the library, independent of the database ids
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserExport {
+    pub version: u32,
+    pub username: String,
+    /// rfc3339
+    pub exported_at: String,
+    pub progress: Vec<ProgressExport>,
+    pub history: Vec<DeviceProgressExport>,
+    pub bookmarks: Vec<BookmarkExport>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub progress: usize,
+    pub history: usize,
+    pub bookmarks: usize,
+    /// the entries not written, because they are older than the current data or already exist
+    pub skipped: usize,
+    /// the entries whose book or chapter is not in the library
+    pub unmatched: Vec<String>,
+}
+
+#[server]
+/// export the progress, history and bookmarks of a user as json
+pub async fn export_user_data(account_id: i32) -> Result<String, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::SelfOr(account_id, Permission::ManageUsers)).await?;
+    let db = db()?;
+    let data = crate::tools::export_user_data(account_id, &db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    Ok(serde_json::to_string_pretty(&data)?)
+}
+
+#[server]
+/// import the json created by [`export_user_data`] into a user, matching the books by title and
+/// author, nothing is written when `dry_run` is set
+pub async fn import_user_data(
+    account_id: i32,
+    data: String,
+    dry_run: bool,
+) -> Result<ImportReport, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::SelfOr(account_id, Permission::ManageUsers)).await?;
+    let db = db()?;
+    let data: UserExport =
+        serde_json::from_str(&data).map_err(|e| ServerFnError::new(e.to_string()))?;
+    crate::tools::import_user_data(account_id, data, dry_run, &db)
+        .await
+        .map_err(|e| ServerFnError::new(e.to_string()))
+}
@@ -0,0 +1,795 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::PageItems;
+
+/// stored by [`WebhookEvent::as_str`] in `webhook.events` and `webhook_delivery.event`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    BookAdded,
+    BookDeleted,
+    UserCreated,
+    BookFinished,
+    /// only sent by the test button
+    Ping,
+}
+
+impl WebhookEvent {
+    /// the events a webhook can subscribe to
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::BookAdded,
+        WebhookEvent::BookDeleted,
+        WebhookEvent::UserCreated,
+        WebhookEvent::BookFinished,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::BookAdded => "book.added",
+            WebhookEvent::BookDeleted => "book.deleted",
+            WebhookEvent::UserCreated => "user.created",
+            WebhookEvent::BookFinished => "book.finished",
+            WebhookEvent::Ping => "ping",
+        }
+    }
+
+    pub fn parse(event: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .chain([WebhookEvent::Ping])
+            .find(|e| e.as_str() == event)
+    }
+}
+
+/// the events of `webhook.events`, unknown events are skipped
+pub fn parse_events(events: &str) -> Vec<WebhookEvent> {
+    events
+        .split(',')
+        .filter_map(|e| WebhookEvent::parse(e.trim()))
+        .collect()
+}
+
+pub fn join_events(events: &[WebhookEvent]) -> String {
+    events
+        .iter()
+        .map(|e| e.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// stored by [`DeliveryStatus::as_str`] in `webhook_delivery.status`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryStatus {
+    /// waiting for its first attempt or a retry
+    Pending,
+    Delivered,
+    /// every attempt failed
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        [
+            DeliveryStatus::Pending,
+            DeliveryStatus::Delivered,
+            DeliveryStatus::Failed,
+        ]
+        .into_iter()
+        .find(|s| s.as_str() == status)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookDetail {
+    pub id: i32,
+    pub name: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    /// rfc3339
+    pub created: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryDetail {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    /// the json body
+    pub payload: String,
+    pub status: Option<DeliveryStatus>,
+    pub attempts: i32,
+    /// rfc3339, when a pending delivery is sent
+    pub next_attempt: Option<String>,
+    /// the http status of the last attempt
+    pub response_status: Option<i32>,
+    /// why the last attempt failed, empty when it did not
+    pub error: String,
+    /// rfc3339
+    pub created: String,
+    pub delivered: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use std::{
+        sync::OnceLock,
+        time::{Duration, Instant},
+    };
+
+    use hmac::{Hmac, Mac};
+    use leptos::ServerFnError;
+    use sea_orm::{
+        ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, IntoActiveModel,
+        QueryFilter, QueryOrder, QuerySelect, Set,
+    };
+    use serde_json::{json, Value};
+    use sha2::Sha256;
+    use tokio::sync::Notify;
+
+    use super::{parse_events, DeliveryDetail, DeliveryStatus, WebhookEvent};
+    use crate::{
+        entities::{prelude::*, *},
+        server_api::ssr::{date_from_rfc3339, date_to_rfc3339, now},
+    };
+
+    pub const SECRET_PREFIX: &str = "whsec_";
+    pub const EVENT_HEADER: &str = "X-Audiobookroom-Event";
+    pub const DELIVERY_HEADER: &str = "X-Audiobookroom-Delivery";
+    pub const TIMESTAMP_HEADER: &str = "X-Audiobookroom-Timestamp";
+    pub const SIGNATURE_HEADER: &str = "X-Audiobookroom-Signature";
+    /// attempts of a delivery before it fails
+    pub const MAX_ATTEMPTS: i32 = 6;
+    /// days the delivered and failed deliveries are kept
+    pub const DELIVERY_RETENTION_DAYS: i64 = 30;
+    /// how often the queue is checked, for the retries and the events of the command line tools
+    const POLL_INTERVAL: Duration = Duration::from_secs(15);
+    const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    /// deliveries sent in one pass
+    const BATCH: u64 = 20;
+
+    /// wakes the delivery task when an event is queued
+    fn wake() -> &'static Notify {
+        static WAKE: OnceLock<Notify> = OnceLock::new();
+        WAKE.get_or_init(Notify::new)
+    }
+
+    pub fn generate_secret() -> String {
+        use rand::{distributions::Alphanumeric, Rng};
+        let random: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        format!("{}{}", SECRET_PREFIX, random)
+    }
+
+    /// the `X-Audiobookroom-Signature` of a body, the hmac-sha256 of `{timestamp}.{body}`
+    pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("hmac accepts keys of any length");
+        mac.update(format!("{}.{}", timestamp, body).as_bytes());
+        let hex: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("sha256={}", hex)
+    }
+
+    /// the wait after the nth failed attempt, 30s, 2m, 8m, 32m and 2h8m. `None` when the delivery
+    /// has failed
+    pub fn retry_delay(attempts: i32) -> Option<chrono::Duration> {
+        if !(1..MAX_ATTEMPTS).contains(&attempts) {
+            return None;
+        }
+        Some(chrono::Duration::seconds(
+            30 * 4i64.pow(attempts as u32 - 1),
+        ))
+    }
+
+    pub fn payload(event: WebhookEvent, created: &str, data: Value) -> String {
+        json!({ "event": event.as_str(), "created": created, "data": data }).to_string()
+    }
+
+    /// the trimmed url, only http and https are sent to
+    pub fn check_url(url: &str) -> Result<String, ServerFnError> {
+        let url = url.trim();
+        match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(url.to_string()),
+            _ => Err(ServerFnError::new("Enter an http or https url.")),
+        }
+    }
+
+    pub fn user_data(account: &account::Model) -> Value {
+        json!({ "id": account.id, "name": account.name, "role": account.role_level })
+    }
+
+    pub async fn book_data(book: &music::Model, db: &DatabaseConnection) -> Result<Value, DbErr> {
+        let author = Author::find_by_id(book.author_id)
+            .one(db)
+            .await?
+            .map(|a| a.name)
+            .unwrap_or_default();
+        Ok(json!({
+            "id": book.id,
+            "name": book.name,
+            "author": author,
+            "library_id": book.library_id,
+            "chapters": book.chapters,
+        }))
+    }
+
+    fn date_after(delay: chrono::Duration) -> crate::ProgressDateType {
+        let date = chrono::Utc::now() + delay;
+        date_from_rfc3339(&date.to_rfc3339()).expect("rfc3339 of a date")
+    }
+
+    async fn queue(
+        webhook_id: i32,
+        event: WebhookEvent,
+        payload: String,
+        db: &DatabaseConnection,
+    ) -> Result<webhook_delivery::Model, DbErr> {
+        webhook_delivery::ActiveModel {
+            webhook_id: Set(webhook_id),
+            event: Set(event.as_str().to_string()),
+            payload: Set(payload),
+            status: Set(DeliveryStatus::Pending.as_str().to_string()),
+            attempts: Set(0),
+            next_attempt: Set(Some(now())),
+            response_status: Set(None),
+            error: Set(String::new()),
+            created: Set(now()),
+            delivered: Set(None),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// queue an event for the enabled webhooks subscribed to it, they are sent in the background
+    pub async fn trigger(
+        event: WebhookEvent,
+        data: Value,
+        db: &DatabaseConnection,
+    ) -> Result<(), DbErr> {
+        let webhooks: Vec<_> = Webhook::find()
+            .filter(webhook::Column::Enabled.eq(true))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|w| parse_events(&w.events).contains(&event))
+            .collect();
+        if webhooks.is_empty() {
+            return Ok(());
+        }
+        let payload = payload(event, &chrono::Utc::now().to_rfc3339(), data);
+        for webhook in webhooks {
+            queue(webhook.id, event, payload.clone(), db).await?;
+        }
+        wake().notify_one();
+        Ok(())
+    }
+
+    /// queue a ping to a webhook, enabled or not, and send it now
+    pub async fn ping(
+        webhook: &webhook::Model,
+        db: &DatabaseConnection,
+    ) -> Result<webhook_delivery::Model, DbErr> {
+        let data = json!({ "webhook": { "id": webhook.id, "name": webhook.name } });
+        let payload = payload(WebhookEvent::Ping, &chrono::Utc::now().to_rfc3339(), data);
+        let delivery = queue(webhook.id, WebhookEvent::Ping, payload, db).await?;
+        attempt(&reqwest::Client::new(), webhook, delivery, db).await
+    }
+
+    /// post a payload to a url, signed with the secret. returns the http status, or the status and
+    /// the reason of a failure
+    pub async fn send(
+        client: &reqwest::Client,
+        url: &str,
+        secret: &str,
+        event: &str,
+        delivery_id: i32,
+        body: &str,
+    ) -> Result<u16, (Option<u16>, String)> {
+        let timestamp = chrono::Utc::now().timestamp();
+        let response = client
+            .post(url)
+            .timeout(TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event)
+            .header(DELIVERY_HEADER, delivery_id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, signature(secret, timestamp, body))
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(status.as_u16())
+        } else {
+            Err((
+                Some(status.as_u16()),
+                format!("the receiver answered {}", status),
+            ))
+        }
+    }
+
+    /// send a delivery and record the attempt, a failed attempt is retried later
+    pub async fn attempt(
+        client: &reqwest::Client,
+        webhook: &webhook::Model,
+        delivery: webhook_delivery::Model,
+        db: &DatabaseConnection,
+    ) -> Result<webhook_delivery::Model, DbErr> {
+        let result = send(
+            client,
+            &webhook.url,
+            &webhook.secret,
+            &delivery.event,
+            delivery.id,
+            &delivery.payload,
+        )
+        .await;
+        let attempts = delivery.attempts + 1;
+        let mut delivery = delivery.into_active_model();
+        delivery.attempts = Set(attempts);
+        match result {
+            Ok(status) => {
+                delivery.status = Set(DeliveryStatus::Delivered.as_str().to_string());
+                delivery.response_status = Set(Some(i32::from(status)));
+                delivery.error = Set(String::new());
+                delivery.next_attempt = Set(None);
+                delivery.delivered = Set(Some(now()));
+            }
+            Err((status, error)) => {
+                tracing::warn!("webhook {} failed: {}", webhook.id, error);
+                delivery.response_status = Set(status.map(i32::from));
+                delivery.error = Set(error);
+                match retry_delay(attempts) {
+                    Some(delay) => delivery.next_attempt = Set(Some(date_after(delay))),
+                    None => {
+                        delivery.status = Set(DeliveryStatus::Failed.as_str().to_string());
+                        delivery.next_attempt = Set(None);
+                    }
+                }
+            }
+        }
+        delivery.update(db).await
+    }
+
+    /// send the pending deliveries that are due, returns how many were sent
+    pub async fn send_due(client: &reqwest::Client, db: &DatabaseConnection) -> Result<u64, DbErr> {
+        let due = WebhookDelivery::find()
+            .filter(webhook_delivery::Column::Status.eq(DeliveryStatus::Pending.as_str()))
+            .filter(webhook_delivery::Column::NextAttempt.lte(now()))
+            .order_by_asc(webhook_delivery::Column::Id)
+            .limit(BATCH)
+            .all(db)
+            .await?;
+        let sent = due.len() as u64;
+        for delivery in due {
+            match Webhook::find_by_id(delivery.webhook_id).one(db).await? {
+                Some(webhook) if webhook.enabled => {
+                    attempt(client, &webhook, delivery, db).await?;
+                }
+                _ => {
+                    let mut delivery = delivery.into_active_model();
+                    delivery.status = Set(DeliveryStatus::Failed.as_str().to_string());
+                    delivery.next_attempt = Set(None);
+                    delivery.error = Set("the webhook is disabled".to_string());
+                    delivery.update(db).await?;
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// send a delivery again from its first attempt
+    pub async fn retry(delivery_id: i32, db: &DatabaseConnection) -> Result<bool, DbErr> {
+        let Some(delivery) = WebhookDelivery::find_by_id(delivery_id).one(db).await? else {
+            return Ok(false);
+        };
+        let mut delivery = delivery.into_active_model();
+        delivery.status = Set(DeliveryStatus::Pending.as_str().to_string());
+        delivery.attempts = Set(0);
+        delivery.next_attempt = Set(Some(now()));
+        delivery.update(db).await?;
+        wake().notify_one();
+        Ok(true)
+    }
+
+    /// delete the delivered and failed deliveries older than the retention, returns how many
+    pub async fn prune_deliveries(db: &DatabaseConnection) -> Result<u64, DbErr> {
+        let cutoff = date_after(-chrono::Duration::days(DELIVERY_RETENTION_DAYS));
+        let result = WebhookDelivery::delete_many()
+            .filter(webhook_delivery::Column::Status.ne(DeliveryStatus::Pending.as_str()))
+            .filter(webhook_delivery::Column::Created.lt(cutoff))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// send the deliveries in the background, right after an event is queued and every few
+    /// seconds for the retries. the old deliveries are pruned once a day
+    pub fn spawn_delivery(db: DatabaseConnection) {
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let mut pruned: Option<Instant> = None;
+            loop {
+                match send_due(&client, &db).await {
+                    // there may be more due
+                    Ok(BATCH) => continue,
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("failed to send the webhooks: {}", e),
+                }
+                if !pruned.is_some_and(|p| p.elapsed() < PRUNE_INTERVAL) {
+                    pruned = Some(Instant::now());
+                    match prune_deliveries(&db).await {
+                        Ok(0) => {}
+                        Ok(n) => tracing::info!("pruned {} webhook deliveries", n),
+                        Err(e) => tracing::error!("failed to prune the webhook deliveries: {}", e),
+                    }
+                }
+                tokio::select! {
+                    _ = wake().notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+        });
+    }
+
+    impl From<webhook_delivery::Model> for DeliveryDetail {
+        fn from(d: webhook_delivery::Model) -> Self {
+            Self {
+                id: d.id,
+                webhook_id: d.webhook_id,
+                event: d.event,
+                payload: d.payload,
+                status: DeliveryStatus::parse(&d.status),
+                attempts: d.attempts,
+                next_attempt: d.next_attempt.as_ref().map(date_to_rfc3339),
+                response_status: d.response_status,
+                error: d.error,
+                created: date_to_rfc3339(&d.created),
+                delivered: d.delivered.as_ref().map(date_to_rfc3339),
+            }
+        }
+    }
+}
+
+#[server]
+/// the webhooks, for the admins
+pub async fn list_webhooks() -> Result<Vec<WebhookDetail>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let webhooks = Webhook::find()
+        .all(&db()?)
+        .await?
+        .into_iter()
+        .map(|w| WebhookDetail {
+            id: w.id,
+            name: w.name,
+            url: w.url,
+            events: parse_events(&w.events),
+            enabled: w.enabled,
+            created: date_to_rfc3339(&w.created),
+        })
+        .collect();
+    Ok(webhooks)
+}
+
+#[server]
+/// add a webhook for some events.
+///
+/// returns the secret the payloads are signed with, it can not be shown again
+pub async fn create_webhook(
+    name: String,
+    url: String,
+    events: Vec<WebhookEvent>,
+) -> Result<String, ServerFnError> {
+    use self::ssr::{check_url, generate_secret};
+    use super::audit::{ssr::audit, AuditAction};
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::Set;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(ServerFnError::new("The webhook name is empty."));
+    }
+    let url = check_url(&url)?;
+    let events: Vec<_> = WebhookEvent::ALL
+        .into_iter()
+        .filter(|e| events.contains(e))
+        .collect();
+    if events.is_empty() {
+        return Err(ServerFnError::new("Select at least one event."));
+    }
+    let db = db()?;
+    let secret = generate_secret();
+    let summary = format!("{}, {}, {}", name, url, join_events(&events));
+    let webhook = Webhook::insert(webhook::ActiveModel {
+        name: Set(name),
+        url: Set(url),
+        secret: Set(secret.clone()),
+        events: Set(join_events(&events)),
+        enabled: Set(true),
+        created: Set(now()),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    audit(
+        AuditAction::WebhookSaved,
+        &format!("webhook {}", webhook.last_insert_id),
+        "",
+        &summary,
+        &db,
+    )
+    .await?;
+    Ok(secret)
+}
+
+#[server]
+/// a disabled webhook keeps its deliveries but gets no new ones
+pub async fn set_webhook_enabled(webhook_id: i32, enabled: bool) -> Result<(), ServerFnError> {
+    use super::audit::{ssr::audit, AuditAction};
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::Set;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let webhook = Webhook::find_by_id(webhook_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Webhook not found"))?;
+    let state = |enabled: bool| match enabled {
+        true => "enabled",
+        false => "disabled",
+    };
+    Webhook::update(webhook::ActiveModel {
+        id: Set(webhook_id),
+        enabled: Set(enabled),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    audit(
+        AuditAction::WebhookSaved,
+        &format!("webhook {}", webhook_id),
+        &format!("{}, {}", webhook.name, state(webhook.enabled)),
+        &format!("{}, {}", webhook.name, state(enabled)),
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server]
+/// delete a webhook and its deliveries
+pub async fn delete_webhook(webhook_id: i32) -> Result<(), ServerFnError> {
+    use super::audit::{ssr::audit, AuditAction};
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let webhook = Webhook::find_by_id(webhook_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Webhook not found"))?;
+    WebhookDelivery::delete_many()
+        .filter(webhook_delivery::Column::WebhookId.eq(webhook_id))
+        .exec(&db)
+        .await?;
+    Webhook::delete_by_id(webhook_id).exec(&db).await?;
+    audit(
+        AuditAction::WebhookDeleted,
+        &format!("webhook {}", webhook_id),
+        &format!("{}, {}, {}", webhook.name, webhook.url, webhook.events),
+        "",
+        &db,
+    )
+    .await?;
+    Ok(())
+}
+
+#[server]
+/// send a `ping` to a webhook now, to check the receiver
+pub async fn test_webhook(webhook_id: i32) -> Result<DeliveryDetail, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let webhook = Webhook::find_by_id(webhook_id)
+        .one(&db)
+        .await?
+        .ok_or(ServerFnError::new("Webhook not found"))?;
+    Ok(self::ssr::ping(&webhook, &db).await?.into())
+}
+
+#[server]
+/// the deliveries, newest first, optionally of one webhook
+pub async fn list_webhook_deliveries(
+    page_num: u64,
+    max_item: u64,
+    webhook_id: Option<i32>,
+) -> Result<PageItems<DeliveryDetail>, ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    use crate::entities::*;
+    use sea_orm::QueryOrder;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    let db = db()?;
+    let mut query = WebhookDelivery::find().order_by_desc(webhook_delivery::Column::Id);
+    if let Some(webhook_id) = webhook_id {
+        query = query.filter(webhook_delivery::Column::WebhookId.eq(webhook_id));
+    }
+    let page = fetch_page(query, page_num, max_item, &db).await?;
+    Ok(page.map(Into::into))
+}
+
+#[server]
+/// send a delivery again, from its first attempt
+pub async fn retry_webhook_delivery(delivery_id: i32) -> Result<(), ServerFnError> {
+    use super::permission::{authorize, Access, Permission};
+    use super::ssr::*;
+    authorize(Access::Permission(Permission::ManageUsers)).await?;
+    if !self::ssr::retry(delivery_id, &db()?).await? {
+        return Err(ServerFnError::new("Delivery not found"));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{ssr::*, *};
+
+    #[test]
+    fn events_roundtrip() {
+        let events = [WebhookEvent::BookAdded, WebhookEvent::BookFinished];
+        assert_eq!(join_events(&events), "book.added,book.finished");
+        assert_eq!(parse_events("book.added, book.finished,unknown"), events);
+        assert_eq!(parse_events(""), []);
+        assert_eq!(WebhookEvent::parse("ping"), Some(WebhookEvent::Ping));
+        assert!(!WebhookEvent::ALL.contains(&WebhookEvent::Ping));
+    }
+
+    #[test]
+    fn signs_the_timestamp_and_body() {
+        // the hmac-sha256 of "1700000000.{}" with the key "secret"
+        assert_eq!(
+            signature("secret", 1700000000, "{}"),
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+        assert_ne!(
+            signature("secret", 1700000001, "{}"),
+            signature("secret", 1700000000, "{}")
+        );
+    }
+
+    #[test]
+    fn backs_off_then_fails() {
+        let delays: Vec<_> = (1..MAX_ATTEMPTS)
+            .map(|n| retry_delay(n).unwrap().num_seconds())
+            .collect();
+        assert_eq!(delays, [30, 120, 480, 1920, 7680]);
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+        assert_eq!(retry_delay(0), None);
+    }
+
+    #[test]
+    fn only_http_urls() {
+        assert_eq!(
+            check_url(" http://localhost:9000/hook ").unwrap(),
+            "http://localhost:9000/hook"
+        );
+        assert!(check_url("https://chat.example.com/hooks/abc").is_ok());
+        assert!(check_url("file:///etc/passwd").is_err());
+        assert!(check_url("not a url").is_err());
+    }
+
+    /// a receiver on localhost that answers `status` and returns the request it got
+    async fn receiver(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| {
+                            let (name, value) = l.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let response = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status);
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn delivers_to_a_local_receiver() {
+        let (url, request) = receiver("204 No Content").await;
+        let body = payload(
+            WebhookEvent::BookAdded,
+            "2024-03-16T10:00:00+00:00",
+            serde_json::json!({ "id": 7 }),
+        );
+        let client = reqwest::Client::new();
+        let status = send(&client, &url, "whsec_test", "book.added", 3, &body).await;
+        assert_eq!(status, Ok(204));
+
+        let request = request.await.unwrap();
+        let (head, received) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /hook HTTP/1.1"));
+        assert_eq!(received, body);
+        let header = |name: &str| {
+            head.lines()
+                .find_map(|l| {
+                    let (n, v) = l.split_once(':')?;
+                    n.eq_ignore_ascii_case(name).then(|| v.trim().to_string())
+                })
+                .unwrap()
+        };
+        assert_eq!(header(EVENT_HEADER), "book.added");
+        assert_eq!(header(DELIVERY_HEADER), "3");
+        let timestamp: i64 = header(TIMESTAMP_HEADER).parse().unwrap();
+        assert_eq!(
+            header(SIGNATURE_HEADER),
+            signature("whsec_test", timestamp, &body)
+        );
+        let json: serde_json::Value = serde_json::from_str(received).unwrap();
+        assert_eq!(json["event"], "book.added");
+        assert_eq!(json["data"]["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn reports_a_failing_receiver() {
+        let (url, request) = receiver("500 Internal Server Error").await;
+        let client = reqwest::Client::new();
+        let result = send(&client, &url, "whsec_test", "ping", 1, "{}").await;
+        request.await.unwrap();
+        let (status, error) = result.unwrap_err();
+        assert_eq!(status, Some(500));
+        assert!(error.contains("500"));
+    }
+}
@@ -0,0 +1,1044 @@
+//! a subsonic compatible api at `/rest`, for the music players that speak it.
+//!
+//! authors are artists, books are albums and chapters are songs. the players log in with the user
+//! name and an app password, as a salted token or as the password, or with an api token as the
+//! `apiKey` of opensubsonic. the play queue of the players is the progress of the books, and a
+//! played chapter moves the progress to the start of the next one.
+
+use std::{net::SocketAddr, str::FromStr};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, FromRequest, Path, Query, Request, State},
+    http::{header::CONTENT_TYPE, Method},
+    response::{IntoResponse, Response},
+    routing::get,
+    Form, Router,
+};
+use md5::{Digest, Md5};
+use rand::seq::SliceRandom;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, LoaderTrait, QueryFilter, QueryOrder,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    abs::mime_type,
+    entities::{prelude::*, *},
+    fetchbook::{chapter_file, cover_file, serve_file},
+    server_api::{
+        app_password::ssr::user_from_app_password,
+        auth_event::{
+            ssr::{check_throttle, client_ip, record},
+            AuthEventKind,
+        },
+        authors::AuthorDetail,
+        book::{
+            ssr::{book_chapters, visible_books},
+            BookDetail, ChapterDetail,
+        },
+        library::ssr::{find_visible_book, find_visible_chapter, visible_library_ids},
+        permission::Permission,
+        progress::{save_progress, ProgressUpdate},
+        ssr::date_to_rfc3339,
+        token::ssr::user_from_token,
+        User,
+    },
+    state::AppState,
+    xml::escape_xml,
+};
+
+/// the version of the subsonic api
+pub const API_VERSION: &str = "1.16.1";
+const SOURCE: &str = "subsonic";
+const XMLNS: &str = "http://subsonic.org/restapi";
+const DEFAULT_LIST_SIZE: usize = 10;
+const MAX_LIST_SIZE: usize = 500;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/:method", get(handle).post(handle))
+}
+
+/// the errors of the subsonic api, they are sent with the status `200 OK` like the real server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubsonicError {
+    pub code: u32,
+    pub message: String,
+}
+
+impl SubsonicError {
+    pub fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn missing(param: &str) -> Self {
+        Self::new(10, format!("Required parameter is missing: {}", param))
+    }
+
+    pub fn invalid(param: &str) -> Self {
+        Self::new(0, format!("Invalid parameter: {}", param))
+    }
+
+    pub fn wrong_credentials() -> Self {
+        Self::new(40, "Wrong username or password")
+    }
+
+    pub fn not_authorized(permission: &str) -> Self {
+        Self::new(
+            50,
+            format!("You do not have the permission: {}", permission),
+        )
+    }
+
+    /// hidden books are not found either, so their ids can not be probed
+    pub fn not_found(what: &str) -> Self {
+        Self::new(70, format!("{} not found", what))
+    }
+
+    /// the details are only logged
+    pub fn internal(e: impl std::fmt::Display) -> Self {
+        tracing::error!("subsonic: {}", e);
+        Self::new(0, "Internal server error")
+    }
+}
+
+impl From<DbErr> for SubsonicError {
+    fn from(e: DbErr) -> Self {
+        Self::internal(e)
+    }
+}
+
+/// the parameters of a request, a name can be repeated
+#[derive(Debug, Default)]
+pub struct Params(pub Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, SubsonicError> {
+        self.get(name).ok_or_else(|| SubsonicError::missing(name))
+    }
+
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, SubsonicError> {
+        self.get(name)
+            .map(|v| v.parse().map_err(|_| SubsonicError::invalid(name)))
+            .transpose()
+    }
+}
+
+/// the method of a path like `getAlbum.view`
+pub fn method_name(path: &str) -> &str {
+    path.strip_suffix(".view").unwrap_or(path)
+}
+
+/// the token of the token login, the md5 of the password and the salt in lowercase hex
+pub fn salted_token(password: &str, salt: &str) -> String {
+    Md5::new()
+        .chain_update(password.as_bytes())
+        .chain_update(salt.as_bytes())
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// the password of the `p` parameter, it is plain or hex encoded after `enc:`
+pub fn decode_password(p: &str) -> Option<String> {
+    let Some(hex) = p.strip_prefix("enc:") else {
+        return Some(p.to_string());
+    };
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// the ids of the api have the kind in front, because the players mix them up
+pub fn artist_id(id: i32) -> String {
+    format!("ar-{}", id)
+}
+
+pub fn album_id(id: i32) -> String {
+    format!("al-{}", id)
+}
+
+pub fn song_id(id: i32) -> String {
+    format!("tr-{}", id)
+}
+
+/// the number of an id with `prefix` like `al-`
+pub fn parse_id(prefix: &str, id: &str) -> Option<i32> {
+    id.strip_prefix(prefix)?.parse().ok()
+}
+
+fn seconds(length: Option<f64>) -> i64 {
+    length.filter(|l| l.is_finite()).unwrap_or(0.0).round() as i64
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Artist {
+    pub id: String,
+    pub name: String,
+    pub album_count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub album: Vec<Album>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Album {
+    pub id: String,
+    pub name: String,
+    pub artist: String,
+    pub artist_id: String,
+    pub cover_art: String,
+    pub song_count: i32,
+    /// seconds
+    pub duration: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub song: Vec<Song>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Song {
+    pub id: String,
+    pub parent: String,
+    pub is_dir: bool,
+    pub title: String,
+    pub album: String,
+    pub artist: String,
+    pub track: i32,
+    pub cover_art: String,
+    pub content_type: String,
+    pub suffix: String,
+    /// seconds
+    pub duration: i64,
+    pub path: String,
+    pub album_id: String,
+    pub artist_id: String,
+    /// the subsonic media type
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// the opensubsonic media type
+    pub media_type: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayQueue {
+    pub current: String,
+    /// milliseconds in the current song
+    pub position: i64,
+    pub username: String,
+    pub changed: String,
+    pub changed_by: String,
+    pub entry: Vec<Song>,
+}
+
+pub fn album(book: &BookDetail, author: &AuthorDetail) -> Album {
+    Album {
+        id: album_id(book.id),
+        name: book.name.clone(),
+        artist: author.name.clone(),
+        artist_id: artist_id(author.id),
+        cover_art: album_id(book.id),
+        song_count: book.chapters,
+        duration: seconds(book.total_time),
+        song: vec![],
+    }
+}
+
+pub fn song(chapter: &ChapterDetail, book: &BookDetail, author: &AuthorDetail) -> Song {
+    let suffix = chapter
+        .chapter_url
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .unwrap_or_default();
+    Song {
+        id: song_id(chapter.id),
+        parent: album_id(book.id),
+        is_dir: false,
+        title: chapter.chapter_name.clone(),
+        album: book.name.clone(),
+        artist: author.name.clone(),
+        track: chapter.chapter_num,
+        cover_art: album_id(book.id),
+        content_type: mime_type(&chapter.chapter_url).to_string(),
+        suffix,
+        duration: seconds(chapter.chapter_length),
+        path: chapter.chapter_url.clone(),
+        album_id: album_id(book.id),
+        artist_id: artist_id(author.id),
+        kind: "audiobook".to_string(),
+        media_type: "song".to_string(),
+    }
+}
+
+/// the index letter of an artist, `#` for the names that do not start with a letter
+pub fn index_name(name: &str) -> String {
+    match name.chars().next() {
+        Some(c) if c.is_alphabetic() => c.to_uppercase().collect(),
+        _ => "#".to_string(),
+    }
+}
+
+/// the body of a response, with the key and the value of the method when there is one
+pub fn envelope(result: Result<Option<(&str, Value)>, SubsonicError>) -> Value {
+    let mut body = Map::new();
+    body.insert("status".to_string(), json!("ok"));
+    body.insert("version".to_string(), json!(API_VERSION));
+    body.insert("type".to_string(), json!("audiobookroom"));
+    body.insert(
+        "serverVersion".to_string(),
+        json!(env!("CARGO_PKG_VERSION")),
+    );
+    body.insert("openSubsonic".to_string(), json!(true));
+    match result {
+        Ok(Some((key, value))) => {
+            body.insert(key.to_string(), value);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            body.insert("status".to_string(), json!("failed"));
+            body.insert(
+                "error".to_string(),
+                json!({ "code": e.code, "message": e.message }),
+            );
+        }
+    }
+    json!({ "subsonic-response": body })
+}
+
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// an element of the xml format, the scalars of an object are its attributes and the objects and
+/// arrays are its children, like the json format is derived from the xml one
+fn write_xml(name: &str, value: &Value, out: &mut String) {
+    out.push('<');
+    out.push_str(name);
+    let Value::Object(map) = value else {
+        out.push('>');
+        out.push_str(&escape_xml(&scalar_text(value).unwrap_or_default()));
+        out.push_str(&format!("</{}>", name));
+        return;
+    };
+    for (key, value) in map {
+        if let Some(text) = scalar_text(value) {
+            out.push_str(&format!(" {}=\"{}\"", key, escape_xml(&text)));
+        }
+    }
+    let mut children = String::new();
+    for (key, value) in map {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    write_xml(key, item, &mut children);
+                }
+            }
+            Value::Object(_) => write_xml(key, value, &mut children),
+            _ => {}
+        }
+    }
+    if children.is_empty() {
+        out.push_str("/>");
+    } else {
+        out.push('>');
+        out.push_str(&children);
+        out.push_str(&format!("</{}>", name));
+    }
+}
+
+/// the xml format of a response [`envelope`]
+pub fn to_xml(envelope: &Value) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    if let Some(Value::Object(body)) = envelope.get("subsonic-response") {
+        let mut body = body.clone();
+        body.insert("xmlns".to_string(), json!(XMLNS));
+        write_xml("subsonic-response", &Value::Object(body), &mut out);
+    }
+    out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Xml,
+    Json,
+}
+
+impl Format {
+    fn of(params: &Params) -> Self {
+        match params.get("f") {
+            Some("json") => Format::Json,
+            _ => Format::Xml,
+        }
+    }
+
+    fn respond(self, result: Result<Option<(&str, Value)>, SubsonicError>) -> Response {
+        let envelope = envelope(result);
+        match self {
+            Format::Json => axum::Json(envelope).into_response(),
+            Format::Xml => (
+                [(CONTENT_TYPE, "text/xml; charset=utf-8")],
+                to_xml(&envelope),
+            )
+                .into_response(),
+        }
+    }
+}
+
+enum Reply {
+    Empty,
+    Data(&'static str, Value),
+    File(Response),
+}
+
+fn data(key: &'static str, value: impl Serialize) -> Result<Reply, SubsonicError> {
+    Ok(Reply::Data(
+        key,
+        serde_json::to_value(value).map_err(SubsonicError::internal)?,
+    ))
+}
+
+/// `/rest/:method`, the parameters are in the query or in a form for `POST`
+async fn handle(
+    State(db): State<DatabaseConnection>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Path(method): Path<String>,
+    request: Request,
+) -> Response {
+    let mut params = Params(
+        Query::<Vec<(String, String)>>::try_from_uri(request.uri())
+            .map(|q| q.0)
+            .unwrap_or_default(),
+    );
+    let ip = client_ip(peer.ip(), request.headers()).to_string();
+    // the files are served with the range of the original request
+    let mut file_request = Request::new(Body::empty());
+    *file_request.headers_mut() = request.headers().clone();
+    if request.method() == Method::POST {
+        if let Ok(Form(form)) = Form::<Vec<(String, String)>>::from_request(request, &()).await {
+            params.0.extend(form);
+        }
+    }
+    let format = Format::of(&params);
+    let user = match authenticate(&params, &ip, &db).await {
+        Ok(user) => user,
+        Err(e) => return format.respond(Err(e)),
+    };
+    match call(method_name(&method), &user, &params, file_request, &db).await {
+        Ok(Reply::Empty) => format.respond(Ok(None)),
+        Ok(Reply::Data(key, value)) => format.respond(Ok(Some((key, value)))),
+        Ok(Reply::File(response)) => response,
+        Err(e) => format.respond(Err(e)),
+    }
+}
+
+/// the user of the credentials of a request.
+///
+/// only the failures are recorded in the login events, the players send the credentials with every
+/// request
+async fn authenticate(
+    params: &Params,
+    ip: &str,
+    db: &DatabaseConnection,
+) -> Result<User, SubsonicError> {
+    if let Some(key) = params.get("apiKey") {
+        if params.get("u").is_some() {
+            return Err(SubsonicError::new(
+                43,
+                "Multiple conflicting authentication mechanisms provided",
+            ));
+        }
+        return user_from_token(key, db)
+            .await?
+            .ok_or_else(|| SubsonicError::new(44, "Invalid API key"));
+    }
+    let username = params.required("u")?;
+    check_throttle(username, ip, db)
+        .await
+        .map_err(|e| match e {
+            leptos::ServerFnError::ServerError(message) => SubsonicError::new(0, message),
+            e => SubsonicError::internal(e),
+        })?;
+    let user = match (params.get("t"), params.get("s"), params.get("p")) {
+        (Some(token), Some(salt), _) => {
+            let token = token.to_lowercase();
+            user_from_app_password(username, |p| salted_token(p, salt) == token, db).await?
+        }
+        (_, _, Some(password)) => match decode_password(password) {
+            Some(password) => user_from_app_password(username, |p| p == password, db).await?,
+            None => None,
+        },
+        _ => return Err(SubsonicError::missing("p")),
+    };
+    match user {
+        Some(user) => Ok(user),
+        None => {
+            record(AuthEventKind::LoginFailure, username, ip, SOURCE, db).await?;
+            Err(SubsonicError::wrong_credentials())
+        }
+    }
+}
+
+async fn call(
+    method: &str,
+    user: &User,
+    params: &Params,
+    file_request: Request,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    match method {
+        "ping" => Ok(Reply::Empty),
+        "getLicense" => data("license", json!({ "valid": true })),
+        "getOpenSubsonicExtensions" => data(
+            "openSubsonicExtensions",
+            json!([
+                { "name": "apiKeyAuthentication", "versions": [1] },
+                { "name": "formPost", "versions": [1] },
+            ]),
+        ),
+        "getMusicFolders" => get_music_folders(user, db).await,
+        "getArtists" => get_artists(user, params, db).await,
+        "getArtist" => get_artist(user, params, db).await,
+        "getAlbumList2" => get_album_list(user, params, db).await,
+        "getAlbum" => get_album(user, params, db).await,
+        "getSong" => get_song(user, params, db).await,
+        "stream" | "download" => stream(user, params, file_request, db).await,
+        "getCoverArt" => get_cover_art(user, params, file_request, db).await,
+        "savePlayQueue" => save_play_queue(user, params, db).await,
+        "getPlayQueue" => get_play_queue(user, db).await,
+        "scrobble" => scrobble(user, params, db).await,
+        method => Err(SubsonicError::new(
+            0,
+            format!("Method not supported: {}", method),
+        )),
+    }
+}
+
+/// the books the user can see with their authors, of one library with `musicFolderId`
+async fn visible_albums(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Vec<(BookDetail, AuthorDetail)>, SubsonicError> {
+    let mut books = visible_books(user, db).await?;
+    if let Some(library_id) = params.parse::<i32>("musicFolderId")? {
+        books = books.filter(music::Column::LibraryId.eq(library_id));
+    }
+    let books = books.all(db).await?;
+    let authors = books.load_one(Author, db).await?;
+    Ok(books
+        .into_iter()
+        .zip(authors)
+        .filter_map(|(book, author)| Some((book.into(), author?.into())))
+        .collect())
+}
+
+/// the book with the id of the `id` parameter when the user can see it
+async fn visible_book(
+    user: &User,
+    id: Option<i32>,
+    db: &DatabaseConnection,
+) -> Result<music::Model, SubsonicError> {
+    let id = id.ok_or_else(|| SubsonicError::not_found("album"))?;
+    find_visible_book(user, id, db)
+        .await
+        .map_err(|_| SubsonicError::not_found("album"))
+}
+
+/// a visible book with its author
+async fn visible_album(
+    user: &User,
+    id: Option<i32>,
+    db: &DatabaseConnection,
+) -> Result<(BookDetail, AuthorDetail), SubsonicError> {
+    let book = visible_book(user, id, db).await?;
+    let author = Author::find_by_id(book.author_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| SubsonicError::not_found("artist"))?;
+    Ok((book.into(), author.into()))
+}
+
+/// the songs of a book in order
+async fn songs(
+    book: &BookDetail,
+    author: &AuthorDetail,
+    db: &DatabaseConnection,
+) -> Result<Vec<Song>, DbErr> {
+    Ok(book_chapters(book.id)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| song(&c.into(), book, author))
+        .collect())
+}
+
+/// the chapter of a `tr-` id when the user can see it
+async fn visible_song(
+    user: &User,
+    id: &str,
+    db: &DatabaseConnection,
+) -> Result<(chapter::Model, music::Model), SubsonicError> {
+    let id = parse_id("tr-", id).ok_or_else(|| SubsonicError::not_found("song"))?;
+    find_visible_chapter(user, id, db)
+        .await
+        .map_err(|_| SubsonicError::not_found("song"))
+}
+
+async fn get_music_folders(user: &User, db: &DatabaseConnection) -> Result<Reply, SubsonicError> {
+    let ids = visible_library_ids(user, db).await?;
+    let folders: Vec<Value> = Library::find()
+        .filter(library::Column::Id.is_in(ids))
+        .order_by_asc(library::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|l| json!({ "id": l.id, "name": l.name }))
+        .collect();
+    data("musicFolders", json!({ "musicFolder": folders }))
+}
+
+/// the authors with a book the user can see
+fn artists(albums: &[(BookDetail, AuthorDetail)]) -> Vec<Artist> {
+    let mut artists: Vec<Artist> = vec![];
+    for (_, author) in albums {
+        match artists.iter_mut().find(|a| a.id == artist_id(author.id)) {
+            Some(artist) => artist.album_count += 1,
+            None => artists.push(Artist {
+                id: artist_id(author.id),
+                name: author.name.clone(),
+                album_count: 1,
+                album: vec![],
+            }),
+        }
+    }
+    artists.sort_by_key(|a| a.name.to_lowercase());
+    artists
+}
+
+async fn get_artists(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let albums = visible_albums(user, params, db).await?;
+    let mut index: Vec<Value> = vec![];
+    let mut current: Option<(String, Vec<Artist>)> = None;
+    for artist in artists(&albums) {
+        let name = index_name(&artist.name);
+        match &mut current {
+            Some((letter, artists)) if *letter == name => artists.push(artist),
+            _ => {
+                if let Some((letter, artists)) = current.take() {
+                    index.push(json!({ "name": letter, "artist": artists }));
+                }
+                current = Some((name, vec![artist]));
+            }
+        }
+    }
+    if let Some((letter, artists)) = current {
+        index.push(json!({ "name": letter, "artist": artists }));
+    }
+    data("artists", json!({ "ignoredArticles": "", "index": index }))
+}
+
+async fn get_artist(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let id = parse_id("ar-", params.required("id")?);
+    let mut albums = visible_albums(user, &Params::default(), db).await?;
+    albums.retain(|(_, author)| Some(author.id) == id);
+    albums.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    let mut artist = artists(&albums)
+        .pop()
+        .ok_or_else(|| SubsonicError::not_found("artist"))?;
+    artist.album = albums.iter().map(|(b, a)| album(b, a)).collect();
+    data("artist", artist)
+}
+
+/// `getAlbumList2`, there are no ratings, stars, years or genres so those lists are empty. the
+/// books are not counted when played, `frequent` is the same as `recent`
+async fn get_album_list(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let kind = params.required("type")?;
+    let size = params
+        .parse::<usize>("size")?
+        .unwrap_or(DEFAULT_LIST_SIZE)
+        .min(MAX_LIST_SIZE);
+    let offset = params.parse::<usize>("offset")?.unwrap_or(0);
+    let mut albums = visible_albums(user, params, db).await?;
+    match kind {
+        "alphabeticalByName" => {
+            albums.sort_by_key(|(b, _)| b.name.to_lowercase());
+        }
+        "alphabeticalByArtist" => {
+            albums.sort_by_key(|(b, a)| (a.name.to_lowercase(), b.name.to_lowercase()));
+        }
+        // the ids grow with the books added
+        "newest" => albums.sort_by_key(|(b, _)| std::cmp::Reverse(b.id)),
+        "recent" | "frequent" => {
+            let played: Vec<i32> = Progress::find()
+                .filter(progress::Column::AccountId.eq(user.id))
+                .order_by_desc(progress::Column::Update)
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|p| p.music_id)
+                .collect();
+            albums.retain(|(b, _)| played.contains(&b.id));
+            albums.sort_by_key(|(b, _)| played.iter().position(|id| *id == b.id));
+        }
+        "random" => albums.shuffle(&mut rand::thread_rng()),
+        "highest" | "starred" | "byYear" | "byGenre" => albums.clear(),
+        _ => return Err(SubsonicError::invalid("type")),
+    }
+    let albums: Vec<Album> = albums
+        .iter()
+        .skip(offset)
+        .take(size)
+        .map(|(b, a)| album(b, a))
+        .collect();
+    data("albumList2", json!({ "album": albums }))
+}
+
+async fn get_album(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let id = parse_id("al-", params.required("id")?);
+    let (book, author) = visible_album(user, id, db).await?;
+    let mut result = album(&book, &author);
+    result.song = songs(&book, &author, db).await?;
+    data("album", result)
+}
+
+async fn get_song(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let (chapter, book) = visible_song(user, params.required("id")?, db).await?;
+    let (book, author) = visible_album(user, Some(book.id), db).await?;
+    data("song", song(&chapter.into(), &book, &author))
+}
+
+/// `stream` and `download`, the files are not transcoded
+async fn stream(
+    user: &User,
+    params: &Params,
+    file_request: Request,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    if !user.can(Permission::Download) {
+        return Err(SubsonicError::not_authorized("download"));
+    }
+    let (chapter, book) = visible_song(user, params.required("id")?, db).await?;
+    let file = chapter_file(db, &chapter, &book)
+        .await
+        .map_err(|_| SubsonicError::not_found("file"))?;
+    Ok(Reply::File(serve_file(file, file_request).await))
+}
+
+/// `getCoverArt`, the cover image in the folder of the book of an album or a song
+async fn get_cover_art(
+    user: &User,
+    params: &Params,
+    file_request: Request,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let id = params.required("id")?;
+    let book_id = match parse_id("al-", id) {
+        Some(id) => Some(id),
+        None => visible_song(user, id, db).await.ok().map(|(_, b)| b.id),
+    };
+    let book = visible_book(user, book_id, db).await?;
+    let file = cover_file(db, &book)
+        .await
+        .map_err(|_| SubsonicError::not_found("cover art"))?;
+    Ok(Reply::File(serve_file(file, file_request).await))
+}
+
+/// the device of the progress saved by a player, from the client name `c`
+fn device(params: &Params) -> (String, String) {
+    let client = params.get("c").unwrap_or(SOURCE);
+    (format!("subsonic-{}", client), client.to_string())
+}
+
+/// `savePlayQueue`, the position in the current song is the progress of its book
+async fn save_play_queue(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    let Some(current) = params.get("current") else {
+        return Ok(Reply::Empty);
+    };
+    let position = params.parse::<i64>("position")?.unwrap_or(0).max(0);
+    let (chapter, book) = visible_song(user, current, db).await?;
+    let (device_id, device_name) = device(params);
+    let update = ProgressUpdate {
+        music_id: book.id,
+        chapter_id: chapter.id,
+        progress: position as f64 / 1000.0,
+        device_id,
+        device_name,
+        client_update: chrono::Utc::now().timestamp_millis(),
+    };
+    save_progress(db, user.id, update)
+        .await
+        .map_err(SubsonicError::internal)?;
+    Ok(Reply::Empty)
+}
+
+/// `getPlayQueue`, the chapters of the last book played with its progress
+async fn get_play_queue(user: &User, db: &DatabaseConnection) -> Result<Reply, SubsonicError> {
+    let progress = Progress::find()
+        .filter(progress::Column::AccountId.eq(user.id))
+        .order_by_desc(progress::Column::Update)
+        .all(db)
+        .await?;
+    for progress in progress {
+        let Ok((book, author)) = visible_album(user, Some(progress.music_id), db).await else {
+            continue;
+        };
+        let entry = songs(&book, &author, db).await?;
+        return data(
+            "playQueue",
+            PlayQueue {
+                current: song_id(progress.chapter_id),
+                position: (progress.progress * 1000.0).round() as i64,
+                username: user.username.clone(),
+                changed: date_to_rfc3339(&progress.update),
+                changed_by: progress.device_id,
+                entry,
+            },
+        );
+    }
+    Ok(Reply::Empty)
+}
+
+/// `scrobble`, a played song moves the progress to the start of the next chapter, or to the end
+/// of the last one. the progress never goes back and the now playing notifications are ignored
+async fn scrobble(
+    user: &User,
+    params: &Params,
+    db: &DatabaseConnection,
+) -> Result<Reply, SubsonicError> {
+    if params.get("submission") == Some("false") {
+        return Ok(Reply::Empty);
+    }
+    let times: Vec<&str> = params.all("time").collect();
+    for (i, id) in params.all("id").enumerate() {
+        let (chapter, book) = visible_song(user, id, db).await?;
+        let client_update = match times.get(i) {
+            Some(time) => time.parse().map_err(|_| SubsonicError::invalid("time"))?,
+            None => chrono::Utc::now().timestamp_millis(),
+        };
+        let chapters = book_chapters(book.id).all(db).await?;
+        let Some(played) = chapters.iter().position(|c| c.id == chapter.id) else {
+            continue;
+        };
+        let (target, progress) = match chapters.get(played + 1) {
+            Some(_) => (played + 1, 0.0),
+            None => (played, chapter.chapter_length.unwrap_or(0.0)),
+        };
+        let saved = Progress::find_by_id((user.id, book.id)).one(db).await?;
+        let ahead = saved.is_some_and(|p| {
+            let chapter = chapters.iter().position(|c| c.id == p.chapter_id);
+            chapter.is_some_and(|c| (c, p.progress) >= (target, progress))
+        });
+        if ahead {
+            continue;
+        }
+        let (device_id, device_name) = device(params);
+        let update = ProgressUpdate {
+            music_id: book.id,
+            chapter_id: chapters[target].id,
+            progress,
+            device_id,
+            device_name,
+            client_update,
+        };
+        save_progress(db, user.id, update)
+            .await
+            .map_err(SubsonicError::internal)?;
+    }
+    Ok(Reply::Empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn salted_token_matches_the_api_example() {
+        assert_eq!(
+            salted_token("sesame", "c19b2d"),
+            "26719a1196d2a940705a59634eb18eab"
+        );
+    }
+
+    #[test]
+    fn decodes_plain_and_hex_passwords() {
+        assert_eq!(decode_password("sesame").as_deref(), Some("sesame"));
+        assert_eq!(
+            decode_password("enc:736573616d65").as_deref(),
+            Some("sesame")
+        );
+        assert_eq!(decode_password("enc:7365f"), None);
+        assert_eq!(decode_password("enc:zz"), None);
+    }
+
+    #[test]
+    fn strips_the_view_suffix() {
+        assert_eq!(method_name("getAlbum.view"), "getAlbum");
+        assert_eq!(method_name("getAlbum"), "getAlbum");
+    }
+
+    #[test]
+    fn parses_prefixed_ids() {
+        assert_eq!(parse_id("al-", &album_id(12)), Some(12));
+        assert_eq!(parse_id("al-", &song_id(12)), None);
+        assert_eq!(parse_id("tr-", "tr-x"), None);
+    }
+
+    #[test]
+    fn params_repeat_and_fail_on_missing() {
+        let params = Params(vec![
+            ("id".to_string(), "tr-1".to_string()),
+            ("id".to_string(), "tr-2".to_string()),
+            ("size".to_string(), "x".to_string()),
+        ]);
+        assert_eq!(params.get("id"), Some("tr-1"));
+        assert_eq!(params.all("id").collect::<Vec<_>>(), ["tr-1", "tr-2"]);
+        assert_eq!(params.required("type").unwrap_err().code, 10);
+        assert!(params.parse::<usize>("size").is_err());
+        assert_eq!(params.parse::<usize>("offset"), Ok(None));
+    }
+
+    #[test]
+    fn indexes_by_first_letter() {
+        assert_eq!(index_name("ursula"), "U");
+        assert_eq!(index_name("Édouard"), "É");
+        assert_eq!(index_name("1984 society"), "#");
+        assert_eq!(index_name(""), "#");
+    }
+
+    #[test]
+    fn errors_are_failed_responses() {
+        let body = envelope(Err(SubsonicError::wrong_credentials()));
+        let body = &body["subsonic-response"];
+        assert_eq!(body["status"], "failed");
+        assert_eq!(body["error"]["code"], 40);
+        assert_eq!(body["version"], API_VERSION);
+    }
+
+    #[test]
+    fn renders_xml_with_attributes_and_children() {
+        let body = envelope(Ok(Some((
+            "album",
+            json!({
+                "id": "al-1",
+                "name": "Tom & \"Jerry\" <1>",
+                "songCount": 2,
+                "song": [{ "id": "tr-1", "isDir": false }, { "id": "tr-2", "isDir": false }],
+            }),
+        ))));
+        let xml = to_xml(&body);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<subsonic-response"));
+        assert!(xml.contains(&format!(" xmlns=\"{}\"", XMLNS)));
+        assert!(xml.contains(" status=\"ok\""));
+        assert!(xml.contains(
+            "<album id=\"al-1\" name=\"Tom &amp; &quot;Jerry&quot; &lt;1&gt;\" songCount=\"2\">"
+        ));
+        assert!(
+            xml.contains("<song id=\"tr-1\" isDir=\"false\"/><song id=\"tr-2\" isDir=\"false\"/>")
+        );
+        assert!(xml.ends_with("</album></subsonic-response>"));
+    }
+
+    #[test]
+    fn renders_scalar_arrays_as_text_elements() {
+        let body = envelope(Ok(Some((
+            "openSubsonicExtensions",
+            json!([{ "name": "formPost", "versions": [1] }]),
+        ))));
+        let xml = to_xml(&body);
+        assert!(xml.contains(
+            "<openSubsonicExtensions name=\"formPost\"><versions>1</versions></openSubsonicExtensions>"
+        ));
+    }
+
+    #[test]
+    fn songs_point_to_their_album_and_artist() {
+        let author = AuthorDetail {
+            id: 3,
+            avatar: String::new(),
+            name: "J. R. R. Tolkien".to_string(),
+            description: String::new(),
+        };
+        let book = BookDetail {
+            id: 7,
+            author_id: 3,
+            name: "The Hobbit".to_string(),
+            chapters: 1,
+            total_time: Some(61.6),
+            file_folder: "tolkien/the hobbit".to_string(),
+            music_type: 0,
+            library_id: 1,
+        };
+        let chapter = ChapterDetail {
+            id: 11,
+            music_id: 7,
+            chapter_num: 1,
+            chapter_name: "Chapter 1".to_string(),
+            chapter_url: "tolkien/the hobbit/0001.MP3".to_string(),
+            chapter_length: Some(61.4),
+        };
+        let song = song(&chapter, &book, &author);
+        assert_eq!(song.id, "tr-11");
+        assert_eq!(song.parent, "al-7");
+        assert_eq!(song.album_id, "al-7");
+        assert_eq!(song.artist_id, "ar-3");
+        assert_eq!(song.suffix, "mp3");
+        assert_eq!(song.content_type, "audio/mpeg");
+        assert_eq!(song.duration, 61);
+        assert_eq!(album(&book, &author).duration, 62);
+    }
+}
@@ -1,7 +1,21 @@
 use std::path::{Path, PathBuf};
 
 use crate::entities::{prelude::*, *};
-use sea_orm::{ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter, Set};
+use crate::server_api::{
+    importer::{ImportEntry, ImportMatch, ImportSelection, ImportTarget},
+    library::ssr::grant_default_library,
+    ssr::{date_from_rfc3339, date_to_rfc3339, now, parse_date},
+    transfer::{
+        BookRef, BookmarkExport, ChapterRef, DeviceProgressExport, ImportReport, ProgressExport,
+        UserExport, EXPORT_VERSION,
+    },
+    webhook::{ssr as webhook, WebhookEvent},
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, ModelTrait, QueryFilter, Set,
+};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
 use tracing::{debug, error, info};
 pub async fn arrange_new_folder(
     src_dir: impl AsRef<Path>,
@@ -95,13 +109,19 @@ fn get_files_in_dir(dir: impl AsRef<Path>) -> eyre::Result<Vec<PathBuf>> {
     Ok(out)
 }
 
+/// returns the id of the new book
 pub async fn create_new_book(
     author_name: String,
     new_book_name: String,
-    book_dir: &Path,
+    library_id: i32,
     source_dir: &Path,
     db: &sea_orm::DatabaseConnection,
-) -> eyre::Result<()> {
+) -> eyre::Result<i32> {
+    let library = Library::find_by_id(library_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| eyre::eyre!("library not found: {}", library_id))?;
+    let book_dir = Path::new(&library.root);
     let db_book_dir = format!("{}/{}", author_name, new_book_name);
     info!("book dir:{:?}", book_dir);
 
@@ -146,6 +166,7 @@ pub async fn create_new_book(
         chapters: sea_orm::ActiveValue::Set(srcs.len() as i32),
         file_folder: sea_orm::ActiveValue::Set(db_book_dir.clone()),
         music_type: sea_orm::ActiveValue::Set(music_type),
+        library_id: sea_orm::ActiveValue::Set(library_id),
         ..Default::default()
     })
     .exec(db)
@@ -169,7 +190,11 @@ pub async fn create_new_book(
         .collect::<Vec<_>>();
     Chapter::insert_many(models).exec(db).await.unwrap();
     // insert the chapters
-    Ok(())
+    if let Some(book) = Music::find_by_id(book_id).one(db).await? {
+        let data = webhook::book_data(&book, db).await?;
+        webhook::trigger(WebhookEvent::BookAdded, json!({ "book": data }), db).await?;
+    }
+    Ok(book_id)
 }
 
 pub async fn create_new_user(
@@ -189,12 +214,18 @@ pub async fn create_new_user(
         role_level: sea_orm::ActiveValue::Set(role),
         ..Default::default()
     };
-    let _user = user.insert(db).await.unwrap();
+    let user = user.insert(db).await.unwrap();
+    grant_default_library(user.id, db).await.unwrap();
+    let data = json!({ "user": webhook::user_data(&user) });
+    webhook::trigger(WebhookEvent::UserCreated, data, db)
+        .await
+        .unwrap();
 }
 pub async fn alter_user(
     username: String,
     password: Option<String>,
     role: Option<i32>,
+    reset_totp: bool,
     db: &sea_orm::DatabaseConnection,
 ) {
     pub use bcrypt::{hash, DEFAULT_COST};
@@ -215,11 +246,690 @@ pub async fn alter_user(
         if let Some(role) = role {
             user.role_level = Set(role);
         }
-        user.save(db).await.unwrap();
+        let user = user.save(db).await.unwrap();
+        if reset_totp {
+            crate::server_api::totp::ssr::reset_totp(user.id.unwrap(), db)
+                .await
+                .unwrap();
+            info!("two-factor authentication reset:{}", username);
+        }
         info!("user altered:{}", username);
     }else{
         info!("user not found:{}", username);
     }
 }
+
+/// the book and chapter references of the rows of a user, keyed by the database ids
+async fn load_refs(
+    music_ids: Vec<i32>,
+    chapter_ids: Vec<i32>,
+    db: &sea_orm::DatabaseConnection,
+) -> eyre::Result<(HashMap<i32, BookRef>, HashMap<i32, ChapterRef>)> {
+    let books = Music::find()
+        .filter(music::Column::Id.is_in(music_ids))
+        .find_also_related(Author)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(book, author)| {
+            let book_ref = BookRef {
+                title: book.name,
+                author: author.map(|a| a.name).unwrap_or_default(),
+            };
+            (book.id, book_ref)
+        })
+        .collect();
+    let chapters = Chapter::find()
+        .filter(chapter::Column::Id.is_in(chapter_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| {
+            let chapter_ref = ChapterRef {
+                chapter_num: c.chapter_num,
+                chapter_name: c.chapter_name,
+            };
+            (c.id, chapter_ref)
+        })
+        .collect();
+    Ok((books, chapters))
+}
+
+/// export the progress, history and bookmarks of a user, keyed by book title, author and chapter
+/// name instead of the database ids
+pub async fn export_user_data(
+    account_id: i32,
+    db: &sea_orm::DatabaseConnection,
+) -> eyre::Result<UserExport> {
+    let account = Account::find_by_id(account_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| eyre::eyre!("user not found: {}", account_id))?;
+    let progresses = account.find_related(Progress).all(db).await?;
+    let devices = account.find_related(DeviceProgress).all(db).await?;
+    let bookmarks = account.find_related(Bookmark).all(db).await?;
+
+    let music_ids = progresses
+        .iter()
+        .map(|p| p.music_id)
+        .chain(devices.iter().map(|d| d.music_id))
+        .chain(bookmarks.iter().map(|b| b.music_id))
+        .collect::<HashSet<_>>();
+    let chapter_ids = progresses
+        .iter()
+        .map(|p| p.chapter_id)
+        .chain(devices.iter().map(|d| d.chapter_id))
+        .chain(bookmarks.iter().map(|b| b.chapter_id))
+        .collect::<HashSet<_>>();
+    let (books, chapters) = load_refs(
+        music_ids.into_iter().collect(),
+        chapter_ids.into_iter().collect(),
+        db,
+    )
+    .await?;
+
+    let progress = progresses
+        .into_iter()
+        .map(|p| ProgressExport {
+            book: books[&p.music_id].clone(),
+            chapter: chapters[&p.chapter_id].clone(),
+            progress: p.progress,
+            updated: date_to_rfc3339(&p.update),
+        })
+        .collect();
+    let history = devices
+        .into_iter()
+        .map(|d| DeviceProgressExport {
+            book: books[&d.music_id].clone(),
+            chapter: chapters[&d.chapter_id].clone(),
+            device_id: d.device_id,
+            device_name: d.device_name,
+            progress: d.progress,
+            client_update: d.client_update,
+        })
+        .collect();
+    let bookmarks = bookmarks
+        .into_iter()
+        .map(|b| BookmarkExport {
+            book: books[&b.music_id].clone(),
+            chapter: chapters[&b.chapter_id].clone(),
+            position: b.position,
+            name: b.name,
+            note: b.note,
+            created: date_to_rfc3339(&b.created),
+        })
+        .collect();
+    Ok(UserExport {
+        version: EXPORT_VERSION,
+        username: account.name,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        progress,
+        history,
+        bookmarks,
+    })
+}
+
+/// find the books and chapters of an export in the current library
+#[derive(Default)]
+pub struct LibraryMatcher {
+    books: HashMap<BookRef, Option<i32>>,
+}
+
+impl LibraryMatcher {
+    pub async fn find_book(
+        &mut self,
+        book: &BookRef,
+        db: &sea_orm::DatabaseConnection,
+    ) -> eyre::Result<Option<i32>> {
+        if let Some(found) = self.books.get(book) {
+            return Ok(*found);
+        }
+        let found = Music::find()
+            .filter(music::Column::Name.eq(&book.title))
+            .find_also_related(Author)
+            .all(db)
+            .await?
+            .into_iter()
+            .find(|(_, author)| author.as_ref().is_some_and(|a| a.name == book.author))
+            .map(|(m, _)| m.id);
+        self.books.insert(book.clone(), found);
+        Ok(found)
+    }
+
+    pub async fn find_chapter(
+        &mut self,
+        music_id: i32,
+        chapter: &ChapterRef,
+        db: &sea_orm::DatabaseConnection,
+    ) -> eyre::Result<Option<i32>> {
+        let by_name = Chapter::find()
+            .filter(chapter::Column::MusicId.eq(music_id))
+            .filter(chapter::Column::ChapterName.eq(&chapter.chapter_name))
+            .one(db)
+            .await?;
+        if let Some(c) = by_name {
+            return Ok(Some(c.id));
+        }
+        let by_num = Chapter::find()
+            .filter(chapter::Column::MusicId.eq(music_id))
+            .filter(chapter::Column::ChapterNum.eq(chapter.chapter_num))
+            .one(db)
+            .await?;
+        Ok(by_num.map(|c| c.id))
+    }
+
+    /// the music id and chapter id, `None` when either is not in the library
+    pub async fn find(
+        &mut self,
+        book: &BookRef,
+        chapter: &ChapterRef,
+        db: &sea_orm::DatabaseConnection,
+    ) -> eyre::Result<Option<(i32, i32)>> {
+        let Some(music_id) = self.find_book(book, db).await? else {
+            return Ok(None);
+        };
+        let chapter_id = self.find_chapter(music_id, chapter, db).await?;
+        Ok(chapter_id.map(|chapter_id| (music_id, chapter_id)))
+    }
+}
+
+fn describe(kind: &str, book: &BookRef, chapter: &ChapterRef) -> String {
+    format!(
+        "{}: {} - {} / {}",
+        kind, book.author, book.title, chapter.chapter_name
+    )
+}
+
+/// import an export into a user, newer data already in the database is kept.
+///
+/// nothing is written when `dry_run` is set, the report still tells what would be imported
+pub async fn import_user_data(
+    account_id: i32,
+    data: UserExport,
+    dry_run: bool,
+    db: &sea_orm::DatabaseConnection,
+) -> eyre::Result<ImportReport> {
+    if data.version > EXPORT_VERSION {
+        eyre::bail!("unsupported export version: {}", data.version);
+    }
+    Account::find_by_id(account_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| eyre::eyre!("user not found: {}", account_id))?;
+    let mut report = ImportReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut matcher = LibraryMatcher::default();
+
+    for p in data.progress {
+        let Some((music_id, chapter_id)) = matcher.find(&p.book, &p.chapter, db).await? else {
+            report.unmatched.push(describe("progress", &p.book, &p.chapter));
+            continue;
+        };
+        let Some(updated) = date_from_rfc3339(&p.updated) else {
+            report.unmatched.push(describe("progress(bad date)", &p.book, &p.chapter));
+            continue;
+        };
+        let current = Progress::find_by_id((account_id, music_id)).one(db).await?;
+        let is_newer = current.as_ref().is_none_or(|c| {
+            parse_date(&date_to_rfc3339(&c.update)) < parse_date(&p.updated)
+        });
+        if !is_newer {
+            report.skipped += 1;
+            continue;
+        }
+        report.progress += 1;
+        if dry_run {
+            continue;
+        }
+        let model = progress::ActiveModel {
+            account_id: Set(account_id),
+            music_id: Set(music_id),
+            chapter_id: Set(chapter_id),
+            progress: Set(p.progress),
+            update: Set(updated),
+            ..Default::default()
+        };
+        match current {
+            Some(_) => {
+                model.update(db).await?;
+            }
+            None => {
+                let mut model = model;
+                model.device_id = Set("".to_string());
+                model.client_update = Set(None);
+                Progress::insert(model).exec(db).await?;
+            }
+        }
+    }
+
+    for d in data.history {
+        let Some((music_id, chapter_id)) = matcher.find(&d.book, &d.chapter, db).await? else {
+            report.unmatched.push(describe("history", &d.book, &d.chapter));
+            continue;
+        };
+        let current = DeviceProgress::find_by_id((account_id, music_id, d.device_id.clone()))
+            .one(db)
+            .await?;
+        if current
+            .as_ref()
+            .is_some_and(|c| c.client_update >= d.client_update)
+        {
+            report.skipped += 1;
+            continue;
+        }
+        report.history += 1;
+        if dry_run {
+            continue;
+        }
+        let model = device_progress::ActiveModel {
+            account_id: Set(account_id),
+            music_id: Set(music_id),
+            device_id: Set(d.device_id),
+            device_name: Set(d.device_name),
+            chapter_id: Set(chapter_id),
+            progress: Set(d.progress),
+            client_update: Set(d.client_update),
+            update: Set(now()),
+        };
+        match current {
+            Some(_) => {
+                model.update(db).await?;
+            }
+            None => {
+                DeviceProgress::insert(model).exec(db).await?;
+            }
+        }
+    }
+
+    for b in data.bookmarks {
+        let Some((music_id, chapter_id)) = matcher.find(&b.book, &b.chapter, db).await? else {
+            report.unmatched.push(describe("bookmark", &b.book, &b.chapter));
+            continue;
+        };
+        let exists = Bookmark::find()
+            .filter(bookmark::Column::AccountId.eq(account_id))
+            .filter(bookmark::Column::ChapterId.eq(chapter_id))
+            .filter(bookmark::Column::Name.eq(&b.name))
+            .all(db)
+            .await?
+            .iter()
+            .any(|c| (c.position - b.position).abs() < 1.0);
+        if exists {
+            report.skipped += 1;
+            continue;
+        }
+        report.bookmarks += 1;
+        if dry_run {
+            continue;
+        }
+        bookmark::ActiveModel {
+            account_id: Set(account_id),
+            music_id: Set(music_id),
+            chapter_id: Set(chapter_id),
+            position: Set(b.position),
+            name: Set(b.name),
+            note: Set(b.note),
+            created: Set(date_from_rfc3339(&b.created).unwrap_or_else(now)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+    info!(
+        "import into user {}: {} progress, {} history, {} bookmarks, {} skipped, {} unmatched",
+        account_id,
+        report.progress,
+        report.history,
+        report.bookmarks,
+        report.skipped,
+        report.unmatched.len()
+    );
+    Ok(report)
+}
+/// the minimal similarity for a book of another player to be matched
+pub const MATCH_THRESHOLD: f64 = 0.6;
+
+/// lower case, only letters and digits, words separated by one space
+fn normalize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_lowercase().next().unwrap_or(c)
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// the similarity of two names between 0 and 1, ignoring case and punctuation.
+///
+/// a name containing the other one (e.g. "title (unabridged)") is considered very similar
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.;
+    }
+    if a == b {
+        return 1.;
+    }
+    let (ca, cb) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    let distance = levenshtein(&ca, &cb) as f64;
+    let score = 1. - distance / ca.len().max(cb.len()) as f64;
+    if a.contains(&b) || b.contains(&a) {
+        score.max(0.9)
+    } else {
+        score
+    }
+}
+
+/// find the entries of another player in the library
+pub async fn match_import_entries(
+    entries: Vec<ImportEntry>,
+    db: &sea_orm::DatabaseConnection,
+) -> eyre::Result<Vec<ImportMatch>> {
+    let books = Music::find().find_also_related(Author).all(db).await?;
+    let mut chapters: HashMap<i32, Vec<chapter::Model>> = HashMap::new();
+    for c in Chapter::find().all(db).await? {
+        chapters.entry(c.music_id).or_default().push(c);
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| ImportMatch {
+            target: match_import_entry(&entry, &books, &chapters),
+            entry,
+        })
+        .collect())
+}
+
+/// the most similar book and its chapter, `None` when no book or chapter is similar enough
+fn match_import_entry(
+    entry: &ImportEntry,
+    books: &[(music::Model, Option<author::Model>)],
+    chapters: &HashMap<i32, Vec<chapter::Model>>,
+) -> Option<ImportTarget> {
+    let (book, author, score) = books
+        .iter()
+        .map(|(book, author)| {
+            let author = author.as_ref().map(|a| a.name.as_str()).unwrap_or_default();
+            let title_score = similarity(&entry.title, &book.name);
+            let score = if entry.author.trim().is_empty() {
+                title_score
+            } else {
+                title_score * 0.75 + similarity(&entry.author, author) * 0.25
+            };
+            (book, author, score)
+        })
+        .filter(|(_, _, score)| *score >= MATCH_THRESHOLD)
+        .max_by(|a, b| a.2.total_cmp(&b.2))?;
+    let book_chapters = chapters.get(&book.id)?;
+    // prefer a chapter with a similar name, then the chapter at the same index
+    let by_name = entry.chapter_name.as_ref().and_then(|name| {
+        book_chapters
+            .iter()
+            .map(|c| (c, similarity(name, &c.chapter_name)))
+            .filter(|(_, score)| *score >= MATCH_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(c, _)| c)
+    });
+    let by_index = || {
+        let index = entry.chapter_index?;
+        book_chapters.iter().find(|c| c.chapter_num == index)
+    };
+    let chapter = by_name.or_else(by_index)?;
+    Some(ImportTarget {
+        music_id: book.id,
+        title: book.name.clone(),
+        author: author.to_string(),
+        chapter_id: chapter.id,
+        chapter_num: chapter.chapter_num,
+        chapter_name: chapter.chapter_name.clone(),
+        score,
+    })
+}
+
+/// write the accepted matches as the progress of a user, the current progress is replaced
+pub async fn apply_progress_import(
+    account_id: i32,
+    selections: Vec<ImportSelection>,
+    db: &sea_orm::DatabaseConnection,
+) -> eyre::Result<usize> {
+    Account::find_by_id(account_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| eyre::eyre!("user not found: {}", account_id))?;
+    let mut written = HashSet::new();
+    for s in selections {
+        let chapter = Chapter::find_by_id(s.chapter_id)
+            .one(db)
+            .await?
+            .filter(|c| c.music_id == s.music_id)
+            .ok_or_else(|| {
+                eyre::eyre!("chapter {} not found in book {}", s.chapter_id, s.music_id)
+            })?;
+        let model = progress::ActiveModel {
+            account_id: Set(account_id),
+            music_id: Set(s.music_id),
+            chapter_id: Set(chapter.id),
+            progress: Set(s.progress.max(0.)),
+            update: Set(s
+                .updated
+                .as_deref()
+                .and_then(date_from_rfc3339)
+                .unwrap_or_else(now)),
+            // the next update of any device should be accepted
+            device_id: Set("".to_string()),
+            client_update: Set(None),
+        };
+        match Progress::find_by_id((account_id, s.music_id))
+            .one(db)
+            .await?
+        {
+            Some(_) => {
+                model.update(db).await?;
+            }
+            None => {
+                Progress::insert(model).exec(db).await?;
+            }
+        }
+        written.insert(s.music_id);
+    }
+    info!(
+        "imported the progress of {} books into user {}",
+        written.len(),
+        account_id
+    );
+    Ok(written.len())
+}
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::server_api::importer::parse;
+
+    fn entry(title: &str, author: &str, index: Option<i32>, name: Option<&str>) -> ImportEntry {
+        ImportEntry {
+            title: title.to_string(),
+            author: author.to_string(),
+            chapter_index: index,
+            chapter_name: name.map(str::to_string),
+            seconds: 12.,
+            updated: None,
+        }
+    }
+
+    fn books() -> Vec<(music::Model, Option<author::Model>)> {
+        let book = |id: i32, author_id: i32, name: &str| music::Model {
+            id,
+            author_id,
+            name: name.to_string(),
+            chapters: 2,
+            total_time: None,
+            file_folder: format!("{}/{}", author_id, name),
+            music_type: 0,
+            library_id: 1,
+        };
+        let author = |id: i32, name: &str| author::Model {
+            id,
+            name: name.to_string(),
+            avatar: String::new(),
+            description: String::new(),
+        };
+        vec![
+            (
+                book(1, 1, "The Hobbit"),
+                Some(author(1, "J. R. R. Tolkien")),
+            ),
+            (
+                book(2, 1, "The Silmarillion"),
+                Some(author(1, "J. R. R. Tolkien")),
+            ),
+            (book(3, 2, "Dune"), Some(author(2, "Frank Herbert"))),
+        ]
+    }
+
+    fn chapters() -> HashMap<i32, Vec<chapter::Model>> {
+        let chapter = |id: i32, music_id: i32, chapter_num: i32, name: &str| chapter::Model {
+            id,
+            music_id,
+            chapter_num,
+            chapter_name: name.to_string(),
+            chapter_url: format!("{}.mp3", name),
+            chapter_length: Some(100.),
+        };
+        [
+            chapter(10, 1, 0, "An Unexpected Party"),
+            chapter(11, 1, 1, "Roast Mutton"),
+            chapter(20, 2, 0, "Ainulindale"),
+            chapter(30, 3, 0, "0001"),
+            chapter(31, 3, 1, "0002"),
+        ]
+        .into_iter()
+        .fold(HashMap::<i32, Vec<_>>::new(), |mut chapters, c| {
+            chapters.entry(c.music_id).or_default().push(c);
+            chapters
+        })
+    }
+
+    #[test]
+    fn similarity_ignores_case_and_punctuation() {
+        assert_eq!(similarity("The Hobbit", "the hobbit!"), 1.);
+        assert_eq!(similarity("", "the hobbit"), 0.);
+        assert_eq!(similarity("The Hobbit (Unabridged)", "The Hobbit"), 0.9);
+        assert!(similarity("The Hobit", "The Hobbit") > MATCH_THRESHOLD);
+        assert!(similarity("Dune", "The Hobbit") < MATCH_THRESHOLD);
+    }
+
+    #[test]
+    fn entries_are_matched_by_title_and_chapter() {
+        let (books, chapters) = (books(), chapters());
+        // the chapter name wins over the index
+        let target = match_import_entry(
+            &entry("the hobbit", "Tolkien", Some(0), Some("roast mutton")),
+            &books,
+            &chapters,
+        )
+        .unwrap();
+        assert_eq!((target.music_id, target.chapter_id), (1, 11));
+        // the index is used when no chapter name is similar
+        let target = match_import_entry(
+            &entry("Dune (Unabridged)", "", Some(1), Some("chapter two")),
+            &books,
+            &chapters,
+        )
+        .unwrap();
+        assert_eq!((target.music_id, target.chapter_id), (3, 31));
+        assert_eq!(target.score, 0.9);
+        // no similar book, and no chapter at the index
+        assert_eq!(
+            match_import_entry(&entry("Neuromancer", "", Some(0), None), &books, &chapters),
+            None
+        );
+        assert_eq!(
+            match_import_entry(&entry("Dune", "", Some(5), None), &books, &chapters),
+            None
+        );
+    }
+
+    #[test]
+    fn audiobookshelf_keeps_the_newest_session_of_each_book() {
+        let data = r#"{"sessions": [
+            {"libraryItemId": "li_1", "displayTitle": "The Hobbit", "displayAuthor": "Tolkien",
+             "currentTime": 130, "updatedAt": 1700000000000,
+             "chapters": [{"start": 0, "title": "One"}, {"start": 100, "title": "Two"}]},
+            {"libraryItemId": "li_1", "displayTitle": "The Hobbit", "displayAuthor": "Tolkien",
+             "currentTime": 20, "updatedAt": 1600000000000, "chapters": []},
+            {"displayTitle": "Dune", "currentTime": 42.5}
+        ]}"#;
+        let entries = parse::audiobookshelf(data).unwrap();
+        assert_eq!(entries.len(), 2);
+        let hobbit = &entries[0];
+        assert_eq!(hobbit.title, "The Hobbit");
+        assert_eq!(hobbit.chapter_index, Some(1));
+        assert_eq!(hobbit.chapter_name.as_deref(), Some("Two"));
+        assert_eq!(hobbit.seconds, 30.);
+        assert_eq!(hobbit.updated.as_deref(), Some("2023-11-14T22:13:20+00:00"));
+        let dune = &entries[1];
+        assert_eq!((dune.chapter_index, dune.seconds), (Some(0), 42.5));
+        assert_eq!(dune.updated, None);
+        // a plain list of sessions is accepted too
+        let list = r#"[{"displayTitle": "Dune", "currentTime": 1}]"#;
+        assert_eq!(parse::audiobookshelf(list).unwrap().len(), 1);
+        assert!(parse::audiobookshelf("not json").is_err());
+    }
+
+    #[test]
+    fn smart_audiobook_player_uses_the_file_as_the_chapter() {
+        let data = r#"{"books": [
+            {"folderName": "The Hobbit", "author": "Tolkien", "currentFile": "02 Roast Mutton.mp3",
+             "positionMs": 61500, "lastPlayed": 1700000000000},
+            {"name": "Dune", "position": 0}
+        ]}"#;
+        let entries = parse::smart_audiobook_player(data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "The Hobbit");
+        assert_eq!(entries[0].chapter_name.as_deref(), Some("02 Roast Mutton"));
+        assert_eq!(entries[0].chapter_index, None);
+        assert_eq!(entries[0].seconds, 61.5);
+        assert!(entries[0].updated.is_some());
+        assert_eq!(entries[1].chapter_name, None);
+    }
+
+    #[test]
+    fn csv_lines_are_parsed_and_checked() {
+        let data = "title,author,chapter,seconds\n\
+                    \"The Hobbit, or There and Back Again\",Tolkien,2,61.5\n\
+                    \n\
+                    \"Say \"\"Hi\"\"\",,1,0\n";
+        let entries = parse::csv(data).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "The Hobbit, or There and Back Again");
+        assert_eq!(entries[0].chapter_index, Some(1));
+        assert_eq!(entries[0].seconds, 61.5);
+        assert_eq!(entries[1].title, "Say \"Hi\"");
+        assert_eq!(entries[1].author, "");
+
+        assert!(parse::csv("Dune,Herbert,0,10").is_err());
+        assert!(parse::csv("Dune,Herbert,-1,10").is_err());
+        assert!(parse::csv("Dune,Herbert,1").is_err());
+        assert!(parse::csv("Dune,Herbert,1,10\nDune,Herbert,one,10").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::server_api::auth::*;
+use crate::server_api::totp::VerifyLoginTotp;
 use crate::server_api::User;
 use crate::ui::login_page::LoginPage;
 use crate::ui::main_authors::AuthorDetailPage;
@@ -9,6 +10,8 @@ use crate::ui::main_books::ChapterView;
 use crate::ui::main_index::MainIndex;
 use crate::ui::main_page::MainPage;
 use crate::ui::main_setting::MainSettings;
+use crate::ui::password_reset::{ForgotPasswordPage, ResetPasswordPage};
+use crate::ui::register::{InvitePage, RegisterPage};
 
 use leptos::*;
 use leptos_meta::{provide_meta_context, Link, Stylesheet};
@@ -31,12 +34,14 @@ pub fn App() -> impl IntoView {
     let logout: Action<Logout, Result<(), ServerFnError>> = create_server_action::<Logout>();
     let login = create_server_action::<Login>();
     let signup = create_server_action::<Signup>();
+    let verify_totp = create_server_action::<VerifyLoginTotp>();
     let user = create_resource(
         move || {
             (
                 login.version().get(),
                 signup.version().get(),
                 logout.version().get(),
+                verify_totp.version().get(),
             )
         },
         move |_| async move { get_user().await },
@@ -76,7 +81,7 @@ pub fn App() -> impl IntoView {
                                                                     .into_view()
                                                             }
                                                             None => {
-                                                                view! { <LoginPage login=login signup=signup/> }.into_view()
+                                                                view! { <LoginPage login=login signup=signup verify_totp=verify_totp/> }.into_view()
                                                             }
                                                         }
                                                     }
@@ -90,6 +95,11 @@ pub fn App() -> impl IntoView {
                         }
                     />
 
+                    <Route path="/invite/:token" view=InvitePage/>
+                    <Route path="/register" view=RegisterPage/>
+                    <Route path="/forgot-password" view=ForgotPasswordPage/>
+                    <Route path="/reset-password" view=ResetPasswordPage/>
+
                     <Route
                         path="/"
                         view=move || {
@@ -0,0 +1,182 @@
+use leptos::{
+    html::{Audio, Input, Textarea},
+    *,
+};
+
+use crate::{server_api::bookmark::*, ui::player::AudioProps};
+
+#[component]
+/// list, add and export the bookmarks of the current book
+pub fn BookmarkPanel(book_id: i32, chapter_id: i32, player: NodeRef<Audio>) -> impl IntoView {
+    let set_player_props = use_context::<WriteSignal<Option<AudioProps>>>().unwrap();
+    let add_bookmark_action = create_server_action::<AddBookmark>();
+    let delete_bookmark_action = create_server_action::<DeleteBookmark>();
+    let (markdown, set_markdown) = create_signal(None::<String>);
+    let name_node = create_node_ref::<Input>();
+    let note_node = create_node_ref::<Textarea>();
+
+    let bookmarks = create_resource(
+        move || {
+            (
+                add_bookmark_action.version().get(),
+                delete_bookmark_action.version().get(),
+            )
+        },
+        move |_| async move { get_bookmarks_by_book(book_id).await },
+    );
+
+    let on_add_bookmark = move |_| {
+        let position = player.get().unwrap().current_time();
+        let name = name_node.get().unwrap().value();
+        let note = note_node.get().unwrap().value();
+        add_bookmark_action.dispatch(AddBookmark {
+            music_id: book_id,
+            chapter_id,
+            position,
+            name,
+            note: Some(note),
+        });
+        name_node.get().unwrap().set_value("");
+        note_node.get().unwrap().set_value("");
+    };
+    let on_jump = move |bookmark_chapter_id: i32, position: f64| {
+        if bookmark_chapter_id == chapter_id {
+            player.get().unwrap().set_current_time(position);
+        } else {
+            set_player_props(Some(AudioProps {
+                book_id,
+                chapter_id: bookmark_chapter_id,
+                init_time: position,
+            }));
+        }
+    };
+    let on_export = move |_| {
+        spawn_local(async move {
+            let md = export_bookmarks_markdown(book_id).await;
+            set_markdown(Some(md.unwrap_or_else(|e| e.to_string())));
+        });
+    };
+    let add_result = add_bookmark_action.value();
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2 border border-solid rounded">
+            <input
+                class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                type="text"
+                ref=name_node
+                placeholder="Bookmark Name"
+            />
+            <textarea
+                class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded"
+                ref=note_node
+                placeholder="Note (optional)"
+            ></textarea>
+            <div class="flex items-center justify-evenly flex-row w-full flex-wrap">
+                <button
+                    class="bg-blue-50 hover:bg-green-50 font-bold py-2 px-4 border border-solid rounded"
+                    on:click=on_add_bookmark
+                >
+                    Add Here
+                </button>
+                <button
+                    class="bg-blue-50 hover:bg-green-50 font-bold py-2 px-4 border border-solid rounded"
+                    on:click=on_export
+                >
+                    Export
+                </button>
+            </div>
+            <p>
+                {move || {
+                    add_result
+                        .get()
+                        .and_then(|r| r.err())
+                        .map(|e| format!("Error: {}", e))
+                }}
+
+            </p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    bookmarks
+                        .get()
+                        .map(|bookmarks| {
+                            match bookmarks {
+                                Ok(bookmarks) if bookmarks.is_empty() => {
+                                    view! { <p>{"No bookmarks"}</p> }.into_view()
+                                }
+                                Ok(bookmarks) => {
+                                    bookmarks
+                                        .into_iter()
+                                        .map(|(bookmark, chapter)| {
+                                            let bookmark_id = bookmark.id;
+                                            let bookmark_chapter_id = bookmark.chapter_id;
+                                            let position = bookmark.position;
+                                            view! {
+                                                <div class="flex flex-row w-full justify-between items-center space-x-1">
+                                                    <button
+                                                        class="flex-1 text-left px-2 py-1 bg-blue-50 hover:bg-green-50 border border-solid rounded-sm"
+                                                        on:click=move |_| on_jump(bookmark_chapter_id, position)
+                                                    >
+                                                        <h3>{bookmark.name}</h3>
+                                                        <p class="text-sm text-grey">
+                                                            {format!(
+                                                                "{} {}",
+                                                                chapter.chapter_name,
+                                                                format_position(position),
+                                                            )}
+
+                                                        </p>
+                                                        <p class="text-sm">{bookmark.note}</p>
+                                                    </button>
+                                                    <button
+                                                        class="bg-red-200 hover:bg-red-300 py-1 px-2 rounded"
+                                                        on:click=move |_| {
+                                                            delete_bookmark_action
+                                                                .dispatch(DeleteBookmark {
+                                                                    bookmark_id,
+                                                                });
+                                                        }
+                                                    >
+
+                                                        Delete
+                                                    </button>
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()
+                                }
+                                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                            }
+                        })
+                }}
+
+            </Transition>
+            {move || {
+                markdown
+                    .get()
+                    .map(|md| {
+                        view! {
+                            <a
+                                class="text-blue-500"
+                                download=format!("bookmarks-{}.md", book_id)
+                                href=format!(
+                                    "data:text/markdown;charset=utf-8,{}",
+                                    super::encode_uri_component(&md),
+                                )
+                            >
+
+                                {"Download Markdown"}
+                            </a>
+                            <textarea
+                                class="w-full h-32 px-2 py-1 bg-gray-100 border border-solid rounded"
+                                readonly=true
+                            >
+                                {md}
+                            </textarea>
+                        }
+                    })
+            }}
+
+        </div>
+    }
+}
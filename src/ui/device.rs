@@ -0,0 +1,59 @@
+//! identify the browser the player is running in, so the server can tell the devices of a user apart
+
+const DEVICE_ID_KEY: &str = "audiobookroom_device_id";
+
+/// a random id for this browser, generated once and kept in the local storage
+pub fn device_id() -> String {
+    let storage = web_sys::window().and_then(|w| w.local_storage().ok().flatten());
+    if let Some(id) = storage
+        .as_ref()
+        .and_then(|s| s.get_item(DEVICE_ID_KEY).ok().flatten())
+    {
+        return id;
+    }
+    let id = format!(
+        "{:08x}{:08x}",
+        (js_sys::Math::random() * u32::MAX as f64) as u32,
+        (js_sys::Math::random() * u32::MAX as f64) as u32
+    );
+    if let Some(storage) = storage {
+        let _ = storage.set_item(DEVICE_ID_KEY, &id);
+    }
+    id
+}
+
+/// a human readable name for this browser, like "Firefox on Android"
+pub fn device_name() -> String {
+    let user_agent = web_sys::window()
+        .and_then(|w| w.navigator().user_agent().ok())
+        .unwrap_or_default();
+    let os = [
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("Mac OS", "Mac"),
+        ("Windows", "Windows"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, name)| name)
+    .unwrap_or("Unknown");
+    // the order matters, chrome also claims to be safari, edge also claims to be chrome
+    let browser = [
+        ("Firefox", "Firefox"),
+        ("Edg", "Edge"),
+        ("Chrome", "Chrome"),
+        ("Safari", "Safari"),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, name)| name)
+    .unwrap_or("Browser");
+    format!("{} on {}", browser, os)
+}
+
+/// the client time in milliseconds since the unix epoch
+pub fn client_now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
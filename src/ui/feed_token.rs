@@ -0,0 +1,25 @@
+//! keep the podcast feed token in the browser that created it, the server only stores its hash
+
+fn feed_token_key(account_id: i32) -> String {
+    format!("audiobookroom_feed_token_{}", account_id)
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+/// the feed token of an account kept in this browser
+pub fn stored_feed_token(account_id: i32) -> Option<String> {
+    storage().and_then(|s| s.get_item(&feed_token_key(account_id)).ok().flatten())
+}
+
+/// keep a new feed token, `None` forgets it after it is revoked
+pub fn store_feed_token(account_id: i32, token: Option<&str>) {
+    let Some(storage) = storage() else {
+        return;
+    };
+    let _ = match token {
+        Some(token) => storage.set_item(&feed_token_key(account_id), token),
+        None => storage.remove_item(&feed_token_key(account_id)),
+    };
+}
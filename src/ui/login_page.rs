@@ -1,11 +1,13 @@
-use crate::server_api::auth::*;
+use crate::server_api::{auth::*, totp::VerifyLoginTotp};
+use crate::ui::totp::TotpEnrol;
 
 use leptos::*;
 
 #[component]
 pub fn LoginPage(
-    login: Action<Login, Result<(), ServerFnError>>,
+    login: Action<Login, Result<LoginStep, ServerFnError>>,
     signup: super::ServerAction<Signup, ()>,
+    verify_totp: super::ServerAction<VerifyLoginTotp, ()>,
 ) -> impl IntoView {
     let username: NodeRef<html::Input> = create_node_ref();
     let password: NodeRef<html::Input> = create_node_ref();
@@ -48,6 +50,15 @@ pub fn LoginPage(
             have_user().await
         },
     );
+    let sso = create_resource(|| {}, |_| sso_provider());
+    let password_reset = create_resource(
+        || {},
+        |_| crate::server_api::password_reset::password_reset_available(),
+    );
+    let registration = create_resource(
+        || {},
+        |_| crate::server_api::registration::registration_open(),
+    );
 
     view! {
         <div class="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
@@ -79,94 +90,214 @@ pub fn LoginPage(
                 </h2>
             </div>
 
-            <div class="mt-10 sm:mx-auto sm:w-full sm:max-w-sm">
-                <form class="space-y-6">
-                    <div>
-                        <label
-                            for="username"
-                            class="block text-sm font-medium leading-6 text-gray-900"
-                        >
-                            User Name
-                        </label>
-                        <div class="mt-2">
-                            <input
-                                id="username"
-                                name="username"
-                                type="text"
-                                required
-                                ref=username
-                                class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
-                            />
+            <Show
+                when=move || {
+                    !matches!(
+                        login.value().get(),
+                        Some(Ok(LoginStep::Totp | LoginStep::EnrolTotp))
+                    )
+                }
+
+                fallback=move || {
+                    match login.value().get() {
+                        Some(Ok(LoginStep::EnrolTotp)) => {
+                            view! {
+                                <div class="mt-10 sm:mx-auto sm:w-full sm:max-w-sm">
+                                    <p>{"Your role requires two-factor authentication."}</p>
+                                    <TotpEnrol continue_href="/"/>
+                                </div>
+                            }
+                                .into_view()
+                        }
+                        _ => view! { <TotpLogin verify_totp=verify_totp/> }.into_view(),
+                    }
+                }
+            >
+
+                <div class="mt-10 sm:mx-auto sm:w-full sm:max-w-sm">
+                    <form class="space-y-6">
+                        <div>
+                            <label
+                                for="username"
+                                class="block text-sm font-medium leading-6 text-gray-900"
+                            >
+                                User Name
+                            </label>
+                            <div class="mt-2">
+                                <input
+                                    id="username"
+                                    name="username"
+                                    type="text"
+                                    required
+                                    ref=username
+                                    class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+                                />
+                            </div>
                         </div>
-                    </div>
-
-                    <div>
-                        <label
-                            for="password"
-                            class="block text-sm font-medium leading-6 text-gray-900"
-                        >
-                            Password
-                        </label>
-
-                        <div class="mt-2">
-                            <input
-                                id="password"
-                                name="password"
-                                type="password"
-                                ref=password
-                                autocomplete="current-password"
-                                required
-                                class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
-                            />
+
+                        <div>
+                            <label
+                                for="password"
+                                class="block text-sm font-medium leading-6 text-gray-900"
+                            >
+                                Password
+                            </label>
+
+                            <div class="mt-2">
+                                <input
+                                    id="password"
+                                    name="password"
+                                    type="password"
+                                    ref=password
+                                    autocomplete="current-password"
+                                    required
+                                    class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+                                />
+                            </div>
                         </div>
-                    </div>
-                    <div class="flex justify-between items-center ">
-                        <p>"Remember me? "</p>
-                        <input ref=remember type="checkbox" name="remember" class="auth-input"/>
-                    </div>
-                    <div class="flex space-x-2">
-                        <Transition fallback=move || {}>
-                            {move || {
-                                have_user
-                                    .get()
-                                    .map(|have_user| {
-                                        match have_user {
-                                            Ok(have_user) => {
-                                                if have_user {
-                                                    view! {
-                                                        <button
-                                                            type="button"
-                                                            on:click=on_login
-                                                            class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600"
-                                                        >
-                                                            Sign in
-                                                        </button>
-                                                    }
-                                                        .into_view()
-                                                } else {
-                                                    view! {
-                                                        <button
-                                                            type="button"
-                                                            on:click=on_signup
-                                                            class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600"
-                                                        >
-                                                            Sign up
-                                                        </button>
+                        <div class="flex justify-between items-center ">
+                            <p>"Remember me? "</p>
+                            <input ref=remember type="checkbox" name="remember" class="auth-input"/>
+                        </div>
+                        <div class="flex space-x-2">
+                            <Transition fallback=move || {}>
+                                {move || {
+                                    have_user
+                                        .get()
+                                        .map(|have_user| {
+                                            match have_user {
+                                                Ok(have_user) => {
+                                                    if have_user {
+                                                        view! {
+                                                            <button
+                                                                type="button"
+                                                                on:click=on_login
+                                                                class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600"
+                                                            >
+                                                                Sign in
+                                                            </button>
+                                                        }
+                                                            .into_view()
+                                                    } else {
+                                                        view! {
+                                                            <button
+                                                                type="button"
+                                                                on:click=on_signup
+                                                                class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600"
+                                                            >
+                                                                Sign up
+                                                            </button>
+                                                        }
+                                                            .into_view()
                                                     }
-                                                        .into_view()
                                                 }
+                                                Err(_) => view! { <span>"Error"</span> }.into_view(),
                                             }
-                                            Err(_) => view! { <span>"Error"</span> }.into_view(),
+                                        })
+                                }}
+
+                            </Transition>
+
+                        </div>
+                        <p>
+                            {move || {
+                                login.value().get().and_then(Result::err).map(|e| e.to_string())
+                            }}
+
+                        </p>
+                        <Transition fallback=move || {}>
+                            {move || {
+                                sso
+                                    .get()
+                                    .and_then(Result::ok)
+                                    .flatten()
+                                    .map(|name| {
+                                        view! {
+                                            <a
+                                                href="/auth/oidc/login"
+                                                rel="external"
+                                                class="flex w-full justify-center rounded-md bg-gray-100 px-3 py-1.5 text-sm font-semibold leading-6 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 hover:bg-gray-50"
+                                            >
+                                                {format!("Sign in with {}", name)}
+                                            </a>
+                                        }
+                                    })
+                            }}
+
+                        </Transition>
+                        <Transition fallback=move || {}>
+                            {move || {
+                                registration
+                                    .get()
+                                    .and_then(Result::ok)
+                                    .filter(|open| *open)
+                                    .map(|_| {
+                                        view! {
+                                            <a class="text-blue-500" href="/register">
+                                                {"No account? Register"}
+                                            </a>
                                         }
                                     })
                             }}
 
                         </Transition>
+                        <Transition fallback=move || {}>
+                            {move || {
+                                password_reset
+                                    .get()
+                                    .and_then(Result::ok)
+                                    .filter(|available| *available)
+                                    .map(|_| {
+                                        view! {
+                                            <a class="block text-blue-500" href="/forgot-password">
+                                                {"Forgot password?"}
+                                            </a>
+                                        }
+                                    })
+                            }}
 
-                    </div>
+                        </Transition>
 
-                </form>
-            </div>
+                    </form>
+                </div>
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+/// the second step of the login
+fn TotpLogin(verify_totp: super::ServerAction<VerifyLoginTotp, ()>) -> impl IntoView {
+    let code: NodeRef<html::Input> = create_node_ref();
+    let on_verify = move |_| {
+        verify_totp.dispatch(VerifyLoginTotp {
+            code: code().unwrap().value(),
+        });
+    };
+    view! {
+        <div class="mt-10 sm:mx-auto sm:w-full sm:max-w-sm space-y-6">
+            <label for="code" class="block text-sm font-medium leading-6 text-gray-900">
+                {"Code from your authenticator app, or a recovery code"}
+            </label>
+            <input
+                id="code"
+                name="code"
+                type="text"
+                autocomplete="one-time-code"
+                required
+                ref=code
+                class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+            />
+            <button
+                type="button"
+                on:click=on_verify
+                class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500 focus-visible:outline focus-visible:outline-2 focus-visible:outline-offset-2 focus-visible:outline-indigo-600"
+            >
+                {"Verify"}
+            </button>
+            <p>
+                {move || verify_totp.value().get().and_then(Result::err).map(|e| e.to_string())}
+            </p>
         </div>
     }
 }
@@ -1,7 +1,7 @@
 use leptos::{html::Input, *};
 use leptos_router::{use_params, Route, A};
 
-use crate::{server_api::User, ui::player::AudioProps};
+use crate::ui::player::AudioProps;
 
 #[component(transparent)]
 pub fn MainBooks() -> impl IntoView {
@@ -140,11 +140,9 @@ pub fn BookDetail() -> impl IntoView {
     }
     use crate::server_api::book::*;
     let params = use_params::<Para>();
-    let set_player_props = use_context::<WriteSignal<Option<AudioProps>>>().unwrap();
     let (current_page, set_current_page) = create_signal(0u64);
     let page_node_ref: NodeRef<Input> = create_node_ref();
     let (max_item, _set_max_item) = create_signal(100);
-    // let user = use_context::<User>().unwrap();
     let book_author_chapters_detail = create_resource(
         move || {
             (
@@ -160,20 +158,20 @@ pub fn BookDetail() -> impl IntoView {
         },
     );
 
-    // use crate::server_api::progress::*;
-    let user = use_context::<User>().unwrap();
-
-    let on_progress_button_click = move |account_id: i32, book_id: i32| {
-        spawn_local(async move {
-            let progress = crate::server_api::progress::get_progress(book_id, account_id).await;
-            if let Ok(Some(k)) = progress {
-                set_player_props(Some(crate::ui::player::AudioProps {
-                    book_id,
-                    chapter_id: k.chapter_id,
-                    init_time: k.progress,
-                }));
-            }
-        });
+    // the local storage is only read in the browser, after the hydration
+    let account_id = use_context::<crate::server_api::User>().unwrap().id;
+    let feed_url = create_rw_signal(None::<String>);
+    create_effect(move |_| {
+        let book_id = params.get().unwrap().book_id;
+        feed_url.set(
+            crate::ui::feed_token::stored_feed_token(account_id)
+                .map(|token| crate::server_api::feed_token::feed_path(book_id, &token)),
+        );
+    });
+
+    let resume = use_context::<crate::ui::resume::ResumeContext>().unwrap();
+    let on_progress_button_click = move |book_id: i32| {
+        resume.resume(book_id);
     };
     view! {
         <div class="flex flex-col items-center text-center w-full ">
@@ -200,7 +198,7 @@ pub fn BookDetail() -> impl IntoView {
                                                 <button
                                                     class="w-full mx-2 px-2 py-1 bg-blue-50 hover:bg-green-50 border border-solid rounded-sm shadow-md hover:shadow-lg"
                                                     on:click=move |_e| {
-                                                        on_progress_button_click(user.id, book.id);
+                                                        on_progress_button_click(book.id);
                                                     }
                                                 >
 
@@ -225,6 +223,18 @@ pub fn BookDetail() -> impl IntoView {
                                         }
                                         None => view! { <p>{"No Progress"}</p> }.into_view(),
                                     }}
+                                    {move || {
+                                        feed_url
+                                            .get()
+                                            .map(|url| {
+                                                view! {
+                                                    <a class="underline" href=url rel="external">
+                                                        {"Podcast feed"}
+                                                    </a>
+                                                }
+                                            })
+                                    }}
+
 
                                     <div class="flex-col flex space-x-0 w-full my-1 py-1">
 
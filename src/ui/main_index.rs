@@ -5,8 +5,6 @@ use crate::server_api::User;
 #[component]
 pub fn MainIndex() -> impl IntoView {
     let user = use_context::<User>().unwrap();
-    let set_player_prop =
-        use_context::<WriteSignal<Option<crate::ui::player::AudioProps>>>().unwrap();
     let refresh_signle: RwSignal<crate::ui::main_page::RefreshSignal> = use_context().unwrap();
     let current_progress = create_resource(
         move || refresh_signle.get(),
@@ -18,19 +16,11 @@ pub fn MainIndex() -> impl IntoView {
             current_p
         },
     );
-    let on_progress_button_click = move |account_id: i32, book_id: i32| {
-        spawn_local(async move {
-            let progress = crate::server_api::progress::get_progress(book_id, account_id).await;
-            if let Ok(Some(k)) = progress {
-                set_player_prop(Some(crate::ui::player::AudioProps {
-                    book_id,
-                    chapter_id: k.chapter_id,
-                    init_time: k.progress,
-                }));
-                // refresh the signle
-                refresh_signle.set(crate::ui::main_page::RefreshSignal);
-            }
-        });
+    let resume = use_context::<crate::ui::resume::ResumeContext>().unwrap();
+    let on_progress_button_click = move |book_id: i32| {
+        resume.resume(book_id);
+        // refresh the signle
+        refresh_signle.set(crate::ui::main_page::RefreshSignal);
     };
 
     view! {
@@ -67,7 +57,7 @@ pub fn MainIndex() -> impl IntoView {
                                                 <button
                                                     class="w-full mx-2 px-2 py-1 bg-blue-50 hover:bg-green-50 border border-solid rounded-sm shadow-md hover:shadow-lg"
                                                     on:click=move |_e| {
-                                                        on_progress_button_click(user.id, book_id);
+                                                        on_progress_button_click(book_id);
                                                     }
                                                 >
 
@@ -2,8 +2,13 @@ use leptos::{ *};
 use leptos_router::{Outlet, A};
 
 use crate::{
-    server_api::{progress::SetProgress, User},
-    ui::player::{AudioProps, Player},
+    server_api::{progress::ProgressUpdate, User},
+    ui::{
+        device::{client_now, device_id, device_name},
+        offline,
+        player::{AudioProps, Player},
+        resume::{ResumeChooser, ResumeContext},
+    },
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -42,11 +47,16 @@ pub fn MainPage(user: User) -> impl IntoView {
                 prop.init_time = 0.;
                 set_player_props(Some(prop));
                 // save the progress
-                let _ = crate::server_api::progress::set_progress(
+                offline::send_progress(
                     user.id,
-                    prop.book_id,
-                    prop.chapter_id,
-                    0.,
+                    ProgressUpdate {
+                        music_id: prop.book_id,
+                        chapter_id: prop.chapter_id,
+                        progress: 0.,
+                        device_id: device_id(),
+                        device_name: device_name(),
+                        client_update: client_now(),
+                    },
                 )
                 .await;
                 refresh_signle.set(RefreshSignal);
@@ -80,11 +90,16 @@ pub fn MainPage(user: User) -> impl IntoView {
                 prop.init_time = 0.;
                 set_player_props(Some(prop));
                 // save the progress
-                let _ = crate::server_api::progress::set_progress(
+                offline::send_progress(
                     user.id,
-                    prop.book_id,
-                    prop.chapter_id,
-                    0.,
+                    ProgressUpdate {
+                        music_id: prop.book_id,
+                        chapter_id: prop.chapter_id,
+                        progress: 0.,
+                        device_id: device_id(),
+                        device_name: device_name(),
+                        client_update: client_now(),
+                    },
                 )
                 .await;
                 refresh_signle.set(RefreshSignal);
@@ -95,13 +110,14 @@ pub fn MainPage(user: User) -> impl IntoView {
             }
         })
     };
-    let set_progress_action: Action<SetProgress, Result<(), ServerFnError>> =
-        create_server_action::<SetProgress>();
+    // send the progress queued while offline, now and every time the connection is back
+    create_effect(move |_| spawn_local(offline::replay()));
+    let _ = window_event_listener(ev::online, move |_| spawn_local(offline::replay()));
 
-    // send the progress to server
-    provide_context(set_progress_action);
     // set current play info
     provide_context(set_player_props);
+    // resume a book, asking which device to continue from
+    provide_context(ResumeContext::new(user.id, set_player_props));
     // set current user
     provide_context(user.clone());
     provide_context(refresh_signle);
@@ -147,6 +163,7 @@ pub fn MainPage(user: User) -> impl IntoView {
 
             </div>
             <div>
+                <ResumeChooser/>
                 <Player
                     props=player_props
                     on_ended=player_on_ended
@@ -1,4 +1,7 @@
-use leptos::{html::Input, *};
+use leptos::{
+    html::{Input, Select},
+    *,
+};
 
 use crate::server_api::book::AddBookResult;
 #[component]
@@ -7,6 +10,8 @@ pub fn AddBook() -> impl IntoView {
     let name_node = create_node_ref::<Input>();
     let author_node = create_node_ref::<Input>();
     let source_node = create_node_ref::<Input>();
+    let library_node = create_node_ref::<Select>();
+    let libraries = create_resource(|| {}, |_| crate::server_api::library::list_libraries());
 
     let add_result = create_resource(
         move || add_info.get(),
@@ -15,8 +20,9 @@ pub fn AddBook() -> impl IntoView {
             //     let result = crate::server_api::book::add_book(author, name, source).await;
             //     result
             // })
-            if let Some((name, author, source)) = add_info {
-                let result = crate::server_api::book::add_book(author, name, source).await;
+            if let Some((name, author, source, library_id)) = add_info {
+                let result =
+                    crate::server_api::book::add_book(author, name, source, library_id).await;
                 Some(result)
             } else {
                 None
@@ -47,6 +53,28 @@ pub fn AddBook() -> impl IntoView {
                 ref=source_node
                 placeholder="Source Directory"
             />
+            <h2>Library:</h2>
+            <select
+                class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                ref=library_node
+            >
+                <Transition fallback=move || {
+                    view! { <option>"Loading..."</option> }
+                }>
+                    {move || {
+                        libraries
+                            .get()
+                            .map(|libraries| {
+                                libraries
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|l| view! { <option value=l.id>{l.name}</option> })
+                                    .collect_view()
+                            })
+                    }}
+
+                </Transition>
+            </select>
             <div class="w-full py-12">
                 <button
                     class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
@@ -56,6 +84,7 @@ pub fn AddBook() -> impl IntoView {
                                 name_node.get().unwrap().value(),
                                 author_node.get().unwrap().value(),
                                 source_node.get().unwrap().value(),
+                                library_node.get().unwrap().value().parse().unwrap_or(1),
                             )),
                         )
                     }
@@ -1,22 +1,25 @@
-use leptos::{html::Input, *};
+use leptos::{
+    html::{Input, Select},
+    *,
+};
 #[component]
 pub fn AddUser() -> impl IntoView {
     use crate::server_api::auth::*;
     let user_name_node = create_node_ref::<Input>();
     let password_node = create_node_ref::<Input>();
-    let role_node = create_node_ref::<Input>();
+    let role_node = create_node_ref::<Select>();
+    let roles = create_resource(|| {}, |_| crate::server_api::role::list_roles());
     let create_user_action = create_server_action::<AddUser>();
     let action_result = create_user_action.value();
     let (error_msg, set_error_msg) = create_signal(String::new());
     let on_create_user = move |_| {
         let username = user_name_node.get().unwrap().value();
         let password = password_node.get().unwrap().value();
-        let role = role_node.get().unwrap().value().parse::<i32>().unwrap();
-
-        if role != 0 && role != 1 {
-            set_error_msg("Role must be 0 or 1".to_string());
+        let Ok(role) = role_node.get().unwrap().value().parse::<i32>() else {
+            set_error_msg("Select a role".to_string());
             return;
-        }
+        };
+        set_error_msg(String::new());
         create_user_action.dispatch(AddUser {
             username,
             password,
@@ -25,7 +28,6 @@ pub fn AddUser() -> impl IntoView {
         // clear the input
         user_name_node.get().unwrap().set_value("");
         password_node.get().unwrap().set_value("");
-        role_node.get().unwrap().set_value("");
     };
     view! {
         <div class="flex flex-col w-full space-y-1 p-2">
@@ -42,12 +44,27 @@ pub fn AddUser() -> impl IntoView {
                 ref=password_node
                 placeholder="Password"
             />
-            <input
+            <select
                 class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
-                type="number"
                 ref=role_node
-                placeholder="Role 0 for admin, 1 for user"
-            />
+            >
+                <Transition fallback=move || {
+                    view! { <option>"Loading..."</option> }
+                }>
+                    {move || {
+                        roles
+                            .get()
+                            .map(|roles| {
+                                roles
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|role| view! { <option value=role.id>{role.name}</option> })
+                                    .collect_view()
+                            })
+                    }}
+
+                </Transition>
+            </select>
             <button
                 class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
                 on:click=on_create_user
@@ -0,0 +1,135 @@
+use leptos::{html::Input, *};
+
+use crate::server_api::token::{list_api_tokens, CreateApiToken, RevokeApiToken};
+
+#[component]
+/// the api tokens of the current user, for players and scripts that can not use the login page
+pub fn ApiTokens() -> impl IntoView {
+    let create_action = create_server_action::<CreateApiToken>();
+    let revoke_action = create_server_action::<RevokeApiToken>();
+    let tokens = create_resource(
+        move || (create_action.version().get(), revoke_action.version().get()),
+        |_| list_api_tokens(),
+    );
+    let name_node = create_node_ref::<Input>();
+    let days_node = create_node_ref::<Input>();
+    let on_create = move |_| {
+        let expires_in_days = days_node.get().unwrap().value().trim().parse().ok();
+        create_action.dispatch(CreateApiToken {
+            name: name_node.get().unwrap().value(),
+            expires_in_days,
+        });
+        name_node.get().unwrap().set_value("");
+        days_node.get().unwrap().set_value("");
+    };
+    let message = move || {
+        revoke_action
+            .value()
+            .get()
+            .map(|r| match r {
+                Ok(_) => "Token revoked".to_string(),
+                Err(e) => format!("Error: {}", e),
+            })
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"API Tokens"}</h2>
+            <p>
+                {"Send a token as "} <code>{"Authorization: Bearer <token>"}</code>
+                {" to use the api and fetch the books without logging in."}
+            </p>
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=name_node
+                placeholder="Token Name, e.g. phone"
+            />
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="number"
+                min="1"
+                ref=days_node
+                placeholder="Expires in days, empty for never"
+            />
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_create
+            >
+                {"Create Token"}
+            </button>
+            {move || {
+                create_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(token) => {
+                            view! {
+                                <p>{"Copy the token now, it will not be shown again:"}</p>
+                                <code class="break-all">{token}</code>
+                            }
+                                .into_view()
+                        }
+                        Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                    })
+            }}
+
+            <p>{message}</p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    tokens
+                        .get()
+                        .map(|tokens| match tokens {
+                            Ok(tokens) if tokens.is_empty() => {
+                                view! { <p>{"No tokens"}</p> }.into_view()
+                            }
+                            Ok(tokens) => {
+                                tokens
+                                    .into_iter()
+                                    .map(|token| {
+                                        let token_id = token.id;
+                                        view! {
+                                            <div class="flex flex-row w-full items-center p-2 border border-solid rounded">
+                                                <div class="flex flex-col flex-1">
+                                                    <span>{token.name}</span>
+                                                    <span class="text-sm">
+                                                        {format!("Created: {}", token.created)}
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!(
+                                                            "Expires: {}",
+                                                            token.expires.unwrap_or("never".to_string()),
+                                                        )}
+
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!(
+                                                            "Last used: {}",
+                                                            token.last_used.unwrap_or("never".to_string()),
+                                                        )}
+
+                                                    </span>
+                                                </div>
+                                                <button
+                                                    class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                    on:click=move |_| {
+                                                        revoke_action.dispatch(RevokeApiToken { token_id })
+                                                    }
+                                                >
+
+                                                    {"Revoke"}
+                                                </button>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
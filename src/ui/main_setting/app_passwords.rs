@@ -0,0 +1,115 @@
+use leptos::{html::Input, *};
+
+use crate::server_api::app_password::{list_app_passwords, CreateAppPassword, RevokeAppPassword};
+
+#[component]
+/// the app passwords of the current user, for the subsonic players
+pub fn AppPasswords() -> impl IntoView {
+    let create_action = create_server_action::<CreateAppPassword>();
+    let revoke_action = create_server_action::<RevokeAppPassword>();
+    let passwords = create_resource(
+        move || (create_action.version().get(), revoke_action.version().get()),
+        |_| list_app_passwords(),
+    );
+    let name_node = create_node_ref::<Input>();
+    let on_create = move |_| {
+        create_action.dispatch(CreateAppPassword {
+            name: name_node.get().unwrap().value(),
+        });
+        name_node.get().unwrap().set_value("");
+    };
+    let message = move || {
+        revoke_action.value().get().map(|r| match r {
+            Ok(_) => "App password revoked".to_string(),
+            Err(e) => format!("Error: {}", e),
+        })
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"App Passwords"}</h2>
+            <p>
+                {"Subsonic players log in with the username and an app password, the server address is "}
+                <code>{"/rest"}</code> {" of this site."}
+            </p>
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=name_node
+                placeholder="Password Name, e.g. car"
+            />
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_create
+            >
+                {"Create App Password"}
+            </button>
+            {move || {
+                create_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(password) => {
+                            view! {
+                                <p>{"Copy the password now, it will not be shown again:"}</p>
+                                <code class="break-all">{password}</code>
+                            }
+                                .into_view()
+                        }
+                        Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                    })
+            }}
+
+            <p>{message}</p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    passwords
+                        .get()
+                        .map(|passwords| match passwords {
+                            Ok(passwords) if passwords.is_empty() => {
+                                view! { <p>{"No app passwords"}</p> }.into_view()
+                            }
+                            Ok(passwords) => {
+                                passwords
+                                    .into_iter()
+                                    .map(|password| {
+                                        let password_id = password.id;
+                                        view! {
+                                            <div class="flex flex-row w-full items-center p-2 border border-solid rounded">
+                                                <div class="flex flex-col flex-1">
+                                                    <span>{password.name}</span>
+                                                    <span class="text-sm">
+                                                        {format!("Created: {}", password.created)}
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!(
+                                                            "Last used: {}",
+                                                            password.last_used.unwrap_or("never".to_string()),
+                                                        )}
+
+                                                    </span>
+                                                </div>
+                                                <button
+                                                    class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                    on:click=move |_| {
+                                                        revoke_action
+                                                            .dispatch(RevokeAppPassword { password_id })
+                                                    }
+                                                >
+
+                                                    {"Revoke"}
+                                                </button>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
@@ -0,0 +1,207 @@
+use leptos::{html::Input, html::Select, *};
+
+use crate::server_api::audit::{
+    get_audit_retention, list_audit_events, AuditAction, SetAuditRetention,
+};
+
+const EVENTS_PER_PAGE: u64 = 20;
+
+#[component]
+/// who added, deleted or changed what, for the admins
+pub fn AuditEvents() -> impl IntoView {
+    let (page, set_page) = create_signal(0u64);
+    let (filter, set_filter) = create_signal((None::<AuditAction>, None::<String>, None::<String>));
+    let retention_action = create_server_action::<SetAuditRetention>();
+    let events = create_resource(
+        move || (page.get(), filter.get()),
+        |(page, (action, actor, target))| {
+            list_audit_events(page, EVENTS_PER_PAGE, action, actor, target)
+        },
+    );
+    let retention = create_resource(
+        move || retention_action.version().get(),
+        |_| get_audit_retention(),
+    );
+    let actor_node = create_node_ref::<Input>();
+    let target_node = create_node_ref::<Input>();
+    let action_node = create_node_ref::<Select>();
+    let retention_node = create_node_ref::<Input>();
+    let on_filter = move |_| {
+        let action = AuditAction::parse(&action_node.get().unwrap().value());
+        let actor = Some(actor_node.get().unwrap().value()).filter(|a| !a.is_empty());
+        let target = Some(target_node.get().unwrap().value()).filter(|t| !t.is_empty());
+        set_page(0);
+        set_filter((action, actor, target));
+    };
+    let on_retention = move |_| {
+        if let Ok(days) = retention_node.get().unwrap().value().trim().parse() {
+            retention_action.dispatch(SetAuditRetention { days });
+        }
+    };
+    let retention_message = move || {
+        retention_action.value().get().map(|r| match r {
+            Ok(_) => "Retention saved, the older events are deleted within a day".to_string(),
+            Err(e) => format!("Error: {}", e),
+        })
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Audit Log"}</h2>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    retention
+                        .get()
+                        .map(|retention| match retention {
+                            Ok(days) => {
+                                view! {
+                                    <label class="flex flex-row items-center justify-between space-x-2">
+                                        <span>{"Keep the events for days, at least 30, 0 keeps them forever"}</span>
+                                        <input
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            type="number"
+                                            min="0"
+                                            ref=retention_node
+                                            value=days
+                                        />
+                                    </label>
+                                    <button
+                                        class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                        on:click=on_retention
+                                    >
+                                        {"Save Retention"}
+                                    </button>
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            <p>{retention_message}</p>
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=actor_node
+                placeholder="User Name, empty for all"
+            />
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=target_node
+                placeholder="Target, e.g. book 12, empty for all"
+            />
+            <select
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                ref=action_node
+            >
+                <option value="">{"All actions"}</option>
+                {AuditAction::ALL
+                    .into_iter()
+                    .map(|action| view! { <option value=action.as_str()>{action.as_str()}</option> })
+                    .collect_view()}
+            </select>
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_filter
+            >
+                {"Filter"}
+            </button>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    events
+                        .get()
+                        .map(|events| match events {
+                            Ok(events) => {
+                                let number_of_pages = events.number_of_pages;
+                                view! {
+                                    <div class="flex flex-row w-full items-center justify-between">
+                                        <button
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            on:click=move |_| {
+                                                if page.get_untracked() > 0 {
+                                                    set_page(page.get_untracked() - 1)
+                                                }
+                                            }
+                                        >
+
+                                            {"Previous"}
+                                        </button>
+                                        <span>
+                                            {format!(
+                                                "Page {} of {}",
+                                                events.page + 1,
+                                                number_of_pages.max(1),
+                                            )}
+
+                                        </span>
+                                        <button
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            on:click=move |_| {
+                                                if page.get_untracked() + 1 < number_of_pages {
+                                                    set_page(page.get_untracked() + 1)
+                                                }
+                                            }
+                                        >
+
+                                            {"Next"}
+                                        </button>
+                                    </div>
+                                    {events
+                                        .items
+                                        .into_iter()
+                                        .map(|event| {
+                                            let actor = if event.actor_name.is_empty() {
+                                                "anonymous".to_string()
+                                            } else {
+                                                event.actor_name
+                                            };
+                                            view! {
+                                                <div class="flex flex-col w-full p-2 border border-solid rounded">
+                                                    <span>
+                                                        {format!(
+                                                            "{} {} by {}",
+                                                            event.action.map_or("unknown", |a| a.as_str()),
+                                                            event.target,
+                                                            actor,
+                                                        )}
+
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!("{} from {}", event.created, event.ip)}
+                                                    </span>
+                                                    {(!event.before.is_empty())
+                                                        .then(|| {
+                                                            view! {
+                                                                <span class="text-sm">
+                                                                    {format!("before: {}", event.before)}
+                                                                </span>
+                                                            }
+                                                        })}
+                                                    {(!event.after.is_empty())
+                                                        .then(|| {
+                                                            view! {
+                                                                <span class="text-sm">
+                                                                    {format!("after: {}", event.after)}
+                                                                </span>
+                                                            }
+                                                        })}
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()}
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
@@ -0,0 +1,127 @@
+use leptos::{html::Input, html::Select, *};
+
+use crate::server_api::auth_event::{list_auth_events, AuthEventKind};
+
+const EVENTS_PER_PAGE: u64 = 20;
+
+#[component]
+/// the logins and the failed attempts, for the admins
+pub fn AuthEvents() -> impl IntoView {
+    let (page, set_page) = create_signal(0u64);
+    let (filter, set_filter) = create_signal((None::<AuthEventKind>, None::<String>));
+    let events = create_resource(
+        move || (page.get(), filter.get()),
+        |(page, (kind, username))| list_auth_events(page, EVENTS_PER_PAGE, kind, username),
+    );
+    let username_node = create_node_ref::<Input>();
+    let kind_node = create_node_ref::<Select>();
+    let on_filter = move |_| {
+        let kind = AuthEventKind::parse(&kind_node.get().unwrap().value());
+        let username = Some(username_node.get().unwrap().value()).filter(|u| !u.is_empty());
+        set_page(0);
+        set_filter((kind, username));
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Login Events"}</h2>
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=username_node
+                placeholder="User Name, empty for all"
+            />
+            <select
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                ref=kind_node
+            >
+                <option value="">{"All events"}</option>
+                {AuthEventKind::ALL
+                    .into_iter()
+                    .map(|kind| view! { <option value=kind.as_str()>{kind.as_str()}</option> })
+                    .collect_view()}
+            </select>
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_filter
+            >
+                {"Filter"}
+            </button>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    events
+                        .get()
+                        .map(|events| match events {
+                            Ok(events) => {
+                                let number_of_pages = events.number_of_pages;
+                                view! {
+                                    <div class="flex flex-row w-full items-center justify-between">
+                                        <button
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            on:click=move |_| {
+                                                if page.get_untracked() > 0 {
+                                                    set_page(page.get_untracked() - 1)
+                                                }
+                                            }
+                                        >
+
+                                            {"Previous"}
+                                        </button>
+                                        <span>
+                                            {format!(
+                                                "Page {} of {}",
+                                                events.page + 1,
+                                                number_of_pages.max(1),
+                                            )}
+
+                                        </span>
+                                        <button
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            on:click=move |_| {
+                                                if page.get_untracked() + 1 < number_of_pages {
+                                                    set_page(page.get_untracked() + 1)
+                                                }
+                                            }
+                                        >
+
+                                            {"Next"}
+                                        </button>
+                                    </div>
+                                    {events
+                                        .items
+                                        .into_iter()
+                                        .map(|event| {
+                                            let failed = event.kind.is_some_and(|k| k.is_failure());
+                                            view! {
+                                                <div
+                                                    class="flex flex-col w-full p-2 border border-solid rounded"
+                                                    class:bg-red-50=failed
+                                                >
+                                                    <span>
+                                                        {format!(
+                                                            "{} {}",
+                                                            event.kind.map_or("unknown", |k| k.as_str()),
+                                                            event.username,
+                                                        )}
+
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!("{} from {}", event.created, event.ip)}
+                                                    </span>
+                                                    <span class="text-sm">{event.detail}</span>
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()}
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
@@ -3,13 +3,16 @@ use leptos::{html::Input, *};
 #[component]
 pub fn ChangePassword()->impl IntoView{
     let user = use_context::<crate::server_api::User>().unwrap();
+    let user_id = user.id;
     let password_node = create_node_ref::<Input>();
+    let logout_node = create_node_ref::<Input>();
     let change_password = create_server_action::<crate::server_api::auth::ChangeUserPasswd>();
     let on_change_password = move |_| {
         let password = password_node.get().unwrap().value();
         change_password.dispatch(crate::server_api::auth::ChangeUserPasswd {
             user_id: user.id,
             new_password: password,
+            logout_other_sessions: logout_node.get().unwrap().checked(),
         });
         password_node.get().unwrap().set_value("");
     };
@@ -23,6 +26,10 @@ pub fn ChangePassword()->impl IntoView{
                 ref=password_node
                 placeholder="New Password"
             />
+            <div class="flex justify-between items-center">
+                <p>{"Log out my other sessions"}</p>
+                <input ref=logout_node type="checkbox" checked/>
+            </div>
             <button
                 class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
                 on:click=on_change_password
@@ -41,5 +48,66 @@ pub fn ChangePassword()->impl IntoView{
             }}
 
         </p>
+        <ChangeEmail user_id=user_id/>
+    }
+}
+
+#[component]
+/// the address of the password reset mails
+fn ChangeEmail(user_id: i32) -> impl IntoView {
+    use crate::server_api::auth::{get_user_email, SetUserEmail};
+    let email_node = create_node_ref::<Input>();
+    let set_email = create_server_action::<SetUserEmail>();
+    let email = create_resource(
+        move || set_email.version().get(),
+        move |_| get_user_email(user_id),
+    );
+    let on_save = move |_| {
+        set_email.dispatch(SetUserEmail {
+            user_id,
+            email: email_node.get().unwrap().value(),
+        });
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Email"}</h2>
+            <p>{"Used to reset a forgotten password."}</p>
+            <Transition fallback=move || {}>
+                {move || {
+                    email
+                        .get()
+                        .map(|email| {
+                            view! {
+                                <input
+                                    class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                    type="email"
+                                    ref=email_node
+                                    placeholder="Email, empty for none"
+                                    value=email.ok().flatten().unwrap_or_default()
+                                />
+                            }
+                        })
+                }}
+
+            </Transition>
+            <button
+                class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_save
+            >
+                {"Save Email"}
+            </button>
+            <p>
+                {move || {
+                    set_email
+                        .value()
+                        .get()
+                        .map(|result| match result {
+                            Ok(_) => "Email saved".to_string(),
+                            Err(e) => e.to_string(),
+                        })
+                }}
+
+            </p>
+        </div>
     }
 }
\ No newline at end of file
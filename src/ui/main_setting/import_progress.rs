@@ -0,0 +1,202 @@
+use leptos::{
+    html::{Select, Textarea},
+    *,
+};
+
+use crate::server_api::{
+    bookmark::format_position,
+    importer::{
+        apply_progress_import, preview_progress_import, ImportFormat, ImportMatch, ImportSelection,
+    },
+};
+
+type Reviewed = Vec<(ImportMatch, RwSignal<bool>)>;
+
+#[component]
+/// read the positions of another player, review the matches and write the selected ones
+pub fn ImportProgress() -> impl IntoView {
+    let users = create_resource(|| {}, |_| crate::server_api::auth::get_all_users());
+    let account_node = create_node_ref::<Select>();
+    let format_node = create_node_ref::<Select>();
+    let data_node = create_node_ref::<Textarea>();
+    let reviewed = create_rw_signal(None::<Result<Reviewed, ServerFnError>>);
+    let applied = create_rw_signal(None::<Result<usize, ServerFnError>>);
+
+    let on_preview = move |_| {
+        let format = format_node.get().unwrap().value();
+        let format = ImportFormat::ALL
+            .into_iter()
+            .find(|f| f.name() == format)
+            .unwrap_or(ImportFormat::Csv);
+        let data = data_node.get().unwrap().value();
+        applied.set(None);
+        spawn_local(async move {
+            let matches = preview_progress_import(format, data).await.map(|matches| {
+                matches
+                    .into_iter()
+                    .map(|m| {
+                        let selected = create_rw_signal(m.target.is_some());
+                        (m, selected)
+                    })
+                    .collect()
+            });
+            reviewed.set(Some(matches));
+        });
+    };
+    let on_apply = move |_| {
+        let Ok(account_id) = account_node.get().unwrap().value().parse::<i32>() else {
+            return;
+        };
+        let selections = reviewed.with_untracked(|reviewed| match reviewed {
+            Some(Ok(reviewed)) => reviewed
+                .iter()
+                .filter(|(_, selected)| selected.get_untracked())
+                .filter_map(|(m, _)| {
+                    let target = m.target.as_ref()?;
+                    Some(ImportSelection {
+                        music_id: target.music_id,
+                        chapter_id: target.chapter_id,
+                        progress: m.entry.seconds,
+                        updated: m.entry.updated.clone(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        });
+        spawn_local(async move {
+            applied.set(Some(apply_progress_import(account_id, selections).await));
+        });
+    };
+
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Import progress from other players"}</h2>
+            <label>{"Into user"}</label>
+            <select class="w-full px-2 py-1 bg-gray-100 border border-solid rounded" ref=account_node>
+                <Transition fallback=move || {
+                    view! { <option>"Loading..."</option> }
+                }>
+                    {move || {
+                        users
+                            .get()
+                            .map(|users| {
+                                users
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .map(|user| {
+                                        view! { <option value=user.id>{user.username}</option> }
+                                    })
+                                    .collect_view()
+                            })
+                    }}
+
+                </Transition>
+            </select>
+            <label>{"From"}</label>
+            <select class="w-full px-2 py-1 bg-gray-100 border border-solid rounded" ref=format_node>
+                {ImportFormat::ALL
+                    .into_iter()
+                    .map(|f| view! { <option value=f.name()>{f.name()}</option> })
+                    .collect_view()}
+            </select>
+            <textarea
+                class="w-full h-32 px-2 py-1 bg-gray-100 border border-solid rounded"
+                ref=data_node
+                placeholder="Paste the export here, for CSV: title,author,chapter index,seconds"
+            ></textarea>
+            <button
+                class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_preview
+            >
+                {"Review matches"}
+            </button>
+            {move || {
+                reviewed
+                    .get()
+                    .map(|reviewed| {
+                        match reviewed {
+                            Ok(reviewed) => {
+                                let matched = reviewed.iter().filter(|(m, _)| m.target.is_some()).count();
+                                view! {
+                                    <p>
+                                        {format!(
+                                            "{} of {} entries found in the library",
+                                            matched,
+                                            reviewed.len(),
+                                        )}
+
+                                    </p>
+                                    <ul class="flex flex-col w-full space-y-1 text-left text-sm">
+                                        {reviewed
+                                            .into_iter()
+                                            .map(|(m, selected)| view! { <ReviewRow m=m selected=selected/> })
+                                            .collect_view()}
+                                    </ul>
+                                    <button
+                                        class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                        on:click=on_apply
+                                    >
+                                        {"Apply selected"}
+                                    </button>
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                        }
+                    })
+            }}
+            {move || {
+                applied
+                    .get()
+                    .map(|applied| match applied {
+                        Ok(books) => format!("Progress of {} books imported", books),
+                        Err(e) => format!("Error: {}", e),
+                    })
+            }}
+
+        </div>
+    }
+}
+
+#[component]
+fn ReviewRow(m: ImportMatch, selected: RwSignal<bool>) -> impl IntoView {
+    let entry = &m.entry;
+    let source = format!(
+        "{} - {} / {} {}",
+        entry.author,
+        entry.title,
+        entry
+            .chapter_name
+            .clone()
+            .or(entry.chapter_index.map(|i| format!("#{}", i + 1)))
+            .unwrap_or_default(),
+        format_position(entry.seconds),
+    );
+    let has_target = m.target.is_some();
+    let target = match m.target {
+        Some(t) => format!(
+            "{} - {} / {} ({:.0}%)",
+            t.author,
+            t.title,
+            t.chapter_name,
+            t.score * 100.
+        ),
+        None => "not found".to_string(),
+    };
+    view! {
+        <li class="flex flex-row w-full space-x-2 p-1 border border-solid rounded-sm items-center">
+            <input
+                type="checkbox"
+                prop:checked=selected
+                prop:disabled=!has_target
+                on:change=move |ev| selected.set(event_target_checked(&ev))
+            />
+            <div class="flex flex-col">
+                <p>{source}</p>
+                <p class=if has_target { "text-green-700" } else { "text-red-700" }>
+                    {format!("→ {}", target)}
+                </p>
+            </div>
+        </li>
+    }
+}
@@ -0,0 +1,371 @@
+use leptos::{
+    html::{Input, Select},
+    *,
+};
+
+use crate::server_api::{
+    auth::DeleteUser,
+    invitation::{list_invitations, CreateInvitation, RevokeInvitation},
+    library::list_libraries,
+    registration::{
+        get_registration_settings, list_pending_accounts, ApproveAccount,
+        SaveRegistrationSettings,
+    },
+    role::list_roles,
+};
+
+#[component]
+/// invite links and the self-registration, for the admins
+pub fn Invitations() -> impl IntoView {
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Invitations"}</h2>
+            <CreateInvitationForm/>
+            <h2>{"Registration"}</h2>
+            <Registration/>
+        </div>
+    }
+}
+
+#[component]
+fn CreateInvitationForm() -> impl IntoView {
+    let roles = create_resource(|| {}, |_| list_roles());
+    let libraries = create_resource(|| {}, |_| list_libraries());
+    let create_action = create_server_action::<CreateInvitation>();
+    let revoke_action = create_server_action::<RevokeInvitation>();
+    let invitations = create_resource(
+        move || (create_action.version().get(), revoke_action.version().get()),
+        |_| list_invitations(),
+    );
+    let role_node = create_node_ref::<Select>();
+    let uses_node = create_node_ref::<Input>();
+    let days_node = create_node_ref::<Input>();
+    let selected_libraries = create_rw_signal(Vec::<i32>::new());
+    let (error_msg, set_error_msg) = create_signal(String::new());
+    let on_create = move |_| {
+        let Ok(role) = role_node.get().unwrap().value().parse::<i32>() else {
+            set_error_msg("Select a role".to_string());
+            return;
+        };
+        let expires_in_days = days_node.get().unwrap().value().trim().parse().unwrap_or(7);
+        set_error_msg(String::new());
+        create_action.dispatch(CreateInvitation {
+            role,
+            libraries: selected_libraries.get_untracked(),
+            max_uses: uses_node.get().unwrap().value().trim().parse().ok(),
+            expires_in_days,
+        });
+    };
+    let role_name = move |role_id: i32| {
+        roles
+            .get()
+            .and_then(Result::ok)
+            .and_then(|roles| roles.into_iter().find(|r| r.id == role_id))
+            .map_or(role_id.to_string(), |r| r.name)
+    };
+    let library_names = move |ids: Vec<i32>| {
+        if ids.is_empty() {
+            // the new account gets the default library
+            return "default".to_string();
+        }
+        let libraries = libraries.get().and_then(Result::ok).unwrap_or_default();
+        ids.into_iter()
+            .map(|id| {
+                libraries
+                    .iter()
+                    .find(|l| l.id == id)
+                    .map_or(id.to_string(), |l| l.name.clone())
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    view! {
+        <select
+            class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+            ref=role_node
+        >
+            <Transition fallback=move || {
+                view! { <option>"Loading..."</option> }
+            }>
+                {move || {
+                    roles
+                        .get()
+                        .map(|roles| {
+                            roles
+                                .unwrap_or_default()
+                                .into_iter()
+                                .map(|role| view! { <option value=role.id>{role.name}</option> })
+                                .collect_view()
+                        })
+                }}
+
+            </Transition>
+        </select>
+        <Transition fallback=move || {}>
+            {move || {
+                libraries
+                    .get()
+                    .map(|libraries| {
+                        libraries
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|library| {
+                                let library_id = library.id;
+                                view! {
+                                    <label class="flex flex-row items-center space-x-2">
+                                        <input
+                                            type="checkbox"
+                                            on:change=move |ev| {
+                                                let checked = event_target_checked(&ev);
+                                                selected_libraries
+                                                    .update(|ids| {
+                                                        ids.retain(|id| *id != library_id);
+                                                        if checked {
+                                                            ids.push(library_id);
+                                                        }
+                                                    });
+                                            }
+                                        />
+
+                                        <span>{library.name}</span>
+                                    </label>
+                                }
+                            })
+                            .collect_view()
+                    })
+            }}
+
+        </Transition>
+        <input
+            class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+            type="number"
+            min="1"
+            ref=uses_node
+            placeholder="Uses, empty for no limit"
+        />
+        <input
+            class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+            type="number"
+            min="1"
+            max="365"
+            ref=days_node
+            placeholder="Expires in days, 7 when empty"
+        />
+        <button
+            class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+            on:click=on_create
+        >
+            {"Create Invite Link"}
+        </button>
+        <p>{error_msg}</p>
+        {move || {
+            create_action
+                .value()
+                .get()
+                .map(|r| match r {
+                    Ok(path) => {
+                        let origin = window().location().origin().unwrap_or_default();
+                        view! {
+                            <p>{"Copy the link now, it will not be shown again:"}</p>
+                            <code class="break-all">{format!("{}{}", origin, path)}</code>
+                        }
+                            .into_view()
+                    }
+                    Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                })
+        }}
+
+        <Transition fallback=move || {
+            view! { <span>"Loading..."</span> }
+        }>
+            {move || {
+                invitations
+                    .get()
+                    .map(|invitations| match invitations {
+                        Ok(invitations) if invitations.is_empty() => {
+                            view! { <p>{"No invitations"}</p> }.into_view()
+                        }
+                        Ok(invitations) => {
+                            invitations
+                                .into_iter()
+                                .map(|invitation| {
+                                    let invitation_id = invitation.id;
+                                    view! {
+                                        <div class="flex flex-row w-full items-center p-2 border border-solid rounded">
+                                            <div class="flex flex-col flex-1">
+                                                <span>
+                                                    {format!("Role: {}", role_name(invitation.role))}
+                                                </span>
+                                                <span class="text-sm">
+                                                    {format!(
+                                                        "Libraries: {}",
+                                                        library_names(invitation.libraries),
+                                                    )}
+
+                                                </span>
+                                                <span class="text-sm">
+                                                    {match invitation.max_uses {
+                                                        Some(max) => {
+                                                            format!("Used: {} of {}", invitation.uses, max)
+                                                        }
+                                                        None => format!("Used: {}", invitation.uses),
+                                                    }}
+
+                                                </span>
+                                                <span class="text-sm">
+                                                    {format!("Expires: {}", invitation.expires)}
+                                                </span>
+                                            </div>
+                                            <button
+                                                class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                on:click=move |_| {
+                                                    revoke_action.dispatch(RevokeInvitation { invitation_id })
+                                                }
+                                            >
+
+                                                {"Revoke"}
+                                            </button>
+                                        </div>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                        Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                    })
+            }}
+
+        </Transition>
+    }
+}
+
+#[component]
+/// the self-registration setting and the accounts waiting for approval
+fn Registration() -> impl IntoView {
+    let roles = create_resource(|| {}, |_| list_roles());
+    let save_action = create_server_action::<SaveRegistrationSettings>();
+    let approve_action = create_server_action::<ApproveAccount>();
+    let reject_action = create_server_action::<DeleteUser>();
+    let settings = create_resource(
+        move || save_action.version().get(),
+        |_| get_registration_settings(),
+    );
+    let pending = create_resource(
+        move || (approve_action.version().get(), reject_action.version().get()),
+        |_| list_pending_accounts(),
+    );
+    let open_node = create_node_ref::<Input>();
+    let role_node = create_node_ref::<Select>();
+    let on_save = move |_| {
+        let Ok(role) = role_node.get().unwrap().value().parse::<i32>() else {
+            return;
+        };
+        save_action.dispatch(SaveRegistrationSettings {
+            open: open_node.get().unwrap().checked(),
+            role,
+        });
+    };
+    view! {
+        <Transition fallback=move || {
+            view! { <span>"Loading..."</span> }
+        }>
+            {move || {
+                settings
+                    .get()
+                    .map(|settings| match settings {
+                        Ok(settings) => {
+                            let roles = roles.get().and_then(Result::ok).unwrap_or_default();
+                            view! {
+                                <label class="flex flex-row items-center justify-between">
+                                    <span>{"Anyone can register, an admin approves the accounts"}</span>
+                                    <input ref=open_node type="checkbox" checked=settings.open/>
+                                </label>
+                                <select
+                                    class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                    ref=role_node
+                                >
+                                    {roles
+                                        .into_iter()
+                                        .map(|role| {
+                                            view! {
+                                                <option value=role.id selected=role.id == settings.role>
+                                                    {role.name}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                                <button
+                                    class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                    on:click=on_save
+                                >
+                                    {"Save"}
+                                </button>
+                            }
+                                .into_view()
+                        }
+                        Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                    })
+            }}
+
+        </Transition>
+        <p>
+            {move || {
+                save_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(_) => "Saved".to_string(),
+                        Err(e) => format!("Error: {}", e),
+                    })
+            }}
+
+        </p>
+        <h2>{"Waiting for Approval"}</h2>
+        <Transition fallback=move || {
+            view! { <span>"Loading..."</span> }
+        }>
+            {move || {
+                pending
+                    .get()
+                    .map(|pending| match pending {
+                        Ok(users) if users.is_empty() => {
+                            view! { <p>{"No accounts"}</p> }.into_view()
+                        }
+                        Ok(users) => {
+                            users
+                                .into_iter()
+                                .map(|user| {
+                                    let user_id = user.id;
+                                    view! {
+                                        <div class="flex flex-row w-full items-center p-2 space-x-2 border border-solid rounded">
+                                            <span class="flex-1">{user.username}</span>
+                                            <button
+                                                class="px-4 py-1 bg-green-200 hover:bg-green-300 border border-solid rounded-full"
+                                                on:click=move |_| {
+                                                    approve_action.dispatch(ApproveAccount { user_id })
+                                                }
+                                            >
+
+                                                {"Approve"}
+                                            </button>
+                                            <button
+                                                class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                on:click=move |_| {
+                                                    reject_action.dispatch(DeleteUser { user_id })
+                                                }
+                                            >
+
+                                                {"Reject"}
+                                            </button>
+                                        </div>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                        Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                    })
+            }}
+
+        </Transition>
+    }
+}
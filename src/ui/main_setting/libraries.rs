@@ -0,0 +1,179 @@
+use leptos::{html::Input, *};
+
+use crate::server_api::{
+    auth::get_all_users,
+    library::{
+        get_library_grants, list_libraries, DeleteLibrary, LibraryDetail, SaveLibrary,
+        SetLibraryGrant,
+    },
+};
+
+#[component]
+/// edit the libraries and which users can see them
+pub fn Libraries() -> impl IntoView {
+    let save_action = create_server_action::<SaveLibrary>();
+    let delete_action = create_server_action::<DeleteLibrary>();
+    let libraries = create_resource(
+        move || (save_action.version().get(), delete_action.version().get()),
+        |_| list_libraries(),
+    );
+    let message = move || {
+        let result = save_action
+            .value()
+            .get()
+            .map(|r| r.map(|_| "Library saved"))
+            .or(delete_action.value().get().map(|r| r.map(|_| "Library deleted")));
+        result.map(|r| match r {
+            Ok(msg) => msg.to_string(),
+            Err(e) => format!("Error: {}", e),
+        })
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Libraries"}</h2>
+            <p>{message}</p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    libraries
+                        .get()
+                        .map(|libraries| match libraries {
+                            Ok(libraries) => {
+                                libraries
+                                    .into_iter()
+                                    .map(|library| {
+                                        view! {
+                                            <LibraryEditor
+                                                library=Some(library)
+                                                save_action=save_action
+                                                delete_action=delete_action
+                                            />
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            <h3>{"New library"}</h3>
+            <LibraryEditor library=None save_action=save_action delete_action=delete_action/>
+        </div>
+    }
+}
+
+#[component]
+fn LibraryEditor(
+    library: Option<LibraryDetail>,
+    save_action: Action<SaveLibrary, Result<i32, ServerFnError>>,
+    delete_action: Action<DeleteLibrary, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let library_id = library.as_ref().map(|l| l.id);
+    let name_node = create_node_ref::<Input>();
+    let root_node = create_node_ref::<Input>();
+    let (name, root) = library.map(|l| (l.name, l.root)).unwrap_or_default();
+    let on_save = move |_| {
+        save_action.dispatch(SaveLibrary {
+            library_id,
+            name: name_node.get().unwrap().value(),
+            root: root_node.get().unwrap().value(),
+        });
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2 border border-solid rounded">
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=name_node
+                placeholder="Library Name"
+                value=name
+            />
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=root_node
+                placeholder="Folder on the server, e.g. ./fetchbook_kids"
+                value=root
+            />
+            {library_id.map(|library_id| view! { <LibraryGrants library_id=library_id/> })}
+            <div class="flex flex-row w-full space-x-2">
+                <button
+                    class="flex-1 px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                    on:click=on_save
+                >
+                    {if library_id.is_some() { "Save" } else { "Create" }}
+                </button>
+                {library_id
+                    .map(|library_id| {
+                        view! {
+                            <button
+                                class="flex-1 px-1 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                on:click=move |_| delete_action.dispatch(DeleteLibrary { library_id })
+                            >
+                                {"Delete"}
+                            </button>
+                        }
+                    })}
+
+            </div>
+        </div>
+    }
+}
+
+#[component]
+/// a checkbox for each user, checked when the user can see the library
+fn LibraryGrants(library_id: i32) -> impl IntoView {
+    let grant_action = create_server_action::<SetLibraryGrant>();
+    let grants = create_resource(
+        move || grant_action.version().get(),
+        move |_| async move {
+            let users = get_all_users().await?;
+            let grants = get_library_grants(library_id).await?;
+            Ok::<_, ServerFnError>((users, grants))
+        },
+    );
+    view! {
+        <div class="flex flex-row w-full flex-wrap">
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    grants
+                        .get()
+                        .map(|grants| match grants {
+                            Ok((users, grants)) => {
+                                users
+                                    .into_iter()
+                                    .map(|user| {
+                                        let account_id = user.id;
+                                        view! {
+                                            <label class="px-2">
+                                                <input
+                                                    type="checkbox"
+                                                    prop:checked=grants.contains(&account_id)
+                                                    on:change=move |ev| {
+                                                        grant_action
+                                                            .dispatch(SetLibraryGrant {
+                                                                library_id,
+                                                                account_id,
+                                                                granted: event_target_checked(&ev),
+                                                            })
+                                                    }
+                                                />
+
+                                                {user.username}
+                                            </label>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
@@ -1,12 +1,29 @@
 use leptos::*;
 
-use crate::{server_api::auth::Logout, ui::app::LogoutContext};
+use crate::{
+    server_api::{auth::Logout, permission::Permission},
+    ui::app::LogoutContext,
+};
 
 mod add_book;
 mod add_user;
+mod api_tokens;
+mod app_passwords;
+mod audit_events;
+mod auth_events;
 mod change_passwd;
 mod del_book;
 mod del_user;
+mod import_progress;
+mod invitations;
+mod libraries;
+mod podcast_feeds;
+mod preference;
+mod roles;
+mod sessions;
+mod transfer;
+mod two_factor;
+mod webhooks;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum SettingsContent {
@@ -16,13 +33,31 @@ pub enum SettingsContent {
     DeleteBookDetail(i32),
     AddUser,
     DeleteUser,
+    Roles,
+    Libraries,
     ChangePassword,
+    Preferences,
+    TransferData,
+    ImportProgress,
+    ApiTokens,
+    AppPasswords,
+    PodcastFeeds,
+    TwoFactor,
+    Sessions,
+    AuthEvents,
+    Invitations,
+    AuditEvents,
+    Webhooks,
 }
 
 #[component]
 pub fn MainSettings() -> impl IntoView {
     let (current_content, set_current_content) = create_signal(SettingsContent::Main);
     let user = use_context::<crate::server_api::User>().unwrap();
+    let can_upload = user.can(Permission::Upload);
+    let can_delete = user.can(Permission::Delete);
+    let can_manage_users = user.can(Permission::ManageUsers);
+    let can_manage_library = user.can(Permission::ManageLibrary);
     let logout_action = use_context::<LogoutContext>().unwrap().0;
     view! {
         {move || {
@@ -33,15 +68,15 @@ pub fn MainSettings() -> impl IntoView {
                             <h1>{"Settings"}</h1>
                             <button
                                 class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
-                                prop:disabled=move || { user.role != 0 }
+                                prop:disabled=move || { !can_upload }
                                 on:click=move |_| {
-                                    if user.role == 0 {
+                                    if can_upload {
                                         set_current_content(SettingsContent::AddBook)
                                     }
                                 }
                             >
 
-                                {if user.role == 0 {
+                                {if can_upload {
                                     "Add Book"
                                 } else {
                                     "Add Book(no permission)"
@@ -50,15 +85,15 @@ pub fn MainSettings() -> impl IntoView {
                             </button>
                             <button
                                 class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
-                                prop:disabled=move || { user.role != 0 }
+                                prop:disabled=move || { !can_delete }
                                 on:click=move |_| {
-                                    if user.role == 0 {
+                                    if can_delete {
                                         set_current_content(SettingsContent::DeleteBook)
                                     }
                                 }
                             >
 
-                                {if user.role == 0 {
+                                {if can_delete {
                                     "Delete Book"
                                 } else {
                                     "Delete Book(no permission)"
@@ -67,15 +102,15 @@ pub fn MainSettings() -> impl IntoView {
                             </button>
                             <button
                                 class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
-                                prop:disabled=move || { user.role != 0 }
+                                prop:disabled=move || { !can_manage_users }
                                 on:click=move |_| {
-                                    if user.role == 0 {
+                                    if can_manage_users {
                                         set_current_content(SettingsContent::AddUser)
                                     }
                                 }
                             >
 
-                                {if user.role == 0 {
+                                {if can_manage_users {
                                     "Add User"
                                 } else {
                                     "Add User(no permission)"
@@ -84,20 +119,140 @@ pub fn MainSettings() -> impl IntoView {
                             </button>
                             <button
                                 class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
-                                prop:disabled=move || { user.role != 0 }
+                                prop:disabled=move || { !can_manage_users }
                                 on:click=move |_| {
-                                    if user.role == 0 {
+                                    if can_manage_users {
                                         set_current_content(SettingsContent::DeleteUser)
                                     }
                                 }
                             >
 
-                                {if user.role == 0 {
+                                {if can_manage_users {
                                     "Delete User"
                                 } else {
                                     "Delete User(no permission)"
                                 }}
 
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_users }
+                                on:click=move |_| {
+                                    if can_manage_users {
+                                        set_current_content(SettingsContent::Roles)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_users {
+                                    "Roles"
+                                } else {
+                                    "Roles(no permission)"
+                                }}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_library }
+                                on:click=move |_| {
+                                    if can_manage_library {
+                                        set_current_content(SettingsContent::Libraries)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_library {
+                                    "Libraries"
+                                } else {
+                                    "Libraries(no permission)"
+                                }}
+
+                            </button>
+
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_users }
+                                on:click=move |_| {
+                                    if can_manage_users {
+                                        set_current_content(SettingsContent::ImportProgress)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_users {
+                                    "Import Progress"
+                                } else {
+                                    "Import Progress(no permission)"
+                                }}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_users }
+                                on:click=move |_| {
+                                    if can_manage_users {
+                                        set_current_content(SettingsContent::AuthEvents)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_users {
+                                    "Login Events"
+                                } else {
+                                    "Login Events(no permission)"
+                                }}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_users }
+                                on:click=move |_| {
+                                    if can_manage_users {
+                                        set_current_content(SettingsContent::Invitations)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_users {
+                                    "Invitations & Registration"
+                                } else {
+                                    "Invitations & Registration(no permission)"
+                                }}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_users }
+                                on:click=move |_| {
+                                    if can_manage_users {
+                                        set_current_content(SettingsContent::AuditEvents)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_users {
+                                    "Audit Log"
+                                } else {
+                                    "Audit Log(no permission)"
+                                }}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full disabled:opacity-50 disabled:bg-gray-200"
+                                prop:disabled=move || { !can_manage_users }
+                                on:click=move |_| {
+                                    if can_manage_users {
+                                        set_current_content(SettingsContent::Webhooks)
+                                    }
+                                }
+                            >
+
+                                {if can_manage_users {
+                                    "Webhooks"
+                                } else {
+                                    "Webhooks(no permission)"
+                                }}
+
                             </button>
 
                             <button
@@ -109,6 +264,62 @@ pub fn MainSettings() -> impl IntoView {
 
                                 {"Change Password"}
 
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::Preferences) }
+                            >
+
+                                {"Preferences"}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::TransferData) }
+                            >
+
+                                {"Export / Import"}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::ApiTokens) }
+                            >
+
+                                {"API Tokens"}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::AppPasswords) }
+                            >
+
+                                {"App Passwords"}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::PodcastFeeds) }
+                            >
+
+                                {"Podcast Feeds"}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::TwoFactor) }
+                            >
+
+                                {"Two-Factor Authentication"}
+
+                            </button>
+                            <button
+                                class="w-full text-center bg-blue-50 hover:bg-green-50 px-1 py-1 my-2 border border-solid rounded-full"
+                                on:click=move |_| { set_current_content(SettingsContent::Sessions) }
+                            >
+
+                                {"Devices & Sessions"}
+
                             </button>
                             <button
                                 class="w-full text-center bg-red-200 hover:bg-red-300 px-1 py-1 my-2 border border-solid rounded-full"
@@ -140,6 +351,10 @@ pub fn MainSettings() -> impl IntoView {
                 SettingsContent::DeleteUser => {
                     view! { <del_user::DeleteUser></del_user::DeleteUser> }.into_view()
                 }
+                SettingsContent::Roles => view! { <roles::Roles></roles::Roles> }.into_view(),
+                SettingsContent::Libraries => {
+                    view! { <libraries::Libraries></libraries::Libraries> }.into_view()
+                }
                 SettingsContent::DeleteBookDetail(id) => {
                     view! {
                         <del_book::DeleteBookDetail
@@ -153,6 +368,45 @@ pub fn MainSettings() -> impl IntoView {
                     view! { <change_passwd::ChangePassword></change_passwd::ChangePassword> }
                         .into_view()
                 }
+                SettingsContent::Preferences => {
+                    view! { <preference::Preferences></preference::Preferences> }.into_view()
+                }
+                SettingsContent::TransferData => {
+                    view! { <transfer::TransferData></transfer::TransferData> }.into_view()
+                }
+                SettingsContent::ImportProgress => {
+                    view! { <import_progress::ImportProgress></import_progress::ImportProgress> }
+                        .into_view()
+                }
+                SettingsContent::ApiTokens => {
+                    view! { <api_tokens::ApiTokens></api_tokens::ApiTokens> }.into_view()
+                }
+                SettingsContent::AppPasswords => {
+                    view! { <app_passwords::AppPasswords></app_passwords::AppPasswords> }
+                        .into_view()
+                }
+                SettingsContent::PodcastFeeds => {
+                    view! { <podcast_feeds::PodcastFeeds></podcast_feeds::PodcastFeeds> }
+                        .into_view()
+                }
+                SettingsContent::TwoFactor => {
+                    view! { <two_factor::TwoFactor></two_factor::TwoFactor> }.into_view()
+                }
+                SettingsContent::Sessions => {
+                    view! { <sessions::Sessions></sessions::Sessions> }.into_view()
+                }
+                SettingsContent::Invitations => {
+                    view! { <invitations::Invitations></invitations::Invitations> }.into_view()
+                }
+                SettingsContent::AuthEvents => {
+                    view! { <auth_events::AuthEvents></auth_events::AuthEvents> }.into_view()
+                }
+                SettingsContent::AuditEvents => {
+                    view! { <audit_events::AuditEvents></audit_events::AuditEvents> }.into_view()
+                }
+                SettingsContent::Webhooks => {
+                    view! { <webhooks::Webhooks></webhooks::Webhooks> }.into_view()
+                }
             }
         }}
     }
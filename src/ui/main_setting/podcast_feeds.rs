@@ -0,0 +1,91 @@
+use leptos::*;
+
+use crate::{
+    server_api::feed_token::{has_feed_token, ResetFeedToken, RevokeFeedToken},
+    ui::feed_token::{store_feed_token, stored_feed_token},
+};
+
+#[component]
+/// the feed token of the current user, it is in the podcast feed url of every book
+pub fn PodcastFeeds() -> impl IntoView {
+    let account_id = use_context::<crate::server_api::User>().unwrap().id;
+    let reset_action = create_server_action::<ResetFeedToken>();
+    let revoke_action = create_server_action::<RevokeFeedToken>();
+    let has_token = create_resource(
+        move || (reset_action.version().get(), revoke_action.version().get()),
+        |_| has_feed_token(),
+    );
+    // the local storage is only read in the browser, after the hydration
+    let stored_token = create_rw_signal(None::<String>);
+    create_effect(move |_| stored_token.set(stored_feed_token(account_id)));
+    create_effect(move |_| {
+        if let Some(Ok(token)) = reset_action.value().get() {
+            store_feed_token(account_id, Some(&token));
+            stored_token.set(Some(token));
+        }
+    });
+    create_effect(move |_| {
+        if let Some(Ok(())) = revoke_action.value().get() {
+            store_feed_token(account_id, None);
+            stored_token.set(None);
+        }
+    });
+    let message = move || {
+        let reset = reset_action.value().get().map(|r| match r {
+            Ok(_) => "New feed token created, the old feed urls stopped working".to_string(),
+            Err(e) => format!("Error: {}", e),
+        });
+        let revoke = revoke_action.value().get().map(|r| match r {
+            Ok(_) => "Feed token revoked".to_string(),
+            Err(e) => format!("Error: {}", e),
+        });
+        reset.or(revoke)
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Podcast Feeds"}</h2>
+            <p>
+                {"Every book has a private podcast feed for your podcast app, its link is on the page of the book. "}
+                {"The links contain your feed token, anyone with a link can listen to the book."}
+            </p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    has_token
+                        .get()
+                        .map(|has_token| match has_token {
+                            Ok(true) if stored_token.get().is_some() => {
+                                view! { <p>{"Your podcast feeds are enabled."}</p> }.into_view()
+                            }
+                            Ok(true) => {
+                                view! {
+                                    <p>
+                                        {"Your podcast feeds are enabled, their links are only shown in the browser that created the feed token. "}
+                                        {"Create a new feed token to show them here, the old links stop working."}
+                                    </p>
+                                }
+                                    .into_view()
+                            }
+                            Ok(false) => view! { <p>{"Your podcast feeds are disabled."}</p> }.into_view(),
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=move |_| reset_action.dispatch(ResetFeedToken {})
+            >
+                {"Create New Feed Token"}
+            </button>
+            <button
+                class="w-full px-1 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                on:click=move |_| revoke_action.dispatch(RevokeFeedToken {})
+            >
+                {"Revoke Feed Token"}
+            </button>
+            <p>{message}</p>
+        </div>
+    }
+}
@@ -0,0 +1,110 @@
+use leptos::{html::Input, *};
+
+#[component]
+pub fn Preferences() -> impl IntoView {
+    use crate::server_api::preference::*;
+    let speed_node = create_node_ref::<Input>();
+    let skip_back_node = create_node_ref::<Input>();
+    let sleep_timer_node = create_node_ref::<Input>();
+    let set_preference_action = create_server_action::<SetPreference>();
+    let result = set_preference_action.value();
+    let (error_msg, set_error_msg) = create_signal(String::new());
+
+    let preference = create_resource(|| {}, move |_| async move { get_preference().await });
+    let on_save = move |_| {
+        let Ok(playback_speed) = speed_node.get().unwrap().value().parse::<f64>() else {
+            set_error_msg("Playback speed must be a number".to_string());
+            return;
+        };
+        if !(MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED).contains(&playback_speed) {
+            set_error_msg(format!(
+                "Playback speed must be between {} and {}",
+                MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED
+            ));
+            return;
+        }
+        let skip_back_secs = skip_back_node
+            .get()
+            .unwrap()
+            .value()
+            .parse::<i32>()
+            .unwrap_or_default();
+        let sleep_timer_mins = sleep_timer_node.get().unwrap().value().parse::<i32>().ok();
+        set_error_msg(String::new());
+        set_preference_action.dispatch(SetPreference {
+            playback_speed,
+            skip_back_secs,
+            sleep_timer_mins,
+        });
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Preferences"}</h2>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    preference
+                        .get()
+                        .map(|p| {
+                            match p {
+                                Ok(p) => {
+                                    view! {
+                                        <h3>{"Default playback speed"}</h3>
+                                        <input
+                                            class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                            type="number"
+                                            step="0.25"
+                                            min=MIN_PLAYBACK_SPEED
+                                            max=MAX_PLAYBACK_SPEED
+                                            ref=speed_node
+                                            value=p.playback_speed
+                                        />
+                                        <h3>{"Skip back after resume (seconds)"}</h3>
+                                        <input
+                                            class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                            type="number"
+                                            min="0"
+                                            ref=skip_back_node
+                                            value=p.skip_back_secs
+                                        />
+                                        <h3>{"Default sleep timer (minutes, empty for none)"}</h3>
+                                        <input
+                                            class="w-full  my-1 px-4 py-1 bg-gray-100 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                            type="number"
+                                            min="0"
+                                            ref=sleep_timer_node
+                                            value=p.sleep_timer_mins.map(|mins| mins.to_string())
+                                        />
+                                    }
+                                        .into_view()
+                                }
+                                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                            }
+                        })
+                }}
+
+            </Transition>
+            <button
+                class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_save
+            >
+                {"Save"}
+            </button>
+            <p>{error_msg}</p>
+            <p>
+                {move || {
+                    result
+                        .get()
+                        .map(|r| {
+                            match r {
+                                Ok(_) => "Preferences saved".to_string(),
+                                Err(e) => format!("Error: {}", e),
+                            }
+                        })
+                }}
+
+            </p>
+        </div>
+    }
+}
@@ -0,0 +1,210 @@
+use leptos::{html::Input, *};
+
+use crate::server_api::{
+    auth::get_all_users,
+    permission::Permission,
+    role::{list_roles, DeleteRole, RoleDetail, SaveRole, SetUserRole},
+};
+
+#[component]
+/// edit the permissions of the roles and the role of each user
+pub fn Roles() -> impl IntoView {
+    let self_id = use_context::<crate::server_api::User>().unwrap().id;
+    let save_action = create_server_action::<SaveRole>();
+    let delete_action = create_server_action::<DeleteRole>();
+    let set_role_action = create_server_action::<SetUserRole>();
+    let roles = create_resource(
+        move || (save_action.version().get(), delete_action.version().get()),
+        |_| list_roles(),
+    );
+    let users = create_resource(move || set_role_action.version().get(), |_| get_all_users());
+
+    let message = move || {
+        let result = save_action
+            .value()
+            .get()
+            .map(|r| r.map(|_| "Role saved"))
+            .or(delete_action.value().get().map(|r| r.map(|_| "Role deleted")))
+            .or(set_role_action.value().get().map(|r| r.map(|_| "User role changed")));
+        result.map(|r| match r {
+            Ok(msg) => msg.to_string(),
+            Err(e) => format!("Error: {}", e),
+        })
+    };
+
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Roles"}</h2>
+            <p>{message}</p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    roles
+                        .get()
+                        .map(|roles| match roles {
+                            Ok(roles) => {
+                                roles
+                                    .into_iter()
+                                    .map(|role| {
+                                        view! {
+                                            <RoleEditor
+                                                role=Some(role)
+                                                save_action=save_action
+                                                delete_action=delete_action
+                                            />
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            <h3>{"New role"}</h3>
+            <RoleEditor role=None save_action=save_action delete_action=delete_action/>
+
+            <h2>{"Users"}</h2>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    let roles = roles.get().and_then(Result::ok).unwrap_or_default();
+                    users
+                        .get()
+                        .map(|users| match users {
+                            Ok(users) => {
+                                users
+                                    .into_iter()
+                                    .map(|user| {
+                                        let user_id = user.id;
+                                        view! {
+                                            <div class="flex flex-row w-full space-x-2 p-1 justify-between items-center">
+                                                <p>{user.username}</p>
+                                                <select
+                                                    class="px-2 py-1 bg-gray-100 border border-solid rounded"
+                                                    prop:disabled=user_id == self_id
+                                                    on:change=move |ev| {
+                                                        if let Ok(role_id) = event_target_value(&ev).parse() {
+                                                            set_role_action.dispatch(SetUserRole { user_id, role_id });
+                                                        }
+                                                    }
+                                                >
+
+                                                    {roles
+                                                        .iter()
+                                                        .map(|role| {
+                                                            view! {
+                                                                <option value=role.id selected=role.id == user.role>
+                                                                    {role.name.clone()}
+                                                                </option>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </select>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
+
+#[component]
+fn RoleEditor(
+    role: Option<RoleDetail>,
+    save_action: Action<SaveRole, Result<i32, ServerFnError>>,
+    delete_action: Action<DeleteRole, Result<(), ServerFnError>>,
+) -> impl IntoView {
+    let role_id = role.as_ref().map(|r| r.id);
+    let name_node = create_node_ref::<Input>();
+    let permissions = create_rw_signal(
+        role.as_ref()
+            .map(|r| r.permissions.clone())
+            .unwrap_or_default(),
+    );
+    let require_totp = create_rw_signal(role.as_ref().is_some_and(|r| r.require_totp));
+    let on_save = move |_| {
+        let name = name_node.get().unwrap().value();
+        save_action.dispatch(SaveRole {
+            role_id,
+            name,
+            permissions: permissions.get_untracked(),
+            require_totp: require_totp.get_untracked(),
+        });
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2 border border-solid rounded">
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=name_node
+                placeholder="Role Name"
+                value=role.map(|r| r.name).unwrap_or_default()
+            />
+            <div class="flex flex-row w-full flex-wrap">
+                {Permission::ALL
+                    .into_iter()
+                    .map(|p| {
+                        view! {
+                            <label class="px-2">
+                                <input
+                                    type="checkbox"
+                                    prop:checked=move || permissions.with(|ps| ps.contains(&p))
+                                    on:change=move |ev| {
+                                        let checked = event_target_checked(&ev);
+                                        permissions
+                                            .update(|ps| {
+                                                ps.retain(|x| *x != p);
+                                                if checked {
+                                                    ps.push(p);
+                                                }
+                                            });
+                                    }
+                                />
+
+                                {p.as_str()}
+                            </label>
+                        }
+                    })
+                    .collect_view()}
+            </div>
+            <label class="px-2">
+                <input
+                    type="checkbox"
+                    prop:checked=move || require_totp.get()
+                    on:change=move |ev| require_totp.set(event_target_checked(&ev))
+                />
+
+                {"require two-factor authentication"}
+            </label>
+            <div class="flex flex-row w-full space-x-2">
+                <button
+                    class="flex-1 px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                    on:click=on_save
+                >
+                    {if role_id.is_some() { "Save" } else { "Create" }}
+                </button>
+                {role_id
+                    .map(|role_id| {
+                        view! {
+                            <button
+                                class="flex-1 px-1 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                on:click=move |_| delete_action.dispatch(DeleteRole { role_id })
+                            >
+                                {"Delete"}
+                            </button>
+                        }
+                    })}
+
+            </div>
+        </div>
+    }
+}
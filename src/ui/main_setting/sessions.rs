@@ -0,0 +1,166 @@
+use leptos::*;
+
+use crate::server_api::{
+    auth::get_all_users,
+    permission::Permission,
+    session::{list_sessions, ForceLogout, RevokeOtherSessions, RevokeSession},
+};
+
+#[component]
+/// the browsers and devices logged in as the current user
+pub fn Sessions() -> impl IntoView {
+    let user = use_context::<crate::server_api::User>().unwrap();
+    let can_manage_users = user.can(Permission::ManageUsers);
+    let revoke_action = create_server_action::<RevokeSession>();
+    let revoke_others_action = create_server_action::<RevokeOtherSessions>();
+    let sessions = create_resource(
+        move || (revoke_action.version().get(), revoke_others_action.version().get()),
+        |_| list_sessions(),
+    );
+    let message = move || {
+        revoke_others_action
+            .value()
+            .get()
+            .map(|r| match r {
+                Ok(count) => format!("{} sessions logged out", count),
+                Err(e) => format!("Error: {}", e),
+            })
+            .or_else(|| {
+                revoke_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(_) => "Session logged out".to_string(),
+                        Err(e) => format!("Error: {}", e),
+                    })
+            })
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Devices & Sessions"}</h2>
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=move |_| revoke_others_action.dispatch(RevokeOtherSessions {})
+            >
+                {"Log Out Other Sessions"}
+            </button>
+            <p>{message}</p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    sessions
+                        .get()
+                        .map(|sessions| match sessions {
+                            Ok(sessions) => {
+                                sessions
+                                    .into_iter()
+                                    .map(|session| {
+                                        let session_id = session.id;
+                                        let current = session.current;
+                                        view! {
+                                            <div class="flex flex-row w-full items-center p-2 border border-solid rounded">
+                                                <div class="flex flex-col flex-1">
+                                                    <span class="break-all">
+                                                        {if current {
+                                                            format!("This browser: {}", session.user_agent)
+                                                        } else {
+                                                            session.user_agent
+                                                        }}
+
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!("IP: {}", session.ip)}
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!("Created: {}", session.created)}
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!("Last activity: {}", session.last_seen)}
+                                                    </span>
+                                                </div>
+                                                <Show when=move || !current>
+                                                    <button
+                                                        class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                        on:click=move |_| {
+                                                            revoke_action.dispatch(RevokeSession { session_id })
+                                                        }
+                                                    >
+
+                                                        {"Log Out"}
+                                                    </button>
+                                                </Show>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            <Show when=move || can_manage_users>
+                <ForceLogoutUsers/>
+            </Show>
+        </div>
+    }
+}
+
+#[component]
+/// log out every session of a user, for the admins
+fn ForceLogoutUsers() -> impl IntoView {
+    let self_id = use_context::<crate::server_api::User>().unwrap().id;
+    let force_logout_action = create_server_action::<ForceLogout>();
+    let users = create_resource(|| {}, |_| get_all_users());
+    view! {
+        <h2>{"Log Out a User"}</h2>
+        <p>
+            {move || {
+                force_logout_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(count) => format!("{} sessions logged out", count),
+                        Err(e) => format!("Error: {}", e),
+                    })
+            }}
+
+        </p>
+        <Transition fallback=move || {
+            view! { <span>"Loading..."</span> }
+        }>
+            {move || {
+                users
+                    .get()
+                    .map(|users| match users {
+                        Ok(users) => {
+                            users
+                                .into_iter()
+                                .filter(|user| user.id != self_id)
+                                .map(|user| {
+                                    let user_id = user.id;
+                                    view! {
+                                        <div class="flex flex-row w-full items-center p-2 border border-solid rounded">
+                                            <span class="flex-1">{user.username}</span>
+                                            <button
+                                                class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                on:click=move |_| {
+                                                    force_logout_action.dispatch(ForceLogout { user_id })
+                                                }
+                                            >
+
+                                                {"Log Out Everywhere"}
+                                            </button>
+                                        </div>
+                                    }
+                                })
+                                .collect_view()
+                        }
+                        Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                    })
+            }}
+
+        </Transition>
+    }
+}
@@ -0,0 +1,122 @@
+use leptos::{html::Textarea, *};
+
+use crate::server_api::transfer::*;
+
+#[component]
+pub fn TransferData() -> impl IntoView {
+    let user = use_context::<crate::server_api::User>().unwrap();
+    let (export, set_export) = create_signal(None::<Result<String, ServerFnError>>);
+    let data_node = create_node_ref::<Textarea>();
+    let import_action = create_server_action::<ImportUserData>();
+    let import_result = import_action.value();
+
+    let on_export = move |_| {
+        spawn_local(async move {
+            set_export(Some(export_user_data(user.id).await));
+        });
+    };
+    let on_import = move |dry_run: bool| {
+        let data = data_node.get().unwrap().value();
+        import_action.dispatch(ImportUserData {
+            account_id: user.id,
+            data,
+            dry_run,
+        });
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Export"}</h2>
+            <button
+                class="w-full   px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_export
+            >
+                {"Export progress and bookmarks"}
+            </button>
+            {move || {
+                export
+                    .get()
+                    .map(|export| {
+                        match export {
+                            Ok(json) => {
+                                view! {
+                                    <a
+                                        class="text-blue-500"
+                                        download=format!("audiobookroom-{}.json", user.username)
+                                        href=format!(
+                                            "data:application/json;charset=utf-8,{}",
+                                            crate::ui::encode_uri_component(&json),
+                                        )
+                                    >
+
+                                        {"Download JSON"}
+                                    </a>
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        }
+                    })
+            }}
+
+            <h2>{"Import"}</h2>
+            <textarea
+                class="w-full h-32 px-2 py-1 bg-gray-100 border border-solid rounded"
+                ref=data_node
+                placeholder="Paste an exported JSON here"
+            ></textarea>
+            <div class="flex flex-row w-full space-x-2">
+                <button
+                    class="flex-1 px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                    on:click=move |_| on_import(true)
+                >
+                    {"Check"}
+                </button>
+                <button
+                    class="flex-1 px-1 py-1  bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                    on:click=move |_| on_import(false)
+                >
+                    {"Import"}
+                </button>
+            </div>
+            {move || {
+                import_result
+                    .get()
+                    .map(|result| {
+                        match result {
+                            Ok(report) => {
+                                view! {
+                                    <p>
+                                        {format!(
+                                            "{}{} progress, {} history, {} bookmarks, {} skipped",
+                                            if report.dry_run { "Would import: " } else { "Imported: " },
+                                            report.progress,
+                                            report.history,
+                                            report.bookmarks,
+                                            report.skipped,
+                                        )}
+
+                                    </p>
+                                    {(!report.unmatched.is_empty())
+                                        .then(|| {
+                                            view! {
+                                                <p>{"Not found in the library:"}</p>
+                                                <ul class="text-left text-sm">
+                                                    {report
+                                                        .unmatched
+                                                        .into_iter()
+                                                        .map(|u| view! { <li>{u}</li> })
+                                                        .collect_view()}
+                                                </ul>
+                                            }
+                                        })}
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                        }
+                    })
+            }}
+
+        </div>
+    }
+}
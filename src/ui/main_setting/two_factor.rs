@@ -0,0 +1,106 @@
+use leptos::{html::Input, *};
+
+use crate::{
+    server_api::totp::{get_totp_status, DisableTotp, RegenerateRecoveryCodes},
+    ui::totp::{RecoveryCodes, TotpEnrol},
+};
+
+#[component]
+pub fn TwoFactor() -> impl IntoView {
+    let disable_action = create_server_action::<DisableTotp>();
+    let regenerate_action = create_server_action::<RegenerateRecoveryCodes>();
+    let status = create_resource(
+        move || (disable_action.version().get(), regenerate_action.version().get()),
+        |_| get_totp_status(),
+    );
+    let code_node = create_node_ref::<Input>();
+    let code = move || {
+        let code = code_node.get().unwrap().value();
+        code_node.get().unwrap().set_value("");
+        code
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Two-Factor Authentication"}</h2>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    status
+                        .get()
+                        .map(|status| match status {
+                            Ok(status) if status.enabled => {
+                                view! {
+                                    <p>{"Two-factor authentication is enabled."}</p>
+                                    <p>
+                                        {format!(
+                                            "{} recovery codes left.",
+                                            status.recovery_codes_left,
+                                        )}
+                                    </p>
+                                    <input
+                                        class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                        type="text"
+                                        autocomplete="one-time-code"
+                                        ref=code_node
+                                        placeholder="Code or recovery code"
+                                    />
+                                    <button
+                                        class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                        on:click=move |_| {
+                                            regenerate_action
+                                                .dispatch(RegenerateRecoveryCodes {
+                                                    code: code(),
+                                                })
+                                        }
+                                    >
+
+                                        {"New recovery codes"}
+                                    </button>
+                                    <button
+                                        class="w-full px-1 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full disabled:opacity-50"
+                                        prop:disabled=status.required
+                                        on:click=move |_| disable_action.dispatch(DisableTotp { code: code() })
+                                    >
+
+                                        {if status.required {
+                                            "Disable(required by your role)"
+                                        } else {
+                                            "Disable"
+                                        }}
+
+                                    </button>
+                                }
+                                    .into_view()
+                            }
+                            Ok(_) => view! { <TotpEnrol/> }.into_view(),
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            {move || {
+                regenerate_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(codes) => view! { <RecoveryCodes codes=codes/> }.into_view(),
+                        Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                    })
+            }}
+
+            <p>
+                {move || {
+                    disable_action
+                        .value()
+                        .get()
+                        .map(|r| match r {
+                            Ok(_) => "Two-factor authentication disabled".to_string(),
+                            Err(e) => format!("Error: {}", e),
+                        })
+                }}
+
+            </p>
+        </div>
+    }
+}
@@ -0,0 +1,355 @@
+use leptos::{html::Input, *};
+
+use crate::server_api::webhook::{
+    list_webhook_deliveries, list_webhooks, CreateWebhook, DeleteWebhook, DeliveryStatus,
+    RetryWebhookDelivery, SetWebhookEnabled, TestWebhook, WebhookEvent,
+};
+
+const DELIVERIES_PER_PAGE: u64 = 20;
+
+#[component]
+/// the webhooks and their deliveries, for the admins
+pub fn Webhooks() -> impl IntoView {
+    let create_action = create_server_action::<CreateWebhook>();
+    let enable_action = create_server_action::<SetWebhookEnabled>();
+    let delete_action = create_server_action::<DeleteWebhook>();
+    let test_action = create_server_action::<TestWebhook>();
+    let retry_action = create_server_action::<RetryWebhookDelivery>();
+    let webhooks = create_resource(
+        move || {
+            (
+                create_action.version().get(),
+                enable_action.version().get(),
+                delete_action.version().get(),
+            )
+        },
+        |_| list_webhooks(),
+    );
+    let (page, set_page) = create_signal(0u64);
+    let (webhook_filter, set_webhook_filter) = create_signal(None::<i32>);
+    let deliveries = create_resource(
+        move || {
+            (
+                page.get(),
+                webhook_filter.get(),
+                delete_action.version().get(),
+                test_action.version().get(),
+                retry_action.version().get(),
+            )
+        },
+        |(page, webhook_id, _, _, _)| {
+            list_webhook_deliveries(page, DELIVERIES_PER_PAGE, webhook_id)
+        },
+    );
+    let name_node = create_node_ref::<Input>();
+    let url_node = create_node_ref::<Input>();
+    let selected_events = create_rw_signal(Vec::<WebhookEvent>::new());
+    let on_create = move |_| {
+        create_action.dispatch(CreateWebhook {
+            name: name_node.get().unwrap().value(),
+            url: url_node.get().unwrap().value(),
+            events: selected_events.get_untracked(),
+        });
+    };
+    let message = move || {
+        test_action.value().get().map(|r| match r {
+            Ok(delivery) if delivery.status == Some(DeliveryStatus::Delivered) => format!(
+                "Ping delivered, the receiver answered {}",
+                delivery.response_status.unwrap_or_default()
+            ),
+            Ok(delivery) => format!("Ping failed: {}", delivery.error),
+            Err(e) => format!("Error: {}", e),
+        })
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            <h2>{"Webhooks"}</h2>
+            <p>
+                {"The events are posted as json to the url, signed with the secret in the "}
+                <code>{"X-Audiobookroom-Signature"}</code> {" header."}
+            </p>
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="text"
+                ref=name_node
+                placeholder="Webhook Name, e.g. family chat"
+            />
+            <input
+                class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                type="url"
+                ref=url_node
+                placeholder="Url, e.g. http://localhost:9000/hook"
+            />
+            {WebhookEvent::ALL
+                .into_iter()
+                .map(|event| {
+                    view! {
+                        <label class="flex flex-row items-center space-x-2">
+                            <input
+                                type="checkbox"
+                                on:change=move |ev| {
+                                    let checked = event_target_checked(&ev);
+                                    selected_events
+                                        .update(|events| {
+                                            events.retain(|e| *e != event);
+                                            if checked {
+                                                events.push(event);
+                                            }
+                                        });
+                                }
+                            />
+
+                            <span>{event.as_str()}</span>
+                        </label>
+                    }
+                })
+                .collect_view()}
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=on_create
+            >
+                {"Create Webhook"}
+            </button>
+            {move || {
+                create_action
+                    .value()
+                    .get()
+                    .map(|r| match r {
+                        Ok(secret) => {
+                            view! {
+                                <p>{"Copy the secret now, it will not be shown again:"}</p>
+                                <code class="break-all">{secret}</code>
+                            }
+                                .into_view()
+                        }
+                        Err(e) => view! { <p>{format!("Error: {}", e)}</p> }.into_view(),
+                    })
+            }}
+
+            <p>{message}</p>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    webhooks
+                        .get()
+                        .map(|webhooks| match webhooks {
+                            Ok(webhooks) if webhooks.is_empty() => {
+                                view! { <p>{"No webhooks"}</p> }.into_view()
+                            }
+                            Ok(webhooks) => {
+                                webhooks
+                                    .into_iter()
+                                    .map(|webhook| {
+                                        let webhook_id = webhook.id;
+                                        let enabled = webhook.enabled;
+                                        let events = webhook
+                                            .events
+                                            .iter()
+                                            .map(|e| e.as_str())
+                                            .collect::<Vec<_>>()
+                                            .join(", ");
+                                        view! {
+                                            <div class="flex flex-col w-full p-2 border border-solid rounded">
+                                                <span>
+                                                    {format!(
+                                                        "{}{}",
+                                                        webhook.name,
+                                                        if enabled { "" } else { " (disabled)" },
+                                                    )}
+
+                                                </span>
+                                                <span class="text-sm break-all">{webhook.url}</span>
+                                                <span class="text-sm">{events}</span>
+                                                <span class="text-sm">
+                                                    {format!("Created: {}", webhook.created)}
+                                                </span>
+                                                <div class="flex flex-row w-full space-x-1">
+                                                    <button
+                                                        class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                                        on:click=move |_| {
+                                                            test_action.dispatch(TestWebhook { webhook_id })
+                                                        }
+                                                    >
+
+                                                        {"Send Test"}
+                                                    </button>
+                                                    <button
+                                                        class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                                        on:click=move |_| {
+                                                            set_page(0);
+                                                            set_webhook_filter(Some(webhook_id));
+                                                        }
+                                                    >
+
+                                                        {"Deliveries"}
+                                                    </button>
+                                                    <button
+                                                        class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                                        on:click=move |_| {
+                                                            enable_action
+                                                                .dispatch(SetWebhookEnabled {
+                                                                    webhook_id,
+                                                                    enabled: !enabled,
+                                                                })
+                                                        }
+                                                    >
+
+                                                        {if enabled { "Disable" } else { "Enable" }}
+                                                    </button>
+                                                    <button
+                                                        class="px-4 py-1 bg-red-200 hover:bg-red-300 border border-solid rounded-full"
+                                                        on:click=move |_| {
+                                                            delete_action.dispatch(DeleteWebhook { webhook_id })
+                                                        }
+                                                    >
+
+                                                        {"Delete"}
+                                                    </button>
+                                                </div>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+            <h2>{"Deliveries"}</h2>
+            <button
+                class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                on:click=move |_| {
+                    set_page(0);
+                    set_webhook_filter(None);
+                }
+            >
+
+                {move || match webhook_filter.get() {
+                    Some(webhook_id) => format!("Webhook {}, show all", webhook_id),
+                    None => "All webhooks".to_string(),
+                }}
+
+            </button>
+            <Transition fallback=move || {
+                view! { <span>"Loading..."</span> }
+            }>
+                {move || {
+                    deliveries
+                        .get()
+                        .map(|deliveries| match deliveries {
+                            Ok(deliveries) => {
+                                let number_of_pages = deliveries.number_of_pages;
+                                view! {
+                                    <div class="flex flex-row w-full items-center justify-between">
+                                        <button
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            on:click=move |_| {
+                                                if page.get_untracked() > 0 {
+                                                    set_page(page.get_untracked() - 1)
+                                                }
+                                            }
+                                        >
+
+                                            {"Previous"}
+                                        </button>
+                                        <span>
+                                            {format!(
+                                                "Page {} of {}",
+                                                deliveries.page + 1,
+                                                number_of_pages.max(1),
+                                            )}
+
+                                        </span>
+                                        <button
+                                            class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                            on:click=move |_| {
+                                                if page.get_untracked() + 1 < number_of_pages {
+                                                    set_page(page.get_untracked() + 1)
+                                                }
+                                            }
+                                        >
+
+                                            {"Next"}
+                                        </button>
+                                    </div>
+                                    {deliveries
+                                        .items
+                                        .into_iter()
+                                        .map(|delivery| {
+                                            let delivery_id = delivery.id;
+                                            let status = delivery.status;
+                                            view! {
+                                                <div class="flex flex-col w-full p-2 border border-solid rounded">
+                                                    <span>
+                                                        {format!(
+                                                            "{} to webhook {}: {}",
+                                                            delivery.event,
+                                                            delivery.webhook_id,
+                                                            status.map_or("unknown", |s| s.as_str()),
+                                                        )}
+
+                                                    </span>
+                                                    <span class="text-sm">
+                                                        {format!(
+                                                            "{}, {} attempts{}",
+                                                            delivery.created,
+                                                            delivery.attempts,
+                                                            delivery
+                                                                .response_status
+                                                                .map(|s| format!(", answered {}", s))
+                                                                .unwrap_or_default(),
+                                                        )}
+
+                                                    </span>
+                                                    {delivery
+                                                        .next_attempt
+                                                        .map(|next| {
+                                                            view! {
+                                                                <span class="text-sm">
+                                                                    {format!("next attempt: {}", next)}
+                                                                </span>
+                                                            }
+                                                        })}
+                                                    {(!delivery.error.is_empty())
+                                                        .then(|| {
+                                                            view! {
+                                                                <span class="text-sm">
+                                                                    {format!("error: {}", delivery.error)}
+                                                                </span>
+                                                            }
+                                                        })}
+                                                    <code class="text-sm break-all">{delivery.payload}</code>
+                                                    {(status == Some(DeliveryStatus::Failed))
+                                                        .then(|| {
+                                                            view! {
+                                                                <button
+                                                                    class="px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                                                    on:click=move |_| {
+                                                                        retry_action
+                                                                            .dispatch(RetryWebhookDelivery {
+                                                                                delivery_id,
+                                                                            })
+                                                                    }
+                                                                >
+
+                                                                    {"Retry"}
+                                                                </button>
+                                                            }
+                                                        })}
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()}
+                                }
+                                    .into_view()
+                            }
+                            Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                        })
+                }}
+
+            </Transition>
+        </div>
+    }
+}
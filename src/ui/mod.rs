@@ -2,12 +2,20 @@
 use leptos::ServerFnError;
 
 pub mod app;
+pub mod bookmark;
+pub mod device;
+pub mod feed_token;
 pub mod main_authors;
 pub mod main_books;
 pub mod main_index;
 pub mod main_page;
 pub mod main_setting;
+pub mod offline;
+pub mod password_reset;
 pub mod player;
+pub mod register;
+pub mod resume;
+pub mod totp;
 pub mod login_page;
 
 
@@ -21,4 +29,17 @@ pub fn translate_time(time: i64) -> (u32, u32) {
 
 pub fn formate_time(min:u32,sec:u32)->String{
     format!("{:02}:{:02}",min,sec)
-}
\ No newline at end of file
+}
+
+/// percent-encode a string so it can be used inside a url, like js `encodeURIComponent`
+pub fn encode_uri_component(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'!' | b'~' | b'*'
+            | b'\'' | b'(' | b')' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
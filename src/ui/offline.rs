@@ -0,0 +1,84 @@
+//! keep the progress updates that could not be sent in the local storage, and replay them once the
+//! connection is back
+
+use crate::server_api::progress::{newest_per_book, set_progress_many, ProgressUpdate};
+
+const QUEUE_KEY: &str = "audiobookroom_progress_queue";
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window().and_then(|w| w.local_storage().ok().flatten())
+}
+
+fn load_queue() -> Vec<ProgressUpdate> {
+    storage()
+        .and_then(|s| s.get_item(QUEUE_KEY).ok().flatten())
+        .and_then(|queue| serde_json::from_str(&queue).ok())
+        .unwrap_or_default()
+}
+
+fn store_queue(queue: &[ProgressUpdate]) {
+    let Some(storage) = storage() else {
+        return;
+    };
+    if queue.is_empty() {
+        let _ = storage.remove_item(QUEUE_KEY);
+    } else if let Ok(queue) = serde_json::to_string(queue) {
+        let _ = storage.set_item(QUEUE_KEY, &queue);
+    }
+}
+
+/// queue an update to be sent later, only the newest update of each book is kept
+pub fn enqueue(update: ProgressUpdate) {
+    let mut queue = load_queue();
+    queue.push(update);
+    store_queue(&newest_per_book(queue));
+}
+
+/// send all queued updates, they are put back into the queue if sending fails. the updates the
+/// server rejects, like those of a book that was hidden meanwhile, are dropped
+pub async fn replay() {
+    let queue = load_queue();
+    if queue.is_empty() {
+        return;
+    }
+    store_queue(&[]);
+    match set_progress_many(queue.clone()).await {
+        Ok(rejected) => {
+            for (update, reason) in rejected {
+                tracing::warn!(
+                    "progress of book {} rejected, dropped: {}",
+                    update.music_id,
+                    reason
+                );
+            }
+        }
+        Err(e) => {
+            tracing::warn!("replay progress failed: {}", e);
+            for update in queue {
+                enqueue(update);
+            }
+        }
+    }
+}
+
+/// send an update, queue it when the server can not be reached
+pub async fn send_progress(account_id: i32, update: ProgressUpdate) {
+    use crate::server_api::progress::set_progress;
+    let result = set_progress(
+        account_id,
+        update.music_id,
+        update.chapter_id,
+        update.progress,
+        update.device_id.clone(),
+        update.device_name.clone(),
+        update.client_update,
+    )
+    .await;
+    match result {
+        Ok(_) => replay().await,
+        Err(e) => {
+            tracing::warn!("save progress failed, queue it: {}", e);
+            enqueue(update);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use leptos::{html::Input, *};
+use leptos_router::use_query_map;
+
+use crate::server_api::password_reset::{request_password_reset, ResetPassword};
+
+#[component]
+/// `/forgot-password`, ask for a reset link by mail
+pub fn ForgotPasswordPage() -> impl IntoView {
+    let login = create_node_ref::<Input>();
+    let request = create_action(|login: &String| request_password_reset(login.clone()));
+    view! {
+        <div class="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
+            <div class="sm:mx-auto sm:w-full sm:max-w-sm space-y-6">
+                <h2 class="text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">
+                    {"Forgot your password?"}
+                </h2>
+                <input
+                    class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+                    type="text"
+                    ref=login
+                    placeholder="User Name or Email"
+                />
+                <button
+                    type="button"
+                    class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500"
+                    on:click=move |_| request.dispatch(login.get().unwrap().value())
+                >
+                    {"Send Reset Link"}
+                </button>
+                <p>
+                    {move || {
+                        request
+                            .value()
+                            .get()
+                            .map(|r| match r {
+                                Ok(_) => {
+                                    "If the account has an email address, a reset link was sent to it."
+                                        .to_string()
+                                }
+                                Err(e) => e.to_string(),
+                            })
+                    }}
+
+                </p>
+                <a class="text-blue-500" href="/login">
+                    {"go to login"}
+                </a>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+/// `/reset-password?token=...`, the page of the link in the mail
+pub fn ResetPasswordPage() -> impl IntoView {
+    let query = use_query_map();
+    let password = create_node_ref::<Input>();
+    let reset = create_server_action::<ResetPassword>();
+    let on_reset = move |_| {
+        reset.dispatch(ResetPassword {
+            token: query.with_untracked(|q| q.get("token").cloned().unwrap_or_default()),
+            new_password: password.get().unwrap().value(),
+        });
+    };
+    view! {
+        <div class="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
+            <div class="sm:mx-auto sm:w-full sm:max-w-sm space-y-6">
+                <h2 class="text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">
+                    {"Choose a new password"}
+                </h2>
+                <input
+                    class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+                    type="password"
+                    autocomplete="new-password"
+                    ref=password
+                    placeholder="New Password"
+                />
+                <button
+                    type="button"
+                    class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500"
+                    on:click=on_reset
+                >
+                    {"Set Password"}
+                </button>
+                <p>
+                    {move || {
+                        reset
+                            .value()
+                            .get()
+                            .map(|r| match r {
+                                Ok(_) => "Password changed, log in with the new password.".to_string(),
+                                Err(e) => e.to_string(),
+                            })
+                    }}
+
+                </p>
+                <a class="text-blue-500" href="/login">
+                    {"go to login"}
+                </a>
+            </div>
+        </div>
+    }
+}
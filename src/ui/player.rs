@@ -1,6 +1,13 @@
-use crate::server_api::{
-    book::{get_book_detail, get_chatper_detail},
-    User,
+use crate::{
+    server_api::{
+        book::{get_book_detail, get_chatper_detail},
+        User,
+    },
+    ui::{
+        bookmark::BookmarkPanel,
+        device::{client_now, device_id, device_name},
+        offline::send_progress,
+    },
 };
 use chrono::{DateTime, Local};
 use leptos::{ev::MouseEvent, html::Audio, *};
@@ -24,7 +31,7 @@ pub fn Player(
     let props = create_memo(move |_| props.get());
     let player_ref_node: NodeRef<Audio> = create_node_ref();
     let (sleep_countdown, set_sleep_countdown) = create_signal::<Option<DateTime<Local>>>(None);
-    let (last_saved_time, set_last_saved_time) = create_signal(None::<SetProgress>);
+    let (last_saved_time, set_last_saved_time) = create_signal(None::<ProgressUpdate>);
     let (_current_time, set_current_time) = create_signal(0.0);
 
     // update every 4 sec
@@ -39,8 +46,6 @@ pub fn Player(
     });
     let (total_time, set_total_time) = create_signal(0.0);
     let user = use_context::<User>().unwrap();
-    let set_progress_action =
-        use_context::<Action<SetProgress, Result<(), ServerFnError>>>().unwrap();
     // fetch the detail from server
     let play_details = create_resource(
         move || props.get(),
@@ -87,24 +92,28 @@ pub fn Player(
                 if last_save_time.chapter_id != props.chapter_id
                     || (last_save_time.progress - current_time).abs() >= 10.
                 {
-                    let new_set_progress = SetProgress {
-                        account_id: user.id,
+                    let new_set_progress = ProgressUpdate {
                         music_id: props.book_id,
                         chapter_id: props.chapter_id,
                         progress: current_time,
+                        device_id: device_id(),
+                        device_name: device_name(),
+                        client_update: client_now(),
                     };
                     set_last_saved_time(Some(new_set_progress.clone()));
-                    set_progress_action.dispatch(new_set_progress);
+                    spawn_local(send_progress(user.id, new_set_progress));
                 }
             } else {
-                let new_set_progress = SetProgress {
-                    account_id: user.id,
+                let new_set_progress = ProgressUpdate {
                     music_id: props.book_id,
                     chapter_id: props.chapter_id,
                     progress: current_time,
+                    device_id: device_id(),
+                    device_name: device_name(),
+                    client_update: client_now(),
                 };
                 set_last_saved_time(Some(new_set_progress.clone()));
-                set_progress_action.dispatch(new_set_progress);
+                spawn_local(send_progress(user.id, new_set_progress));
             }
         }
     };
@@ -141,6 +150,68 @@ pub fn Player(
     let remove_sleep = move || {
         set_sleep_countdown(None);
     };
+    let (show_bookmarks, set_show_bookmarks) = create_signal(false);
+
+    // the playback speed, saved for the book or as the default of the user
+    use crate::server_api::preference::*;
+    let book_id = create_memo(move |_| props.get().map(|p| p.book_id));
+    let (speed, set_speed) = create_signal(1.0);
+    let (speed_for_book, set_speed_for_book) = create_signal(false);
+    let playback_settings = create_resource(
+        move || book_id.get(),
+        move |book_id| async move {
+            match book_id {
+                Some(book_id) => get_playback_settings(book_id).await.ok(),
+                None => None,
+            }
+        },
+    );
+    create_effect(move |_| {
+        if let Some(Some(settings)) = playback_settings.get() {
+            set_speed(settings.playback_speed);
+            set_speed_for_book(settings.book_override);
+            if let Some(mins) = settings.sleep_timer_mins {
+                if sleep_countdown.get_untracked().is_none() {
+                    on_sleep_clicked(mins as u64 * 60);
+                }
+            }
+        }
+    });
+    let save_speed = move |new_speed: f64, for_book: bool| {
+        let Some(book_id) = book_id.get_untracked() else {
+            return;
+        };
+        spawn_local(async move {
+            if for_book {
+                let _ = set_book_preference(book_id, Some(new_speed)).await;
+            } else if let Ok(p) = get_preference().await {
+                let _ = set_preference(new_speed, p.skip_back_secs, p.sleep_timer_mins).await;
+            }
+        });
+    };
+    let on_speed_changed = move |delta: f64| {
+        let new_speed = ((speed.get_untracked() + delta) * 100.0).round() / 100.0;
+        let new_speed = new_speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED);
+        set_speed(new_speed);
+        save_speed(new_speed, speed_for_book.get_untracked());
+    };
+    let on_speed_for_book_changed = move |for_book: bool| {
+        set_speed_for_book(for_book);
+        if for_book {
+            save_speed(speed.get_untracked(), true);
+            return;
+        }
+        let Some(book_id) = book_id.get_untracked() else {
+            return;
+        };
+        // the override is removed, the book plays at the speed of the other books again
+        spawn_local(async move {
+            let _ = set_book_preference(book_id, None).await;
+            if let Ok(p) = get_preference().await {
+                set_speed(p.playback_speed);
+            }
+        });
+    };
     view! {
         <div>
             <Transition fallback=move || {
@@ -153,7 +224,11 @@ pub fn Player(
                             match p {
                                 Some(p) => {
                                     let (book_detail, chapter_detail, init_time) = p;
-                                    let url = format!("/fetchbook/{}", chapter_detail.chapter_url);
+                                    let url = format!(
+                                        "/fetchbook/{}/{}",
+                                        book_detail.library_id,
+                                        chapter_detail.chapter_url,
+                                    );
                                     view! {
                                         <audio
                                             style="display: none"
@@ -166,6 +241,8 @@ pub fn Player(
                                                 init_time: 0.0,
                                             })
 
+                                            prop:defaultPlaybackRate=move || speed.get()
+                                            prop:playbackRate=move || speed.get()
                                             on:durationchange=on_duration_change
 
                                             on:timeupdate=on_time_updated
@@ -213,7 +290,47 @@ pub fn Player(
                                                     >
                                                         Remove
                                                     </button>
+                                                    <button
+                                                        class="bg-blue-50 hover:bg-green-50 font-bold py-2 px-4 border border-solid rounded"
+                                                        on:click=move |_| set_show_bookmarks.update(|show| *show = !*show)
+                                                    >
+                                                        Bookmarks
+                                                    </button>
                                                 </div>
+                                                <div class="flex items-center justify-evenly flex-row w-full flex-wrap">
+                                                    <button
+                                                        class="bg-blue-50 hover:bg-green-50 font-bold py-2 px-4 border border-solid rounded"
+                                                        on:click=move |_| on_speed_changed(-0.25)
+                                                    >
+                                                        "-"
+                                                    </button>
+                                                    <span class="font-bold">
+                                                        {move || format!("{:.2}x", speed.get())}
+                                                    </span>
+                                                    <button
+                                                        class="bg-blue-50 hover:bg-green-50 font-bold py-2 px-4 border border-solid rounded"
+                                                        on:click=move |_| on_speed_changed(0.25)
+                                                    >
+                                                        "+"
+                                                    </button>
+                                                    <label class="flex items-center space-x-1">
+                                                        <input
+                                                            type="checkbox"
+                                                            prop:checked=move || speed_for_book.get()
+                                                            on:change=move |e| on_speed_for_book_changed(
+                                                                event_target_checked(&e),
+                                                            )
+                                                        />
+                                                        <span>"this book only"</span>
+                                                    </label>
+                                                </div>
+                                                <Show when=move || show_bookmarks.get()>
+                                                    <BookmarkPanel
+                                                        book_id=book_detail.id
+                                                        chapter_id=chapter_detail.id
+                                                        player=player_ref_node
+                                                    />
+                                                </Show>
                                             </div>
 
                                             <div class="flex items-center justify-center bg-red-lightest">
@@ -385,3 +502,16 @@ pub fn Player(
         </div>
     }
 }
+
+/// the props to resume a book from the saved progress, rewinding by the skip back preference
+pub async fn resume_props(book_id: i32, chapter_id: i32, progress: f64) -> AudioProps {
+    let skip_back_secs = crate::server_api::preference::get_preference()
+        .await
+        .map(|p| p.skip_back_secs)
+        .unwrap_or_default();
+    AudioProps {
+        book_id,
+        chapter_id,
+        init_time: (progress - skip_back_secs as f64).max(0.),
+    }
+}
@@ -0,0 +1,163 @@
+use leptos::{html::Input, *};
+use leptos_router::use_params_map;
+
+use crate::server_api::{
+    invitation::{invitation_info, AcceptInvitation},
+    registration::{registration_open, Register},
+};
+
+#[component]
+/// the user name and password of a new account
+fn AccountForm<F>(button: &'static str, on_submit: F) -> impl IntoView
+where
+    F: Fn(String, String) + 'static,
+{
+    let username = create_node_ref::<Input>();
+    let password = create_node_ref::<Input>();
+    view! {
+        <input
+            class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+            type="text"
+            ref=username
+            placeholder="User Name"
+        />
+        <input
+            class="block w-full rounded-md border-0 py-1.5 text-gray-900 shadow-sm ring-1 ring-inset ring-gray-300 placeholder:text-gray-400 focus:ring-2 focus:ring-inset focus:ring-indigo-600 sm:text-sm sm:leading-6"
+            type="password"
+            autocomplete="new-password"
+            ref=password
+            placeholder="Password"
+        />
+        <button
+            type="button"
+            class="flex w-full justify-center rounded-md bg-indigo-600 px-3 py-1.5 text-sm font-semibold leading-6 text-white shadow-sm hover:bg-indigo-500"
+            on:click=move |_| {
+                on_submit(username.get().unwrap().value(), password.get().unwrap().value())
+            }
+        >
+
+            {button}
+        </button>
+    }
+}
+
+#[component]
+/// `/invite/:token`, create an account with an invite link
+pub fn InvitePage() -> impl IntoView {
+    let params = use_params_map();
+    let token = move || params.with(|p| p.get("token").cloned().unwrap_or_default());
+    let info = create_resource(token, invitation_info);
+    let accept = create_server_action::<AcceptInvitation>();
+    // a full load, so the app loads the new user
+    create_effect(move |_| {
+        if let Some(Ok(())) = accept.value().get() {
+            let _ = window().location().set_href("/");
+        }
+    });
+    view! {
+        <div class="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
+            <div class="sm:mx-auto sm:w-full sm:max-w-sm space-y-6">
+                <h2 class="text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">
+                    {"Create your account"}
+                </h2>
+                <Transition fallback=move || {
+                    view! { <p>"Loading..."</p> }
+                }>
+                    {move || {
+                        info.get()
+                            .map(|info| match info {
+                                Ok(Some(info)) => {
+                                    view! {
+                                        <p>
+                                            {format!(
+                                                "You are invited as {}, the invitation expires {}.",
+                                                info.role_name,
+                                                info.expires,
+                                            )}
+
+                                        </p>
+                                        <AccountForm
+                                            button="Create Account"
+                                            on_submit=move |username, password| {
+                                                accept
+                                                    .dispatch(AcceptInvitation {
+                                                        token: token(),
+                                                        username,
+                                                        password,
+                                                    })
+                                            }
+                                        />
+                                    }
+                                        .into_view()
+                                }
+                                Ok(None) => {
+                                    view! { <p>{"The invitation has expired or was used up."}</p> }
+                                        .into_view()
+                                }
+                                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                            })
+                    }}
+
+                </Transition>
+                <p>{move || accept.value().get().and_then(Result::err).map(|e| e.to_string())}</p>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+/// `/register`, an account that waits for the approval of an admin
+pub fn RegisterPage() -> impl IntoView {
+    let open = create_resource(|| {}, |_| registration_open());
+    let register = create_server_action::<Register>();
+    view! {
+        <div class="flex min-h-full flex-col justify-center px-6 py-12 lg:px-8">
+            <div class="sm:mx-auto sm:w-full sm:max-w-sm space-y-6">
+                <h2 class="text-center text-2xl font-bold leading-9 tracking-tight text-gray-900">
+                    {"Register"}
+                </h2>
+                <Transition fallback=move || {
+                    view! { <p>"Loading..."</p> }
+                }>
+                    {move || {
+                        open.get()
+                            .map(|open| match open {
+                                Ok(true) => {
+                                    view! {
+                                        <AccountForm
+                                            button="Register"
+                                            on_submit=move |username, password| {
+                                                register.dispatch(Register { username, password })
+                                            }
+                                        />
+                                    }
+                                        .into_view()
+                                }
+                                Ok(false) => view! { <p>{"Registration is closed."}</p> }.into_view(),
+                                Err(e) => view! { <p>{e.to_string()}</p> }.into_view(),
+                            })
+                    }}
+
+                </Transition>
+                <p>
+                    {move || {
+                        register
+                            .value()
+                            .get()
+                            .map(|r| match r {
+                                Ok(_) => {
+                                    "Registered, you can log in once an admin approves your account."
+                                        .to_string()
+                                }
+                                Err(e) => e.to_string(),
+                            })
+                    }}
+
+                </p>
+                <a class="text-blue-500" href="/login">
+                    {"go to login"}
+                </a>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,118 @@
+use leptos::*;
+
+use crate::{
+    server_api::{
+        bookmark::format_position,
+        progress::{get_progress, get_progress_conflicts, DeviceProgressResult},
+    },
+    ui::player::{resume_props, AudioProps},
+};
+
+/// resume a book from the saved progress.
+///
+/// when the devices of the user disagree about the position, the choices are kept here and the
+/// [`ResumeChooser`] asks the user which one to continue from.
+#[derive(Clone, Copy)]
+pub struct ResumeContext {
+    account_id: i32,
+    set_player_props: WriteSignal<Option<AudioProps>>,
+    choices: RwSignal<Option<Vec<DeviceProgressResult>>>,
+}
+
+impl ResumeContext {
+    pub fn new(account_id: i32, set_player_props: WriteSignal<Option<AudioProps>>) -> Self {
+        Self {
+            account_id,
+            set_player_props,
+            choices: create_rw_signal(None),
+        }
+    }
+
+    pub fn resume(&self, book_id: i32) {
+        let this = *self;
+        spawn_local(async move {
+            let conflicts = get_progress_conflicts(book_id).await.unwrap_or_default();
+            if !conflicts.is_empty() {
+                this.choices.set(Some(conflicts));
+                return;
+            }
+            if let Ok(Some(p)) = get_progress(book_id, this.account_id).await {
+                (this.set_player_props)(Some(
+                    resume_props(book_id, p.chapter_id, p.progress).await,
+                ));
+            }
+        });
+    }
+
+    pub fn choose(&self, choice: DeviceProgressResult) {
+        let this = *self;
+        this.choices.set(None);
+        spawn_local(async move {
+            (this.set_player_props)(Some(
+                resume_props(choice.music_id, choice.chapter_id, choice.progress).await,
+            ));
+        });
+    }
+}
+
+#[component]
+/// ask which device to continue from when they disagree
+pub fn ResumeChooser() -> impl IntoView {
+    let resume = use_context::<ResumeContext>().unwrap();
+    view! {
+        {move || {
+            resume
+                .choices
+                .get()
+                .map(|choices| {
+                    let this_device = crate::ui::device::device_id();
+                    view! {
+                        <div class="flex flex-col w-full space-y-1 p-2 border border-solid rounded bg-yellow-50">
+                            <h3>{"Your devices are at different positions, continue from:"}</h3>
+                            {choices
+                                .into_iter()
+                                .map(|choice| {
+                                    let device_name = if choice.device_id == this_device {
+                                        format!("this device ({})", choice.device_name)
+                                    } else {
+                                        choice.device_name.clone()
+                                    };
+                                    let played_at = chrono::DateTime::from_timestamp(
+                                            choice.client_update.div_euclid(1000),
+                                            choice.client_update.rem_euclid(1000) as u32 * 1_000_000,
+                                        )
+                                        .map(|t| {
+                                            t.with_timezone(&chrono::Local)
+                                                .format("%Y-%m-%d %H:%M:%S")
+                                                .to_string()
+                                        })
+                                        .unwrap_or_default();
+                                    let label = format!(
+                                        "{} at {} {}",
+                                        device_name,
+                                        choice.chapter_name,
+                                        format_position(choice.progress),
+                                    );
+                                    view! {
+                                        <button
+                                            class="w-full px-2 py-1 bg-blue-50 hover:bg-green-50 border border-solid rounded-sm shadow-md hover:shadow-lg"
+                                            on:click=move |_| resume.choose(choice.clone())
+                                        >
+                                            <p>{label}</p>
+                                            <p class="text-sm">{format!("Last played: {}", played_at)}</p>
+                                        </button>
+                                    }
+                                })
+                                .collect_view()}
+                            <button
+                                class="bg-blue-50 hover:bg-green-50 py-1 px-4 rounded"
+                                on:click=move |_| resume.choices.set(None)
+                            >
+                                {"Cancel"}
+                            </button>
+                        </div>
+                    }
+                })
+        }}
+    }
+}
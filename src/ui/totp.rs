@@ -0,0 +1,96 @@
+use leptos::{html::Input, *};
+
+use crate::server_api::totp::{BeginTotpEnrolment, ConfirmTotpEnrolment};
+
+#[component]
+/// set up an authenticator: scan the qr code, confirm a code and save the recovery codes.
+///
+/// `continue_href` is shown after the recovery codes, it reloads the page
+pub fn TotpEnrol(#[prop(optional)] continue_href: Option<&'static str>) -> impl IntoView {
+    let begin_action = create_server_action::<BeginTotpEnrolment>();
+    let confirm_action = create_server_action::<ConfirmTotpEnrolment>();
+    let code_node = create_node_ref::<Input>();
+    let on_confirm = move |_| {
+        confirm_action.dispatch(ConfirmTotpEnrolment {
+            code: code_node.get().unwrap().value(),
+        });
+    };
+    view! {
+        <div class="flex flex-col w-full space-y-1 p-2">
+            {move || match confirm_action.value().get() {
+                Some(Ok(codes)) => {
+                    view! {
+                        <RecoveryCodes codes=codes/>
+                        {continue_href
+                            .map(|href| {
+                                view! {
+                                    <a class="text-blue-500" href=href rel="external">
+                                        {"Continue"}
+                                    </a>
+                                }
+                            })}
+                    }
+                        .into_view()
+                }
+                confirmed => {
+                    match begin_action.value().get() {
+                        Some(Ok(enrolment)) => {
+                            view! {
+                                <p>{"Scan the code with your authenticator app:"}</p>
+                                <div class="w-52" inner_html=enrolment.qr_svg></div>
+                                <p class="text-sm break-all">
+                                    {format!("or enter the key: {}", enrolment.secret)}
+                                </p>
+                                <input
+                                    class="w-full px-4 py-1 bg-gray-100 hover:bg-gray-50 border border-solid rounded-full"
+                                    type="text"
+                                    inputmode="numeric"
+                                    autocomplete="one-time-code"
+                                    ref=code_node
+                                    placeholder="6 digit code"
+                                />
+                                <button
+                                    class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                    on:click=on_confirm
+                                >
+                                    {"Confirm"}
+                                </button>
+                                <p>
+                                    {confirmed
+                                        .and_then(Result::err)
+                                        .map(|e| format!("Error: {}", e))}
+                                </p>
+                            }
+                                .into_view()
+                        }
+                        begun => {
+                            view! {
+                                <button
+                                    class="w-full px-1 py-1 bg-gray-400 hover:bg-gray-50 hover:shadow-lg border border-solid rounded-full"
+                                    on:click=move |_| begin_action.dispatch(BeginTotpEnrolment {})
+                                >
+                                    {"Set up two-factor authentication"}
+                                </button>
+                                <p>{begun.and_then(Result::err).map(|e| format!("Error: {}", e))}</p>
+                            }
+                                .into_view()
+                        }
+                    }
+                }
+            }}
+
+        </div>
+    }
+}
+
+#[component]
+pub fn RecoveryCodes(codes: Vec<String>) -> impl IntoView {
+    view! {
+        <p>
+            {"Save these recovery codes, each can be used once when the authenticator is lost:"}
+        </p>
+        <ul class="font-mono">
+            {codes.into_iter().map(|code| view! { <li>{code}</li> }).collect_view()}
+        </ul>
+    }
+}